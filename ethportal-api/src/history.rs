@@ -1,8 +1,12 @@
+use crate::types::gossip_status::GossipStatus;
+use crate::types::inventory::{LocalContentFilter, LocalContentInventory, LocalContentStats};
 use crate::types::portal::FindNodesInfo;
 use crate::types::portal::{
-    AcceptInfo, ContentInfo, DataRadius, PaginateLocalContentInfo, PongInfo, TraceContentInfo,
+    AcceptInfo, BatchContentInfo, ContentInfo, DataRadius, PaginateLocalContentInfo, PongInfo,
+    RoutingTableHealth, TraceContentInfo, TraceNodesInfo,
 };
 use crate::{NodeId, RoutingTableInfo};
+use ethereum_types::H256;
 use jsonrpsee::{core::RpcResult, proc_macros::rpc};
 use trin_types::content_key::HistoryContentKey;
 use trin_types::content_value::{HistoryContentValue, PossibleHistoryContentValue};
@@ -15,6 +19,10 @@ pub trait HistoryNetworkApi {
     #[method(name = "historyRoutingTableInfo")]
     async fn routing_table_info(&self) -> RpcResult<RoutingTableInfo>;
 
+    /// Returns diagnostics of the health of the overlay routing table.
+    #[method(name = "historyRoutingTableHealth")]
+    async fn routing_table_health(&self) -> RpcResult<RoutingTableHealth>;
+
     /// Returns meta information about overlay routing table.
     #[method(name = "historyRadius")]
     async fn radius(&self) -> RpcResult<DataRadius>;
@@ -48,6 +56,10 @@ pub trait HistoryNetworkApi {
     #[method(name = "historyRecursiveFindNodes")]
     async fn recursive_find_nodes(&self, node_id: NodeId) -> RpcResult<Vec<Enr>>;
 
+    /// Lookup a target node within in the network. Return tracing info.
+    #[method(name = "historyTraceRecursiveFindNodes")]
+    async fn trace_recursive_find_nodes(&self, node_id: NodeId) -> RpcResult<TraceNodesInfo>;
+
     /// Send FINDCONTENT message to get the content with a content key.
     #[method(name = "historyFindContent")]
     async fn find_content(
@@ -70,6 +82,16 @@ pub trait HistoryNetworkApi {
         content_key: HistoryContentKey,
     ) -> RpcResult<TraceContentInfo>;
 
+    /// Lookup a batch of target content keys in the network, with at most `max_concurrency`
+    /// lookups in flight. Duplicate keys are looked up once. Return the result of each lookup, in
+    /// the order of the content keys.
+    #[method(name = "historyRecursiveFindContentBatch")]
+    async fn recursive_find_content_batch(
+        &self,
+        content_keys: Vec<HistoryContentKey>,
+        max_concurrency: Option<usize>,
+    ) -> RpcResult<Vec<BatchContentInfo>>;
+
     /// Pagination of local content keys
     #[method(name = "paginateLocalContentKeys")]
     async fn paginate_local_content_keys(
@@ -78,6 +100,23 @@ pub trait HistoryNetworkApi {
         limit: u64,
     ) -> RpcResult<PaginateLocalContentInfo>;
 
+    /// List up to `limit` local content items that match the filter, in ascending order of
+    /// content id, starting after the content id `cursor`. The returned `nextCursor` continues
+    /// after the page.
+    #[method(name = "historyLocalContentInventory")]
+    async fn local_content_inventory(
+        &self,
+        filter: LocalContentFilter,
+        cursor: Option<H256>,
+        limit: u64,
+    ) -> RpcResult<LocalContentInventory>;
+
+    /// Return the number of local content items that match the filter, and their total size, for
+    /// each content type.
+    #[method(name = "historyLocalContentStats")]
+    async fn local_content_stats(&self, filter: LocalContentFilter)
+        -> RpcResult<LocalContentStats>;
+
     /// Send the provided content value to interested peers. Clients may choose to send to some or all peers.
     /// Return the number of peers that the content was gossiped to.
     #[method(name = "historyGossip")]
@@ -87,6 +126,11 @@ pub trait HistoryNetworkApi {
         content_value: HistoryContentValue,
     ) -> RpcResult<u32>;
 
+    /// Return the delivery status of the most recent gossip of the content key: the number of
+    /// peers that stored, declined or failed to receive the content.
+    #[method(name = "historyGossipStatus")]
+    async fn gossip_status(&self, content_key: HistoryContentKey) -> RpcResult<GossipStatus>;
+
     /// Send an OFFER request with given ContentKey, to the designated peer and wait for a response.
     /// Returns the content keys bitlist upon successful content transmission or empty bitlist receive.
     #[method(name = "historyOffer")]
@@ -111,4 +155,18 @@ pub trait HistoryNetworkApi {
         &self,
         content_key: HistoryContentKey,
     ) -> RpcResult<PossibleHistoryContentValue>;
+
+    /// Pin content in the local database, so that it is stored even if it falls outside of the
+    /// radius, and never evicted. Returns false if the content was already pinned.
+    #[method(name = "historyPinContent")]
+    async fn pin_content(&self, content_key: HistoryContentKey) -> RpcResult<bool>;
+
+    /// Unpin content in the local database. Returns false if the content was not pinned.
+    #[method(name = "historyUnpinContent")]
+    async fn unpin_content(&self, content_key: HistoryContentKey) -> RpcResult<bool>;
+
+    /// List the content keys of all content pinned with `historyPin`, whether it is stored or not.
+    /// Content that is only pinned by its content type is not listed.
+    #[method(name = "historyPinnedContent")]
+    async fn pinned_content(&self) -> RpcResult<Vec<HistoryContentKey>>;
 }
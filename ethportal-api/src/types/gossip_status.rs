@@ -0,0 +1 @@
+pub type GossipStatus = trin_types::gossip_status::GossipStatus;
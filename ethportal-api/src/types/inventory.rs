@@ -0,0 +1,5 @@
+pub type LocalContentFilter = trin_types::inventory::LocalContentFilter;
+pub type LocalContentEntry = trin_types::inventory::LocalContentEntry;
+pub type LocalContentInventory = trin_types::inventory::LocalContentInventory;
+pub type LocalContentStats = trin_types::inventory::LocalContentStats;
+pub type ContentTypeStats = trin_types::inventory::ContentTypeStats;
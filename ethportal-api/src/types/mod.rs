@@ -1,2 +1,4 @@
+pub mod gossip_status;
+pub mod inventory;
 pub mod portal;
 pub mod query_trace;
@@ -1,8 +1,11 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 use ssz_types::{typenum, BitList};
 use trin_types::content_key::HistoryContentKey;
 use trin_types::content_value::{HistoryContentValue, PossibleHistoryContentValue};
 use trin_types::enr::Enr;
+use trin_types::node_id::NodeId;
 
 use super::query_trace::QueryTrace;
 
@@ -63,10 +66,75 @@ pub struct TraceContentInfo {
     pub trace: QueryTrace,
 }
 
+/// Response for TraceRecursiveFindNodes endpoint
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceNodesInfo {
+    pub nodes: Vec<Enr>,
+    pub trace: QueryTrace,
+}
+
+/// Response for RoutingTableHealth endpoint
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutingTableHealth {
+    pub num_nodes: usize,
+    pub num_connected: usize,
+    pub num_disconnected: usize,
+    /// Number of buckets with a pending entry.
+    pub num_pending: usize,
+    /// Estimate of the number of nodes in the network, derived from bucket occupancy.
+    pub estimated_network_size: u64,
+    /// The non-empty buckets of the routing table.
+    pub buckets: Vec<BucketHealth>,
+    /// Number of peers by the number of bits of their data radius, from 0 (zero radius) to 256
+    /// (maximum radius).
+    pub radius_distribution: BTreeMap<u16, usize>,
+    /// Number of peers by the client version in their ENR.
+    pub client_distribution: BTreeMap<String, usize>,
+}
+
+/// Part of a RoutingTableHealth response
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketHealth {
+    /// Log2 distance of the nodes in the bucket from the local node.
+    pub distance: u16,
+    /// Fraction of the capacity of the bucket that is filled.
+    pub fill: f64,
+    pub num_connected: usize,
+    pub num_disconnected: usize,
+    pub has_pending: bool,
+    pub nodes: Vec<NodeHealth>,
+}
+
+/// Part of a RoutingTableHealth response
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeHealth {
+    pub node_id: NodeId,
+    pub connected: bool,
+    /// Seconds since the node last sent a request or a response.
+    pub last_seen_secs: Option<u64>,
+    /// Seconds since the node last responded to a request.
+    pub last_response_secs: Option<u64>,
+}
+
+/// Part of a RecursiveFindContentBatch response
+///
+/// The content is "0x" if the lookup failed, in which case `error` describes the failure.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchContentInfo {
+    pub content_key: HistoryContentKey,
+    pub content: PossibleHistoryContentValue,
+    pub error: Option<String>,
+}
+
 /// Response for PaginateLocalContentKeys endpoint
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct PaginateLocalContentInfo {
-    pub content_keys: Vec<HistoryContentKey>,
+pub struct PaginateLocalContentInfo<TContentKey = HistoryContentKey> {
+    pub content_keys: Vec<TContentKey>,
     pub total_entries: u64,
 }
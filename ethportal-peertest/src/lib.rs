@@ -1,7 +1,7 @@
 pub mod constants;
 pub mod scenarios;
 
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
 use std::{thread, time};
 
@@ -103,7 +103,7 @@ async fn launch_node(trin_config: TrinConfig) -> anyhow::Result<PeertestNode> {
 
 fn generate_trin_config(id: u16, bootnode_enr: Option<&Enr>) -> TrinConfig {
     let discovery_port: u16 = 9000 + id;
-    let discovery_port: String = discovery_port.to_string();
+    let discovery_port_arg: String = discovery_port.to_string();
     let web3_ipc_path = PathBuf::from(format!("/tmp/ethportal-peertest-buddy-{id}.ipc"));
     // This specific private key scheme is chosen to enforce that the first peer node will be in
     // the 256 kbucket of the bootnode, to ensure consistent `FindNodes` tests.
@@ -112,11 +112,16 @@ fn generate_trin_config(id: u16, bootnode_enr: Option<&Enr>) -> TrinConfig {
     let private_key = hex_encode(private_key);
     match bootnode_enr {
         Some(enr) => {
-            let external_addr = format!(
-                "{}:{}",
-                enr.ip4().expect("bootnode must have IP"),
-                discovery_port
-            );
+            let bootnode_ip = enr
+                .ip4()
+                .map(IpAddr::from)
+                .or_else(|| enr.ip6().map(IpAddr::from))
+                .expect("bootnode must have IP");
+            let ip_mode = match bootnode_ip {
+                IpAddr::V4(_) => "ip4",
+                IpAddr::V6(_) => "ip6",
+            };
+            let external_addr = SocketAddr::new(bootnode_ip, discovery_port).to_string();
             let enr_base64 = enr.to_base64();
             let web3_ipc_path_str = web3_ipc_path.as_path().display().to_string();
             let trin_config_args = vec![
@@ -125,10 +130,12 @@ fn generate_trin_config(id: u16, bootnode_enr: Option<&Enr>) -> TrinConfig {
                 "history,state",
                 "--external-address",
                 external_addr.as_str(),
+                "--ip-mode",
+                ip_mode,
                 "--bootnodes",
                 enr_base64.as_str(),
                 "--discovery-port",
-                discovery_port.as_str(),
+                discovery_port_arg.as_str(),
                 "--web3-ipc-path",
                 &web3_ipc_path_str[..],
                 "--unsafe-private-key",
@@ -139,7 +146,7 @@ fn generate_trin_config(id: u16, bootnode_enr: Option<&Enr>) -> TrinConfig {
         }
         None => {
             let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
-            let external_addr = format!("{ip_addr}:{discovery_port}");
+            let external_addr = SocketAddr::new(ip_addr, discovery_port).to_string();
             let web3_ipc_path_str = web3_ipc_path.as_path().display().to_string();
             let trin_config_args = vec![
                 "trin",
@@ -150,7 +157,7 @@ fn generate_trin_config(id: u16, bootnode_enr: Option<&Enr>) -> TrinConfig {
                 "--bootnodes",
                 "none",
                 "--discovery-port",
-                discovery_port.as_str(),
+                discovery_port_arg.as_str(),
                 "--web3-ipc-path",
                 &web3_ipc_path_str[..],
                 "--unsafe-private-key",
@@ -0,0 +1,173 @@
+//! Measures eviction in a full `PortalStorage`, with millions of entries.
+//!
+//! Run with `cargo bench -p portalnet --bench storage_eviction`. The number of entries defaults to
+//! 1 million, and can be changed with the `TRIN_BENCH_ENTRIES` environment variable.
+
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+use discv5::enr::NodeId;
+use rand::RngCore;
+use rocksdb::WriteBatch;
+use rusqlite::params;
+
+use portalnet::{
+    storage::{ContentStore, PortalStorage, PortalStorageConfig},
+    types::messages::ProtocolId,
+    utils::db::setup_temp_dir,
+};
+use trin_types::{content_key::IdentityContentKey, distance::Distance};
+use trin_utils::bytes::hex_encode;
+
+const DEFAULT_NUM_ENTRIES: u64 = 1_000_000;
+const BYTES_IN_MB: u64 = 1_000_000;
+const VALUE_SIZE: usize = 100;
+/// Number of entries written per RocksDB batch and SQLite transaction while loading the store.
+const LOAD_BATCH_SIZE: u64 = 100_000;
+/// Number of content items stored into the full store.
+const NUM_STORES: u32 = 10_000;
+
+fn main() -> anyhow::Result<()> {
+    let num_entries = env::var("TRIN_BENCH_ENTRIES")
+        .ok()
+        .and_then(|entries| entries.parse().ok())
+        .unwrap_or(DEFAULT_NUM_ENTRIES);
+    let temp_dir = setup_temp_dir()?;
+    let node_id = NodeId::random();
+    let loaded_mb = num_entries * VALUE_SIZE as u64 / BYTES_IN_MB;
+
+    // Create the tables, then bypass `PortalStorage` to load the entries in bulk.
+    let config = PortalStorageConfig::new(loaded_mb, temp_dir.path().to_path_buf(), node_id)?;
+    drop(PortalStorage::new(config.clone(), ProtocolId::History)?);
+    let started = Instant::now();
+    load_entries(&config, node_id, num_entries)?;
+    println!("loaded {num_entries} entries in {:?}", started.elapsed());
+
+    // Restart with 1% less capacity, which evicts the farthest 1% of the entries.
+    let capacity_mb = loaded_mb - (loaded_mb / 100).max(1);
+    let config = PortalStorageConfig {
+        storage_capacity_mb: capacity_mb,
+        ..config
+    };
+    let started = Instant::now();
+    let mut storage = PortalStorage::new(config.clone(), ProtocolId::History)?;
+    println!(
+        "restarted with {capacity_mb}mb capacity in {:?}",
+        started.elapsed()
+    );
+    check_pruned(&config, &storage)?;
+
+    // Store new content into the full store, each evicting the farthest content.
+    let mut latencies = Vec::with_capacity(NUM_STORES as usize);
+    for _ in 0..NUM_STORES {
+        let content_key = IdentityContentKey::new(random_bytes());
+        let started = Instant::now();
+        // Content outside of the radius is rejected, which is part of the workload.
+        let _ = storage.put(content_key, vec![0; VALUE_SIZE]);
+        latencies.push(started.elapsed());
+    }
+    latencies.sort();
+    let total: Duration = latencies.iter().sum();
+    println!(
+        "stored {NUM_STORES} items at capacity: mean {:?}, p50 {:?}, p99 {:?}, max {:?}",
+        total / NUM_STORES,
+        latencies[latencies.len() / 2],
+        latencies[latencies.len() * 99 / 100],
+        latencies[latencies.len() - 1]
+    );
+    check_pruned(&config, &storage)?;
+
+    drop(storage);
+    temp_dir.close()?;
+    Ok(())
+}
+
+/// Writes `num_entries` random entries straight into the history content databases.
+fn load_entries(
+    config: &PortalStorageConfig,
+    node_id: NodeId,
+    num_entries: u64,
+) -> anyhow::Result<()> {
+    let node_id_short = short_id(&node_id.raw());
+    let column_family = config
+        .db
+        .cf_handle(PortalStorage::column_family_name(&ProtocolId::History))
+        .expect("History column family to exist");
+    let mut conn = config.sql_connection_pool.get()?;
+
+    let mut loaded = 0;
+    while loaded < num_entries {
+        let batch_size = LOAD_BATCH_SIZE.min(num_entries - loaded);
+        let mut batch = WriteBatch::default();
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare(&format!(
+                "INSERT INTO {}
+                    (content_id_long, content_id_short, content_key, content_size, distance_short)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                PortalStorage::metadata_table_name(&ProtocolId::History)
+            ))?;
+            for _ in 0..batch_size {
+                // The content id of an identity content key is the key itself.
+                let content_id = random_bytes();
+                let content_id_short = short_id(&content_id);
+                let content_key = hex_encode(content_id).trim_start_matches("0x").to_string();
+                insert.execute(params![
+                    content_id.to_vec(),
+                    content_id_short,
+                    content_key,
+                    VALUE_SIZE,
+                    content_id_short ^ node_id_short
+                ])?;
+                batch.put_cf(column_family, content_id, [0; VALUE_SIZE]);
+            }
+        }
+        config.db.write(batch)?;
+        tx.commit()?;
+        loaded += batch_size;
+    }
+    Ok(())
+}
+
+/// Checks that pruning brought the store back to its capacity, and shrank the radius to the
+/// farthest content that is left. Pruning stops before the item that would take the store under
+/// its capacity, so the store may exceed it by less than one item.
+fn check_pruned(config: &PortalStorageConfig, storage: &PortalStorage) -> anyhow::Result<()> {
+    let table = PortalStorage::metadata_table_name(&ProtocolId::History);
+    let conn = config.sql_connection_pool.get()?;
+    let stored_bytes: f64 = conn.query_row(
+        &format!("SELECT TOTAL(content_size) FROM {table}"),
+        [],
+        |row| row.get(0),
+    )?;
+    let capacity_bytes = config.storage_capacity_mb * BYTES_IN_MB;
+    assert!(
+        (stored_bytes as u64) < capacity_bytes + VALUE_SIZE as u64,
+        "{stored_bytes} bytes stored over a capacity of {capacity_bytes} bytes"
+    );
+
+    let farthest: Vec<u8> = conn.query_row(
+        &format!("SELECT content_id_long FROM {table} ORDER BY distance_short DESC LIMIT 1"),
+        [],
+        |row| row.get(0),
+    )?;
+    let farthest: [u8; 32] = farthest
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Invalid content id in {table}"))?;
+    assert!(storage.radius() < Distance::MAX);
+    assert!(storage.distance_to_content_id(&farthest) <= storage.radius());
+    Ok(())
+}
+
+fn random_bytes() -> [u8; 32] {
+    let mut bytes = [0; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Returns the most significant 4 bytes of `id`, as stored in the metadata table.
+fn short_id(id: &[u8; 32]) -> u32 {
+    u32::from_be_bytes([id[0], id[1], id[2], id[3]])
+}
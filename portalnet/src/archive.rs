@@ -0,0 +1,598 @@
+use std::io::{self, Read, Write};
+
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::{
+    storage::{ContentStore, ContentStoreError, PortalStorage},
+    types::messages::ProtocolId,
+};
+use trin_types::content_key::OverlayContentKey;
+use trin_utils::bytes::hex_encode;
+use trin_validation::validator::Validator;
+
+/// Bytes at the start of every content archive.
+const ARCHIVE_MAGIC: [u8; 8] = *b"TRINARCH";
+
+/// Version of the archive format.
+const ARCHIVE_VERSION: u8 = 2;
+
+/// Size of the archive header: magic, version and protocol id.
+const HEADER_SIZE: usize = ARCHIVE_MAGIC.len() + 1 + 2;
+
+/// Size of the archive trailer: entry count and checksum.
+const TRAILER_SIZE: usize = 8 + 32;
+
+/// Tag that precedes every entry of an archive.
+const ENTRY_TAG: u8 = 0x01;
+
+/// Tag that precedes the trailer, after the last entry of an archive.
+const TRAILER_TAG: u8 = 0x00;
+
+/// Number of content keys read from the store at once while exporting.
+const EXPORT_BATCH_SIZE: u64 = 1000;
+
+/// Number of validated entries inserted into the store at once while importing.
+const IMPORT_BATCH_SIZE: usize = 100;
+
+/// An error from reading or writing a content archive.
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("invalid archive header: {0}")]
+    InvalidHeader(String),
+
+    #[error("invalid archive entry tag {0}")]
+    InvalidEntryTag(u8),
+
+    #[error("archive checksum does not match its entries")]
+    ChecksumMismatch,
+
+    #[error("archive holds {found} content, expected {expected} content")]
+    UnexpectedProtocol {
+        expected: ProtocolId,
+        found: ProtocolId,
+    },
+
+    #[error("content store error: {0}")]
+    Store(#[from] ContentStoreError),
+}
+
+/// The header of a content archive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArchiveHeader {
+    /// The subnetwork of the archived content.
+    pub protocol: ProtocolId,
+}
+
+impl ArchiveHeader {
+    fn to_bytes(&self) -> Result<Vec<u8>, ArchiveError> {
+        let protocol: Vec<u8> = self.protocol.clone().try_into().map_err(|_| {
+            ArchiveError::InvalidHeader(format!("unsupported protocol {}", self.protocol))
+        })?;
+        if protocol.len() != 2 {
+            return Err(ArchiveError::InvalidHeader(format!(
+                "unsupported protocol {}",
+                self.protocol
+            )));
+        }
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        bytes.extend_from_slice(&ARCHIVE_MAGIC);
+        bytes.push(ARCHIVE_VERSION);
+        bytes.extend_from_slice(&protocol);
+        Ok(bytes)
+    }
+
+    fn from_bytes(bytes: &[u8; HEADER_SIZE]) -> Result<Self, ArchiveError> {
+        if bytes[..8] != ARCHIVE_MAGIC {
+            return Err(ArchiveError::InvalidHeader(
+                "not a content archive".to_owned(),
+            ));
+        }
+        if bytes[8] != ARCHIVE_VERSION {
+            return Err(ArchiveError::InvalidHeader(format!(
+                "unsupported version {}",
+                bytes[8]
+            )));
+        }
+        let protocol = format!("0x{:02X}{:02X}", bytes[9], bytes[10]);
+        let protocol: ProtocolId = protocol
+            .parse()
+            .map_err(|_| ArchiveError::InvalidHeader(format!("unknown protocol id {protocol}")))?;
+        Ok(Self { protocol })
+    }
+}
+
+/// The trailer of a content archive, written after the last entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveTrailer {
+    /// Number of entries in the archive.
+    pub num_entries: u64,
+    /// SHA-256 digest of all entries, in order.
+    pub checksum: [u8; 32],
+}
+
+impl ArchiveTrailer {
+    fn to_bytes(&self) -> [u8; TRAILER_SIZE] {
+        let mut bytes = [0; TRAILER_SIZE];
+        bytes[..8].copy_from_slice(&self.num_entries.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.checksum);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; TRAILER_SIZE]) -> Self {
+        let mut num_entries = [0; 8];
+        num_entries.copy_from_slice(&bytes[..8]);
+        let mut checksum = [0; 32];
+        checksum.copy_from_slice(&bytes[8..]);
+        Self {
+            num_entries: u64::from_be_bytes(num_entries),
+            checksum,
+        }
+    }
+}
+
+/// A content key and the content it refers to, as stored in an archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub content_key: Vec<u8>,
+    pub content: Vec<u8>,
+}
+
+/// Writes a content archive entry by entry. The entry count and the checksum go in a trailer
+/// after the last entry, so the archive is written strictly in order, and may be streamed.
+pub struct ArchiveWriter<W: Write> {
+    writer: W,
+    num_entries: u64,
+    hasher: Sha256,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    /// Starts an archive of the content of `protocol`.
+    pub fn new(mut writer: W, protocol: ProtocolId) -> Result<Self, ArchiveError> {
+        writer.write_all(&ArchiveHeader { protocol }.to_bytes()?)?;
+        Ok(Self {
+            writer,
+            num_entries: 0,
+            hasher: Sha256::new(),
+        })
+    }
+
+    /// Appends an entry to the archive.
+    pub fn write_entry(&mut self, content_key: &[u8], content: &[u8]) -> Result<(), ArchiveError> {
+        self.writer.write_all(&[ENTRY_TAG])?;
+        for field in [content_key, content] {
+            let len = u32::try_from(field.len()).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "archive entry too large")
+            })?;
+            let len = len.to_be_bytes();
+            self.writer.write_all(&len)?;
+            self.writer.write_all(field)?;
+            self.hasher.update(len);
+            self.hasher.update(field);
+        }
+        self.num_entries += 1;
+        Ok(())
+    }
+
+    /// Writes the trailer, and returns the trailer and the underlying writer.
+    pub fn finish(mut self) -> Result<(ArchiveTrailer, W), ArchiveError> {
+        let trailer = ArchiveTrailer {
+            num_entries: self.num_entries,
+            checksum: self.hasher.finalize().into(),
+        };
+        self.writer.write_all(&[TRAILER_TAG])?;
+        self.writer.write_all(&trailer.to_bytes())?;
+        self.writer.flush()?;
+        Ok((trailer, self.writer))
+    }
+}
+
+/// Reads the entries of a content archive in order. The entry count and the checksum are verified
+/// against the trailer once the last entry is read.
+pub struct ArchiveReader<R: Read> {
+    reader: R,
+    header: ArchiveHeader,
+    num_read: u64,
+    hasher: Sha256,
+    finished: bool,
+}
+
+impl<R: Read> ArchiveReader<R> {
+    /// Reads the header of the archive at the current position of `reader`.
+    pub fn new(mut reader: R) -> Result<Self, ArchiveError> {
+        let mut header = [0; HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+        Ok(Self {
+            reader,
+            header: ArchiveHeader::from_bytes(&header)?,
+            num_read: 0,
+            hasher: Sha256::new(),
+            finished: false,
+        })
+    }
+
+    pub fn header(&self) -> &ArchiveHeader {
+        &self.header
+    }
+
+    /// Reads the next entry, or returns `None` after the last entry if the trailer matches the
+    /// entries that were read.
+    pub fn next_entry(&mut self) -> Result<Option<ArchiveEntry>, ArchiveError> {
+        if self.finished {
+            return Ok(None);
+        }
+        let mut tag = [0; 1];
+        self.reader.read_exact(&mut tag)?;
+        match tag[0] {
+            ENTRY_TAG => {
+                let content_key = self.read_field()?;
+                let content = self.read_field()?;
+                self.num_read += 1;
+                Ok(Some(ArchiveEntry {
+                    content_key,
+                    content,
+                }))
+            }
+            TRAILER_TAG => {
+                let mut trailer = [0; TRAILER_SIZE];
+                self.reader.read_exact(&mut trailer)?;
+                let trailer = ArchiveTrailer::from_bytes(&trailer);
+                let checksum: [u8; 32] = self.hasher.clone().finalize().into();
+                if trailer.num_entries != self.num_read || trailer.checksum != checksum {
+                    return Err(ArchiveError::ChecksumMismatch);
+                }
+                self.finished = true;
+                Ok(None)
+            }
+            tag => Err(ArchiveError::InvalidEntryTag(tag)),
+        }
+    }
+
+    /// Reads all remaining entries, and checks them against the trailer.
+    pub fn verify(mut self) -> Result<(), ArchiveError> {
+        while self.next_entry()?.is_some() {}
+        Ok(())
+    }
+
+    fn read_field(&mut self) -> Result<Vec<u8>, ArchiveError> {
+        let mut len = [0; 4];
+        self.reader.read_exact(&mut len)?;
+        // Read rather than allocate the announced length, which may be corrupted.
+        let field_len = u32::from_be_bytes(len) as u64;
+        let mut field = vec![];
+        (&mut self.reader).take(field_len).read_to_end(&mut field)?;
+        if field.len() as u64 != field_len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        self.hasher.update(len);
+        self.hasher.update(&field);
+        Ok(field)
+    }
+}
+
+/// Selects the stored content to export.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExportFilter {
+    /// SSZ union selectors of the content types to export, or every content type if empty.
+    pub content_types: Vec<u8>,
+    /// Lowest content id to export, inclusive.
+    pub min_content_id: Option<[u8; 32]>,
+    /// Highest content id to export, inclusive.
+    pub max_content_id: Option<[u8; 32]>,
+}
+
+impl ExportFilter {
+    fn matches(&self, content_id: &[u8; 32], content_key: &[u8]) -> bool {
+        let is_selected_type = self.content_types.is_empty()
+            || content_key.first().map_or(false, |content_type| {
+                self.content_types.contains(content_type)
+            });
+        is_selected_type
+            && self.min_content_id.map_or(true, |min| *content_id >= min)
+            && self.max_content_id.map_or(true, |max| *content_id <= max)
+    }
+}
+
+/// The outcome of an import of a content archive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Number of entries that were stored.
+    pub num_imported: u64,
+    /// Number of entries that failed validation.
+    pub num_invalid: u64,
+    /// Number of valid entries that the store declined, e.g. because they fall outside its radius.
+    pub num_rejected: u64,
+}
+
+/// Writes the content of `storage` that matches `filter` to an archive, in ascending order of
+/// content id. Returns the trailer of the archive.
+pub fn export_content<W: Write>(
+    storage: &PortalStorage,
+    writer: W,
+    filter: &ExportFilter,
+) -> Result<ArchiveTrailer, ArchiveError> {
+    let mut archive = ArchiveWriter::new(writer, storage.protocol().clone())?;
+    let mut after = None;
+    let mut past_max_content_id = false;
+    while !past_max_content_id {
+        let batch = storage.content_keys_after(after, EXPORT_BATCH_SIZE)?;
+        match batch.last() {
+            Some((last_content_id, _)) => after = Some(*last_content_id),
+            None => break,
+        }
+
+        let mut selected = Vec::with_capacity(batch.len());
+        for (content_id, content_key) in batch {
+            // Content ids are in ascending order, so nothing after this one is selected.
+            if filter.max_content_id.map_or(false, |max| content_id > max) {
+                past_max_content_id = true;
+                break;
+            }
+            if filter.matches(&content_id, &content_key) {
+                selected.push((content_id, content_key));
+            }
+        }
+        write_entries(selected, storage, &mut archive)?;
+    }
+    let (trailer, _) = archive.finish()?;
+    Ok(trailer)
+}
+
+/// Reads the content of the `selected` keys from `storage` and appends it to `archive`.
+fn write_entries<W: Write>(
+    selected: Vec<([u8; 32], Vec<u8>)>,
+    storage: &PortalStorage,
+    archive: &mut ArchiveWriter<W>,
+) -> Result<(), ArchiveError> {
+    let content_ids: Vec<[u8; 32]> = selected.iter().map(|(content_id, _)| *content_id).collect();
+    let contents = storage.get_by_content_ids(&content_ids)?;
+    for ((content_id, content_key), content) in selected.into_iter().zip(contents) {
+        match content {
+            Some(content) => archive.write_entry(&content_key, &content)?,
+            // The content was evicted since its key was read.
+            None => debug!(
+                content.id = hex_encode(content_id),
+                "Skipping evicted content during export"
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Validates every entry of the archive in `reader` with `validator`, and inserts the valid
+/// entries into `storage`.
+///
+/// The archive is read once, in order, so it may be streamed. Its checksum is verified
+/// incrementally, so a truncated or corrupted archive is only detected at its end, and fails the
+/// import after some of the entries before it may have been stored. Every entry is validated on
+/// its own before it is stored, so those entries are still valid content.
+pub async fn import_content<TContentKey, TValidator, R>(
+    storage: &mut PortalStorage,
+    reader: R,
+    validator: &TValidator,
+) -> Result<ImportSummary, ArchiveError>
+where
+    TContentKey: OverlayContentKey,
+    TValidator: Validator<TContentKey>,
+    R: Read,
+{
+    let mut archive = ArchiveReader::new(reader)?;
+    let protocol = &archive.header().protocol;
+    if protocol != storage.protocol() {
+        return Err(ArchiveError::UnexpectedProtocol {
+            expected: storage.protocol().clone(),
+            found: protocol.clone(),
+        });
+    }
+
+    let mut summary = ImportSummary::default();
+    let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    while let Some(entry) = archive.next_entry()? {
+        let key_hex = hex_encode(&entry.content_key);
+        let content_key = match TContentKey::try_from(entry.content_key) {
+            Ok(content_key) => content_key,
+            Err(_) => {
+                warn!(
+                    content.key = key_hex,
+                    "Skipping archived content with invalid key"
+                );
+                summary.num_invalid += 1;
+                continue;
+            }
+        };
+        if let Err(err) = validator
+            .validate_content(&content_key, &entry.content)
+            .await
+        {
+            warn!(
+                content.key = key_hex,
+                error = %err,
+                "Skipping archived content that failed validation"
+            );
+            summary.num_invalid += 1;
+            continue;
+        }
+        batch.push((content_key, entry.content));
+        if batch.len() == IMPORT_BATCH_SIZE {
+            store_batch(storage, &mut batch, &mut summary)?;
+        }
+    }
+    store_batch(storage, &mut batch, &mut summary)?;
+    Ok(summary)
+}
+
+/// Inserts the validated entries of `batch` into `storage`, and counts them in `summary`.
+fn store_batch<TContentKey: OverlayContentKey>(
+    storage: &mut PortalStorage,
+    batch: &mut Vec<(TContentKey, Vec<u8>)>,
+    summary: &mut ImportSummary,
+) -> Result<(), ArchiveError> {
+    for result in storage.put_batch(std::mem::take(batch))? {
+        match result {
+            Ok(_) => summary.num_imported += 1,
+            Err(err) => {
+                debug!(error = %err, "Store declined archived content");
+                summary.num_rejected += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::{
+        storage::PortalStorageConfig,
+        utils::{
+            db::setup_temp_dir,
+            validator::{TestValidator, INVALID_CONTENT},
+        },
+    };
+    use trin_types::content_key::IdentityContentKey;
+
+    #[test]
+    fn archive_round_trip() {
+        // Archives are written and read in order, without seeking.
+        let mut archive = ArchiveWriter::new(vec![], ProtocolId::History).unwrap();
+        archive.write_entry(&[0x00, 0x01], &[0x02; 10]).unwrap();
+        archive.write_entry(&[0x01], &[]).unwrap();
+        let (trailer, bytes) = archive.finish().unwrap();
+        assert_eq!(trailer.num_entries, 2);
+
+        let mut archive = ArchiveReader::new(&bytes[..]).unwrap();
+        assert_eq!(archive.header().protocol, ProtocolId::History);
+        assert_eq!(
+            archive.next_entry().unwrap(),
+            Some(ArchiveEntry {
+                content_key: vec![0x00, 0x01],
+                content: vec![0x02; 10],
+            })
+        );
+        assert_eq!(
+            archive.next_entry().unwrap(),
+            Some(ArchiveEntry {
+                content_key: vec![0x01],
+                content: vec![],
+            })
+        );
+        assert_eq!(archive.next_entry().unwrap(), None);
+        assert_eq!(archive.next_entry().unwrap(), None);
+    }
+
+    #[test]
+    fn corrupted_archive_fails_verification() {
+        let mut archive = ArchiveWriter::new(vec![], ProtocolId::State).unwrap();
+        archive.write_entry(&[0x00], &[0x01; 10]).unwrap();
+        let (_, mut bytes) = archive.finish().unwrap();
+
+        // Corrupt the checksum in the trailer.
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let archive = ArchiveReader::new(&bytes[..]).unwrap();
+        assert!(matches!(
+            archive.verify(),
+            Err(ArchiveError::ChecksumMismatch)
+        ));
+
+        // A truncated archive ends early.
+        let archive = ArchiveReader::new(&bytes[..last]).unwrap();
+        assert!(matches!(archive.verify(), Err(ArchiveError::Io(_))));
+
+        // An archive cut off right after an entry has no trailer.
+        let archive = ArchiveReader::new(&bytes[..last - TRAILER_SIZE]).unwrap();
+        assert!(matches!(archive.verify(), Err(ArchiveError::Io(_))));
+
+        // Corrupt the tag of the first entry.
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_SIZE] = 0x02;
+        let archive = ArchiveReader::new(&corrupted[..]).unwrap();
+        assert!(matches!(
+            archive.verify(),
+            Err(ArchiveError::InvalidEntryTag(0x02))
+        ));
+
+        bytes[0] = 0x00;
+        assert!(matches!(
+            ArchiveReader::new(&bytes[..]),
+            Err(ArchiveError::InvalidHeader(_))
+        ));
+    }
+
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn export_and_import_content() {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = discv5::enr::NodeId::random();
+        let storage_config =
+            PortalStorageConfig::new(10, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config.clone(), ProtocolId::History).unwrap();
+        for i in 0..250u8 {
+            let content = if i % 10 == 0 {
+                INVALID_CONTENT.to_vec()
+            } else {
+                vec![i]
+            };
+            storage
+                .put(IdentityContentKey::new([i; 32]), content)
+                .unwrap();
+        }
+
+        // Filter by content type, i.e. the first byte of the content key, and by content id.
+        let filter = ExportFilter {
+            content_types: (0..200).collect(),
+            min_content_id: Some([50; 32]),
+            max_content_id: None,
+        };
+        let mut archive = vec![];
+        let trailer = export_content(&storage, &mut archive, &filter).unwrap();
+        assert_eq!(trailer.num_entries, 150);
+
+        // Import into the state subnetwork is refused.
+        let mut state_storage =
+            PortalStorage::new(storage_config.clone(), ProtocolId::State).unwrap();
+        assert!(matches!(
+            import_content::<IdentityContentKey, _, _>(
+                &mut state_storage,
+                &archive[..],
+                &TestValidator
+            )
+            .await,
+            Err(ArchiveError::UnexpectedProtocol { .. })
+        ));
+
+        // Empty the history subnetwork, then import the archive again.
+        for i in 0..250u8 {
+            storage.evict([i; 32]).unwrap();
+        }
+        let summary =
+            import_content::<IdentityContentKey, _, _>(&mut storage, &archive[..], &TestValidator)
+                .await
+                .unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                num_imported: 135,
+                num_invalid: 15,
+                num_rejected: 0,
+            }
+        );
+        for i in 0..250u8 {
+            let stored = storage.get(&IdentityContentKey::new([i; 32])).unwrap();
+            let expected = (50..200).contains(&i) && i % 10 != 0;
+            assert_eq!(stored, expected.then(|| vec![i]));
+        }
+
+        std::mem::drop(storage);
+        std::mem::drop(state_storage);
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+use lru::LruCache;
+
+/// Default maximum total size of the content held by a `ContentCache`, in bytes.
+pub const DEFAULT_CONTENT_CACHE_CAPACITY_BYTES: u64 = 25 * 1000 * 1000;
+
+/// A size-bounded, least-recently-used cache of validated content fetched from the network.
+///
+/// Unlike the `ContentStore`, the cache is not bound by the local data radius. It holds the
+/// content from recent lookups, so that repeated requests for the same content can be answered
+/// without running a network query.
+#[derive(Debug)]
+pub struct ContentCache {
+    /// The cached content, keyed by content ID.
+    entries: LruCache<[u8; 32], Vec<u8>>,
+    /// The maximum total size of the cached content, in bytes.
+    capacity_bytes: u64,
+    /// The current total size of the cached content, in bytes.
+    size_bytes: u64,
+    /// Number of lookups answered by the cache.
+    hits: u64,
+    /// Number of lookups that the cache could not answer.
+    misses: u64,
+}
+
+impl ContentCache {
+    /// Constructs a new cache holding at most `capacity_bytes` of content. A capacity of zero
+    /// disables the cache.
+    pub fn new(capacity_bytes: u64) -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            capacity_bytes,
+            size_bytes: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns whether the cache is able to hold any content.
+    pub fn is_enabled(&self) -> bool {
+        self.capacity_bytes > 0
+    }
+
+    /// Looks up the content with `content_id`, marking it as most recently used.
+    pub fn get(&mut self, content_id: &[u8; 32]) -> Option<Vec<u8>> {
+        match self.entries.get(content_id) {
+            Some(content) => {
+                self.hits += 1;
+                Some(content.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Inserts `content` with `content_id`, evicting the least recently used content until the
+    /// cache is within its capacity. Content larger than the capacity is not cached.
+    pub fn put(&mut self, content_id: [u8; 32], content: Vec<u8>) {
+        let content_size = content.len() as u64;
+        if !self.is_enabled() || content_size > self.capacity_bytes {
+            return;
+        }
+
+        if let Some(previous) = self.entries.put(content_id, content) {
+            self.size_bytes -= previous.len() as u64;
+        }
+        self.size_bytes += content_size;
+
+        while self.size_bytes > self.capacity_bytes {
+            match self.entries.pop_lru() {
+                Some((_, evicted)) => self.size_bytes -= evicted.len() as u64,
+                None => break,
+            }
+        }
+    }
+
+    /// Returns the number of cached items.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the cache holds no content.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the current total size of the cached content, in bytes.
+    pub fn size_bytes(&self) -> u64 {
+        self.size_bytes
+    }
+
+    /// Returns the fraction of lookups answered by the cache.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+impl Default for ContentCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CONTENT_CACHE_CAPACITY_BYTES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used_content() {
+        let mut cache = ContentCache::new(10);
+        cache.put([1; 32], vec![0; 4]);
+        cache.put([2; 32], vec![0; 4]);
+
+        // Touch the first item, so that the second is evicted.
+        assert!(cache.get(&[1; 32]).is_some());
+        cache.put([3; 32], vec![0; 4]);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.size_bytes(), 8);
+        assert!(cache.get(&[1; 32]).is_some());
+        assert!(cache.get(&[2; 32]).is_none());
+        assert!(cache.get(&[3; 32]).is_some());
+    }
+
+    #[test]
+    fn replacing_content_updates_size() {
+        let mut cache = ContentCache::new(10);
+        cache.put([1; 32], vec![0; 4]);
+        cache.put([1; 32], vec![0; 6]);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.size_bytes(), 6);
+    }
+
+    #[test]
+    fn oversized_content_is_not_cached() {
+        let mut cache = ContentCache::new(10);
+        cache.put([1; 32], vec![0; 11]);
+        assert!(cache.is_empty());
+
+        let mut disabled = ContentCache::new(0);
+        assert!(!disabled.is_enabled());
+        disabled.put([1; 32], vec![0; 1]);
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn hit_rate() {
+        let mut cache = ContentCache::new(10);
+        assert_eq!(cache.hit_rate(), 0.0);
+        cache.put([1; 32], vec![0; 1]);
+        assert!(cache.get(&[1; 32]).is_some());
+        assert!(cache.get(&[2; 32]).is_none());
+        assert_eq!(cache.hit_rate(), 0.5);
+    }
+}
@@ -2,20 +2,29 @@ use anyhow::anyhow;
 use async_trait::async_trait;
 use discv5::{
     enr::{CombinedKey, EnrBuilder, NodeId},
-    Discv5, Discv5ConfigBuilder, Discv5Event, RequestError, TalkRequest,
+    Discv5, Discv5ConfigBuilder, Discv5Event, IpMode as Discv5IpMode, RequestError, TalkRequest,
 };
 use lru::LruCache;
 use parking_lot::RwLock;
 use serde_json::{json, Value};
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, info, warn};
 use utp_rs::{cid::ConnectionPeer, udp::AsyncUdpSocket};
 
-use super::types::messages::{PortalnetConfig, ProtocolId};
+use super::types::{
+    messages::{PortalnetConfig, ProtocolId},
+    payload_extension::{ENR_PAYLOAD_EXTENSION_KEY, PAYLOAD_EXTENSION_VERSION},
+};
 use crate::socket;
 use ethportal_api::{NodeId as EthportalNodeId, NodeInfo};
 use std::str::FromStr;
-use std::{convert::TryFrom, fmt, io, net::SocketAddr, sync::Arc};
+use std::{
+    convert::TryFrom,
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+use trin_types::cli::IpMode;
 use trin_types::enr::Enr;
 use trin_utils::bytes::hex_encode;
 use trin_utils::version::get_trin_version;
@@ -23,6 +32,9 @@ use trin_utils::version::get_trin_version;
 /// Size of the buffer of the Discv5 TALKREQ channel.
 const TALKREQ_CHANNEL_BUFFER: usize = 100;
 
+/// Size of the buffer of the external socket address update channel.
+const EXTERNAL_ADDR_CHANNEL_BUFFER: usize = 16;
+
 /// ENR key for portal network client version.
 const ENR_PORTAL_CLIENT_KEY: &str = "c";
 
@@ -43,6 +55,10 @@ pub struct Discovery {
     discv5: Discv5,
     /// A cache of the latest observed `NodeAddress` for a node ID.
     node_addr_cache: Arc<RwLock<LruCache<NodeId, NodeAddress>>>,
+    /// The sender half of a channel for updates to the external socket address in the local ENR.
+    external_addr_tx: broadcast::Sender<SocketAddr>,
+    /// The local port mapped on the gateway through UPnP, if any.
+    upnp_port: Option<u16>,
     /// Indicates if the Discv5 service has been started.
     pub started: bool,
     /// The socket address that the Discv5 service listens on.
@@ -63,25 +79,49 @@ impl fmt::Debug for Discovery {
 
 impl Discovery {
     pub fn new(portal_config: PortalnetConfig) -> Result<Self, String> {
-        let listen_all_ips = SocketAddr::new(
-            "0.0.0.0"
-                .parse()
-                .expect("Parsing static socket address to work"),
-            portal_config.listen_port,
-        );
+        let ip_mode = portal_config.ip_mode;
+        if let Some(external_addr) = portal_config.external_addr {
+            let supported = match external_addr {
+                SocketAddr::V4(_) => ip_mode.supports_ip4(),
+                SocketAddr::V6(_) => ip_mode.supports_ip6(),
+            };
+            if !supported {
+                return Err(format!(
+                    "External address {external_addr} is not supported in ip mode {ip_mode}"
+                ));
+            }
+        }
+
+        // A dual-stack node listens on a single IPv6 socket that also accepts IPv4 traffic.
+        let listen_ip: IpAddr = match ip_mode {
+            IpMode::Ip4 => Ipv4Addr::UNSPECIFIED.into(),
+            IpMode::Ip6 | IpMode::DualStack => Ipv6Addr::UNSPECIFIED.into(),
+        };
+        let listen_all_ips = SocketAddr::new(listen_ip, portal_config.listen_port);
 
-        let (enr_address, enr_port) = if portal_config.no_stun {
-            (None, portal_config.listen_port)
+        // UPnP mappings are only requested for IPv4 gateways.
+        let upnp_addr = if portal_config.upnp && ip_mode.supports_ip4() {
+            upnp_map_port(portal_config.listen_port)
         } else {
-            let known_external = portal_config
-                .external_addr
-                .or_else(|| socket::stun_for_external(&listen_all_ips));
+            None
+        };
 
-            match known_external {
-                Some(socket) => (Some(socket.ip()), socket.port()),
-                None => (None, portal_config.listen_port),
-            }
+        // Look up the external address of each IP version independently, so that a dual-stack node
+        // advertises both of them.
+        let external_ip4 = match portal_config.external_addr {
+            Some(addr @ SocketAddr::V4(_)) => Some(addr),
+            _ if !ip_mode.supports_ip4() => None,
+            // The STUN servers are only queried over IPv4.
+            _ => upnp_addr.or_else(|| {
+                if portal_config.no_stun {
+                    return None;
+                }
+                let stun_socket =
+                    SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), portal_config.listen_port);
+                socket::stun_for_external(&stun_socket, &portal_config.stun_servers)
+            }),
         };
+        let external_ip6 = portal_config.external_addr.filter(SocketAddr::is_ipv6);
 
         let enr_key =
             CombinedKey::secp256k1_from_bytes(portal_config.private_key.0.clone().as_mut_slice())
@@ -89,22 +129,49 @@ impl Discovery {
 
         let enr = {
             let mut builder = EnrBuilder::new("v4");
-            if let Some(ip_address) = enr_address {
-                builder.ip(ip_address);
+            // Advertise a UDP port for each IP version in use, and the external address of each IP
+            // version that is known.
+            if let Some(addr) = external_ip4 {
+                builder.ip(addr.ip());
+            }
+            if let Some(addr) = external_ip6 {
+                builder.ip(addr.ip());
+            }
+            if ip_mode.supports_ip4() {
+                builder.udp4(external_ip4.map_or(portal_config.listen_port, |addr| addr.port()));
+            }
+            if ip_mode.supports_ip6() {
+                builder.udp6(external_ip6.map_or(portal_config.listen_port, |addr| addr.port()));
             }
-            builder.udp4(enr_port);
 
             let trin_version = get_trin_version();
             // Use "t" as short-hand for "Trin" to save bytes in ENR.
             let client_info = format!("t {trin_version}");
             // Use "c" as short-hand for "client".
             builder.add_value(ENR_PORTAL_CLIENT_KEY, client_info.as_bytes());
+            // Advertise support for extensions of the Ping and Pong custom payload.
+            builder.add_value(ENR_PAYLOAD_EXTENSION_KEY, &[PAYLOAD_EXTENSION_VERSION][..]);
             builder
                 .build(&enr_key)
                 .map_err(|e| format!("When adding key to servers ENR: {e:?}"))?
         };
 
-        let discv5_config = Discv5ConfigBuilder::default().build();
+        let discv5_ip_mode = match ip_mode {
+            IpMode::Ip4 => Discv5IpMode::Ip4,
+            IpMode::Ip6 => Discv5IpMode::Ip6 {
+                enable_mapped_addresses: false,
+            },
+            IpMode::DualStack => Discv5IpMode::Ip6 {
+                enable_mapped_addresses: true,
+            },
+        };
+        // Unless the external address is set explicitly, update the local ENR to the socket
+        // address observed by a majority of peers (as reported in discv5 PONG messages).
+        let discv5_config = Discv5ConfigBuilder::default()
+            .ip_mode(discv5_ip_mode)
+            .enr_update(portal_config.external_addr.is_none())
+            .enr_peer_update_min(portal_config.address_vote_threshold)
+            .build();
         let discv5 = Discv5::new(enr, enr_key, discv5_config)
             .map_err(|e| format!("Failed to create discv5 instance: {e}"))?;
 
@@ -117,9 +184,13 @@ impl Discovery {
         let node_addr_cache = LruCache::new(portal_config.node_addr_cache_capacity);
         let node_addr_cache = Arc::new(RwLock::new(node_addr_cache));
 
+        let (external_addr_tx, _) = broadcast::channel(EXTERNAL_ADDR_CHANNEL_BUFFER);
+
         Ok(Self {
             discv5,
             node_addr_cache,
+            external_addr_tx,
+            upnp_port: upnp_addr.map(|_| portal_config.listen_port),
             started: false,
             listen_socket: listen_all_ips,
         })
@@ -147,6 +218,11 @@ impl Discovery {
         let (talk_req_tx, talk_req_rx) = mpsc::channel(TALKREQ_CHANNEL_BUFFER);
 
         let node_addr_cache = Arc::clone(&self.node_addr_cache);
+        let external_addr_tx = self.external_addr_tx.clone();
+
+        if let Some(port) = self.upnp_port {
+            spawn_upnp_renewal(port);
+        }
 
         tokio::spawn(async move {
             while let Some(event) = event_rx.recv().await {
@@ -156,6 +232,7 @@ impl Discovery {
                         let _ = talk_req_tx.send(talk_req).await;
                     }
                     Discv5Event::SessionEstablished(enr, socket_addr) => {
+                        let socket_addr = to_canonical_socket_addr(socket_addr);
                         if let Some(old) = node_addr_cache.write().put(
                             enr.node_id(),
                             NodeAddress {
@@ -172,6 +249,11 @@ impl Discovery {
                             tracing::debug!(addr = ?(enr, socket_addr), "node address cached");
                         }
                     }
+                    Discv5Event::SocketUpdated(socket_addr) => {
+                        // Discv5 has updated the local ENR and bumped its sequence number.
+                        info!(addr = %socket_addr, "External socket address updated");
+                        let _ = external_addr_tx.send(socket_addr);
+                    }
                     _ => continue,
                 }
             }
@@ -196,11 +278,14 @@ impl Discovery {
             enr: Enr::from_str(&self.discv5.local_enr().to_base64())
                 .map_err(|err| anyhow!("{err}"))?,
             node_id: EthportalNodeId::from(self.discv5.local_enr().node_id().raw()),
-            ip: self
-                .discv5
-                .local_enr()
-                .ip4()
-                .map_or(Some("None".to_owned()), |ip| Some(ip.to_string())),
+            ip: {
+                let local_enr = self.discv5.local_enr();
+                let ip = local_enr
+                    .ip4()
+                    .map(IpAddr::from)
+                    .or_else(|| local_enr.ip6().map(IpAddr::from));
+                ip.map_or(Some("None".to_owned()), |ip| Some(ip.to_string()))
+            },
         })
     }
 
@@ -247,6 +332,12 @@ impl Discovery {
         self.discv5.add_enr(enr)
     }
 
+    /// Returns a receiver for updates to the external socket address in the local ENR. An update
+    /// is sent after the local ENR has been updated.
+    pub fn subscribe_external_addr_updates(&self) -> broadcast::Receiver<SocketAddr> {
+        self.external_addr_tx.subscribe()
+    }
+
     /// Returns the cached `NodeAddress` or `None` if not cached.
     pub fn cached_node_addr(&self, node_id: &NodeId) -> Option<NodeAddress> {
         self.node_addr_cache.write().get(node_id).cloned()
@@ -267,6 +358,99 @@ impl Discovery {
     }
 }
 
+/// The transport of overlay network messages.
+///
+/// `Discovery` sends messages as Discovery v5 TALKREQ messages. Other implementations, such as
+/// the simulated transport in `crate::sim`, allow the overlay service to run without a network.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Returns the ENR of the local node.
+    fn local_enr(&self) -> Enr;
+
+    /// Returns the cached `NodeAddress` or `None` if not cached.
+    fn cached_node_addr(&self, node_id: &NodeId) -> Option<NodeAddress>;
+
+    /// Returns a receiver for updates to the external socket address in the local ENR.
+    fn subscribe_external_addr_updates(&self) -> broadcast::Receiver<SocketAddr>;
+
+    /// Sends a TALKREQ message to `enr`.
+    async fn send_talk_req(
+        &self,
+        enr: Enr,
+        protocol: ProtocolId,
+        request: ProtocolRequest,
+    ) -> Result<Vec<u8>, RequestError>;
+}
+
+#[async_trait]
+impl Transport for Discovery {
+    fn local_enr(&self) -> Enr {
+        Discovery::local_enr(self)
+    }
+
+    fn cached_node_addr(&self, node_id: &NodeId) -> Option<NodeAddress> {
+        Discovery::cached_node_addr(self, node_id)
+    }
+
+    fn subscribe_external_addr_updates(&self) -> broadcast::Receiver<SocketAddr> {
+        Discovery::subscribe_external_addr_updates(self)
+    }
+
+    async fn send_talk_req(
+        &self,
+        enr: Enr,
+        protocol: ProtocolId,
+        request: ProtocolRequest,
+    ) -> Result<Vec<u8>, RequestError> {
+        Discovery::send_talk_req(self, enr, protocol, request).await
+    }
+}
+
+/// Requests a UPnP mapping of `port` on the local gateway. Returns the external address of the
+/// mapping.
+#[cfg(feature = "upnp")]
+fn upnp_map_port(port: u16) -> Option<SocketAddr> {
+    socket::upnp::map_port(port)
+}
+
+#[cfg(not(feature = "upnp"))]
+fn upnp_map_port(_port: u16) -> Option<SocketAddr> {
+    warn!("UPnP is not supported by this build. Rebuild with the 'upnp' feature to enable it.");
+    None
+}
+
+/// Renews the UPnP mapping of `port` before its lease expires.
+#[cfg(feature = "upnp")]
+fn spawn_upnp_renewal(port: u16) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(socket::upnp::LEASE_DURATION / 2);
+        // The first tick completes immediately, and the mapping was just created.
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let renewal = tokio::task::spawn_blocking(move || socket::upnp::map_port(port));
+            if !matches!(renewal.await, Ok(Some(_))) {
+                warn!(port, "Unable to renew UPnP port mapping");
+            }
+        }
+    });
+}
+
+#[cfg(not(feature = "upnp"))]
+fn spawn_upnp_renewal(_port: u16) {}
+
+/// Converts an IPv4-mapped IPv6 socket address, as observed by a dual-stack socket, into the
+/// equivalent IPv4 socket address. Other socket addresses are returned unchanged.
+fn to_canonical_socket_addr(socket_addr: SocketAddr) -> SocketAddr {
+    match socket_addr {
+        SocketAddr::V6(addr) => match addr.ip().to_ipv4_mapped() {
+            Some(ip) => SocketAddr::new(ip.into(), addr.port()),
+            None => socket_addr,
+        },
+        SocketAddr::V4(_) => socket_addr,
+    }
+}
+
 pub struct Discv5UdpSocket {
     // `Mutex` for interior mutability.
     // TODO: Figure out a better mechanism here. The socket is the only holder of the lock.
@@ -1,10 +1,12 @@
 use std::str::FromStr;
 
-use discv5::TalkRequest;
+use discv5::{enr::NodeId, TalkRequest};
 use tokio::sync::mpsc;
 use tracing::{error, warn};
 
 use super::types::messages::ProtocolId;
+use crate::metrics::RateLimitMetrics;
+use crate::rate_limit::{RateLimitConfig, RateLimiter, RequestKind, ThrottleAction};
 use trin_utils::bytes::{hex_encode, hex_encode_upper};
 
 /// Main handler for portal network events
@@ -17,6 +19,12 @@ pub struct PortalnetEvents {
     pub state_overlay_sender: Option<mpsc::UnboundedSender<TalkRequest>>,
     /// Send TalkReq events with "utp" protocol id to `UtpListener`
     pub utp_talk_reqs: mpsc::UnboundedSender<TalkRequest>,
+    /// Per-peer rate limiter for history network requests
+    history_rate_limiter: RateLimiter,
+    /// Per-peer rate limiter for state network requests
+    state_rate_limiter: RateLimiter,
+    /// Metrics for throttled requests
+    rate_limit_metrics: RateLimitMetrics,
 }
 
 impl PortalnetEvents {
@@ -25,12 +33,17 @@ impl PortalnetEvents {
         history_overlay_sender: Option<mpsc::UnboundedSender<TalkRequest>>,
         state_overlay_sender: Option<mpsc::UnboundedSender<TalkRequest>>,
         utp_talk_reqs: mpsc::UnboundedSender<TalkRequest>,
+        history_rate_limit: RateLimitConfig,
+        state_rate_limit: RateLimitConfig,
     ) -> Self {
         Self {
             talk_req_receiver,
             history_overlay_sender,
             state_overlay_sender,
             utp_talk_reqs,
+            history_rate_limiter: RateLimiter::new(ProtocolId::History, history_rate_limit),
+            state_rate_limiter: RateLimiter::new(ProtocolId::State, state_rate_limit),
+            rate_limit_metrics: RateLimitMetrics::new(),
         }
     }
 
@@ -41,10 +54,44 @@ impl PortalnetEvents {
         }
     }
 
+    /// Checks a talk request from `node_id` against the rate limit of its overlay network. Returns
+    /// the action to take if the request exceeds the limit, or `None` if it may be dispatched.
+    fn throttle(
+        &mut self,
+        protocol: &ProtocolId,
+        node_id: NodeId,
+        body: &[u8],
+    ) -> Option<ThrottleAction> {
+        let rate_limiter = match protocol {
+            ProtocolId::History => &mut self.history_rate_limiter,
+            ProtocolId::State => &mut self.state_rate_limiter,
+            _ => return None,
+        };
+        let kind = RequestKind::from_talk_req_body(body);
+        if rate_limiter.check(node_id, kind) {
+            return None;
+        }
+        self.rate_limit_metrics
+            .report_throttled_request(protocol, kind);
+        Some(rate_limiter.throttle_action())
+    }
+
     /// Dispatch Discv5 TalkRequest event to overlay networks or uTP socket
-    fn dispatch_discv5_talk_req(&self, request: TalkRequest) {
+    fn dispatch_discv5_talk_req(&mut self, request: TalkRequest) {
         let protocol_id = ProtocolId::from_str(&hex_encode_upper(request.protocol()));
 
+        // Apply the per-peer rate limit of the overlay network before dispatch.
+        if let Ok(protocol) = &protocol_id {
+            if let Some(action) = self.throttle(protocol, *request.node_id(), request.body()) {
+                if action == ThrottleAction::EmptyResponse {
+                    if let Err(err) = request.respond(vec![]) {
+                        warn!(%err, "Error responding to throttled talk request");
+                    }
+                }
+                return;
+            }
+        }
+
         match protocol_id {
             Ok(protocol) => match protocol {
                 ProtocolId::History => {
@@ -87,3 +134,48 @@ impl PortalnetEvents {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::rate_limit::RateLimit;
+
+    #[tokio::test]
+    async fn throttles_requests_over_the_configured_limit() {
+        let (_, talk_req_rx) = mpsc::channel(1);
+        let (utp_talk_reqs, _) = mpsc::unbounded_channel();
+        let history_rate_limit = RateLimitConfig {
+            find_content: Some(RateLimit::new(1, 1.0)),
+            throttle_action: ThrottleAction::EmptyResponse,
+            ..RateLimitConfig::unlimited()
+        };
+        let mut events = PortalnetEvents::new(
+            talk_req_rx,
+            None,
+            None,
+            utp_talk_reqs,
+            history_rate_limit,
+            RateLimitConfig::unlimited(),
+        )
+        .await;
+
+        let peer = NodeId::random();
+        let find_content = [4];
+        assert_eq!(
+            events.throttle(&ProtocolId::History, peer, &find_content),
+            None
+        );
+        assert_eq!(
+            events.throttle(&ProtocolId::History, peer, &find_content),
+            Some(ThrottleAction::EmptyResponse)
+        );
+
+        // Other networks and uTP packets are limited separately.
+        assert_eq!(
+            events.throttle(&ProtocolId::State, peer, &find_content),
+            None
+        );
+        assert_eq!(events.throttle(&ProtocolId::Utp, peer, &find_content), None);
+    }
+}
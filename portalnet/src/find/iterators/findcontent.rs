@@ -27,7 +27,7 @@ use std::time::Instant;
 use discv5::kbucket::{Distance, Key};
 
 use super::super::query_pool::QueryState;
-use super::query::{Query, QueryConfig, QueryPeer, QueryPeerState, QueryProgress};
+use super::query::{PeerLatencyHint, Query, QueryConfig, QueryPeer, QueryPeerState, QueryProgress};
 
 pub enum FindContentQueryResponse<TNodeId> {
     ClosestNodes(Vec<TNodeId>),
@@ -70,6 +70,10 @@ pub struct FindContentQuery<TNodeId> {
     /// The number of peers for which the query is currently waiting for results.
     num_waiting: usize,
 
+    /// The number of waiting peers that exceeded their expected latency. These peers do not count
+    /// against the permitted parallelism.
+    num_hedged: usize,
+
     /// The configuration of the query.
     config: QueryConfig,
 }
@@ -112,6 +116,9 @@ where
                         "Query (on success) reached invalid number of waiting peers"
                     );
                     self.num_waiting -= 1;
+                    if entry.get().is_hedged() {
+                        self.num_hedged -= 1;
+                    }
 
                     let peer = entry.get_mut();
                     peer.set_state(QueryPeerState::Succeeded);
@@ -193,6 +200,9 @@ where
                         "Query (on failure) reached invalid number of waiting peers"
                     );
                     self.num_waiting -= 1;
+                    if entry.get().is_hedged() {
+                        self.num_hedged -= 1;
+                    }
                     entry.get_mut().set_state(QueryPeerState::Failed);
                 }
                 QueryPeerState::Unresponsive => entry.get_mut().set_state(QueryPeerState::Failed),
@@ -219,12 +229,52 @@ where
         // peers to contact, see `active_counter`).
         let mut result_counter = Some(0);
 
+        // Stop counting peers that exceeded their expected latency against the allowed
+        // parallelism, so that a hedged request may be issued to another peer.
+        let peer_timeout = self.config.peer_timeout;
+        for peer in self.closest_peers.values_mut() {
+            if let QueryPeerState::Waiting(timeout) = *peer.state() {
+                if now < timeout && peer.should_hedge(now, timeout, peer_timeout) {
+                    peer.set_hedged();
+                    self.num_hedged += 1;
+                }
+            }
+        }
+
         // Check if the query is at capacity w.r.t. the allowed parallelism.
         let at_capacity = self.at_capacity();
 
-        for peer in self.closest_peers.values_mut() {
+        // Peers whose radius covers the target are expected to store the content, so they are
+        // contacted before any other peers, regardless of their distance to the target.
+        if !at_capacity {
+            if let Some(peer) = self.closest_peers.values_mut().find(|peer| {
+                matches!(peer.state(), QueryPeerState::NotContacted)
+                    && peer.radius_covers_target()
+                    && !peer.latency_hint().deprioritized
+            }) {
+                let timeout = now + self.config.peer_timeout;
+                peer.set_state(QueryPeerState::Waiting(timeout));
+                self.num_waiting += 1;
+                return QueryState::Waiting(Some(peer.key().preimage().clone()));
+            }
+        }
+
+        // The closest peer that has not been contacted because it is deprioritized.
+        let mut deferred_peer = None;
+
+        for (distance, peer) in self.closest_peers.iter_mut() {
             match peer.state() {
                 QueryPeerState::NotContacted => {
+                    // Deprioritized peers are only contacted once no other peers remain to be
+                    // contacted. The query cannot finish while such a peer remains.
+                    if peer.latency_hint().deprioritized {
+                        if deferred_peer.is_none() {
+                            deferred_peer = Some(*distance);
+                        }
+                        result_counter = None;
+                        continue;
+                    }
+
                     // This peer is waiting to be reiterated.
                     if !at_capacity {
                         let timeout = now + self.config.peer_timeout;
@@ -246,6 +296,9 @@ where
                             "Query (poll) reached invalid number of waiting peers"
                         );
                         self.num_waiting -= 1;
+                        if peer.is_hedged() {
+                            self.num_hedged -= 1;
+                        }
                         peer.set_state(QueryPeerState::Unresponsive);
                     } else if at_capacity {
                         // The query is still waiting for a result from a peer and is
@@ -278,6 +331,18 @@ where
             }
         }
 
+        if let Some(distance) = deferred_peer {
+            if at_capacity {
+                return QueryState::WaitingAtCapacity;
+            }
+            if let Some(peer) = self.closest_peers.get_mut(&distance) {
+                let timeout = now + self.config.peer_timeout;
+                peer.set_state(QueryPeerState::Waiting(timeout));
+                self.num_waiting += 1;
+                return QueryState::Waiting(Some(peer.key().preimage().clone()));
+            }
+        }
+
         if self.num_waiting > 0 {
             // The query is still waiting for results and not at capacity w.r.t.
             // the allowed parallelism, but there are no new peers to contact
@@ -372,10 +437,44 @@ where
             closest_peers,
             content: None,
             num_waiting: 0,
+            num_hedged: 0,
             config,
         }
     }
 
+    /// Sets the latency hint for `peer`, if the peer is known to the query.
+    pub fn set_latency_hint(&mut self, peer: &TNodeId, latency_hint: PeerLatencyHint) {
+        let key: Key<TNodeId> = peer.clone().into();
+        let distance = key.distance(&self.target_key);
+        if let Some(peer) = self.closest_peers.get_mut(&distance) {
+            peer.set_latency_hint(latency_hint);
+        }
+    }
+
+    /// Marks `peer` as a peer whose advertised radius covers the target, adding the peer to the
+    /// query if it is not yet known. Such peers are contacted before any other peers.
+    pub fn add_radius_covering_peer(&mut self, peer: Key<TNodeId>) {
+        let distance = peer.distance(&self.target_key);
+        self.closest_peers
+            .entry(distance)
+            .or_insert_with(|| QueryPeer::new(peer, QueryPeerState::NotContacted))
+            .set_radius_covers_target();
+    }
+
+    /// Returns whether the advertised radius of `peer` covers the target.
+    pub fn is_radius_covering_peer(&self, peer: &TNodeId) -> bool {
+        let key: Key<TNodeId> = peer.clone().into();
+        let distance = key.distance(&self.target_key);
+        self.closest_peers
+            .get(&distance)
+            .map_or(false, |peer| peer.radius_covers_target())
+    }
+
+    /// Returns the number of waiting peers that count against the permitted parallelism.
+    fn num_active(&self) -> usize {
+        self.num_waiting.saturating_sub(self.num_hedged)
+    }
+
     /// Checks if the query is at capacity w.r.t. the permitted parallelism.
     ///
     /// While the query is stalled, up to `num_results` parallel requests
@@ -384,8 +483,8 @@ where
     /// k closest nodes it has not already queried".
     fn at_capacity(&self) -> bool {
         match self.progress {
-            QueryProgress::Stalled => self.num_waiting >= self.config.num_results,
-            QueryProgress::Iterating { .. } => self.num_waiting >= self.config.parallelism,
+            QueryProgress::Stalled => self.num_active() >= self.config.num_results,
+            QueryProgress::Iterating { .. } => self.num_active() >= self.config.parallelism,
             QueryProgress::Finished => true,
         }
     }
@@ -728,4 +827,109 @@ mod tests {
 
         QuickCheck::new().tests(10).quickcheck(prop as fn(_) -> _)
     }
+
+    #[test]
+    fn deprioritized_peer_contacted_last() {
+        let target = NodeId::random();
+        let config = QueryConfig {
+            parallelism: 1,
+            num_results: 2,
+            peer_timeout: Duration::from_secs(10),
+        };
+        let peers: Vec<Key<NodeId>> = random_nodes(2).map(Key::from).collect();
+        let mut query = FindContentQuery::with_config(config, target.into(), peers);
+        let (closest, farthest) = {
+            let mut keys = query
+                .closest_peers
+                .values()
+                .map(|peer| *peer.key().preimage());
+            (keys.next().unwrap(), keys.next().unwrap())
+        };
+        query.set_latency_hint(
+            &closest,
+            PeerLatencyHint {
+                expected_latency: None,
+                deprioritized: true,
+            },
+        );
+
+        let now = Instant::now();
+        assert_eq!(query.poll(now), QueryState::Waiting(Some(farthest)));
+        query.on_success(&farthest, FindContentQueryResponse::ClosestNodes(vec![]));
+
+        // The deprioritized peer is still contacted once no other peers remain.
+        assert_eq!(query.poll(now), QueryState::Waiting(Some(closest)));
+        query.on_success(&closest, FindContentQueryResponse::ClosestNodes(vec![]));
+        assert_eq!(query.poll(now), QueryState::Finished);
+    }
+
+    #[test]
+    fn hedged_request_after_expected_latency() {
+        let target = NodeId::random();
+        let config = QueryConfig {
+            parallelism: 1,
+            num_results: 2,
+            peer_timeout: Duration::from_secs(10),
+        };
+        let peers: Vec<Key<NodeId>> = random_nodes(2).map(Key::from).collect();
+        let mut query = FindContentQuery::with_config(config, target.into(), peers);
+        let (closest, farthest) = {
+            let mut keys = query
+                .closest_peers
+                .values()
+                .map(|peer| *peer.key().preimage());
+            (keys.next().unwrap(), keys.next().unwrap())
+        };
+        query.set_latency_hint(
+            &closest,
+            PeerLatencyHint {
+                expected_latency: Some(Duration::from_millis(100)),
+                deprioritized: false,
+            },
+        );
+
+        let mut now = Instant::now();
+        assert_eq!(query.poll(now), QueryState::Waiting(Some(closest)));
+        assert_eq!(query.poll(now), QueryState::WaitingAtCapacity);
+
+        // Once the closest peer exceeds its expected latency, the next peer is contacted while
+        // the query keeps waiting on the closest peer.
+        now += Duration::from_millis(100);
+        assert_eq!(query.poll(now), QueryState::Waiting(Some(farthest)));
+        assert_eq!(query.num_waiting, 2);
+
+        query.on_success(&closest, FindContentQueryResponse::Content(vec![0xef]));
+        assert_eq!(query.num_hedged, 0);
+        assert_eq!(query.poll(now), QueryState::Finished);
+    }
+
+    #[test]
+    fn radius_covering_peer_contacted_first() {
+        let target = NodeId::random();
+        let config = QueryConfig {
+            parallelism: 1,
+            num_results: 2,
+            peer_timeout: Duration::from_secs(10),
+        };
+        let peers: Vec<Key<NodeId>> = random_nodes(2).map(Key::from).collect();
+        let mut query = FindContentQuery::with_config(config, target.into(), peers);
+        let closest = *query
+            .closest_peers
+            .values()
+            .next()
+            .unwrap()
+            .key()
+            .preimage();
+
+        // A peer outside of the closest peers whose radius covers the target.
+        let covering = NodeId::random();
+        query.add_radius_covering_peer(Key::from(covering));
+        assert!(query.is_radius_covering_peer(&covering));
+        assert!(!query.is_radius_covering_peer(&closest));
+
+        let now = Instant::now();
+        assert_eq!(query.poll(now), QueryState::Waiting(Some(covering)));
+        query.on_success(&covering, FindContentQueryResponse::ClosestNodes(vec![]));
+        assert_eq!(query.poll(now), QueryState::Waiting(Some(closest)));
+    }
 }
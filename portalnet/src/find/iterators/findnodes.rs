@@ -22,7 +22,7 @@
 // https://github.com/libp2p/rust-libp2p
 
 use super::super::query_pool::QueryState;
-use super::query::{Query, QueryConfig, QueryPeer, QueryPeerState, QueryProgress};
+use super::query::{PeerLatencyHint, Query, QueryConfig, QueryPeer, QueryPeerState, QueryProgress};
 
 use discv5::kbucket::{Distance, Key};
 use std::{
@@ -48,6 +48,10 @@ pub struct FindNodeQuery<TNodeId> {
     /// The number of peers for which the query is currently waiting for results.
     num_waiting: usize,
 
+    /// The number of waiting peers that exceeded their expected latency. These peers do not count
+    /// against the permitted parallelism.
+    num_hedged: usize,
+
     /// The configuration of the query.
     config: QueryConfig,
 }
@@ -85,6 +89,9 @@ where
                 QueryPeerState::Waiting(..) => {
                     debug_assert!(self.num_waiting > 0);
                     self.num_waiting -= 1;
+                    if e.get().is_hedged() {
+                        self.num_hedged -= 1;
+                    }
                     e.get_mut().set_state(QueryPeerState::Failed);
                 }
                 QueryPeerState::Unresponsive => e.get_mut().set_state(QueryPeerState::Failed),
@@ -117,6 +124,9 @@ where
                         "Query has invalid number of waiting queries"
                     );
                     self.num_waiting -= 1;
+                    if entry.get().is_hedged() {
+                        self.num_hedged -= 1;
+                    }
                     let peer = entry.get_mut();
                     peer.increment_peers_returned(peer_response.len());
                     peer.set_state(QueryPeerState::Succeeded);
@@ -181,12 +191,37 @@ where
         // peers to contact, see `active_counter`).
         let mut result_counter = Some(0);
 
+        // Stop counting peers that exceeded their expected latency against the allowed
+        // parallelism, so that a hedged request may be issued to another peer.
+        let peer_timeout = self.config.peer_timeout;
+        for peer in self.closest_peers.values_mut() {
+            if let QueryPeerState::Waiting(timeout) = *peer.state() {
+                if now < timeout && peer.should_hedge(now, timeout, peer_timeout) {
+                    peer.set_hedged();
+                    self.num_hedged += 1;
+                }
+            }
+        }
+
         // Check if the query is at capacity w.r.t. the allowed parallelism.
         let at_capacity = self.at_capacity();
 
-        for peer in self.closest_peers.values_mut() {
+        // The closest peer that has not been contacted because it is deprioritized.
+        let mut deferred_peer = None;
+
+        for (distance, peer) in self.closest_peers.iter_mut() {
             match peer.state() {
                 QueryPeerState::NotContacted => {
+                    // Deprioritized peers are only contacted once no other peers remain to be
+                    // contacted. The query cannot finish while such a peer remains.
+                    if peer.latency_hint().deprioritized {
+                        if deferred_peer.is_none() {
+                            deferred_peer = Some(*distance);
+                        }
+                        result_counter = None;
+                        continue;
+                    }
+
                     // This peer is waiting to be reiterated.
                     if !at_capacity {
                         let timeout = now + self.config.peer_timeout;
@@ -206,6 +241,9 @@ where
                             "Query reached invalid number of waiting queries"
                         );
                         self.num_waiting -= 1;
+                        if peer.is_hedged() {
+                            self.num_hedged -= 1;
+                        }
                         peer.set_state(QueryPeerState::Unresponsive);
                     } else if at_capacity {
                         // The query is still waiting for a result from a peer and is
@@ -236,6 +274,18 @@ where
             }
         }
 
+        if let Some(distance) = deferred_peer {
+            if at_capacity {
+                return QueryState::WaitingAtCapacity;
+            }
+            if let Some(peer) = self.closest_peers.get_mut(&distance) {
+                let timeout = now + self.config.peer_timeout;
+                peer.set_state(QueryPeerState::Waiting(timeout));
+                self.num_waiting += 1;
+                return QueryState::Waiting(Some(peer.key().preimage().clone()));
+            }
+        }
+
         if self.num_waiting > 0 {
             // The query is still waiting for results and not at capacity w.r.t.
             // the allowed parallelism, but there are no new peers to contact
@@ -299,9 +349,24 @@ where
             progress,
             closest_peers,
             num_waiting: 0,
+            num_hedged: 0,
         }
     }
 
+    /// Sets the latency hint for `peer`, if the peer is known to the query.
+    pub fn set_latency_hint(&mut self, peer: &TNodeId, latency_hint: PeerLatencyHint) {
+        let key: Key<TNodeId> = peer.clone().into();
+        let distance = key.distance(&self.target_key);
+        if let Some(peer) = self.closest_peers.get_mut(&distance) {
+            peer.set_latency_hint(latency_hint);
+        }
+    }
+
+    /// Returns the number of waiting peers that count against the permitted parallelism.
+    fn num_active(&self) -> usize {
+        self.num_waiting.saturating_sub(self.num_hedged)
+    }
+
     /// Checks if the query is at capacity w.r.t. the permitted parallelism.
     ///
     /// While the query is stalled, up to `num_results` parallel requests
@@ -310,8 +375,8 @@ where
     /// k closest nodes it has not already queried".
     fn at_capacity(&self) -> bool {
         match self.progress {
-            QueryProgress::Stalled => self.num_waiting >= self.config.num_results,
-            QueryProgress::Iterating { .. } => self.num_waiting >= self.config.parallelism,
+            QueryProgress::Stalled => self.num_active() >= self.config.num_results,
+            QueryProgress::Iterating { .. } => self.num_active() >= self.config.parallelism,
             QueryProgress::Finished => true,
         }
     }
@@ -160,6 +160,16 @@ pub struct QueryPeer<TNodeId> {
 
     /// The current query state of this peer.
     state: QueryPeerState,
+
+    /// What the local node knows about the responsiveness of this peer.
+    latency_hint: PeerLatencyHint,
+
+    /// Whether the query stopped counting this peer against its parallelism, because the peer
+    /// exceeded its expected latency.
+    hedged: bool,
+
+    /// Whether the advertised data radius of this peer covers the target of the query.
+    radius_covers_target: bool,
 }
 
 impl<TNodeId> QueryPeer<TNodeId> {
@@ -170,6 +180,9 @@ impl<TNodeId> QueryPeer<TNodeId> {
             key,
             peers_returned: 0,
             state,
+            latency_hint: PeerLatencyHint::default(),
+            hedged: false,
+            radius_covers_target: false,
         }
     }
 
@@ -197,6 +210,65 @@ impl<TNodeId> QueryPeer<TNodeId> {
     pub fn set_state(&mut self, state: QueryPeerState) {
         self.state = state;
     }
+
+    /// Returns the latency hint of the query peer.
+    pub fn latency_hint(&self) -> &PeerLatencyHint {
+        &self.latency_hint
+    }
+
+    /// Sets the latency hint of the query peer to `latency_hint`.
+    pub fn set_latency_hint(&mut self, latency_hint: PeerLatencyHint) {
+        self.latency_hint = latency_hint;
+    }
+
+    /// Returns whether a hedged request was issued on behalf of the query peer.
+    pub fn is_hedged(&self) -> bool {
+        self.hedged
+    }
+
+    /// Marks that a hedged request was issued on behalf of the query peer.
+    pub fn set_hedged(&mut self) {
+        self.hedged = true;
+    }
+
+    /// Returns whether the advertised data radius of the query peer covers the query target.
+    pub fn radius_covers_target(&self) -> bool {
+        self.radius_covers_target
+    }
+
+    /// Marks that the advertised data radius of the query peer covers the query target.
+    pub fn set_radius_covers_target(&mut self) {
+        self.radius_covers_target = true;
+    }
+
+    /// Returns whether a hedged request should be issued because the pending request to the query
+    /// peer has exceeded the peer's expected latency. `timeout` is the instant at which the
+    /// pending request times out, `peer_timeout` after it was sent.
+    pub fn should_hedge(&self, now: Instant, timeout: Instant, peer_timeout: Duration) -> bool {
+        if self.hedged {
+            return false;
+        }
+        match self.latency_hint.expected_latency {
+            Some(expected_latency) if expected_latency < peer_timeout => {
+                let sent = timeout - peer_timeout;
+                now >= sent + expected_latency
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Information about the previously observed responsiveness of a peer, used by a query to order
+/// and hedge its requests.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PeerLatencyHint {
+    /// The latency after which the query stops waiting exclusively on the peer and issues a
+    /// request to another peer. `None` if the latency of the peer is unknown.
+    pub expected_latency: Option<Duration>,
+
+    /// Whether the peer is chronically slow or unreliable. Such peers are only contacted once no
+    /// other peers remain to be contacted.
+    pub deprioritized: bool,
 }
 
 /// The state of `QueryPeer` in the context of a query.
@@ -23,6 +23,8 @@ pub struct QueryInfo<TContentKey> {
 
 pub type FindContentResult = (Option<Vec<u8>>, Option<QueryTrace>);
 
+pub type FindNodesResult = (Vec<Enr>, Option<QueryTrace>);
+
 /// Additional information about the query.
 #[derive(Debug)]
 pub enum QueryType<TContentKey> {
@@ -32,7 +34,7 @@ pub enum QueryType<TContentKey> {
         target: NodeId,
 
         /// A callback channel for the result of the query.
-        callback: Option<oneshot::Sender<Vec<Enr>>>,
+        callback: Option<oneshot::Sender<FindNodesResult>>,
 
         /// The number of distances we request for each peer.
         distances_to_request: usize,
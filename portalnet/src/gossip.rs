@@ -0,0 +1,483 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use discv5::enr::NodeId;
+use lru::LruCache;
+use ssz_types::{typenum, BitList};
+
+use crate::overlay_service::OverlayRequestId;
+use trin_types::content_key::RawContentKey;
+use trin_types::enr::Enr;
+use trin_types::gossip_status::{GossipJobId, GossipStatus};
+use trin_utils::bytes::hex_encode;
+
+/// Maximum number of gossip jobs for which delivery status is kept.
+const GOSSIP_JOBS_CAPACITY: usize = 1024;
+
+/// Age after which an outstanding offer is considered failed. Well beyond the timeout of the
+/// OFFER request and the duration of the uTP transfer of the accepted content.
+pub const PENDING_OFFER_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Delivery state of a content key for a single recipient.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DeliveryState {
+    /// The content was offered, and we are waiting for an ACCEPT.
+    Offered,
+    /// The recipient accepted the content, and the uTP transfer is in flight.
+    Accepted,
+    /// The content was transferred to the recipient.
+    Stored,
+    /// The recipient declined the content.
+    Declined,
+    /// The offer or the uTP transfer failed.
+    Failed,
+}
+
+/// Delivery of a single content key within a gossip job.
+#[derive(Debug)]
+struct GossipItem {
+    content_key: RawContentKey,
+    /// The content, kept for as long as retries may be necessary.
+    content: Vec<u8>,
+    /// Delivery state of the content, per recipient.
+    recipients: HashMap<NodeId, DeliveryState>,
+    /// Interested peers that have not been offered the content yet, closest first.
+    candidates: VecDeque<Enr>,
+}
+
+impl GossipItem {
+    fn count(&self, state: DeliveryState) -> u32 {
+        self.recipients
+            .values()
+            .filter(|recipient_state| **recipient_state == state)
+            .count() as u32
+    }
+
+    fn num_pending(&self) -> u32 {
+        self.count(DeliveryState::Offered) + self.count(DeliveryState::Accepted)
+    }
+
+    /// Returns the next candidate to offer the content to, if any.
+    fn next_candidate(&mut self) -> Option<Enr> {
+        while let Some(enr) = self.candidates.pop_front() {
+            if !self.recipients.contains_key(&enr.node_id()) {
+                return Some(enr);
+            }
+        }
+        None
+    }
+}
+
+/// An outstanding OFFER sent on behalf of a gossip job.
+#[derive(Debug)]
+struct PendingOffer {
+    job_id: GossipJobId,
+    recipient: NodeId,
+    /// Indices of the offered items within the job, in the order of the offer.
+    items: Vec<usize>,
+    /// Indices of the items that the recipient accepted. Set once the ACCEPT is received.
+    accepted: Option<Vec<usize>>,
+    /// When the offer was sent.
+    sent_at: Instant,
+}
+
+/// An OFFER to send on behalf of a gossip job.
+#[derive(Debug)]
+pub struct GossipOffer {
+    pub job_id: GossipJobId,
+    pub recipient: Enr,
+    pub content_items: Vec<(RawContentKey, Vec<u8>)>,
+}
+
+/// Tracks the delivery of gossiped content, and selects other interested peers when offers are
+/// declined or fail.
+#[derive(Debug)]
+pub struct GossipTracker {
+    jobs: LruCache<GossipJobId, Vec<GossipItem>>,
+    /// The most recent job that gossiped each content key.
+    latest_job_by_key: LruCache<RawContentKey, GossipJobId>,
+    pending_offers: HashMap<OverlayRequestId, PendingOffer>,
+    next_job_id: GossipJobId,
+}
+
+impl GossipTracker {
+    pub fn new() -> Self {
+        Self {
+            jobs: LruCache::new(GOSSIP_JOBS_CAPACITY),
+            latest_job_by_key: LruCache::new(GOSSIP_JOBS_CAPACITY),
+            pending_offers: HashMap::new(),
+            next_job_id: 0,
+        }
+    }
+
+    /// Starts a new gossip job.
+    pub fn new_job(&mut self) -> GossipJobId {
+        let job_id = self.next_job_id;
+        self.next_job_id += 1;
+        self.jobs.put(job_id, vec![]);
+        job_id
+    }
+
+    /// Adds `content_key` to the job. `candidates` are the interested peers that are not offered
+    /// the content initially, closest first.
+    pub fn add_content(
+        &mut self,
+        job_id: GossipJobId,
+        content_key: RawContentKey,
+        content: Vec<u8>,
+        candidates: Vec<Enr>,
+    ) {
+        if let Some(items) = self.jobs.get_mut(&job_id) {
+            items.push(GossipItem {
+                content_key: content_key.clone(),
+                content,
+                recipients: HashMap::new(),
+                candidates: candidates.into(),
+            });
+            self.latest_job_by_key.put(content_key, job_id);
+        }
+    }
+
+    /// Records an OFFER of `content_keys` to `recipient`, sent with `request_id`.
+    pub fn register_offer(
+        &mut self,
+        request_id: OverlayRequestId,
+        job_id: GossipJobId,
+        recipient: NodeId,
+        content_keys: &[RawContentKey],
+    ) {
+        let items = match self.jobs.get_mut(&job_id) {
+            Some(items) => items,
+            None => return,
+        };
+        let offered: Vec<usize> = content_keys
+            .iter()
+            .filter_map(|key| items.iter().position(|item| &item.content_key == key))
+            .collect();
+        for index in offered.iter() {
+            items[*index]
+                .recipients
+                .insert(recipient, DeliveryState::Offered);
+        }
+        self.pending_offers.insert(
+            request_id,
+            PendingOffer {
+                job_id,
+                recipient,
+                items: offered,
+                accepted: None,
+                sent_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Records the ACCEPT response to the offer sent with `request_id`. Returns the offers to
+    /// send to other peers in place of the recipient, for the content that it declined.
+    pub fn on_accept(
+        &mut self,
+        request_id: OverlayRequestId,
+        content_keys: &BitList<typenum::U8>,
+    ) -> Vec<GossipOffer> {
+        let mut pending = match self.pending_offers.remove(&request_id) {
+            Some(pending) => pending,
+            None => return vec![],
+        };
+        let (accepted, declined): (Vec<usize>, Vec<usize>) = pending
+            .items
+            .iter()
+            .enumerate()
+            .partition(|(i, _)| content_keys.get(*i).unwrap_or(false));
+        let accepted: Vec<usize> = accepted.into_iter().map(|(_, index)| *index).collect();
+        let declined: Vec<usize> = declined.into_iter().map(|(_, index)| *index).collect();
+
+        self.set_state(&pending, &accepted, DeliveryState::Accepted);
+        self.set_state(&pending, &declined, DeliveryState::Declined);
+        let retries = self.retry(pending.job_id, &declined);
+
+        if !accepted.is_empty() {
+            pending.accepted = Some(accepted);
+            self.pending_offers.insert(request_id, pending);
+        }
+        retries
+    }
+
+    /// Records the outcome of the uTP transfer of the content accepted in response to the offer
+    /// sent with `request_id`. Returns the offers to send to other peers if the transfer failed.
+    pub fn on_transfer(&mut self, request_id: OverlayRequestId, success: bool) -> Vec<GossipOffer> {
+        let pending = match self.pending_offers.remove(&request_id) {
+            Some(pending) => pending,
+            None => return vec![],
+        };
+        let accepted = pending.accepted.clone().unwrap_or_default();
+        if success {
+            self.set_state(&pending, &accepted, DeliveryState::Stored);
+            if let Some(items) = self.jobs.get_mut(&pending.job_id) {
+                for index in accepted {
+                    match items.get_mut(index) {
+                        Some(item) if item.num_pending() == 0 => item.content = vec![],
+                        _ => {}
+                    }
+                }
+            }
+            vec![]
+        } else {
+            self.set_state(&pending, &accepted, DeliveryState::Failed);
+            self.retry(pending.job_id, &accepted)
+        }
+    }
+
+    /// Records the failure of the offer sent with `request_id`. Returns the offers to send to
+    /// other peers in place of the recipient. Once the ACCEPT is received, only the accepted
+    /// items are still in flight, as the declined ones were already retried.
+    pub fn on_failure(&mut self, request_id: OverlayRequestId) -> Vec<GossipOffer> {
+        let pending = match self.pending_offers.remove(&request_id) {
+            Some(pending) => pending,
+            None => return vec![],
+        };
+        let items = pending
+            .accepted
+            .clone()
+            .unwrap_or_else(|| pending.items.clone());
+        self.set_state(&pending, &items, DeliveryState::Failed);
+        self.retry(pending.job_id, &items)
+    }
+
+    /// Records the failure of the offers that are older than `PENDING_OFFER_TIMEOUT` at `now`,
+    /// whose outcome was never reported. Returns the offers to send to other peers in place of
+    /// their recipients.
+    pub fn expire_offers(&mut self, now: Instant) -> Vec<GossipOffer> {
+        let expired: Vec<OverlayRequestId> = self
+            .pending_offers
+            .iter()
+            .filter(|(_, pending)| {
+                now.saturating_duration_since(pending.sent_at) > PENDING_OFFER_TIMEOUT
+            })
+            .map(|(request_id, _)| *request_id)
+            .collect();
+        expired
+            .into_iter()
+            .flat_map(|request_id| self.on_failure(request_id))
+            .collect()
+    }
+
+    /// Returns the delivery status of `content_key` in the most recent job that gossiped it.
+    pub fn status(&self, content_key: &RawContentKey) -> Option<GossipStatus> {
+        let job_id = self.latest_job_by_key.peek(content_key)?;
+        let item = self
+            .jobs
+            .peek(job_id)?
+            .iter()
+            .find(|item| &item.content_key == content_key)?;
+        Some(GossipStatus {
+            job_id: *job_id,
+            content_key: hex_encode(content_key),
+            num_stored: item.count(DeliveryState::Stored),
+            num_declined: item.count(DeliveryState::Declined),
+            num_failed: item.count(DeliveryState::Failed),
+            num_pending: item.num_pending(),
+        })
+    }
+
+    fn set_state(&mut self, pending: &PendingOffer, indices: &[usize], state: DeliveryState) {
+        if let Some(items) = self.jobs.get_mut(&pending.job_id) {
+            for index in indices {
+                if let Some(item) = items.get_mut(*index) {
+                    item.recipients.insert(pending.recipient, state);
+                }
+            }
+        }
+    }
+
+    /// Selects the next candidate for each of the items at `indices`, and groups the items into
+    /// one offer per candidate. Releases the content of items that are no longer in flight.
+    fn retry(&mut self, job_id: GossipJobId, indices: &[usize]) -> Vec<GossipOffer> {
+        let items = match self.jobs.get_mut(&job_id) {
+            Some(items) => items,
+            None => return vec![],
+        };
+        let mut offers: Vec<GossipOffer> = vec![];
+        for index in indices {
+            let item = match items.get_mut(*index) {
+                Some(item) => item,
+                None => continue,
+            };
+            match item.next_candidate() {
+                Some(enr) => {
+                    let content_item = (item.content_key.clone(), item.content.clone());
+                    match offers
+                        .iter_mut()
+                        .find(|offer| offer.recipient.node_id() == enr.node_id())
+                    {
+                        Some(offer) => offer.content_items.push(content_item),
+                        None => offers.push(GossipOffer {
+                            job_id,
+                            recipient: enr,
+                            content_items: vec![content_item],
+                        }),
+                    }
+                }
+                None => {
+                    if item.num_pending() == 0 {
+                        item.content = vec![];
+                    }
+                }
+            }
+        }
+        offers
+    }
+}
+
+impl Default for GossipTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    use trin_types::enr::generate_random_remote_enr;
+
+    fn bitlist(bits: &[bool]) -> BitList<typenum::U8> {
+        let mut bitlist = BitList::with_capacity(bits.len()).unwrap();
+        for (i, bit) in bits.iter().enumerate() {
+            bitlist.set(i, *bit).unwrap();
+        }
+        bitlist
+    }
+
+    #[test]
+    fn declined_offer_is_retried_with_candidate() {
+        let mut tracker = GossipTracker::new();
+        let (_, recipient) = generate_random_remote_enr();
+        let (_, candidate) = generate_random_remote_enr();
+        let key_a = vec![0xa];
+        let key_b = vec![0xb];
+
+        let job_id = tracker.new_job();
+        tracker.add_content(job_id, key_a.clone(), vec![1], vec![candidate.clone()]);
+        tracker.add_content(job_id, key_b.clone(), vec![2], vec![]);
+        tracker.register_offer(
+            1,
+            job_id,
+            recipient.node_id(),
+            &[key_a.clone(), key_b.clone()],
+        );
+        assert_eq!(tracker.status(&key_a).unwrap().num_pending, 1);
+
+        // The recipient declines the first key and accepts the second.
+        let retries = tracker.on_accept(1, &bitlist(&[false, true]));
+        assert_eq!(retries.len(), 1);
+        assert_eq!(retries[0].recipient, candidate);
+        assert_eq!(retries[0].content_items, vec![(key_a.clone(), vec![1])]);
+
+        let status_a = tracker.status(&key_a).unwrap();
+        assert_eq!(status_a.num_declined, 1);
+        assert_eq!(status_a.num_pending, 0);
+
+        assert!(tracker.on_transfer(1, true).is_empty());
+        let status_b = tracker.status(&key_b).unwrap();
+        assert_eq!(status_b.num_stored, 1);
+        assert!(status_b.is_complete());
+    }
+
+    #[test]
+    fn failed_offer_without_candidates_completes() {
+        let mut tracker = GossipTracker::new();
+        let (_, recipient) = generate_random_remote_enr();
+        let key = vec![0xa];
+
+        let job_id = tracker.new_job();
+        tracker.add_content(job_id, key.clone(), vec![1], vec![recipient.clone()]);
+        tracker.register_offer(7, job_id, recipient.node_id(), &[key.clone()]);
+
+        // The recipient is not offered the content a second time.
+        assert!(tracker.on_failure(7).is_empty());
+        let status = tracker.status(&key).unwrap();
+        assert_eq!(status.num_failed, 1);
+        assert!(status.is_complete());
+    }
+
+    #[test]
+    fn failure_after_accept_only_retries_accepted_content() {
+        let mut tracker = GossipTracker::new();
+        let (_, recipient) = generate_random_remote_enr();
+        let (_, candidate_a) = generate_random_remote_enr();
+        let (_, candidate_b) = generate_random_remote_enr();
+        let key_a = vec![0xa];
+        let key_b = vec![0xb];
+
+        let job_id = tracker.new_job();
+        tracker.add_content(job_id, key_a.clone(), vec![1], vec![candidate_a.clone()]);
+        tracker.add_content(job_id, key_b.clone(), vec![2], vec![candidate_b.clone()]);
+        tracker.register_offer(
+            5,
+            job_id,
+            recipient.node_id(),
+            &[key_a.clone(), key_b.clone()],
+        );
+
+        // The recipient declines the first key and accepts the second.
+        let retries = tracker.on_accept(5, &bitlist(&[false, true]));
+        assert_eq!(retries.len(), 1);
+        assert_eq!(retries[0].recipient, candidate_a);
+
+        // The transfer of the accepted key fails: only that key is retried.
+        let retries = tracker.on_failure(5);
+        assert_eq!(retries.len(), 1);
+        assert_eq!(retries[0].recipient, candidate_b);
+        assert_eq!(retries[0].content_items, vec![(key_b.clone(), vec![2])]);
+
+        let status_a = tracker.status(&key_a).unwrap();
+        assert_eq!(status_a.num_declined, 1);
+        assert_eq!(status_a.num_failed, 0);
+        let status_b = tracker.status(&key_b).unwrap();
+        assert_eq!(status_b.num_declined, 0);
+        assert_eq!(status_b.num_failed, 1);
+        assert!(tracker.on_failure(5).is_empty());
+    }
+
+    #[test]
+    fn unanswered_offer_expires() {
+        let mut tracker = GossipTracker::new();
+        let (_, recipient) = generate_random_remote_enr();
+        let (_, candidate) = generate_random_remote_enr();
+        let key = vec![0xa];
+
+        let job_id = tracker.new_job();
+        tracker.add_content(job_id, key.clone(), vec![1], vec![candidate.clone()]);
+        tracker.register_offer(3, job_id, recipient.node_id(), &[key.clone()]);
+
+        assert!(tracker.expire_offers(Instant::now()).is_empty());
+        assert_eq!(tracker.pending_offers.len(), 1);
+
+        // The recipient never replies, so the content is offered to the candidate instead.
+        let later = Instant::now() + PENDING_OFFER_TIMEOUT + Duration::from_secs(1);
+        let retries = tracker.expire_offers(later);
+        assert_eq!(retries.len(), 1);
+        assert_eq!(retries[0].recipient, candidate);
+        assert!(tracker.pending_offers.is_empty());
+        assert_eq!(tracker.status(&key).unwrap().num_failed, 1);
+
+        // A late response is ignored.
+        assert!(tracker.on_accept(3, &bitlist(&[true])).is_empty());
+    }
+
+    #[test]
+    fn status_reports_latest_job() {
+        let mut tracker = GossipTracker::new();
+        let key = vec![0xa];
+
+        let first_job = tracker.new_job();
+        tracker.add_content(first_job, key.clone(), vec![1], vec![]);
+        let second_job = tracker.new_job();
+        tracker.add_content(second_job, key.clone(), vec![1], vec![]);
+
+        assert_eq!(tracker.status(&key).unwrap().job_id, second_job);
+        assert_eq!(tracker.status(&vec![0xb]), None);
+    }
+}
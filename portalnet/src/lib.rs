@@ -1,11 +1,20 @@
 #![warn(clippy::unwrap_used)]
 
+pub mod archive;
+pub mod content_cache;
 pub mod discovery;
 pub mod events;
 pub mod find;
+pub mod gossip;
+pub mod liveness;
 pub mod metrics;
 pub mod overlay;
 mod overlay_service;
+pub mod peer_stats;
+pub mod rate_limit;
+pub mod scrub;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
 pub mod socket;
 pub mod storage;
 pub mod types;
@@ -0,0 +1,195 @@
+use std::{collections::HashMap, time::Duration};
+
+use discv5::enr::NodeId;
+use lru::LruCache;
+use trin_types::cli::DEFAULT_PING_MIN_INTERVAL;
+
+/// Interval between pings to nodes in the farthest bucket, if no interval is configured.
+pub use trin_types::cli::DEFAULT_PING_INTERVAL;
+
+/// Maximum number of nodes for which we keep failure counters.
+const FAILURE_COUNTERS_CAPACITY: usize = 2048;
+
+/// Number of buckets, counted from the farthest bucket, over which the ping interval halves.
+const BUCKETS_PER_HALVING: u64 = 2;
+
+/// Largest exponent of the ping interval backoff, to avoid overflow.
+const MAX_BACKOFF_EXPONENT: u32 = 16;
+
+/// Configuration of the liveness checks of the nodes in the routing table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LivenessConfig {
+    /// Minimum interval between pings to a node. Closer buckets are pinged more often than the
+    /// farthest bucket, down to this interval.
+    pub min_ping_interval: Duration,
+    /// Maximum interval between pings to a node that failed to respond.
+    pub max_ping_backoff: Duration,
+    /// Number of consecutive failed requests after which a node is evicted from the routing
+    /// table.
+    pub max_failures: u32,
+}
+
+impl Default for LivenessConfig {
+    fn default() -> Self {
+        Self {
+            min_ping_interval: DEFAULT_PING_MIN_INTERVAL,
+            max_ping_backoff: Duration::from_secs(600),
+            max_failures: 3,
+        }
+    }
+}
+
+/// Tracks consecutive request failures of the nodes in the routing table, and decides when to
+/// ping and when to evict them.
+#[derive(Debug)]
+pub struct LivenessTracker {
+    config: LivenessConfig,
+    /// Interval between pings to responsive nodes in the farthest bucket.
+    ping_interval: Duration,
+    /// Number of consecutive failed requests, per node.
+    failures: LruCache<NodeId, u32>,
+    /// The node waiting for a slot in each full bucket, by the log2 distance of the bucket.
+    pending: HashMap<u64, NodeId>,
+}
+
+impl LivenessTracker {
+    pub fn new(config: LivenessConfig, ping_interval: Duration) -> Self {
+        Self {
+            config,
+            ping_interval,
+            failures: LruCache::new(FAILURE_COUNTERS_CAPACITY),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Records a request to or from `node_id` that succeeded, which resets its failure counter.
+    pub fn record_success(&mut self, node_id: &NodeId) {
+        self.failures.pop(node_id);
+    }
+
+    /// Records a request to `node_id` that failed or timed out. Returns true if the node has
+    /// failed too many consecutive requests, and should be evicted.
+    pub fn record_failure(&mut self, node_id: NodeId) -> bool {
+        let failures = match self.failures.get_mut(&node_id) {
+            Some(failures) => {
+                *failures += 1;
+                *failures
+            }
+            None => {
+                self.failures.put(node_id, 1);
+                1
+            }
+        };
+        failures >= self.config.max_failures
+    }
+
+    /// Returns the number of consecutive failed requests to `node_id`.
+    pub fn failures(&self, node_id: &NodeId) -> u32 {
+        self.failures.peek(node_id).copied().unwrap_or_default()
+    }
+
+    /// Forgets `node_id`, after it was evicted from the routing table.
+    pub fn remove(&mut self, node_id: &NodeId) {
+        self.failures.pop(node_id);
+    }
+
+    /// Records that `node_id` is waiting for a slot in the bucket at `log2_distance`.
+    pub fn record_pending(&mut self, log2_distance: u64, node_id: NodeId) {
+        self.pending.insert(log2_distance, node_id);
+    }
+
+    /// Returns the node waiting for a slot in the bucket at `log2_distance`, if any.
+    pub fn take_pending(&mut self, log2_distance: u64) -> Option<NodeId> {
+        self.pending.remove(&log2_distance)
+    }
+
+    /// Returns the delay until the next ping to `node_id`, in the bucket at `log2_distance`.
+    ///
+    /// The farthest buckets hold most of the routing table and are easily refilled, so nodes in
+    /// closer buckets are pinged more often. Nodes that failed to respond are pinged with an
+    /// exponential backoff.
+    pub fn ping_delay(&self, node_id: &NodeId, log2_distance: u64) -> Duration {
+        let halvings = (256u64.saturating_sub(log2_distance) / BUCKETS_PER_HALVING).min(31) as u32;
+        let interval = (self.ping_interval / 2u32.pow(halvings)).max(self.config.min_ping_interval);
+
+        match self.failures(node_id).min(MAX_BACKOFF_EXPONENT) {
+            0 => interval,
+            failures => interval
+                .saturating_mul(2u32.pow(failures))
+                .min(self.config.max_ping_backoff)
+                .max(interval),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> LivenessTracker {
+        LivenessTracker::new(LivenessConfig::default(), DEFAULT_PING_INTERVAL)
+    }
+
+    #[test]
+    fn close_buckets_are_pinged_more_often() {
+        let tracker = tracker();
+        let node_id = NodeId::random();
+
+        assert_eq!(tracker.ping_delay(&node_id, 256), Duration::from_secs(120));
+        assert_eq!(tracker.ping_delay(&node_id, 255), Duration::from_secs(120));
+        assert_eq!(tracker.ping_delay(&node_id, 254), Duration::from_secs(60));
+        // Closer buckets are pinged at the minimum interval.
+        assert_eq!(tracker.ping_delay(&node_id, 252), Duration::from_secs(30));
+        assert_eq!(tracker.ping_delay(&node_id, 1), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn failing_node_is_pinged_with_backoff() {
+        let mut tracker = tracker();
+        let node_id = NodeId::random();
+
+        assert!(!tracker.record_failure(node_id));
+        assert_eq!(tracker.ping_delay(&node_id, 256), Duration::from_secs(240));
+        assert!(!tracker.record_failure(node_id));
+        assert_eq!(tracker.ping_delay(&node_id, 256), Duration::from_secs(480));
+
+        // The backoff is capped.
+        let config = LivenessConfig {
+            max_failures: 100,
+            ..Default::default()
+        };
+        let mut tracker = LivenessTracker::new(config, DEFAULT_PING_INTERVAL);
+        for _ in 0..50 {
+            tracker.record_failure(node_id);
+        }
+        assert_eq!(tracker.ping_delay(&node_id, 256), config.max_ping_backoff);
+    }
+
+    #[test]
+    fn node_is_evicted_after_consecutive_failures() {
+        let mut tracker = tracker();
+        let node_id = NodeId::random();
+
+        assert!(!tracker.record_failure(node_id));
+        assert!(!tracker.record_failure(node_id));
+        // A success resets the counter.
+        tracker.record_success(&node_id);
+        assert_eq!(tracker.failures(&node_id), 0);
+
+        assert!(!tracker.record_failure(node_id));
+        assert!(!tracker.record_failure(node_id));
+        assert!(tracker.record_failure(node_id));
+    }
+
+    #[test]
+    fn pending_node_per_bucket() {
+        let mut tracker = tracker();
+        let (first, second) = (NodeId::random(), NodeId::random());
+
+        tracker.record_pending(256, first);
+        tracker.record_pending(256, second);
+        assert_eq!(tracker.take_pending(255), None);
+        assert_eq!(tracker.take_pending(256), Some(second));
+        assert_eq!(tracker.take_pending(256), None);
+    }
+}
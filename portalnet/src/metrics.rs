@@ -7,7 +7,10 @@ use prometheus_exporter::{
 };
 use tracing::error;
 
-use crate::types::messages::{ProtocolId, Request, Response};
+use crate::{
+    rate_limit::RequestKind,
+    types::messages::{ProtocolId, Request, Response},
+};
 
 /// General Metrics Strategy (wip)
 /// - Each module should maintain its own metrics reporter
@@ -47,10 +50,19 @@ pub enum MessageLabel {
     Accept,
 }
 
+/// Content Cache Lookup Labels
+pub enum CacheLookupLabel {
+    /// The content was found in the cache
+    Hit,
+    /// The content was not found in the cache
+    Miss,
+}
+
 /// Overlay Service Metrics Reporter
 #[derive(Clone, Debug)]
 pub struct OverlayMetrics {
     message_count: IntCounterVec,
+    cache_lookup_count: IntCounterVec,
 }
 
 impl OverlayMetrics {
@@ -74,7 +86,24 @@ impl OverlayMetrics {
                 .expect("a gauge can always be added to a new custom registry, without conflict")
         });
 
-        Self { message_count }
+        let cache_lookup_options = opts!(
+            "trin_content_cache_lookup_total",
+            "count content cache lookups, by whether the content was found in the cache"
+        );
+        let cache_lookup_labels = &["protocol", "result"];
+        let cache_lookup_count = register_int_counter_vec!(cache_lookup_options.clone(), cache_lookup_labels).unwrap_or_else(|_| {
+            error!("Failed to register prometheus content cache metrics with default registry, creating new");
+
+            let custom_registry = Registry::new_custom(None, None)
+                .expect("Prometheus docs don't explain when it might fail to create a custom registry, so... hopefully never");
+            register_int_counter_vec_with_registry!(cache_lookup_options, cache_lookup_labels, custom_registry)
+                .expect("a gauge can always be added to a new custom registry, without conflict")
+        });
+
+        Self {
+            message_count,
+            cache_lookup_count,
+        }
     }
 
     /// Returns the value of the given metric with the specified labels.
@@ -116,6 +145,26 @@ impl OverlayMetrics {
         );
     }
 
+    /// Returns the number of content cache lookups with the specified result.
+    pub fn cache_lookup_count_by_labels(
+        &self,
+        network: ProtocolLabel,
+        result: CacheLookupLabel,
+    ) -> u64 {
+        let labels = [network.into(), result.into()];
+        self.cache_lookup_count.with_label_values(&labels).get()
+    }
+
+    pub fn report_cache_lookup(&self, protocol: &ProtocolId, hit: bool) {
+        let result = if hit {
+            CacheLookupLabel::Hit
+        } else {
+            CacheLookupLabel::Miss
+        };
+        let labels = [ProtocolLabel::from(protocol).into(), result.into()];
+        self.cache_lookup_count.with_label_values(&labels).inc();
+    }
+
     fn increment_message_count(
         &self,
         protocol: ProtocolLabel,
@@ -127,6 +176,59 @@ impl OverlayMetrics {
     }
 }
 
+/// Inbound Request Rate Limiting Metrics Reporter
+#[derive(Clone, Debug)]
+pub struct RateLimitMetrics {
+    throttled_request_count: IntCounterVec,
+}
+
+impl RateLimitMetrics {
+    pub fn new() -> Self {
+        let throttled_request_options = opts!(
+            "trin_throttled_requests_total",
+            "count inbound requests rejected for exceeding the per-peer rate limit"
+        );
+        let throttled_request_labels = &["protocol", "type"];
+        let throttled_request_count = register_int_counter_vec!(throttled_request_options.clone(), throttled_request_labels).unwrap_or_else(|_| {
+            error!("Failed to register prometheus rate limit metrics with default registry, creating new");
+
+            let custom_registry = Registry::new_custom(None, None)
+                .expect("Prometheus docs don't explain when it might fail to create a custom registry, so... hopefully never");
+            register_int_counter_vec_with_registry!(throttled_request_options, throttled_request_labels, custom_registry)
+                .expect("a gauge can always be added to a new custom registry, without conflict")
+        });
+
+        Self {
+            throttled_request_count,
+        }
+    }
+
+    /// Returns the number of throttled requests of the specified kind.
+    pub fn throttled_request_count_by_labels(
+        &self,
+        network: ProtocolLabel,
+        request: RequestKind,
+    ) -> u64 {
+        let labels = [network.into(), request.as_str()];
+        self.throttled_request_count
+            .with_label_values(&labels)
+            .get()
+    }
+
+    pub fn report_throttled_request(&self, protocol: &ProtocolId, request: RequestKind) {
+        let labels = [ProtocolLabel::from(protocol).into(), request.as_str()];
+        self.throttled_request_count
+            .with_label_values(&labels)
+            .inc();
+    }
+}
+
+impl Default for RateLimitMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 type MetricLabel = &'static str;
 
 impl From<ProtocolLabel> for MetricLabel {
@@ -166,6 +268,15 @@ impl From<MessageLabel> for MetricLabel {
     }
 }
 
+impl From<CacheLookupLabel> for MetricLabel {
+    fn from(label: CacheLookupLabel) -> Self {
+        match label {
+            CacheLookupLabel::Hit => "hit",
+            CacheLookupLabel::Miss => "miss",
+        }
+    }
+}
+
 impl From<&ProtocolId> for ProtocolLabel {
     fn from(protocol: &ProtocolId) -> Self {
         match protocol {
@@ -3,41 +3,53 @@ use std::{
     fmt::{Debug, Display},
     marker::{PhantomData, Sync},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use discv5::{
     enr::NodeId,
-    kbucket::{Filter, KBucketsTable, NodeStatus, MAX_NODES_PER_BUCKET},
+    kbucket::{ConnectionState, Filter, KBucketsTable, NodeStatus, MAX_NODES_PER_BUCKET},
     TalkRequest,
 };
-use futures::channel::oneshot;
+use futures::{channel::oneshot, stream, Stream, StreamExt};
 use parking_lot::RwLock;
-use ssz::Encode;
+use serde_json::Value;
+use thiserror::Error;
 use tokio::sync::mpsc::UnboundedSender;
 use tracing::{debug, error, info, warn};
-use utp_rs::socket::UtpSocket;
+use utp_rs::{conn::ConnectionConfig, socket::UtpSocket};
 
 use crate::{
+    content_cache::{ContentCache, DEFAULT_CONTENT_CACHE_CAPACITY_BYTES},
     discovery::{Discovery, UtpEnr},
-    metrics::{MessageDirectionLabel, MessageLabel, OverlayMetrics, ProtocolLabel},
+    gossip::GossipTracker,
+    liveness::LivenessConfig,
+    metrics::{
+        CacheLookupLabel, MessageDirectionLabel, MessageLabel, OverlayMetrics, ProtocolLabel,
+    },
     overlay_service::{
         OverlayCommand, OverlayRequest, OverlayRequestError, OverlayService, RequestDirection,
         UTP_CONN_CFG,
     },
+    peer_stats::{PeerLatencySummary, PeerStats, PeerStatsTable},
     storage::ContentStore,
+    types::node::{IpBucketFilter, IpTableFilter},
     types::{
         messages::{
             Accept, Content, CustomPayload, FindContent, FindNodes, Message, Nodes, Offer, Ping,
             Pong, PopulatedOffer, ProtocolId, Request, Response,
         },
         node::Node,
+        payload_extension::{supports_payload_extension, PayloadExtension},
     },
 };
+use ethportal_api::types::portal::{BucketHealth, NodeHealth, RoutingTableHealth};
 use ethportal_api::OverlayContentKey;
+use trin_types::cli::{OverlayConfigArgs, DEFAULT_QUERY_PEER_TIMEOUT, DEFAULT_QUERY_TIMEOUT};
 use trin_types::content_key::RawContentKey;
 use trin_types::distance::{Distance, Metric};
 use trin_types::enr::Enr;
+use trin_types::gossip_status::GossipStatus;
 use trin_utils::bytes::hex_encode;
 use trin_validation::validator::Validator;
 
@@ -57,6 +69,18 @@ pub struct OverlayConfig {
     pub query_peer_timeout: Duration,
     pub query_num_results: usize,
     pub findnodes_query_distances_per_peer: usize,
+    /// Maximum total size, in bytes, of the cache of content fetched from the network. Zero
+    /// disables the cache.
+    pub content_cache_capacity_bytes: u64,
+    /// The overlay-specific extension of the custom payload in Ping and Pong messages.
+    pub payload_extension: Option<Arc<dyn PayloadExtension>>,
+    /// The configuration of uTP connections.
+    pub utp_config: ConnectionConfig,
+    /// The configuration of the liveness checks of routing table nodes.
+    pub liveness: LivenessConfig,
+    /// The seed of the RNG that picks the targets of routing table refreshes. The RNG is seeded
+    /// from entropy if `None`.
+    pub rng_seed: Option<u64>,
 }
 
 impl Default for OverlayConfig {
@@ -69,15 +93,121 @@ impl Default for OverlayConfig {
             bucket_filter: None,
             ping_queue_interval: None,
             query_parallelism: 3, // (recommended α from kademlia paper)
-            query_peer_timeout: Duration::from_secs(10),
-            query_timeout: Duration::from_secs(60),
+            query_peer_timeout: DEFAULT_QUERY_PEER_TIMEOUT,
+            query_timeout: DEFAULT_QUERY_TIMEOUT,
             query_num_results: MAX_NODES_PER_BUCKET,
             findnodes_query_distances_per_peer: 3,
+            content_cache_capacity_bytes: DEFAULT_CONTENT_CACHE_CAPACITY_BYTES,
+            payload_extension: None,
+            utp_config: UTP_CONN_CFG,
+            liveness: LivenessConfig::default(),
+            rng_seed: None,
+        }
+    }
+}
+
+impl OverlayConfig {
+    /// Returns the default config, with the settings in `args` overridden.
+    pub fn from_args(args: &OverlayConfigArgs) -> Self {
+        let default = Self::default();
+        let (table_filter, bucket_filter): (
+            Option<Box<dyn Filter<Node>>>,
+            Option<Box<dyn Filter<Node>>>,
+        ) = if args.ip_limit {
+            (
+                Some(Box::new(IpTableFilter)),
+                Some(Box::new(IpBucketFilter)),
+            )
+        } else {
+            (default.table_filter, default.bucket_filter)
+        };
+        let utp_config = ConnectionConfig {
+            max_packet_size: args
+                .utp_max_packet_size
+                .unwrap_or(default.utp_config.max_packet_size),
+            max_conn_attempts: args
+                .utp_max_conn_attempts
+                .unwrap_or(default.utp_config.max_conn_attempts),
+            max_idle_timeout: args
+                .utp_max_idle_timeout
+                .unwrap_or(default.utp_config.max_idle_timeout),
+            initial_timeout: args
+                .utp_initial_timeout
+                .unwrap_or(default.utp_config.initial_timeout),
+            min_timeout: args
+                .utp_min_timeout
+                .unwrap_or(default.utp_config.min_timeout),
+            target_delay: args
+                .utp_target_delay
+                .unwrap_or(default.utp_config.target_delay),
+        };
+
+        let liveness = LivenessConfig {
+            min_ping_interval: args
+                .ping_min_interval
+                .unwrap_or(default.liveness.min_ping_interval),
+            max_ping_backoff: args
+                .ping_max_backoff
+                .unwrap_or(default.liveness.max_ping_backoff),
+            max_failures: args.max_failures.unwrap_or(default.liveness.max_failures),
+        };
+
+        Self {
+            bucket_pending_timeout: args
+                .bucket_pending_timeout
+                .unwrap_or(default.bucket_pending_timeout),
+            max_incoming_per_bucket: args
+                .max_incoming_per_bucket
+                .unwrap_or(default.max_incoming_per_bucket),
+            table_filter,
+            bucket_filter,
+            ping_queue_interval: args.ping_queue_interval.or(default.ping_queue_interval),
+            query_parallelism: args.query_parallelism.unwrap_or(default.query_parallelism),
+            query_timeout: args.query_timeout.unwrap_or(default.query_timeout),
+            query_peer_timeout: args
+                .query_peer_timeout
+                .unwrap_or(default.query_peer_timeout),
+            query_num_results: args.query_num_results.unwrap_or(default.query_num_results),
+            findnodes_query_distances_per_peer: args
+                .findnodes_query_distances_per_peer
+                .unwrap_or(default.findnodes_query_distances_per_peer),
+            utp_config,
+            liveness,
+            ..default
         }
     }
 }
 
-type BucketEntry = (NodeId, Enr, NodeStatus, Distance, Option<String>);
+/// The default number of concurrent queries in a batch content lookup.
+pub const DEFAULT_BATCH_LOOKUP_CONCURRENCY: usize = 8;
+
+/// The maximum number of concurrent queries in a batch content lookup.
+pub const MAX_BATCH_LOOKUP_CONCURRENCY: usize = 64;
+
+/// The maximum number of content keys in a batch content lookup.
+pub const MAX_BATCH_LOOKUP_SIZE: usize = 1024;
+
+/// The reason that a content lookup did not return content.
+#[derive(Clone, Debug, Error)]
+pub enum ContentLookupError {
+    /// The query completed without finding the content.
+    #[error("Content not found")]
+    NotFound,
+
+    /// The query could not be submitted to, or its result received from, the overlay service.
+    #[error("Request error: {0}")]
+    Request(#[from] OverlayRequestError),
+}
+
+type BucketEntry = (
+    NodeId,
+    Enr,
+    NodeStatus,
+    Distance,
+    Option<String>,
+    Option<PeerLatencySummary>,
+    Option<Value>,
+);
 
 /// Overlay protocol is a layer on top of discv5 that handles all requests from the overlay networks
 /// (state, history etc.) and dispatch them to the discv5 protocol TalkReq. Each network should
@@ -108,6 +238,16 @@ pub struct OverlayProtocol<TContentKey, TMetric, TValidator, TStore> {
     validator: Arc<TValidator>,
     /// Runtime telemetry metrics for the overlay network.
     metrics: Arc<OverlayMetrics>,
+    /// Round-trip time and success statistics of outgoing requests, per peer.
+    peer_stats: Arc<RwLock<PeerStatsTable>>,
+    /// Cache of validated content from recent lookups that falls outside the local radius.
+    content_cache: Arc<RwLock<ContentCache>>,
+    /// Delivery status of gossiped content.
+    gossip_tracker: Arc<RwLock<GossipTracker>>,
+    /// The overlay-specific extension of the custom payload in Ping and Pong messages.
+    payload_extension: Option<Arc<dyn PayloadExtension>>,
+    /// The configuration of uTP connections.
+    utp_config: ConnectionConfig,
 }
 
 impl<
@@ -137,6 +277,11 @@ where
 
         // Initialize metrics, keep a reference in order to build metrics summaries for logging
         let metrics = Arc::new(OverlayMetrics::new());
+        let peer_stats = Arc::new(RwLock::new(PeerStatsTable::new()));
+        let content_cache = Arc::new(RwLock::new(ContentCache::new(
+            config.content_cache_capacity_bytes,
+        )));
+        let gossip_tracker = Arc::new(RwLock::new(GossipTracker::new()));
 
         let command_tx = OverlayService::<TContentKey, TMetric, TValidator, TStore>::spawn(
             Arc::clone(&discovery),
@@ -147,12 +292,19 @@ where
             protocol.clone(),
             Arc::clone(&utp_socket),
             Arc::clone(&metrics),
+            Arc::clone(&peer_stats),
+            Arc::clone(&content_cache),
+            Arc::clone(&gossip_tracker),
+            config.payload_extension.clone(),
             Arc::clone(&validator),
             config.query_timeout,
             config.query_peer_timeout,
             config.query_parallelism,
             config.query_num_results,
             config.findnodes_query_distances_per_peer,
+            config.utp_config,
+            config.liveness,
+            config.rng_seed,
         )
         .await;
 
@@ -167,6 +319,11 @@ where
             phantom_metric: PhantomData,
             validator,
             metrics,
+            peer_stats,
+            content_cache,
+            gossip_tracker,
+            payload_extension: config.payload_extension,
+            utp_config: config.utp_config,
         }
     }
 
@@ -213,9 +370,16 @@ where
             content,
             kbuckets,
             self.command_tx.clone(),
+            &self.gossip_tracker,
         )
     }
 
+    /// Returns the delivery status of `content_key` in the most recent gossip of the content.
+    pub fn gossip_status(&self, content_key: &TContentKey) -> Option<GossipStatus> {
+        let content_key: RawContentKey = content_key.clone().into();
+        self.gossip_tracker.read().status(&content_key)
+    }
+
     /// Returns a vector of all ENR node IDs of nodes currently contained in the routing table.
     pub fn table_entries_id(&self) -> Vec<NodeId> {
         self.kbuckets
@@ -238,6 +402,7 @@ where
     ///     key: usize representing bucket index
     ///     value: Vec of tuples, each tuple represents a node
     pub fn bucket_entries(&self) -> BTreeMap<usize, Vec<BucketEntry>> {
+        let peer_stats = self.peer_stats.read();
         self.kbuckets
             .read()
             .buckets_iter()
@@ -262,12 +427,18 @@ where
                                 }
                                 None => None
                             };
+                            let capabilities = match (&self.payload_extension, node.value.payload_extension()) {
+                                (Some(payload_extension), Some(extension)) => payload_extension.extension_to_json(extension),
+                                _ => None,
+                            };
                             (
                                 *node.key.preimage(),
                                 node.value.enr(),
                                 node.status,
                                 node.value.data_radius(),
                                 client_info,
+                                peer_stats.summary(node.key.preimage()),
+                                capabilities,
                             )
                         })
                         .collect(),
@@ -276,12 +447,87 @@ where
             .collect()
     }
 
+    /// Returns diagnostics of the health of the routing table.
+    pub fn routing_table_health(&self) -> RoutingTableHealth {
+        let peer_stats = self.peer_stats.read();
+        let now = Instant::now();
+        let secs_since =
+            |instant: Option<Instant>| instant.map(|i| now.saturating_duration_since(i).as_secs());
+
+        let mut health = RoutingTableHealth::default();
+        let mut occupancy = vec![];
+        for (index, bucket) in self.kbuckets.read().buckets_iter().enumerate() {
+            let distance = index as u16 + 1;
+            let has_pending = bucket.pending().is_some();
+            occupancy.push((distance, bucket.num_entries()));
+            if has_pending {
+                health.num_pending += 1;
+            }
+            if bucket.num_entries() == 0 && !has_pending {
+                continue;
+            }
+
+            let mut bucket_health = BucketHealth {
+                distance,
+                fill: bucket.num_entries() as f64 / MAX_NODES_PER_BUCKET as f64,
+                num_connected: 0,
+                num_disconnected: 0,
+                has_pending,
+                nodes: vec![],
+            };
+            for node in bucket.iter() {
+                let node_id = *node.key.preimage();
+                let connected = node.status.state == ConnectionState::Connected;
+                if connected {
+                    bucket_health.num_connected += 1;
+                } else {
+                    bucket_health.num_disconnected += 1;
+                }
+
+                let radius_bits = node.value.data_radius().log2().map_or(0, |log2| log2 + 1);
+                *health
+                    .radius_distribution
+                    .entry(radius_bits as u16)
+                    .or_default() += 1;
+                // "c" is used as short-hand for "client" within the ENR's key-values.
+                let client = node
+                    .value
+                    .enr()
+                    .get("c")
+                    .and_then(|slice| std::str::from_utf8(slice).ok().map(str::to_owned))
+                    .unwrap_or_else(|| "unknown".to_owned());
+                *health.client_distribution.entry(client).or_default() += 1;
+
+                let stats = peer_stats.get(&node_id);
+                bucket_health.nodes.push(NodeHealth {
+                    node_id: node_id.into(),
+                    connected,
+                    last_seen_secs: secs_since(stats.and_then(PeerStats::last_seen)),
+                    last_response_secs: secs_since(stats.and_then(PeerStats::last_response)),
+                });
+            }
+
+            health.num_nodes += bucket.num_entries();
+            health.num_connected += bucket_health.num_connected;
+            health.num_disconnected += bucket_health.num_disconnected;
+            health.buckets.push(bucket_health);
+        }
+        health.estimated_network_size = estimate_network_size(&occupancy);
+
+        health
+    }
+
     /// Sends a `Ping` request to `enr`.
     pub async fn send_ping(&self, enr: Enr) -> Result<Pong, OverlayRequestError> {
         // Construct the request.
         let enr_seq = self.discovery.local_enr().seq();
-        let data_radius = self.data_radius();
-        let custom_payload = CustomPayload::from(data_radius.as_ssz_bytes());
+        let extension = match &self.payload_extension {
+            Some(payload_extension) if supports_payload_extension(&enr) => {
+                Some(payload_extension.encode_extension())
+            }
+            _ => None,
+        };
+        let custom_payload = CustomPayload::new(self.data_radius(), extension);
         let request = Ping {
             enr_seq,
             custom_payload,
@@ -359,7 +605,10 @@ where
                             .validate_content(&content_key, &content)
                             .await
                         {
-                            Ok(_) => Ok(Content::Content(content)),
+                            Ok(_) => {
+                                self.process_received_utp_content(content_key, content.clone());
+                                Ok(Content::Content(content))
+                            }
                             Err(msg) => Err(OverlayRequestError::FailedValidation(format!(
                                 "Network: {:?}, Reason: {:?}",
                                 self.protocol, msg
@@ -373,6 +622,45 @@ where
         }
     }
 
+    /// Stores validated content received over uTP in response to a `FindContent` request, or
+    /// keeps it in the content cache if it falls outside of the radius. Content received inline
+    /// is handled the same way by the overlay service.
+    fn process_received_utp_content(&self, content_key: TContentKey, content: Vec<u8>) {
+        let content_id = content_key.content_id();
+        match self
+            .store
+            .read()
+            .is_key_within_radius_and_unavailable(&content_key)
+        {
+            Ok(true) => {
+                let store = Arc::clone(&self.store);
+                // Storing blocks on disk, so keep it off the async worker threads.
+                tokio::task::spawn_blocking(move || {
+                    if let Err(err) = store.write().put(content_key.clone(), content) {
+                        error!(
+                            error = %err,
+                            content.key = %content_key,
+                            "Error storing content received over uTP"
+                        );
+                    }
+                });
+            }
+            Ok(false) => {
+                let local_node_id = self.discovery.local_enr().node_id();
+                let is_outside_radius = TMetric::distance(&local_node_id.raw(), &content_id)
+                    > self.store.read().radius();
+                if is_outside_radius {
+                    self.content_cache.write().put(content_id, content);
+                }
+            }
+            Err(err) => error!(
+                error = %err,
+                content.key = %content_key,
+                "Error checking data store for content key"
+            ),
+        }
+    }
+
     /// Initialize FindContent uTP stream with remote node
     async fn init_find_content_stream(
         &self,
@@ -386,7 +674,7 @@ where
         };
         let mut stream = self
             .utp_socket
-            .connect_with_cid(cid, UTP_CONN_CFG)
+            .connect_with_cid(cid, self.utp_config)
             .await
             .map_err(|err| OverlayRequestError::UtpError(format!("{err:?}")))?;
         let mut data = vec![];
@@ -448,40 +736,62 @@ where
         }
     }
 
-    pub async fn lookup_node(&self, target: NodeId) -> Vec<Enr> {
-        if target == self.local_enr().node_id() {
-            return vec![self.local_enr()];
-        }
-        let connected_peer = self
-            .kbuckets
-            .write()
-            .iter()
-            .filter(|entry| entry.status.is_connected())
-            .map(|entry| *entry.node.key.preimage())
-            .find(|node_id| node_id == &target);
-        if let Some(entry) = connected_peer {
-            match self.discovery.find_enr(&entry) {
-                Some(enr) => return vec![enr],
-                None => {
-                    warn!(
-                        protocol = %self.protocol,
-                        "Error finding ENR for node expected to exist in local routing table",
-                    );
-                    return vec![];
-                }
+    /// Performs a node lookup for `target`.
+    /// Returns the closest nodes found along with the peers traversed during the lookup.
+    pub async fn lookup_node(
+        &self,
+        target: NodeId,
+        is_trace: bool,
+    ) -> (Vec<Enr>, Option<QueryTrace>) {
+        // The local node and connected peers are returned without querying the network.
+        let known_enr = if target == self.local_enr().node_id() {
+            Some(self.local_enr())
+        } else {
+            let connected_peer = self
+                .kbuckets
+                .write()
+                .iter()
+                .filter(|entry| entry.status.is_connected())
+                .map(|entry| *entry.node.key.preimage())
+                .find(|node_id| node_id == &target);
+            match connected_peer {
+                Some(entry) => match self.discovery.find_enr(&entry) {
+                    Some(enr) => Some(enr),
+                    None => {
+                        warn!(
+                            protocol = %self.protocol,
+                            "Error finding ENR for node expected to exist in local routing table",
+                        );
+                        return (vec![], None);
+                    }
+                },
+                None => None,
             }
         };
+        if let Some(enr) = known_enr {
+            let trace = if is_trace {
+                let local_enr = self.local_enr();
+                let mut trace = QueryTrace::new(&local_enr, target.into());
+                trace.node_responded_with(&local_enr, vec![&enr]);
+                Some(trace)
+            } else {
+                None
+            };
+            return (vec![enr], trace);
+        }
+
         let (tx, rx) = oneshot::channel();
         if let Err(err) = self.command_tx.send(OverlayCommand::FindNodeQuery {
             target,
             callback: tx,
+            is_trace,
         }) {
             warn!(
                 protocol = %self.protocol,
                 error = %err,
                 "Error submitting FindNode query to service"
             );
-            return vec![];
+            return (vec![], None);
         }
         rx.await.unwrap_or_else(|err| {
             warn!(
@@ -489,7 +799,7 @@ where
                 error = %err,
                 "Error receiving FindNode query response"
             );
-            vec![]
+            (vec![], None)
         })
     }
 
@@ -500,32 +810,87 @@ where
         target: TContentKey,
         is_trace: bool,
     ) -> (Option<Vec<u8>>, Option<QueryTrace>) {
-        let (tx, rx) = oneshot::channel();
         let content_id = target.content_id();
+        self.try_lookup_content(target, is_trace)
+            .await
+            .unwrap_or_else(|err| {
+                warn!(
+                    protocol = %self.protocol,
+                    error = %err,
+                    content.id = %hex_encode(content_id),
+                    "Error performing content lookup",
+                );
+                (None, None)
+            })
+    }
 
-        if let Err(err) = self.command_tx.send(OverlayCommand::FindContentQuery {
-            target,
-            callback: tx,
-            is_trace,
-        }) {
-            warn!(
-                protocol = %self.protocol,
-                error = %err,
-                content.id = %hex_encode(content_id),
-                "Error submitting FindContent query to service"
-            );
-            return (None, None);
+    /// Performs content lookups for `targets`, with at most `max_concurrency` queries in flight.
+    /// The concurrency is capped at `MAX_BATCH_LOOKUP_CONCURRENCY`.
+    ///
+    /// Duplicate keys are looked up once. Keys are queried in order of content ID, so that
+    /// concurrent queries target nearby content and share the peers they discover. Results are
+    /// yielded as the lookups complete, which is not necessarily the order of `targets`.
+    pub fn lookup_content_batch(
+        &self,
+        targets: Vec<TContentKey>,
+        max_concurrency: usize,
+    ) -> impl Stream<Item = (TContentKey, Result<Vec<u8>, ContentLookupError>)> + '_ {
+        let mut seen = HashSet::new();
+        let mut targets: Vec<TContentKey> = targets
+            .into_iter()
+            .filter(|target| seen.insert(target.content_id()))
+            .collect();
+        targets.sort_by_key(|target| target.content_id());
+
+        stream::iter(targets)
+            .map(move |target| async move {
+                let result = match self.try_lookup_content(target.clone(), false).await {
+                    Ok((Some(content), _)) => Ok(content),
+                    Ok((None, _)) => Err(ContentLookupError::NotFound),
+                    Err(err) => Err(ContentLookupError::Request(err)),
+                };
+                (target, result)
+            })
+            .buffer_unordered(max_concurrency.clamp(1, MAX_BATCH_LOOKUP_CONCURRENCY))
+    }
+
+    /// Performs a content lookup for `target`, first consulting the content cache.
+    async fn try_lookup_content(
+        &self,
+        target: TContentKey,
+        is_trace: bool,
+    ) -> Result<(Option<Vec<u8>>, Option<QueryTrace>), OverlayRequestError> {
+        let content_id = target.content_id();
+
+        // Check whether the content was recently fetched, before querying the network.
+        if self.content_cache.read().is_enabled() {
+            let cached_content = self.content_cache.write().get(&content_id);
+            self.metrics
+                .report_cache_lookup(&self.protocol, cached_content.is_some());
+            if let Some(content) = cached_content {
+                let trace = if is_trace {
+                    let local_enr = self.local_enr();
+                    let mut trace = QueryTrace::new(&local_enr, NodeId::new(&content_id).into());
+                    trace.node_responded_with_content(&local_enr);
+                    Some(trace)
+                } else {
+                    None
+                };
+                return Ok((Some(content), trace));
+            }
         }
 
-        rx.await.unwrap_or_else(|err| {
-            warn!(
-                protocol = %self.protocol,
-                error = %err,
-                content.id = %hex_encode(content_id),
-                "Error receiving content from service",
-            );
-            (None, None)
-        })
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(OverlayCommand::FindContentQuery {
+                target,
+                callback: tx,
+                is_trace,
+            })
+            .map_err(|err| OverlayRequestError::ChannelFailure(err.to_string()))?;
+
+        rx.await
+            .map_err(|err| OverlayRequestError::ChannelFailure(err.to_string()))
     }
 
     /// Sends a request through the overlay service.
@@ -594,28 +959,38 @@ where
     }
 
     pub fn get_summary_info(&self) -> String {
+        let cache_hits = self.metrics.cache_lookup_count_by_labels(
+            ProtocolLabel::from(&self.protocol),
+            CacheLookupLabel::Hit,
+        );
+        let cache_misses = self.metrics.cache_lookup_count_by_labels(
+            ProtocolLabel::from(&self.protocol),
+            CacheLookupLabel::Miss,
+        );
         format!(
-            "offers={}/{}, accepts={}/{}",
+            "offers={}/{}, accepts={}/{}, cache_hits={}/{}",
             self.metrics.message_count_by_labels(
-                ProtocolLabel::History,
+                ProtocolLabel::from(&self.protocol),
                 MessageDirectionLabel::Received,
                 MessageLabel::Accept
             ),
             self.metrics.message_count_by_labels(
-                ProtocolLabel::History,
+                ProtocolLabel::from(&self.protocol),
                 MessageDirectionLabel::Sent,
                 MessageLabel::Offer
             ),
             self.metrics.message_count_by_labels(
-                ProtocolLabel::History,
+                ProtocolLabel::from(&self.protocol),
                 MessageDirectionLabel::Sent,
                 MessageLabel::Accept
             ),
             self.metrics.message_count_by_labels(
-                ProtocolLabel::History,
+                ProtocolLabel::from(&self.protocol),
                 MessageDirectionLabel::Received,
                 MessageLabel::Offer
             ),
+            cache_hits,
+            cache_hits + cache_misses,
         )
     }
 }
@@ -646,6 +1021,30 @@ fn validate_find_nodes_distances(distances: &Vec<u16>) -> Result<(), OverlayRequ
     Ok(())
 }
 
+/// Estimates the number of nodes in the network from the number of entries in each bucket, given
+/// as pairs of the log2 distance of the bucket and its number of entries.
+///
+/// The bucket at log2 distance `d` covers a fraction `2^(d - 257)` of the key space. Buckets that
+/// are not full are assumed to contain every node in their range, so the density of nodes in those
+/// buckets is extrapolated to the whole key space. Full buckets only give a lower bound.
+fn estimate_network_size(occupancy: &[(u16, usize)]) -> u64 {
+    let num_nodes: usize = occupancy.iter().map(|(_, entries)| entries).sum();
+    let (sampled_nodes, sampled_fraction) = occupancy
+        .iter()
+        .filter(|(_, entries)| *entries < MAX_NODES_PER_BUCKET)
+        .fold((0, 0.0), |(nodes, fraction), (distance, entries)| {
+            (
+                nodes + entries,
+                fraction + 2f64.powi(i32::from(*distance) - 257),
+            )
+        });
+    if sampled_fraction == 0.0 {
+        return num_nodes as u64;
+    }
+    let estimate = (sampled_nodes as f64 / sampled_fraction).round() as u64;
+    estimate.max(num_nodes as u64)
+}
+
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
 mod test {
@@ -673,4 +1072,54 @@ mod test {
             Err(err) => assert!(err.to_string().contains(&msg)),
         }
     }
+
+    #[test]
+    fn test_estimate_network_size() {
+        // A network of 1024 nodes: the 6 farthest buckets are full, and the nearer buckets hold
+        // every node in their range.
+        let mut occupancy: Vec<(u16, usize)> = (251..=256).map(|d| (d, 16)).collect();
+        occupancy.extend([(250, 8), (249, 4), (248, 2), (247, 1), (246, 1)]);
+        occupancy.extend((1..246).map(|d| (d, 0)));
+        assert_eq!(estimate_network_size(&occupancy), 1024);
+
+        // A table with only full buckets gives a lower bound.
+        assert_eq!(estimate_network_size(&[(256, 16), (255, 16)]), 32);
+        assert_eq!(estimate_network_size(&[]), 0);
+    }
+
+    #[test]
+    fn test_overlay_config_from_args() {
+        let args = OverlayConfigArgs {
+            query_parallelism: Some(8),
+            query_peer_timeout: Some(Duration::from_secs(2)),
+            ip_limit: true,
+            utp_max_packet_size: Some(512),
+            max_failures: Some(5),
+            ..Default::default()
+        };
+        let config = OverlayConfig::from_args(&args);
+        let default = OverlayConfig::default();
+
+        assert_eq!(config.query_parallelism, 8);
+        assert_eq!(config.query_peer_timeout, Duration::from_secs(2));
+        assert_eq!(config.query_timeout, default.query_timeout);
+        assert_eq!(config.query_num_results, default.query_num_results);
+        assert!(config.table_filter.is_some());
+        assert!(config.bucket_filter.is_some());
+        assert_eq!(config.utp_config.max_packet_size, 512);
+        assert_eq!(
+            config.utp_config.initial_timeout,
+            UTP_CONN_CFG.initial_timeout
+        );
+
+        assert_eq!(config.liveness.max_failures, 5);
+        assert_eq!(
+            config.liveness.min_ping_interval,
+            default.liveness.min_ping_interval
+        );
+
+        let config = OverlayConfig::from_args(&OverlayConfigArgs::default());
+        assert_eq!(config.query_parallelism, default.query_parallelism);
+        assert!(config.table_filter.is_none());
+    }
 }
@@ -2,6 +2,7 @@ use std::{
     collections::HashMap,
     fmt::Debug,
     marker::{PhantomData, Sync},
+    net::SocketAddr,
     str::FromStr,
     sync::Arc,
     task::Poll,
@@ -20,47 +21,62 @@ use discv5::{
     rpc::RequestId,
 };
 use futures::{channel::oneshot, future::join_all, prelude::*};
-use parking_lot::RwLock;
-use rand::seq::{IteratorRandom, SliceRandom};
+use lru::LruCache;
+use parking_lot::{Mutex, RwLock};
+use rand::{
+    rngs::StdRng,
+    seq::{IteratorRandom, SliceRandom},
+    SeedableRng,
+};
 use smallvec::SmallVec;
 use ssz::Encode;
 use ssz_types::BitList;
 use thiserror::Error;
 use tokio::{
-    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    sync::{
+        broadcast,
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+    },
     task::JoinHandle,
+    time::Instant,
 };
 use tracing::{debug, error, info, trace, warn};
 use utp_rs::{conn::ConnectionConfig, socket::UtpSocket};
 
 use crate::{
-    discovery::Discovery,
+    content_cache::ContentCache,
+    discovery::Transport,
     find::{
         iterators::{
             findcontent::{FindContentQuery, FindContentQueryResponse, FindContentQueryResult},
             findnodes::FindNodeQuery,
             query::{Query, QueryConfig},
         },
-        query_info::{FindContentResult, QueryInfo, QueryType},
+        query_info::{FindContentResult, FindNodesResult, QueryInfo, QueryType},
         query_pool::{QueryId, QueryPool, QueryPoolState, TargetKey},
     },
+    gossip::{GossipOffer, GossipTracker},
+    liveness::{LivenessConfig, LivenessTracker, DEFAULT_PING_INTERVAL},
     metrics::OverlayMetrics,
-    storage::ContentStore,
+    peer_stats::PeerStatsTable,
+    storage::{ContentStore, ContentStoreError},
     types::{
         messages::{
-            Accept, Content, CustomPayload, FindContent, FindNodes, Message, Nodes, Offer, Ping,
-            Pong, PopulatedOffer, ProtocolId, Request, Response, MAX_PORTAL_CONTENT_PAYLOAD_SIZE,
+            Accept, Content, CustomPayload, FindContent, FindNodes, Message, Nodes, Ping, Pong,
+            PopulatedOffer, ProtocolId, Request, Response, MAX_PORTAL_CONTENT_PAYLOAD_SIZE,
             MAX_PORTAL_NODES_ENRS_SIZE,
         },
         node::Node,
+        payload_extension::{supports_payload_extension, PayloadExtension},
     },
     utils::portal_wire,
 };
 use ethportal_api::OverlayContentKey;
+use trin_types::cli::{DEFAULT_UTP_INITIAL_TIMEOUT, DEFAULT_UTP_MIN_TIMEOUT};
 use trin_types::content_key::RawContentKey;
 use trin_types::distance::{Distance, Metric, XorMetric};
 use trin_types::enr::{Enr, SszEnr};
-use trin_types::query_trace::QueryTrace;
+use trin_types::query_trace::{QueryFailureKind, QueryTrace};
 use trin_utils::bytes::{hex_encode, hex_encode_compact};
 use trin_validation::validator::Validator;
 
@@ -73,6 +89,10 @@ pub const FIND_CONTENT_MAX_NODES: usize = 32;
 /// which is more than 10x the ethereum mainnet node count) into a unique bucket by the 17th bucket index.
 const EXPECTED_NON_EMPTY_BUCKETS: usize = 17;
 
+/// Maximum number of peers that responded to recent FindContent queries, kept to seed subsequent
+/// queries for nearby content.
+const CONTENT_QUERY_PEERS_CAPACITY: usize = 256;
+
 /// Bucket refresh lookup interval in seconds
 const BUCKET_REFRESH_INTERVAL_SECS: u64 = 60;
 
@@ -81,8 +101,8 @@ pub const UTP_CONN_CFG: ConnectionConfig = ConnectionConfig {
     max_packet_size: 1024,
     max_conn_attempts: 3,
     max_idle_timeout: Duration::from_secs(32),
-    initial_timeout: Duration::from_millis(1500),
-    min_timeout: Duration::from_millis(500),
+    initial_timeout: DEFAULT_UTP_INITIAL_TIMEOUT,
+    min_timeout: DEFAULT_UTP_MIN_TIMEOUT,
     target_delay: Duration::from_millis(250),
 };
 
@@ -109,11 +129,17 @@ pub enum OverlayCommand<TContentKey> {
         /// Whether or not a trace for the content query should be kept and returned.
         is_trace: bool,
     },
+    /// Perform a find node query through the overlay.
+    ///
+    /// A `FindNodeQuery` issues multiple requests to find the nodes closest to `target`.
+    /// The result is sent to the issuer over `callback`.
     FindNodeQuery {
         /// The query target.
         target: NodeId,
         /// A callback channel to transmit the result of the query.
-        callback: oneshot::Sender<Vec<Enr>>,
+        callback: oneshot::Sender<FindNodesResult>,
+        /// Whether or not a trace for the node query should be kept and returned.
+        is_trace: bool,
     },
 }
 
@@ -193,7 +219,7 @@ pub enum RequestDirection {
 /// An identifier for an overlay network request. The ID is used to track active outgoing requests.
 // We only have visibility on the request IDs for incoming Discovery v5 talk requests. Here we use
 // a separate identifier to track outgoing talk requests.
-type OverlayRequestId = u128;
+pub(crate) type OverlayRequestId = u128;
 
 /// An overlay request response channel.
 type OverlayResponder = oneshot::Sender<Result<Response, OverlayRequestError>>;
@@ -244,6 +270,77 @@ struct ActiveOutgoingRequest {
     pub query_id: Option<QueryId>,
 }
 
+/// A read of the store that the response to an incoming request depends on.
+enum StoreRead<TContentKey> {
+    /// Reads the content requested by a `FindContent` request.
+    Content(TContentKey),
+    /// Checks which content offered by an `Offer` request to accept.
+    Availability(Vec<TContentKey>),
+}
+
+impl<TContentKey: OverlayContentKey> StoreRead<TContentKey> {
+    /// Executes the read. Blocks until the store can be read.
+    fn execute<TStore: ContentStore>(
+        self,
+        store: &RwLock<TStore>,
+    ) -> StoreReadOutcome<TContentKey> {
+        match self {
+            StoreRead::Content(content_key) => {
+                let content = store.read().get(&content_key);
+                StoreReadOutcome::Content {
+                    content_key,
+                    content,
+                }
+            }
+            StoreRead::Availability(content_keys) => {
+                let store = store.read();
+                let accepted = content_keys
+                    .iter()
+                    .map(|key| store.is_key_within_radius_and_unavailable(key))
+                    .collect();
+                StoreReadOutcome::Availability {
+                    content_keys,
+                    accepted,
+                }
+            }
+        }
+    }
+}
+
+/// The outcome of a `StoreRead`.
+enum StoreReadOutcome<TContentKey> {
+    /// The stored content for the key, if any.
+    Content {
+        content_key: TContentKey,
+        content: Result<Option<Vec<u8>>, ContentStoreError>,
+    },
+    /// Whether each key is within the radius and not stored yet.
+    Availability {
+        content_keys: Vec<TContentKey>,
+        accepted: Result<Vec<bool>, ContentStoreError>,
+    },
+}
+
+/// An incoming request whose store read has completed.
+struct CompletedStoreRead<TContentKey> {
+    /// The incoming request.
+    request: OverlayRequest,
+    /// The Discovery v5 identifier of the request.
+    id: RequestId,
+    /// The node that sent the request.
+    source: NodeId,
+    /// The outcome of the store read.
+    outcome: StoreReadOutcome<TContentKey>,
+}
+
+/// The handling of an incoming request.
+enum RequestHandling<TContentKey> {
+    /// The request is answered with the response.
+    Respond(Result<Response, OverlayRequestError>),
+    /// The request is answered once the store read completes.
+    ReadStore(StoreRead<TContentKey>),
+}
+
 /// A response for a particular overlay request.
 struct OverlayResponse {
     /// The identifier of the associated request.
@@ -254,10 +351,16 @@ struct OverlayResponse {
 
 /// The overlay service.
 pub struct OverlayService<TContentKey, TMetric, TValidator, TStore> {
-    /// The underlying Discovery v5 protocol.
-    discovery: Arc<Discovery>,
+    /// The transport of overlay messages, usually the underlying Discovery v5 protocol.
+    discovery: Arc<dyn Transport>,
     /// The content database of the local node.
     store: Arc<RwLock<TStore>>,
+    /// The data radius last read from the store, used while the store is locked.
+    last_radius: Mutex<Distance>,
+    /// The receiver half of a channel for incoming requests whose store read has completed.
+    store_read_rx: UnboundedReceiver<CompletedStoreRead<TContentKey>>,
+    /// The sender half of a channel for incoming requests whose store read has completed.
+    store_read_tx: UnboundedSender<CompletedStoreRead<TContentKey>>,
     /// The routing table of the local node.
     kbuckets: Arc<RwLock<KBucketsTable<NodeId, Node>>>,
     /// The protocol identifier.
@@ -267,6 +370,9 @@ pub struct OverlayService<TContentKey, TMetric, TValidator, TStore> {
     /// duration equal to some ping interval, and we continuously poll the queue to check for
     /// expired entries.
     peers_to_ping: HashSetDelay<NodeId>,
+    /// Consecutive request failures of routing table nodes, which determine when nodes are pinged
+    /// and evicted.
+    liveness: LivenessTracker,
     // TODO: This should probably be a bounded channel.
     /// The receiver half of the service command channel.
     command_rx: UnboundedReceiver<OverlayCommand<TContentKey>>,
@@ -293,14 +399,32 @@ pub struct OverlayService<TContentKey, TMetric, TValidator, TStore> {
     response_tx: UnboundedSender<OverlayResponse>,
     /// uTP socket.
     utp_socket: Arc<UtpSocket<crate::discovery::UtpEnr>>,
+    /// The configuration of uTP connections.
+    utp_config: ConnectionConfig,
     /// Phantom content key.
     phantom_content_key: PhantomData<TContentKey>,
     /// Phantom metric (distance function).
     phantom_metric: PhantomData<TMetric>,
     /// Metrics reporting component
     metrics: Arc<OverlayMetrics>,
+    /// Round-trip time and success statistics of outgoing requests, per peer.
+    peer_stats: Arc<RwLock<PeerStatsTable>>,
+    /// Cache of validated content from recent lookups that falls outside the local radius.
+    content_cache: Arc<RwLock<ContentCache>>,
+    /// Peers that responded to recent FindContent queries. Subsequent queries for nearby content
+    /// are seeded with these peers, even if they are absent from the routing table.
+    content_query_peers: LruCache<NodeId, Enr>,
+    /// Delivery status of gossiped content.
+    gossip_tracker: Arc<RwLock<GossipTracker>>,
+    /// The overlay-specific extension of the custom payload in Ping and Pong messages.
+    payload_extension: Option<Arc<dyn PayloadExtension>>,
+    /// The receiver half of a channel for updates to the external socket address of the local
+    /// node.
+    external_addr_updates: broadcast::Receiver<SocketAddr>,
     /// Validator for overlay network content.
     validator: Arc<TValidator>,
+    /// The RNG that picks the targets of routing table refreshes.
+    rng: StdRng,
 }
 
 impl<
@@ -319,7 +443,7 @@ where
     /// processes.
     #[allow(clippy::too_many_arguments)]
     pub async fn spawn(
-        discovery: Arc<Discovery>,
+        discovery: Arc<dyn Transport>,
         store: Arc<RwLock<TStore>>,
         kbuckets: Arc<RwLock<KBucketsTable<NodeId, Node>>>,
         bootnode_enrs: Vec<Enr>,
@@ -327,12 +451,19 @@ where
         protocol: ProtocolId,
         utp_socket: Arc<UtpSocket<crate::discovery::UtpEnr>>,
         metrics: Arc<OverlayMetrics>,
+        peer_stats: Arc<RwLock<PeerStatsTable>>,
+        content_cache: Arc<RwLock<ContentCache>>,
+        gossip_tracker: Arc<RwLock<GossipTracker>>,
+        payload_extension: Option<Arc<dyn PayloadExtension>>,
         validator: Arc<TValidator>,
         query_timeout: Duration,
         query_peer_timeout: Duration,
         query_parallelism: usize,
         query_num_results: usize,
         findnodes_query_distances_per_peer: usize,
+        utp_config: ConnectionConfig,
+        liveness_config: LivenessConfig,
+        rng_seed: Option<u64>,
     ) -> UnboundedSender<OverlayCommand<TContentKey>>
     where
         <TContentKey as TryFrom<Vec<u8>>>::Error: Send,
@@ -342,21 +473,30 @@ where
 
         let overlay_protocol = protocol.clone();
 
-        let peers_to_ping = if let Some(interval) = ping_queue_interval {
-            HashSetDelay::new(interval)
-        } else {
-            HashSetDelay::default()
-        };
+        let ping_interval = ping_queue_interval.unwrap_or(DEFAULT_PING_INTERVAL);
+        let peers_to_ping = HashSetDelay::new(ping_interval);
+        let liveness = LivenessTracker::new(liveness_config, ping_interval);
 
         let (response_tx, response_rx) = mpsc::unbounded_channel();
+        let (store_read_tx, store_read_rx) = mpsc::unbounded_channel();
+        let external_addr_updates = discovery.subscribe_external_addr_updates();
+        let last_radius = Mutex::new(store.read().radius());
+        let rng = match rng_seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
 
         tokio::spawn(async move {
             let mut service = Self {
                 discovery,
                 store,
+                last_radius,
+                store_read_rx,
+                store_read_tx,
                 kbuckets,
                 protocol,
                 peers_to_ping,
+                liveness,
                 command_rx,
                 command_tx: internal_command_tx,
                 active_outgoing_requests: Arc::new(RwLock::new(HashMap::new())),
@@ -369,10 +509,18 @@ where
                 response_rx,
                 response_tx,
                 utp_socket,
+                utp_config,
                 phantom_content_key: PhantomData,
                 phantom_metric: PhantomData,
                 metrics,
+                peer_stats,
+                content_cache,
+                content_query_peers: LruCache::new(CONTENT_QUERY_PEERS_CAPACITY),
+                gossip_tracker,
+                payload_extension,
+                external_addr_updates,
                 validator,
+                rng,
             };
 
             info!(protocol = %overlay_protocol, "Starting overlay service");
@@ -421,7 +569,7 @@ where
                     );
 
                     // Queue the node in the ping queue.
-                    self.peers_to_ping.insert(node_id);
+                    self.schedule_ping(node_id);
                 }
             }
         }
@@ -433,14 +581,15 @@ where
         let local_node_id = self.local_enr().node_id();
 
         // Begin request for our local node ID.
-        self.init_find_nodes_query(&local_node_id, None);
+        self.init_find_nodes_query(&local_node_id, None, false);
 
         for bucket_index in (255 - EXPECTED_NON_EMPTY_BUCKETS as u8)..255 {
-            let target_node_id = trin_types::node_id::NodeId::generate_random_node_id(
+            let target_node_id = trin_types::node_id::NodeId::generate_random_node_id_with_rng(
                 bucket_index,
                 self.local_enr().into(),
+                &mut self.rng,
             );
-            self.init_find_nodes_query(&target_node_id.into(), None);
+            self.init_find_nodes_query(&target_node_id.into(), None, false);
         }
     }
 
@@ -449,6 +598,8 @@ where
     ///
     /// Process request: Process an incoming or outgoing request through the overlay.
     ///
+    /// Process store read: Answer an incoming request whose store read has completed.
+    ///
     /// Process response: Process a response to an outgoing request from the local node. Try to
     /// match this response to an active request, and send the response or error over the
     /// associated response channel. Update node state based on result of response.
@@ -477,8 +628,8 @@ where
                                 );
                             }
                         }
-                        OverlayCommand::FindNodeQuery { target, callback } => {
-                            if let Some(query_id) = self.init_find_nodes_query(&target, Some(callback)) {
+                        OverlayCommand::FindNodeQuery { target, callback, is_trace } => {
+                            if let Some(query_id) = self.init_find_nodes_query(&target, Some(callback), is_trace) {
                                 trace!(
                                     query.id = %query_id,
                                     node.id = %hex_encode_compact(target),
@@ -488,6 +639,7 @@ where
                         }
                     }
                 }
+                Some(store_read) = self.store_read_rx.recv() => self.process_store_read(store_read),
                 Some(response) = self.response_rx.recv() => {
                     // Look up active request that corresponds to the response.
                     let optional_active_request = self.active_outgoing_requests.write().remove(&response.request_id);
@@ -499,12 +651,13 @@ where
                         }
 
                         // Perform background processing.
+                        let request_id = response.request_id;
                         match response.response {
                             Ok(response) => {
                                 self.metrics.report_inbound_response(&self.protocol, &response);
-                                self.process_response(response, active_request.destination, active_request.request, active_request.query_id)
+                                self.process_response(response, request_id, active_request.destination, active_request.request, active_request.query_id)
                             }
-                            Err(error) => self.process_request_failure(response.request_id, active_request.destination, error),
+                            Err(error) => self.process_request_failure(request_id, active_request.destination, error, active_request.request, active_request.query_id),
                         }
 
                     } else {
@@ -514,9 +667,13 @@ where
                 Some(Ok(node_id)) = self.peers_to_ping.next() => {
                     // If the node is in the routing table, then ping and re-queue the node.
                     let key = kbucket::Key::from(node_id);
-                    if let kbucket::Entry::Present(ref mut entry, _) = self.kbuckets.write().entry(&key) {
-                        self.ping_node(&entry.value().enr());
-                        self.peers_to_ping.insert(node_id);
+                    let enr = match self.kbuckets.write().entry(&key) {
+                        kbucket::Entry::Present(ref mut entry, _) => Some(entry.value().enr()),
+                        _ => None,
+                    };
+                    if let Some(enr) = enr {
+                        self.ping_node(&enr);
+                        self.schedule_ping(node_id);
                     }
                 }
                 query_event = OverlayService::<TContentKey, TMetric, TValidator, TStore>::query_event_poll(&mut self.find_node_query_pool) => {
@@ -527,9 +684,22 @@ where
                     self.handle_find_content_query_event(query_event);
                 }
                 _ = OverlayService::<TContentKey, TMetric, TValidator, TStore>::bucket_maintenance_poll(self.protocol.clone(), &self.kbuckets) => {}
+                Ok(external_addr) = self.external_addr_updates.recv() => {
+                    // Ping all peers so that they learn the updated local ENR from the ENR
+                    // sequence number in the ping.
+                    debug!(protocol = %self.protocol, addr = %external_addr, "Pinging peers after external address update");
+                    self.ping_connected_peers();
+                }
                 _ = bucket_refresh_interval.tick() => {
                     trace!(protocol = %self.protocol, "Routing table bucket refresh");
                     self.bucket_refresh_lookup();
+                    // Offers whose outcome was never reported are failed, so that they do not
+                    // pile up in the gossip tracker.
+                    let retries = self
+                        .gossip_tracker
+                        .write()
+                        .expire_offers(Instant::now().into_std());
+                    send_gossip_offers(retries, &self.gossip_tracker, &self.command_tx);
                 }
             }
         }
@@ -546,14 +716,15 @@ where
             let buckets = &buckets[256 - EXPECTED_NON_EMPTY_BUCKETS..];
 
             // Randomly pick one of these buckets.
-            let target_bucket = buckets.choose(&mut rand::thread_rng());
+            let target_bucket = buckets.choose(&mut self.rng);
             match target_bucket {
                 Some(bucket) => {
                     trace!(protocol = %self.protocol, bucket = %bucket.0, "Refreshing routing table bucket");
                     match u8::try_from(bucket.0) {
-                        Ok(idx) => trin_types::node_id::NodeId::generate_random_node_id(
+                        Ok(idx) => trin_types::node_id::NodeId::generate_random_node_id_with_rng(
                             idx,
                             self.local_enr().into(),
+                            &mut self.rng,
                         ),
                         Err(err) => {
                             error!(error = %err, "Error downcasting bucket index");
@@ -568,7 +739,7 @@ where
             }
         };
 
-        self.init_find_nodes_query(&target_node_id.into(), None);
+        self.init_find_nodes_query(&target_node_id.into(), None, false);
     }
 
     /// Returns the local ENR of the node.
@@ -577,8 +748,18 @@ where
     }
 
     /// Returns the data radius of the node.
+    ///
+    /// The store may be locked by a long write, such as an eviction, so the radius is read without
+    /// waiting for the lock. While the store is locked, the radius last read is returned.
     fn data_radius(&self) -> Distance {
-        self.store.read().radius()
+        match self.store.try_read() {
+            Some(store) => {
+                let radius = store.radius();
+                *self.last_radius.lock() = radius;
+                radius
+            }
+            None => *self.last_radius.lock(),
+        }
     }
 
     /// Maintains the routing table.
@@ -651,6 +832,12 @@ where
             QueryEvent::Waiting(query_id, node_id, request) => {
                 // Look up the node's ENR.
                 if let Some(enr) = self.find_enr(&node_id) {
+                    if let Some((query_info, _)) = self.find_node_query_pool.get_mut(query_id) {
+                        if let Some(trace) = &mut query_info.trace {
+                            trace.node_request_sent(&enr);
+                        }
+                    }
+
                     let request = OverlayRequest::new(
                         request,
                         RequestDirection::Outgoing { destination: enr },
@@ -699,7 +886,7 @@ where
                     ..
                 } = query_info.query_type
                 {
-                    if let Err(err) = callback.send(found_enrs.clone()) {
+                    if let Err(err) = callback.send((found_enrs.clone(), query_info.trace)) {
                         error!(
                             query.id = %query_id,
                             error = ?err,
@@ -725,6 +912,17 @@ where
         match query_event {
             QueryEvent::Waiting(query_id, node_id, request) => {
                 if let Some(enr) = self.find_enr(&node_id) {
+                    if let Some((query_info, query)) =
+                        self.find_content_query_pool.get_mut(query_id)
+                    {
+                        if let Some(trace) = &mut query_info.trace {
+                            if query.is_radius_covering_peer(&node_id) {
+                                trace.node_contacted_for_radius(&enr);
+                            }
+                            trace.node_request_sent(&enr);
+                        }
+                    }
+
                     // If we find the node's ENR, then send the request on behalf of the
                     // query. No callback channel is necessary for the request, because the
                     // response will be incorporated into the query.
@@ -842,17 +1040,14 @@ where
             RequestDirection::Incoming { id, source } => {
                 self.register_node_activity(source);
 
-                let response = self.handle_request(request.request.clone(), id.clone(), &source);
-                // Send response to responder if present.
-                if let Some(responder) = request.responder {
-                    if let Ok(ref response) = response {
-                        self.metrics
-                            .report_outbound_response(&self.protocol, response);
+                match self.handle_request(request.request.clone(), id.clone(), &source) {
+                    RequestHandling::Respond(response) => {
+                        self.respond(request, id, source, response)
+                    }
+                    RequestHandling::ReadStore(read) => {
+                        self.spawn_store_read(request, id, source, read)
                     }
-                    let _ = responder.send(response);
                 }
-                // Perform background processing.
-                self.process_incoming_request(request.request, id, source);
             }
             RequestDirection::Outgoing { destination } => {
                 self.active_outgoing_requests.write().insert(
@@ -871,31 +1066,134 @@ where
         }
     }
 
+    /// Sends the response to an incoming request over the responder, if present, and then
+    /// processes the request.
+    fn respond(
+        &mut self,
+        request: OverlayRequest,
+        id: RequestId,
+        source: NodeId,
+        response: Result<Response, OverlayRequestError>,
+    ) {
+        if let Some(responder) = request.responder {
+            if let Ok(ref response) = response {
+                self.metrics
+                    .report_outbound_response(&self.protocol, response);
+            }
+            let _ = responder.send(response);
+        }
+        // Perform background processing.
+        self.process_incoming_request(request.request, id, source);
+    }
+
     /// Attempts to build a response for a request.
+    ///
+    /// Answering a `FindContent` or `Offer` request reads the store, which may be locked by a long
+    /// write such as an eviction. Those requests are answered once the store read completes, so
+    /// that the service loop never waits for the lock.
     fn handle_request(
         &mut self,
         request: Request,
         id: RequestId,
         source: &NodeId,
-    ) -> Result<Response, OverlayRequestError> {
+    ) -> RequestHandling<TContentKey> {
         match request {
-            Request::Ping(ping) => Ok(Response::Pong(self.handle_ping(ping, source, id))),
-            Request::FindNodes(find_nodes) => Ok(Response::Nodes(
+            Request::Ping(ping) => {
+                RequestHandling::Respond(Ok(Response::Pong(self.handle_ping(ping, source, id))))
+            }
+            Request::FindNodes(find_nodes) => RequestHandling::Respond(Ok(Response::Nodes(
                 self.handle_find_nodes(find_nodes, source, id),
-            )),
-            Request::FindContent(find_content) => Ok(Response::Content(self.handle_find_content(
-                find_content,
-                source,
-                id,
-            )?)),
-            Request::Offer(offer) => Ok(Response::Accept(self.handle_offer(offer, source, id)?)),
-            Request::PopulatedOffer(_) => Err(OverlayRequestError::InvalidRequest(
-                "An offer with content attached is not a valid network message to receive"
-                    .to_owned(),
-            )),
+            ))),
+            Request::FindContent(find_content) => {
+                match (TContentKey::try_from)(find_content.content_key) {
+                    Ok(content_key) => RequestHandling::ReadStore(StoreRead::Content(content_key)),
+                    Err(_) => RequestHandling::Respond(Err(OverlayRequestError::InvalidRequest(
+                        "Invalid content key".to_string(),
+                    ))),
+                }
+            }
+            Request::Offer(offer) => {
+                match offer
+                    .content_keys
+                    .into_iter()
+                    .map(|k| (TContentKey::try_from)(k))
+                    .collect::<Result<Vec<TContentKey>, _>>()
+                {
+                    Ok(content_keys) => {
+                        RequestHandling::ReadStore(StoreRead::Availability(content_keys))
+                    }
+                    Err(_) => RequestHandling::Respond(Err(OverlayRequestError::AcceptError(
+                        "Unable to build content key from OFFER request".to_owned(),
+                    ))),
+                }
+            }
+            Request::PopulatedOffer(_) => {
+                RequestHandling::Respond(Err(OverlayRequestError::InvalidRequest(
+                    "An offer with content attached is not a valid network message to receive"
+                        .to_owned(),
+                )))
+            }
         }
     }
 
+    /// Reads the store for an incoming request off the service loop. Once the read completes, the
+    /// request is handed back to the service loop to be answered.
+    fn spawn_store_read(
+        &self,
+        request: OverlayRequest,
+        id: RequestId,
+        source: NodeId,
+        read: StoreRead<TContentKey>,
+    ) {
+        let store = Arc::clone(&self.store);
+        let store_read_tx = self.store_read_tx.clone();
+        tokio::spawn(async move {
+            match tokio::task::spawn_blocking(move || read.execute(&store)).await {
+                Ok(outcome) => {
+                    let _ = store_read_tx.send(CompletedStoreRead {
+                        request,
+                        id,
+                        source,
+                        outcome,
+                    });
+                }
+                Err(err) => {
+                    error!(error = %err, "Error reading store for incoming request");
+                    if let Some(responder) = request.responder {
+                        let _ = responder.send(Err(OverlayRequestError::Failure(format!(
+                            "Unable to read store: {err}"
+                        ))));
+                    }
+                }
+            }
+        });
+    }
+
+    /// Answers an incoming request whose store read has completed.
+    fn process_store_read(&mut self, store_read: CompletedStoreRead<TContentKey>) {
+        let CompletedStoreRead {
+            request,
+            id,
+            source,
+            outcome,
+        } = store_read;
+        let response = match outcome {
+            StoreReadOutcome::Content {
+                content_key,
+                content,
+            } => self
+                .handle_find_content(content_key, content, &source, id.clone())
+                .map(Response::Content),
+            StoreReadOutcome::Availability {
+                content_keys,
+                accepted,
+            } => self
+                .handle_offer(content_keys, accepted, &source, id.clone())
+                .map(Response::Accept),
+        };
+        self.respond(request, id, source, response);
+    }
+
     /// Builds a `Pong` response for a `Ping` request.
     fn handle_ping(&self, request: Ping, source: &NodeId, request_id: RequestId) -> Pong {
         trace!(
@@ -906,9 +1204,9 @@ where
             request
         );
 
+        // Only respond with a payload extension to a peer that sent one.
         let enr_seq = self.local_enr().seq();
-        let data_radius = self.data_radius();
-        let custom_payload = CustomPayload::from(data_radius.as_ssz_bytes());
+        let custom_payload = self.custom_payload(request.custom_payload.extension().is_some());
         Pong {
             enr_seq,
             custom_payload,
@@ -938,10 +1236,12 @@ where
         Nodes { total: 1, enrs }
     }
 
-    /// Attempts to build a `Content` response for a `FindContent` request.
+    /// Attempts to build a `Content` response for a `FindContent` request, given the content read
+    /// from the store.
     fn handle_find_content(
         &self,
-        request: FindContent,
+        content_key: TContentKey,
+        content: Result<Option<Vec<u8>>, ContentStoreError>,
         source: &NodeId,
         request_id: RequestId,
     ) -> Result<Content, OverlayRequestError> {
@@ -952,15 +1252,7 @@ where
             "Handling FindContent message",
         );
 
-        let content_key = match (TContentKey::try_from)(request.content_key) {
-            Ok(key) => key,
-            Err(_) => {
-                return Err(OverlayRequestError::InvalidRequest(
-                    "Invalid content key".to_string(),
-                ))
-            }
-        };
-        match self.store.read().get(&content_key) {
+        match content {
             Ok(Some(content)) => {
                 if content.len() <= MAX_PORTAL_CONTENT_PAYLOAD_SIZE {
                     Ok(Content::Content(content))
@@ -978,9 +1270,9 @@ where
                     // Wait for an incoming connection with the given CID. Then, write the data
                     // over the uTP stream.
                     let utp = Arc::clone(&self.utp_socket);
+                    let utp_config = self.utp_config;
                     tokio::spawn(async move {
-                        let mut stream = match utp.accept_with_cid(cid.clone(), UTP_CONN_CFG).await
-                        {
+                        let mut stream = match utp.accept_with_cid(cid.clone(), utp_config).await {
                             Ok(stream) => stream,
                             Err(err) => {
                                 error!(
@@ -1036,10 +1328,12 @@ where
         }
     }
 
-    /// Attempts to build an `Accept` response for an `Offer` request.
+    /// Attempts to build an `Accept` response for an `Offer` request, given whether the store
+    /// accepts each offered key.
     fn handle_offer(
         &self,
-        request: Offer,
+        content_keys: Vec<TContentKey>,
+        accepted: Result<Vec<bool>, ContentStoreError>,
         source: &NodeId,
         request_id: RequestId,
     ) -> Result<Accept, OverlayRequestError> {
@@ -1050,35 +1344,17 @@ where
             "Handling Offer message",
         );
 
-        let mut requested_keys =
-            BitList::with_capacity(request.content_keys.len()).map_err(|_| {
-                OverlayRequestError::AcceptError(
-                    "Unable to initialize bitlist for requested keys.".to_owned(),
-                )
-            })?;
-
-        let content_keys: Vec<TContentKey> = request
-            .content_keys
-            .into_iter()
-            .map(|k| (TContentKey::try_from)(k))
-            .collect::<Result<Vec<TContentKey>, _>>()
-            .map_err(|_| {
-                OverlayRequestError::AcceptError(
-                    "Unable to build content key from OFFER request".to_owned(),
-                )
-            })?;
+        let mut requested_keys = BitList::with_capacity(content_keys.len()).map_err(|_| {
+            OverlayRequestError::AcceptError(
+                "Unable to initialize bitlist for requested keys.".to_owned(),
+            )
+        })?;
 
-        for (i, key) in content_keys.iter().enumerate() {
-            // Accept content if within radius and not already present in the data store.
-            let accept = self
-                .store
-                .read()
-                .is_key_within_radius_and_unavailable(key)
-                .map_err(|err| {
-                    OverlayRequestError::AcceptError(format!(
-                        "Unable to check content availability {err}"
-                    ))
-                })?;
+        // Accept content if within radius and not already present in the data store.
+        let accepted = accepted.map_err(|err| {
+            OverlayRequestError::AcceptError(format!("Unable to check content availability {err}"))
+        })?;
+        for (i, accept) in accepted.into_iter().enumerate() {
             requested_keys.set(i, accept).map_err(|err| {
                 OverlayRequestError::AcceptError(format!(
                     "Unable to set requested keys bits: {err:?}"
@@ -1107,12 +1383,14 @@ where
         let store = Arc::clone(&self.store);
         let kbuckets = Arc::clone(&self.kbuckets);
         let command_tx = self.command_tx.clone();
+        let gossip_tracker = Arc::clone(&self.gossip_tracker);
         let utp = Arc::clone(&self.utp_socket);
+        let utp_config = self.utp_config;
 
         tokio::spawn(async move {
             // Wait for an incoming connection with the given CID. Then, read the data from the uTP
             // stream.
-            let mut stream = match utp.accept_with_cid(cid.clone(), UTP_CONN_CFG).await {
+            let mut stream = match utp.accept_with_cid(cid.clone(), utp_config).await {
                 Ok(stream) => stream,
                 Err(err) => {
                     warn!(%err, cid.send, cid.recv, peer = ?cid.peer.client(), "unable to accept uTP stream");
@@ -1131,6 +1409,7 @@ where
                 store,
                 kbuckets,
                 command_tx,
+                gossip_tracker,
                 content_keys,
                 data,
             )
@@ -1153,23 +1432,34 @@ where
         let discovery = Arc::clone(&self.discovery);
         let protocol = self.protocol.clone();
         let response_tx = self.response_tx.clone();
+        let peer_stats = Arc::clone(&self.peer_stats);
 
         // Spawn a new thread to send the TALK request. Otherwise we would delay processing of
         // other tasks until we receive the response. Send the response over the response channel,
         // which will be received in the main loop.
         tokio::spawn(async move {
+            let node_id = destination.node_id();
+            let sent_at = Instant::now();
             let response = match discovery
                 .send_talk_req(destination, protocol, Message::from(request).into())
                 .await
             {
-                Ok(talk_resp) => match Message::try_from(talk_resp) {
-                    Ok(message) => match Response::try_from(message) {
-                        Ok(response) => Ok(response),
-                        Err(_) => Err(OverlayRequestError::InvalidResponse),
-                    },
-                    Err(_) => Err(OverlayRequestError::DecodeError),
-                },
-                Err(error) => Err(error.into()),
+                Ok(talk_resp) => {
+                    peer_stats
+                        .write()
+                        .record_success(node_id, sent_at.elapsed());
+                    match Message::try_from(talk_resp) {
+                        Ok(message) => match Response::try_from(message) {
+                            Ok(response) => Ok(response),
+                            Err(_) => Err(OverlayRequestError::InvalidResponse),
+                        },
+                        Err(_) => Err(OverlayRequestError::DecodeError),
+                    }
+                }
+                Err(error) => {
+                    peer_stats.write().record_failure(node_id);
+                    Err(error.into())
+                }
             };
 
             let _ = response_tx.send(OverlayResponse {
@@ -1190,6 +1480,9 @@ where
 
     /// Register source NodeId activity in overlay routing table
     fn register_node_activity(&mut self, source: NodeId) {
+        self.peer_stats.write().record_seen(source);
+        self.liveness.record_success(&source);
+
         // Look up the node in the routing table.
         let key = kbucket::Key::from(source);
         let is_node_in_table = matches!(
@@ -1214,10 +1507,7 @@ where
             // peer.
             if let Some(node_addr) = self.discovery.cached_node_addr(&source) {
                 // TODO: Decide default data radius, and define a constant.
-                let node = Node {
-                    enr: node_addr.enr,
-                    data_radius: Distance::MAX,
-                };
+                let node = Node::new(node_addr.enr, Distance::MAX);
                 self.connect_node(node, ConnectionDirection::Incoming);
             }
         }
@@ -1235,19 +1525,16 @@ where
 
         // If the node is in the routing table, then check if we need to update the node.
         if let Some(node) = optional_node {
-            // TODO: How do we handle data in the custom payload? This is unique to each overlay
-            // network, so there may need to be some way to parameterize the update for a
-            // ping/pong.
-
             // If the ENR sequence number in pong is less than the ENR sequence number for the routing
             // table entry, then request the node.
             if node.enr().seq() < ping.enr_seq {
                 self.request_node(&node.enr());
             }
 
-            let data_radius: Distance = ping.custom_payload.into();
-            if node.data_radius != data_radius {
-                self.update_node_radius(node.enr(), data_radius);
+            let data_radius = ping.custom_payload.data_radius();
+            let payload_extension = ping.custom_payload.extension().map(|ext| ext.to_vec());
+            if node.data_radius != data_radius || node.payload_extension != payload_extension {
+                self.update_node_payload(node.enr(), data_radius, payload_extension);
             }
         }
     }
@@ -1258,6 +1545,8 @@ where
         request_id: OverlayRequestId,
         destination: Enr,
         error: OverlayRequestError,
+        request: Request,
+        query_id: Option<QueryId>,
     ) {
         debug!(
             protocol = %self.protocol,
@@ -1267,21 +1556,71 @@ where
             "Request failed",
         );
 
-        // Attempt to mark the node as disconnected.
+        // If the request was sent on behalf of a traced query, then record the failure.
+        if let Some(query_id) = query_id {
+            let kind = match error {
+                OverlayRequestError::Timeout => QueryFailureKind::Timeout,
+                _ => QueryFailureKind::Error,
+            };
+            let query_info = match request {
+                Request::FindNodes(_) => self
+                    .find_node_query_pool
+                    .get_mut(query_id)
+                    .map(|(query_info, _)| query_info),
+                Request::FindContent(_) => self
+                    .find_content_query_pool
+                    .get_mut(query_id)
+                    .map(|(query_info, _)| query_info),
+                _ => None,
+            };
+            if let Some(trace) = query_info.and_then(|query_info| query_info.trace.as_mut()) {
+                trace.node_failed(&destination, kind);
+            }
+        }
+
         let node_id = destination.node_id();
-        let _ = self.update_node_connection_state(node_id, ConnectionState::Disconnected);
-        // Remove the node from the ping queue.
-        self.peers_to_ping.remove(&node_id);
+        let key = kbucket::Key::from(node_id);
+        let is_node_in_table = matches!(
+            self.kbuckets.write().entry(&key),
+            kbucket::Entry::Present(_, _)
+        );
+        if is_node_in_table && self.liveness.record_failure(node_id) {
+            // The node failed too many consecutive requests.
+            self.evict_node(node_id);
+        } else if is_node_in_table {
+            // Mark the node as disconnected, so that a pending node may replace it, and ping it
+            // again after a backoff.
+            let _ = self.update_node_connection_state(node_id, ConnectionState::Disconnected);
+            self.schedule_ping(node_id);
+        } else {
+            // Attempt to mark the node as disconnected.
+            let _ = self.update_node_connection_state(node_id, ConnectionState::Disconnected);
+            // Remove the node from the ping queue.
+            self.peers_to_ping.remove(&node_id);
+        }
+
+        // If the request was a gossip offer, then offer the content to other interested peers.
+        self.fail_gossip_offer(request_id);
+    }
+
+    /// Records the failure of the gossip offer sent with `request_id`, if the request was one, and
+    /// offers the content to other interested peers instead.
+    fn fail_gossip_offer(&self, request_id: OverlayRequestId) {
+        let retries = self.gossip_tracker.write().on_failure(request_id);
+        send_gossip_offers(retries, &self.gossip_tracker, &self.command_tx);
     }
 
     /// Processes a response to an outgoing request from some source node.
     fn process_response(
         &mut self,
         response: Response,
+        request_id: OverlayRequestId,
         source: Enr,
         request: Request,
         query_id: Option<QueryId>,
     ) {
+        self.liveness.record_success(&source.node_id());
+
         // If the node is present in the routing table, but the node is not connected, then
         // use the existing entry's value and direction. Otherwise, build a new entry from
         // the source ENR and establish a connection in the outgoing direction, because this
@@ -1323,6 +1662,12 @@ where
             }
         }
 
+        // An offer is only answered by an ACCEPT, so any other response fails a gossip offer.
+        let is_offer = matches!(request, Request::Offer(_) | Request::PopulatedOffer(_));
+        if is_offer && !matches!(response, Response::Accept(_)) {
+            self.fail_gossip_offer(request_id);
+        }
+
         match response {
             Response::Pong(pong) => self.process_pong(pong, source),
             Response::Nodes(nodes) => self.process_nodes(nodes, source, query_id),
@@ -1340,15 +1685,22 @@ where
                 self.process_content(content, source, find_content_request, query_id)
             }
             Response::Accept(accept) => {
-                if let Err(err) = self.process_accept(accept, source, request) {
-                    error!(response.error = %err, "Error processing ACCEPT message")
+                if let Err(err) = self.process_accept(accept, request_id, source, request) {
+                    error!(response.error = %err, "Error processing ACCEPT message");
+                    self.fail_gossip_offer(request_id);
                 }
             }
         }
     }
 
     // Process ACCEPT response
-    fn process_accept(&self, response: Accept, enr: Enr, offer: Request) -> anyhow::Result<Accept> {
+    fn process_accept(
+        &self,
+        response: Accept,
+        request_id: OverlayRequestId,
+        enr: Enr,
+        offer: Request,
+    ) -> anyhow::Result<Accept> {
         // Check that a valid triggering request was sent
         match &offer {
             Request::Offer(_) => {}
@@ -1358,6 +1710,13 @@ where
             }
         };
 
+        // If the offer was gossip, then offer the declined content to other interested peers.
+        let retries = self
+            .gossip_tracker
+            .write()
+            .on_accept(request_id, &response.content_keys);
+        send_gossip_offers(retries, &self.gossip_tracker, &self.command_tx);
+
         // Do not initialize uTP stream if remote node doesn't have interest in the offered content keys
         if response.content_keys.is_zero() {
             return Ok(response);
@@ -1373,87 +1732,116 @@ where
 
         let store = Arc::clone(&self.store);
         let response_clone = response.clone();
+        let gossip_tracker = Arc::clone(&self.gossip_tracker);
+        let command_tx = self.command_tx.clone();
 
         let utp = Arc::clone(&self.utp_socket);
+        let utp_config = self.utp_config;
         tokio::spawn(async move {
-            let mut stream = match utp.connect_with_cid(cid.clone(), UTP_CONN_CFG).await {
-                Ok(stream) => stream,
-                Err(err) => {
-                    warn!(
-                        %err,
-                        cid.send,
-                        cid.recv,
-                        peer = ?cid.peer.client(),
-                        "Unable to establish uTP conn based on Accept",
-                    );
-                    return;
-                }
-            };
-
-            let content_items = match offer {
-                Request::Offer(offer) => {
-                    Self::provide_requested_content(store, &response_clone, offer.content_keys)
-                }
-                Request::PopulatedOffer(offer) => Ok(response_clone
-                    .content_keys
-                    .iter()
-                    .zip(offer.content_items.into_iter())
-                    .filter(|(is_accepted, _item)| *is_accepted)
-                    .map(|(_is_accepted, (_key, val))| val)
-                    .collect()),
-                // Unreachable because of early return at top of method:
-                _ => Err(anyhow!("Invalid request message paired with ACCEPT")),
-            };
+            let transferred =
+                Self::send_accepted_content(utp, utp_config, cid, store, response_clone, offer)
+                    .await;
 
-            let content_items: Vec<Bytes> = match content_items {
-                Ok(items) => items
-                    .into_iter()
-                    .map(|item| Bytes::from(item.to_vec()))
-                    .collect(),
-                Err(err) => {
-                    error!(
-                        %err,
-                        cid.send,
-                        cid.recv,
-                        peer = ?cid.peer.client(),
-                        "Error decoding previously offered content items"
-                    );
-                    return;
-                }
-            };
+            // If the offer was gossip, then record the outcome of the transfer.
+            let retries = gossip_tracker.write().on_transfer(request_id, transferred);
+            send_gossip_offers(retries, &gossip_tracker, &command_tx);
+        });
 
-            let content_payload = match portal_wire::encode_content_payload(&content_items) {
-                Ok(payload) => payload,
-                Err(err) => {
-                    warn!(%err, "Unable to build content payload");
-                    return;
-                }
-            };
+        Ok(response)
+    }
 
-            // send the content to the acceptor over a uTP stream
-            if let Err(err) = stream.write(&content_payload).await {
+    /// Sends the content accepted in `response` over a uTP stream with connection ID `cid`.
+    /// Returns whether the content was transferred.
+    async fn send_accepted_content(
+        utp: Arc<UtpSocket<crate::discovery::UtpEnr>>,
+        utp_config: ConnectionConfig,
+        cid: utp_rs::cid::ConnectionId<crate::discovery::UtpEnr>,
+        store: Arc<RwLock<TStore>>,
+        response: Accept,
+        offer: Request,
+    ) -> bool {
+        let mut stream = match utp.connect_with_cid(cid.clone(), utp_config).await {
+            Ok(stream) => stream,
+            Err(err) => {
                 warn!(
                     %err,
                     cid.send,
                     cid.recv,
                     peer = ?cid.peer.client(),
-                    "Error sending content over uTP connection"
+                    "Unable to establish uTP conn based on Accept",
                 );
+                return false;
             }
+        };
 
-            // close uTP connection
-            if let Err(err) = stream.shutdown() {
-                warn!(
+        let content_items = match offer {
+            // Reading the content blocks on disk, so keep it off the async worker threads.
+            Request::Offer(offer) => tokio::task::spawn_blocking(move || {
+                Self::provide_requested_content(store, &response, offer.content_keys)
+            })
+            .await
+            .unwrap_or_else(|err| Err(anyhow!("Unable to read offered content: {err}"))),
+            Request::PopulatedOffer(offer) => Ok(response
+                .content_keys
+                .iter()
+                .zip(offer.content_items.into_iter())
+                .filter(|(is_accepted, _item)| *is_accepted)
+                .map(|(_is_accepted, (_key, val))| val)
+                .collect()),
+            // Unreachable because `process_accept` only pairs ACCEPT with offers:
+            _ => Err(anyhow!("Invalid request message paired with ACCEPT")),
+        };
+
+        let content_items: Vec<Bytes> = match content_items {
+            Ok(items) => items
+                .into_iter()
+                .map(|item| Bytes::from(item.to_vec()))
+                .collect(),
+            Err(err) => {
+                error!(
                     %err,
                     cid.send,
                     cid.recv,
                     peer = ?cid.peer.client(),
-                    "Error closing uTP connection"
+                    "Error decoding previously offered content items"
                 );
-            };
-        });
+                return false;
+            }
+        };
 
-        Ok(response)
+        let content_payload = match portal_wire::encode_content_payload(&content_items) {
+            Ok(payload) => payload,
+            Err(err) => {
+                warn!(%err, "Unable to build content payload");
+                return false;
+            }
+        };
+
+        // send the content to the acceptor over a uTP stream
+        let mut transferred = true;
+        if let Err(err) = stream.write(&content_payload).await {
+            warn!(
+                %err,
+                cid.send,
+                cid.recv,
+                peer = ?cid.peer.client(),
+                "Error sending content over uTP connection"
+            );
+            transferred = false;
+        }
+
+        // close uTP connection
+        if let Err(err) = stream.shutdown() {
+            warn!(
+                %err,
+                cid.send,
+                cid.recv,
+                peer = ?cid.peer.client(),
+                "Error closing uTP connection"
+            );
+        };
+
+        transferred
     }
 
     /// Process accepted uTP payload of the OFFER/ACCEPT stream
@@ -1462,6 +1850,7 @@ where
         store: Arc<RwLock<TStore>>,
         kbuckets: Arc<RwLock<KBucketsTable<NodeId, Node>>>,
         command_tx: UnboundedSender<OverlayCommand<TContentKey>>,
+        gossip_tracker: Arc<RwLock<GossipTracker>>,
         content_keys: Vec<TContentKey>,
         payload: Vec<u8>,
     ) -> anyhow::Result<()> {
@@ -1480,13 +1869,10 @@ where
             .into_iter()
             .zip(content_values.to_vec())
             .map(|(key, content_value)| {
-                // Spawn a task that...
-                // - Validates accepted content (this step requires a dedicated task since it
-                // might require non-blocking requests to this/other overlay networks)
-                // - Checks if validated content should be stored, and stores it if true
-                // - Propagate all validated content
+                // Spawn a task that validates accepted content (this step requires a dedicated
+                // task since it might require non-blocking requests to this/other overlay
+                // networks).
                 let validator = Arc::clone(&validator);
-                let store = Arc::clone(&store);
                 tokio::spawn(async move {
                     // Validated received content
                     if let Err(err) = validator
@@ -1501,33 +1887,6 @@ where
                         );
                         return None;
                     }
-
-                    // Check if data should be stored, and store if true.
-                    let key_desired = store.read().is_key_within_radius_and_unavailable(&key);
-                    match key_desired {
-                        Ok(true) => {
-                            if let Err(err) = store.write().put(key.clone(), &content_value) {
-                                warn!(
-                                    error = %err,
-                                    content.key = %key.to_hex(),
-                                    "Error storing accepted content"
-                                );
-                            }
-                        }
-                        Ok(false) => {
-                            warn!(
-                                content.key = %key.to_hex(),
-                                "Accepted content outside radius or already stored"
-                            );
-                        }
-                        Err(err) => {
-                            warn!(
-                                error = %err,
-                                content.key = %key.to_hex(),
-                                "Error checking data store for content key"
-                            );
-                        }
-                    }
                     Some((key, content_value))
                 })
             })
@@ -1538,17 +1897,72 @@ where
             // Whether the spawn fails or the content fails validation, we don't want it:
             .filter_map(|content| content.unwrap_or(None))
             .collect();
+
+        // Store the validated content in a single batch, off the async worker threads.
+        let validated_content = tokio::task::spawn_blocking(move || {
+            Self::store_accepted_content(&store, &validated_content);
+            validated_content
+        })
+        .await?;
         // Propagate all validated content, whether or not it was stored.
         let validated_ids: Vec<String> = validated_content
             .iter()
             .map(|(k, _)| hex_encode_compact(k.content_id()))
             .collect();
         debug!(ids = ?validated_ids, "propagating validated content");
-        propagate_gossip_cross_thread(validated_content, kbuckets, command_tx.clone());
+        propagate_gossip_cross_thread(
+            validated_content,
+            kbuckets,
+            command_tx.clone(),
+            &gossip_tracker,
+        );
 
         Ok(())
     }
 
+    /// Stores the validated content that is within our radius and not already stored, in a
+    /// single batch. Blocks on disk, so it must not run on the async worker threads.
+    fn store_accepted_content(store: &RwLock<TStore>, content: &[(TContentKey, Vec<u8>)]) {
+        let mut desired_content = vec![];
+        for (key, content_value) in content {
+            match store.read().is_key_within_radius_and_unavailable(key) {
+                Ok(true) => desired_content.push((key.clone(), content_value)),
+                Ok(false) => {
+                    warn!(
+                        content.key = %key.to_hex(),
+                        "Accepted content outside radius or already stored"
+                    );
+                }
+                Err(err) => {
+                    warn!(
+                        error = %err,
+                        content.key = %key.to_hex(),
+                        "Error checking data store for content key"
+                    );
+                }
+            }
+        }
+        if desired_content.is_empty() {
+            return;
+        }
+
+        let keys: Vec<TContentKey> = desired_content.iter().map(|(key, _)| key.clone()).collect();
+        match store.write().put_batch(desired_content) {
+            Ok(results) => {
+                for (key, result) in keys.iter().zip(results) {
+                    if let Err(err) = result {
+                        warn!(
+                            error = %err,
+                            content.key = %key.to_hex(),
+                            "Error storing accepted content"
+                        );
+                    }
+                }
+            }
+            Err(err) => warn!(error = %err, "Error storing accepted content"),
+        }
+    }
+
     /// Processes a Pong response.
     ///
     /// Refreshes the node if necessary. Attempts to mark the node as connected.
@@ -1562,8 +1976,6 @@ where
 
         // If the ENR sequence number in pong is less than the ENR sequence number for the routing
         // table entry, then request the node.
-        //
-        // TODO: Perform update on non-ENR node entry state. See note in `process_ping`.
         let key = kbucket::Key::from(node_id);
         let optional_node = match self.kbuckets.write().entry(&key) {
             kbucket::Entry::Present(ref mut entry, _) => Some(entry.value().clone()),
@@ -1575,19 +1987,26 @@ where
                 self.request_node(&node.enr());
             }
 
-            let data_radius: Distance = pong.custom_payload.into();
-            if node.data_radius != data_radius {
-                self.update_node_radius(source, data_radius);
+            let data_radius = pong.custom_payload.data_radius();
+            let payload_extension = pong.custom_payload.extension().map(|ext| ext.to_vec());
+            if node.data_radius != data_radius || node.payload_extension != payload_extension {
+                self.update_node_payload(source, data_radius, payload_extension);
             }
         }
     }
 
-    /// Update the recorded radius of a node in our routing table.
-    fn update_node_radius(&self, enr: Enr, data_radius: Distance) {
+    /// Update the recorded radius and payload extension of a node in our routing table.
+    fn update_node_payload(
+        &self,
+        enr: Enr,
+        data_radius: Distance,
+        payload_extension: Option<Vec<u8>>,
+    ) {
         let node_id = enr.node_id();
         let key = kbucket::Key::from(node_id);
 
-        let updated_node = Node { enr, data_radius };
+        let mut updated_node = Node::new(enr, data_radius);
+        updated_node.set_payload_extension(payload_extension);
 
         if let UpdateResult::Failed(_) = self.kbuckets.write().update_node(&key, updated_node, None)
         {
@@ -1690,14 +2109,17 @@ where
                         return;
                     };
 
-                    if let Err(err) = store.write().put(content_key.clone(), content) {
-                        error!(
-                            error = %err,
-                            content.id = %hex_encode_compact(content_id),
-                            content.key = %content_key,
-                            "Error storing content"
-                        );
-                    }
+                    // Storing blocks on disk, so keep it off the async worker threads.
+                    tokio::task::spawn_blocking(move || {
+                        if let Err(err) = store.write().put(content_key.clone(), content) {
+                            error!(
+                                error = %err,
+                                content.id = %hex_encode_compact(content_id),
+                                content.key = %content_key,
+                                "Error storing content"
+                            );
+                        }
+                    });
                 });
             }
             Ok(false) => {
@@ -1706,6 +2128,29 @@ where
                     content.key = %content_key,
                     "Content not stored (key outside radius or already stored)"
                 );
+
+                // Keep content that falls outside of our radius in the content cache, so that
+                // repeated lookups do not require a network query.
+                let local_node_id = self.local_enr().node_id();
+                let is_outside_radius =
+                    TMetric::distance(&local_node_id.raw(), &content_id) > self.data_radius();
+                if is_outside_radius && self.content_cache.read().is_enabled() {
+                    let validator = Arc::clone(&self.validator);
+                    let content_cache = Arc::clone(&self.content_cache);
+                    tokio::spawn(async move {
+                        if let Err(err) = validator.validate_content(&content_key, &content).await {
+                            warn!(
+                                error = ?err,
+                                content.id = %hex_encode_compact(content_id),
+                                content.key = %content_key,
+                                "Error validating content"
+                            );
+                            return;
+                        };
+
+                        content_cache.write().put(content_id, content);
+                    });
+                }
             }
             Err(err) => {
                 error!(
@@ -1748,10 +2193,8 @@ where
             // then add the node to the ping queue. Ignore insertion failures.
             if let Some(node) = optional_node {
                 if node.enr().seq() < enr.seq() {
-                    let updated_node = Node {
-                        enr,
-                        data_radius: node.data_radius(),
-                    };
+                    let mut updated_node = node.clone();
+                    updated_node.set_enr(enr);
 
                     // The update removed the node because it would violate the incoming peers condition
                     // or a bucket/table filter. Remove the node from the ping queue.
@@ -1775,7 +2218,8 @@ where
                 match kbuckets.insert_or_update(&key, node, status) {
                     InsertResult::Inserted => {
                         debug!(inserted = %node_id, "Inserted discovered node into routing table");
-                        self.peers_to_ping.insert(node_id);
+                        let delay = self.ping_delay(&node_id);
+                        self.peers_to_ping.insert_at(node_id, delay);
                     }
                     InsertResult::Pending { disconnected } => {
                         // The disconnected node is the least-recently connected entry that is
@@ -1817,29 +2261,23 @@ where
         let content_keys_offered: Vec<TContentKey> = content_keys_offered
             .map_err(|_| anyhow!("Unable to decode our own offered content keys"))?;
 
-        let mut content_items: Vec<Vec<u8>> = Vec::new();
-
-        for (i, key) in accept_message
+        let content_keys_accepted: Vec<TContentKey> = accept_message
             .content_keys
             .clone()
             .iter()
-            .zip(content_keys_offered.iter())
-        {
-            if i {
-                match store.read().get(key) {
-                    Ok(content) => match content {
-                        Some(content) => content_items.push(content),
-                        None => return Err(anyhow!("Unable to read offered content!")),
-                    },
-                    Err(err) => {
-                        return Err(anyhow!(
-                            "Unable to get offered content from portal store: {err}"
-                        ))
-                    }
-                }
-            }
-        }
-        Ok(content_items)
+            .zip(content_keys_offered.into_iter())
+            .filter(|(is_accepted, _key)| *is_accepted)
+            .map(|(_is_accepted, key)| key)
+            .collect();
+
+        let content_items = store
+            .read()
+            .get_many(&content_keys_accepted)
+            .map_err(|err| anyhow!("Unable to get offered content from portal store: {err}"))?;
+        content_items
+            .into_iter()
+            .map(|content| content.ok_or_else(|| anyhow!("Unable to read offered content!")))
+            .collect()
     }
 
     /// Advances a find node query (if one is active for the node) using the received ENRs.
@@ -1849,6 +2287,9 @@ where
         // If so, advance the query with the returned data.
         let local_node_id = self.local_enr().node_id();
         if let Some((query_info, query)) = self.find_node_query_pool.get_mut(query_id) {
+            if let Some(trace) = &mut query_info.trace {
+                trace.node_responded_with(&source, enrs.iter().collect());
+            }
             for enr_ref in enrs.iter() {
                 if !query_info
                     .untrusted_enrs
@@ -1862,6 +2303,12 @@ where
                 &source.node_id(),
                 enrs.iter().map(|enr| enr.into()).collect(),
             );
+
+            let peer_stats = self.peer_stats.read();
+            for enr in enrs.iter() {
+                let node_id = enr.node_id();
+                query.set_latency_hint(&node_id, peer_stats.latency_hint(&node_id));
+            }
         }
     }
 
@@ -1873,6 +2320,8 @@ where
         enrs: Vec<Enr>,
     ) {
         let local_node_id = self.local_enr().node_id();
+        self.content_query_peers
+            .put(source.node_id(), source.clone());
         if let Some((query_info, query)) = self.find_content_query_pool.get_mut(*query_id) {
             // If an ENR is not present in the query's untrusted ENRs, then add the ENR.
             // Ignore the local node's ENR.
@@ -1900,8 +2349,23 @@ where
             // Mark the query successful for the source of the response with the closest ENRs.
             query.on_success(
                 &source.node_id(),
-                FindContentQueryResponse::ClosestNodes(closest_nodes),
+                FindContentQueryResponse::ClosestNodes(closest_nodes.clone()),
             );
+
+            // Ask the returned nodes that are known to cover the target ahead of the others.
+            let content_id = query.target().preimage().raw();
+            for node_id in Self::radius_covering_nodes(
+                &self.kbuckets,
+                &content_id,
+                closest_nodes.iter().copied(),
+            ) {
+                query.add_radius_covering_peer(Key::from(node_id));
+            }
+
+            let peer_stats = self.peer_stats.read();
+            for node_id in closest_nodes.iter() {
+                query.set_latency_hint(node_id, peer_stats.latency_hint(node_id));
+            }
         }
     }
 
@@ -1912,6 +2376,8 @@ where
         source: Enr,
         content: Vec<u8>,
     ) {
+        self.content_query_peers
+            .put(source.node_id(), source.clone());
         if let Some((query_info, query)) = self.find_content_query_pool.get_mut(*query_id) {
             if let Some(trace) = &mut query_info.trace {
                 trace.node_responded_with_content(&source);
@@ -1933,8 +2399,7 @@ where
         );
 
         let enr_seq = self.local_enr().seq();
-        let data_radius = self.data_radius();
-        let custom_payload = CustomPayload::from(data_radius.as_ssz_bytes());
+        let custom_payload = self.custom_payload(supports_payload_extension(destination));
         let ping = Request::Ping(Ping {
             enr_seq,
             custom_payload,
@@ -1950,6 +2415,30 @@ where
         let _ = self.command_tx.send(OverlayCommand::Request(request));
     }
 
+    /// Returns the custom payload of the local node for Ping and Pong messages. The payload
+    /// extension of the overlay is included if `with_extension` is true.
+    fn custom_payload(&self, with_extension: bool) -> CustomPayload {
+        let extension = match &self.payload_extension {
+            Some(payload_extension) if with_extension => Some(payload_extension.encode_extension()),
+            _ => None,
+        };
+        CustomPayload::new(self.data_radius(), extension)
+    }
+
+    /// Submits a request to ping each connected node in the routing table.
+    fn ping_connected_peers(&self) {
+        let peers: Vec<Enr> = self
+            .kbuckets
+            .write()
+            .iter()
+            .filter(|entry| entry.status.is_connected())
+            .map(|entry| entry.node.value.enr())
+            .collect();
+        for enr in peers.iter() {
+            self.ping_node(enr);
+        }
+    }
+
     /// Submits a request for the node info of a destination (target) node.
     fn request_node(&self, destination: &Enr) {
         let find_nodes = Request::FindNodes(FindNodes { distances: vec![0] });
@@ -1983,13 +2472,16 @@ where
                     "Node inserted into routing table",
                 );
 
-                self.peers_to_ping.insert(node_id);
+                self.schedule_ping(node_id);
             }
             InsertResult::Pending { disconnected } => {
                 // The disconnected node is the least-recently connected entry that is
                 // currently considered disconnected. This node should be pinged to check
                 // for connectivity.
                 node_to_ping = Some(disconnected);
+                // The node replaces the disconnected node if it is evicted.
+                let log2_distance = self.log2_distance(&node_id);
+                self.liveness.record_pending(log2_distance, node_id);
             }
             InsertResult::StatusUpdated {
                 promoted_to_connected,
@@ -2004,7 +2496,7 @@ where
                         promoted = %node_id,
                         "Node promoted to connected",
                     );
-                    self.peers_to_ping.insert(node_id);
+                    self.schedule_ping(node_id);
                 }
             }
             InsertResult::ValueUpdated | InsertResult::UpdatedPending => {}
@@ -2027,6 +2519,74 @@ where
         }
     }
 
+    /// Removes an unresponsive node from the routing table. The pending node of the bucket, if
+    /// any, is inserted in its place, and pinged like any other node in the routing table.
+    fn evict_node(&mut self, node_id: NodeId) {
+        let key = kbucket::Key::from(node_id);
+        self.peers_to_ping.remove(&node_id);
+        self.liveness.remove(&node_id);
+        let pending_node_id = self.liveness.take_pending(self.log2_distance(&node_id));
+
+        let mut kbuckets = self.kbuckets.write();
+        // Look up the pending node before the removal, which may apply it.
+        let pending = pending_node_id.and_then(|pending_node_id| {
+            let pending_key = kbucket::Key::from(pending_node_id);
+            match kbuckets.entry(&pending_key) {
+                kbucket::Entry::Pending(ref mut entry, status) => {
+                    Some((pending_node_id, entry.value().clone(), status))
+                }
+                _ => None,
+            }
+        });
+        if !kbuckets.remove(&key) {
+            return;
+        }
+        debug!(
+            protocol = %self.protocol,
+            evicted = %node_id,
+            "Evicted unresponsive node from routing table",
+        );
+
+        if let Some((pending_node_id, node, status)) = pending {
+            let pending_key = kbucket::Key::from(pending_node_id);
+            // The removal may already have promoted the pending node into the freed slot.
+            let is_inserted = match kbuckets.insert_or_update(&pending_key, node, status) {
+                InsertResult::Inserted => true,
+                _ => matches!(kbuckets.entry(&pending_key), kbucket::Entry::Present(_, _)),
+            };
+            drop(kbuckets);
+            if is_inserted {
+                debug!(
+                    protocol = %self.protocol,
+                    inserted = %pending_node_id,
+                    "Pending node inserted in place of evicted node",
+                );
+                self.schedule_ping(pending_node_id);
+            }
+        }
+    }
+
+    /// Queues `node_id` in the ping queue, with a delay that depends on the bucket of the node and
+    /// its recent failures.
+    fn schedule_ping(&mut self, node_id: NodeId) {
+        let delay = self.ping_delay(&node_id);
+        self.peers_to_ping.insert_at(node_id, delay);
+    }
+
+    /// Returns the delay until the next ping to `node_id`.
+    fn ping_delay(&self, node_id: &NodeId) -> Duration {
+        self.liveness
+            .ping_delay(node_id, self.log2_distance(node_id))
+    }
+
+    /// Returns the log2 distance of `node_id` from the local node, which is the bucket of the node.
+    fn log2_distance(&self, node_id: &NodeId) -> u64 {
+        let local_node_id = self.local_enr().node_id();
+        XorMetric::distance(&local_node_id.raw(), &node_id.raw())
+            .log2()
+            .unwrap_or_default() as u64
+    }
+
     /// Attempts to update the connection state of a node.
     fn update_node_connection_state(
         &mut self,
@@ -2127,7 +2687,8 @@ where
     fn init_find_nodes_query(
         &mut self,
         target: &NodeId,
-        callback: Option<oneshot::Sender<Vec<Enr>>>,
+        callback: Option<oneshot::Sender<FindNodesResult>>,
+        is_trace: bool,
     ) -> Option<QueryId> {
         let target_key = Key::from(*target);
         let mut closest_enrs: Vec<Enr> = self
@@ -2151,6 +2712,15 @@ where
             peer_timeout: self.query_peer_timeout,
         };
 
+        let trace: Option<QueryTrace> = if is_trace {
+            let local_enr = self.local_enr();
+            let mut trace = QueryTrace::new(&local_enr, (*target).into());
+            trace.node_responded_with(&local_enr, closest_enrs.iter().collect());
+            Some(trace)
+        } else {
+            None
+        };
+
         let query_info = QueryInfo {
             query_type: QueryType::FindNode {
                 target: *target,
@@ -2158,7 +2728,7 @@ where
                 callback,
             },
             untrusted_enrs: SmallVec::from_vec(closest_enrs),
-            trace: None,
+            trace,
         };
 
         let known_closest_peers: Vec<Key<NodeId>> = query_info
@@ -2171,8 +2741,13 @@ where
             warn!("Cannot initialize FindNode query (no known close peers)");
             None
         } else {
-            let find_nodes_query =
+            let mut find_nodes_query =
                 FindNodeQuery::with_config(query_config, query_info.key(), known_closest_peers);
+            let peer_stats = self.peer_stats.read();
+            for enr in query_info.untrusted_enrs.iter() {
+                let node_id = enr.node_id();
+                find_nodes_query.set_latency_hint(&node_id, peer_stats.latency_hint(&node_id));
+            }
             Some(
                 self.find_node_query_pool
                     .add_query(query_info, find_nodes_query),
@@ -2201,7 +2776,7 @@ where
 
         // Look up the closest ENRs to the target.
         // Limit the number of ENRs according to the query config.
-        let closest_enrs: Vec<Enr> = self
+        let mut closest_enrs: Vec<Enr> = self
             .kbuckets
             .write()
             .closest_values(&target_key)
@@ -2209,6 +2784,51 @@ where
             .take(query_config.num_results)
             .collect();
 
+        // Look up the connected nodes whose advertised radius covers the target. These nodes are
+        // asked directly, even if they are not among the closest nodes to the target.
+        let content_id = target.content_id();
+        let mut radius_covering_enrs: Vec<Enr> = self
+            .kbuckets
+            .write()
+            .iter()
+            .filter(|entry| {
+                entry.status.is_connected()
+                    && TMetric::distance(&entry.node.key.preimage().raw(), &content_id)
+                        <= entry.node.value.data_radius()
+            })
+            .map(|entry| entry.node.value.enr())
+            .collect();
+        radius_covering_enrs
+            .sort_by_key(|enr| TMetric::distance(&enr.node_id().raw(), &content_id));
+        radius_covering_enrs.truncate(query_config.num_results);
+        let radius_covering_peers: Vec<Key<NodeId>> = radius_covering_enrs
+            .iter()
+            .map(|enr| Key::from(enr.node_id()))
+            .collect();
+        for enr in radius_covering_enrs {
+            if !closest_enrs
+                .iter()
+                .any(|closest| closest.node_id() == enr.node_id())
+            {
+                closest_enrs.push(enr);
+            }
+        }
+
+        // Seed the query with the peers closest to the target that responded to recent queries.
+        // Lookups for nearby content, e.g. within a batch, then share the peers they discover.
+        let mut recent_peers: Vec<Enr> = self
+            .content_query_peers
+            .iter()
+            .filter(|(node_id, _)| {
+                !closest_enrs
+                    .iter()
+                    .any(|closest| closest.node_id() == **node_id)
+            })
+            .map(|(_, enr)| enr.clone())
+            .collect();
+        recent_peers.sort_by_key(|enr| TMetric::distance(&enr.node_id().raw(), &content_id));
+        closest_enrs.extend(recent_peers.into_iter().take(query_config.num_results));
+
         let trace: Option<QueryTrace> = {
             if is_trace {
                 let mut trace = QueryTrace::new(&self.local_enr(), target_node_id.into());
@@ -2239,11 +2859,40 @@ where
             warn!("Cannot initialize FindContent query (no known close peers)");
             None
         } else {
-            let query = FindContentQuery::with_config(query_config, target_key, closest_enrs);
+            let mut query = FindContentQuery::with_config(query_config, target_key, closest_enrs);
+            for peer in radius_covering_peers {
+                query.add_radius_covering_peer(peer);
+            }
+            let peer_stats = self.peer_stats.read();
+            for enr in query_info.untrusted_enrs.iter() {
+                let node_id = enr.node_id();
+                query.set_latency_hint(&node_id, peer_stats.latency_hint(&node_id));
+            }
             Some(self.find_content_query_pool.add_query(query_info, query))
         }
     }
 
+    /// Returns the nodes in `node_ids` that are connected in the routing table and whose advertised
+    /// radius covers `content_id`.
+    fn radius_covering_nodes(
+        kbuckets: &RwLock<KBucketsTable<NodeId, Node>>,
+        content_id: &[u8; 32],
+        node_ids: impl IntoIterator<Item = NodeId>,
+    ) -> Vec<NodeId> {
+        let mut kbuckets = kbuckets.write();
+        node_ids
+            .into_iter()
+            .filter(|node_id| match kbuckets.entry(&Key::from(*node_id)) {
+                kbucket::Entry::Present(entry, status) => {
+                    status.is_connected()
+                        && TMetric::distance(&node_id.raw(), content_id)
+                            <= entry.value().data_radius()
+                }
+                _ => false,
+            })
+            .collect()
+    }
+
     /// Returns an ENR if one is known for the given NodeId.
     pub fn find_enr(&self, node_id: &NodeId) -> Option<Enr> {
         // Check whether we know this node id in our routing table.
@@ -2283,6 +2932,7 @@ pub fn propagate_gossip_cross_thread<TContentKey: OverlayContentKey>(
     content: Vec<(TContentKey, Vec<u8>)>,
     kbuckets: Arc<RwLock<KBucketsTable<NodeId, Node>>>,
     command_tx: mpsc::UnboundedSender<OverlayCommand<TContentKey>>,
+    gossip_tracker: &RwLock<GossipTracker>,
 ) -> usize {
     // Get all connected nodes from overlay routing table
     let kbuckets = kbuckets.read();
@@ -2300,6 +2950,10 @@ pub fn propagate_gossip_cross_thread<TContentKey: OverlayContentKey>(
     // Key is base64 string of node's ENR.
     let mut enrs_and_content: HashMap<String, Vec<(RawContentKey, Vec<u8>)>> = HashMap::new();
 
+    // Track the delivery of the content, so that declined or failed offers are retried with
+    // other interested peers.
+    let job_id = gossip_tracker.write().new_job();
+
     // Filter all nodes from overlay routing table where XOR_distance(content_id, nodeId) < node radius
     for (content_key, content_value) in content {
         let mut interested_enrs: Vec<Enr> = all_nodes
@@ -2332,11 +2986,21 @@ pub fn propagate_gossip_cross_thread<TContentKey: OverlayContentKey>(
             })
         });
 
-        let gossip_recipients = select_gossip_recipients(interested_enrs);
+        let gossip_recipients = select_gossip_recipients(interested_enrs.clone());
+        let candidates: Vec<Enr> = interested_enrs
+            .into_iter()
+            .filter(|enr| !gossip_recipients.contains(enr))
+            .collect();
 
         // Temporarily store all randomly selected nodes with the content of interest.
         // We want this so we can offer all the content to interested node in one request.
-        let raw_item = (content_key.into(), content_value);
+        let raw_item: (RawContentKey, Vec<u8>) = (content_key.into(), content_value);
+        gossip_tracker.write().add_content(
+            job_id,
+            raw_item.0.clone(),
+            raw_item.1.clone(),
+            candidates,
+        );
         for enr in gossip_recipients {
             enrs_and_content
                 .entry(enr.to_base64())
@@ -2347,6 +3011,7 @@ pub fn propagate_gossip_cross_thread<TContentKey: OverlayContentKey>(
 
     let num_propagated_peers = enrs_and_content.len();
     // Create and send OFFER overlay request to the interested nodes
+    let mut offers = vec![];
     for (enr_string, interested_content) in enrs_and_content.into_iter() {
         let enr = match Enr::from_str(&enr_string) {
             Ok(enr) => enr,
@@ -2355,24 +3020,54 @@ pub fn propagate_gossip_cross_thread<TContentKey: OverlayContentKey>(
                 continue;
             }
         };
-
-        let offer_request = Request::PopulatedOffer(PopulatedOffer {
+        offers.push(GossipOffer {
+            job_id,
+            recipient: enr,
             content_items: interested_content,
         });
+    }
+    send_gossip_offers(offers, gossip_tracker, &command_tx);
+
+    num_propagated_peers
+}
+
+/// Sends OFFER requests on behalf of a gossip job, and registers them with `gossip_tracker` so
+/// that the responses are attributed to the job.
+fn send_gossip_offers<TContentKey>(
+    offers: Vec<GossipOffer>,
+    gossip_tracker: &RwLock<GossipTracker>,
+    command_tx: &mpsc::UnboundedSender<OverlayCommand<TContentKey>>,
+) {
+    for offer in offers {
+        let content_keys: Vec<RawContentKey> = offer
+            .content_items
+            .iter()
+            .map(|(content_key, _)| content_key.clone())
+            .collect();
+        let recipient = offer.recipient.node_id();
 
+        let offer_request = Request::PopulatedOffer(PopulatedOffer {
+            content_items: offer.content_items,
+        });
         let overlay_request = OverlayRequest::new(
             offer_request,
-            RequestDirection::Outgoing { destination: enr },
+            RequestDirection::Outgoing {
+                destination: offer.recipient,
+            },
             None,
             None,
         );
 
+        let request_id = overlay_request.id;
+        gossip_tracker
+            .write()
+            .register_offer(request_id, offer.job_id, recipient, &content_keys);
         if let Err(err) = command_tx.send(OverlayCommand::Request(overlay_request)) {
-            error!(error = %err, "Error sending OFFER message to service")
+            error!(error = %err, "Error sending OFFER message to service");
+            // The service is gone, so there is no point in retrying with other peers.
+            let _ = gossip_tracker.write().on_failure(request_id);
         }
     }
-
-    num_propagated_peers
 }
 
 /// Randomly select `num_enrs` nodes from `enrs`.
@@ -2439,7 +3134,7 @@ mod tests {
         discovery::Discovery,
         overlay::OverlayConfig,
         storage::{DistanceFunction, MemoryContentStore},
-        types::messages::PortalnetConfig,
+        types::{messages::PortalnetConfig, payload_extension::Capabilities},
     };
 
     use trin_types::content_key::IdentityContentKey;
@@ -2447,7 +3142,7 @@ mod tests {
     use trin_types::enr::generate_random_remote_enr;
     use trin_validation::validator::MockValidator;
 
-    use discv5::kbucket::Entry;
+    use discv5::kbucket::{Entry, MAX_NODES_PER_BUCKET};
     use ethereum_types::U256;
     use serial_test::serial;
     use tokio::sync::mpsc::unbounded_channel;
@@ -2474,7 +3169,7 @@ mod tests {
         let utp_socket = Arc::new(utp_socket);
 
         let node_id = discovery.local_enr().node_id();
-        let store = MemoryContentStore::new(node_id, DistanceFunction::Xor);
+        let store = MemoryContentStore::new(node_id, DistanceFunction::Xor, &ProtocolId::History);
         let store = Arc::new(RwLock::new(store));
 
         let overlay_config = OverlayConfig::default();
@@ -2492,15 +3187,24 @@ mod tests {
         let (command_tx, command_rx) = mpsc::unbounded_channel();
         let (response_tx, response_rx) = mpsc::unbounded_channel();
         let metrics = Arc::new(OverlayMetrics::new());
+        let peer_stats = Arc::new(RwLock::new(PeerStatsTable::new()));
+        let content_cache = Arc::new(RwLock::new(ContentCache::default()));
+        let (store_read_tx, store_read_rx) = mpsc::unbounded_channel();
+        let external_addr_updates = discovery.subscribe_external_addr_updates();
         let validator = Arc::new(MockValidator {});
+        let last_radius = Mutex::new(store.read().radius());
 
         OverlayService {
             discovery,
             utp_socket,
             store,
+            last_radius,
+            store_read_rx,
+            store_read_tx,
             kbuckets,
             protocol,
             peers_to_ping,
+            liveness: LivenessTracker::new(overlay_config.liveness, DEFAULT_PING_INTERVAL),
             command_tx,
             command_rx,
             active_outgoing_requests,
@@ -2512,10 +3216,18 @@ mod tests {
             findnodes_query_distances_per_peer: overlay_config.findnodes_query_distances_per_peer,
             response_tx,
             response_rx,
+            utp_config: overlay_config.utp_config,
             phantom_content_key: PhantomData,
             phantom_metric: PhantomData,
             metrics,
+            peer_stats,
+            content_cache,
+            content_query_peers: LruCache::new(CONTENT_QUERY_PEERS_CAPACITY),
+            gossip_tracker: Arc::new(RwLock::new(GossipTracker::new())),
+            payload_extension: None,
+            external_addr_updates,
             validator,
+            rng: StdRng::seed_from_u64(0),
         }
     }
 
@@ -2576,6 +3288,46 @@ mod tests {
         };
     }
 
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn process_ping_payload_extension() {
+        let mut service = task::spawn(build_service());
+        service.payload_extension = Some(Arc::new(Capabilities::new(100, vec![0])));
+
+        let (_, source) = generate_random_remote_enr();
+        let node_id = source.node_id();
+        let key = kbucket::Key::from(node_id);
+        let status = NodeStatus {
+            state: ConnectionState::Connected,
+            direction: ConnectionDirection::Outgoing,
+        };
+        let data_radius = Distance::MAX;
+        let node = Node::new(source.clone(), data_radius);
+        let _ = service
+            .kbuckets
+            .write()
+            .insert_or_update(&key, node, status);
+
+        let extension = Capabilities::new(200, vec![1]).encode_extension();
+        let ping = Ping {
+            enr_seq: source.seq(),
+            custom_payload: CustomPayload::new(data_radius, Some(extension.clone())),
+        };
+        service.process_ping(ping, node_id);
+
+        match service.kbuckets.write().entry(&key) {
+            kbucket::Entry::Present(mut entry, _) => {
+                assert_eq!(entry.value().data_radius(), data_radius);
+                assert_eq!(entry.value().payload_extension(), Some(&extension[..]));
+            }
+            _ => panic!("Node not present in routing table"),
+        }
+
+        // The local extension is only included on request.
+        assert!(service.custom_payload(false).extension().is_none());
+        assert!(service.custom_payload(true).extension().is_some());
+    }
+
     #[test_log::test(tokio::test)]
     #[serial]
     async fn process_ping_source_not_in_table() {
@@ -2595,6 +3347,63 @@ mod tests {
         assert_pending!(poll_command_rx!(service));
     }
 
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn incoming_requests_are_answered_while_store_is_locked() {
+        let mut service = build_service();
+
+        let content_key = IdentityContentKey::new(service.local_enr().node_id().raw());
+        let content = vec![0xef];
+        service
+            .store
+            .write()
+            .put(content_key.clone(), &content)
+            .unwrap();
+
+        let (_, source) = generate_random_remote_enr();
+        let incoming = || RequestDirection::Incoming {
+            id: RequestId::random(),
+            source: source.node_id(),
+        };
+
+        // Hold the store lock, as an eviction would.
+        let store = Arc::clone(&service.store);
+        let eviction = store.write();
+
+        let (content_tx, mut content_rx) = oneshot::channel();
+        let find_content = Request::FindContent(FindContent {
+            content_key: content_key.into(),
+        });
+        service.process_request(OverlayRequest::new(
+            find_content,
+            incoming(),
+            Some(content_tx),
+            None,
+        ));
+
+        // Other requests are answered while the store is locked.
+        let (pong_tx, mut pong_rx) = oneshot::channel();
+        let ping = Request::Ping(Ping {
+            enr_seq: source.seq(),
+            custom_payload: CustomPayload::from(Distance::MAX.as_ssz_bytes()),
+        });
+        service.process_request(OverlayRequest::new(ping, incoming(), Some(pong_tx), None));
+        assert!(matches!(
+            pong_rx.try_recv(),
+            Ok(Some(Ok(Response::Pong(_))))
+        ));
+        assert!(matches!(content_rx.try_recv(), Ok(None)));
+
+        // The FindContent request is answered once the store can be read.
+        drop(eviction);
+        let store_read = service.store_read_rx.recv().await.unwrap();
+        service.process_store_read(store_read);
+        match content_rx.await.unwrap().unwrap() {
+            Response::Content(Content::Content(found)) => assert_eq!(found, content),
+            response => panic!("Unexpected response: {response:?}"),
+        }
+    }
+
     #[test_log::test(tokio::test)]
     #[serial]
     async fn process_request_failure() {
@@ -2625,9 +3434,12 @@ mod tests {
 
         let request_id = rand::random();
         let error = OverlayRequestError::Timeout;
-        service.process_request_failure(request_id, destination, error);
+        let request = Request::FindNodes(FindNodes { distances: vec![0] });
+        service.process_request_failure(request_id, destination, error, request, None);
 
-        assert!(!service.peers_to_ping.contains_key(&node_id));
+        // The node is pinged again after a backoff.
+        assert!(service.peers_to_ping.contains_key(&node_id));
+        assert_eq!(service.liveness.failures(&node_id), 1);
 
         match service.kbuckets.write().entry(&key) {
             kbucket::Entry::Present(_entry, status) => {
@@ -2637,6 +3449,51 @@ mod tests {
         };
     }
 
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn evict_node_pings_promoted_pending_node() {
+        let mut service = task::spawn(build_service());
+
+        // Fill the farthest bucket, and mark its first node as disconnected.
+        let mut enrs = vec![];
+        while enrs.len() <= MAX_NODES_PER_BUCKET {
+            let (_, enr) = generate_random_remote_enr();
+            if service.log2_distance(&enr.node_id()) == 256 {
+                enrs.push(enr);
+            }
+        }
+        let pending_enr = enrs.pop().unwrap();
+        for enr in enrs.iter() {
+            let node = Node::new(enr.clone(), Distance::MAX);
+            service.connect_node(node, ConnectionDirection::Outgoing);
+        }
+        let evicted_id = enrs[0].node_id();
+        let _ = service.update_node_connection_state(evicted_id, ConnectionState::Disconnected);
+
+        // The bucket is full, so the node waits for the disconnected node to be evicted.
+        let pending_id = pending_enr.node_id();
+        let node = Node::new(pending_enr, Distance::MAX);
+        service.connect_node(node, ConnectionDirection::Outgoing);
+        assert!(!service.peers_to_ping.contains_key(&pending_id));
+
+        service.evict_node(evicted_id);
+        assert!(matches!(
+            service
+                .kbuckets
+                .write()
+                .entry(&kbucket::Key::from(evicted_id)),
+            kbucket::Entry::Absent(_)
+        ));
+        assert!(matches!(
+            service
+                .kbuckets
+                .write()
+                .entry(&kbucket::Key::from(pending_id)),
+            kbucket::Entry::Present(_, _)
+        ));
+        assert!(service.peers_to_ping.contains_key(&pending_id));
+    }
+
     #[test_log::test(tokio::test)]
     #[serial]
     async fn process_pong_source_in_table_higher_enr_seq() {
@@ -2857,10 +3714,7 @@ mod tests {
 
         let (_, enr) = generate_random_remote_enr();
         let key = kbucket::Key::from(enr.node_id());
-        let peer = Node {
-            enr,
-            data_radius: Distance::MAX,
-        };
+        let peer = Node::new(enr, Distance::MAX);
         let _ = service
             .kbuckets
             .write()
@@ -2920,10 +3774,7 @@ mod tests {
         // The first node has a maximum radius, so the content SHOULD be offered.
         let (_, enr1) = generate_random_remote_enr();
         let key1 = kbucket::Key::from(enr1.node_id());
-        let peer1 = Node {
-            enr: enr1,
-            data_radius: Distance::MAX,
-        };
+        let peer1 = Node::new(enr1, Distance::MAX);
         let _ = service
             .kbuckets
             .write()
@@ -2932,10 +3783,7 @@ mod tests {
         // The second node has a radius of zero, so the content SHOULD NOT not be offered.
         let (_, enr2) = generate_random_remote_enr();
         let key2 = kbucket::Key::from(enr2.node_id());
-        let peer2 = Node {
-            enr: enr2,
-            data_radius: Distance::from(U256::zero()),
-        };
+        let peer2 = Node::new(enr2, Distance::from(U256::zero()));
         let _ = service
             .kbuckets
             .write()
@@ -3025,6 +3873,47 @@ mod tests {
         assert!(matches!(request.request, Request::Ping { .. }));
     }
 
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn ping_connected_peers() {
+        let mut service = task::spawn(build_service());
+
+        let (_, connected) = generate_random_remote_enr();
+        let (_, disconnected) = generate_random_remote_enr();
+        for (enr, state) in [
+            (connected.clone(), ConnectionState::Connected),
+            (disconnected, ConnectionState::Disconnected),
+        ] {
+            let key = kbucket::Key::from(enr.node_id());
+            let status = NodeStatus {
+                state,
+                direction: ConnectionDirection::Outgoing,
+            };
+            let _ = service.kbuckets.write().insert_or_update(
+                &key,
+                Node::new(enr, Distance::MAX),
+                status,
+            );
+        }
+
+        service.ping_connected_peers();
+
+        // Only the connected node is pinged.
+        let command = assert_ready!(poll_command_rx!(service)).unwrap();
+        let request = match command {
+            OverlayCommand::Request(request) => request,
+            _ => panic!("Unexpected overlay command variant"),
+        };
+        assert!(matches!(request.request, Request::Ping { .. }));
+        assert_eq!(
+            RequestDirection::Outgoing {
+                destination: connected
+            },
+            request.direction
+        );
+        assert_pending!(poll_command_rx!(service));
+    }
+
     #[test_log::test(tokio::test)]
     #[serial]
     async fn connect_node() {
@@ -3179,7 +4068,7 @@ mod tests {
         service.add_bootnodes(bootnodes);
 
         // Initialize the query and call `poll` so that it starts
-        service.init_find_nodes_query(&target_node_id, None);
+        service.init_find_nodes_query(&target_node_id, None, false);
         let _ = service.find_node_query_pool.poll();
 
         let (query_info, query) = service.find_node_query_pool.iter().next().unwrap();
@@ -3204,6 +4093,52 @@ mod tests {
         assert!(query.started().is_some());
     }
 
+    #[test_log::test(tokio::test)]
+    async fn test_trace_find_nodes_query() {
+        let mut service = build_service();
+
+        let (_, bootnode) = generate_random_remote_enr();
+        let bootnode_node_id = bootnode.node_id();
+        service.add_bootnodes(vec![bootnode.clone()]);
+
+        let (_, target_enr) = generate_random_remote_enr();
+        let query_id = service
+            .init_find_nodes_query(&target_enr.node_id(), None, true)
+            .unwrap();
+
+        // The request to the bootnode is recorded in the trace when it is sent.
+        let event = OverlayService::<
+            IdentityContentKey,
+            XorMetric,
+            MockValidator,
+            MemoryContentStore,
+        >::query_event_poll(&mut service.find_node_query_pool)
+        .await;
+        service.handle_find_nodes_query_event(event);
+
+        let (query_info, _) = service.find_node_query_pool.get_mut(query_id).unwrap();
+        let trace = query_info.trace.as_ref().unwrap();
+        assert!(trace.requests_sent.contains_key(&bootnode_node_id.into()));
+        assert!(trace.failures.is_empty());
+
+        // A failed request is recorded in the trace.
+        let request = Request::FindNodes(FindNodes {
+            distances: vec![256],
+        });
+        service.process_request_failure(
+            rand::random(),
+            bootnode,
+            OverlayRequestError::Timeout,
+            request,
+            Some(query_id),
+        );
+
+        let (query_info, _) = service.find_node_query_pool.get_mut(query_id).unwrap();
+        let trace = query_info.trace.as_ref().unwrap();
+        let failure = trace.failures.get(&bootnode_node_id.into()).unwrap();
+        assert_eq!(failure.kind, QueryFailureKind::Timeout);
+    }
+
     #[test_log::test(tokio::test)]
     async fn test_advance_findnodes_query() {
         let mut service = build_service();
@@ -3217,7 +4152,7 @@ mod tests {
 
         service.add_bootnodes(bootnodes);
         service.query_num_results = 3;
-        service.init_find_nodes_query(&target_node_id, None);
+        service.init_find_nodes_query(&target_node_id, None, false);
 
         // Test that the first query event contains a proper query ID and request to the bootnode
         let event = OverlayService::<
@@ -3338,7 +4273,7 @@ mod tests {
 
         service.add_bootnodes(bootnodes);
 
-        service.init_find_nodes_query(&target_node_id, None);
+        service.init_find_nodes_query(&target_node_id, None, false);
 
         let _event = OverlayService::<
             IdentityContentKey,
@@ -3378,10 +4313,7 @@ mod tests {
         let bootnode_key = kbucket::Key::from(bootnode_node_id);
 
         let data_radius = Distance::MAX;
-        let bootnode = Node {
-            enr: bootnode_enr.clone(),
-            data_radius,
-        };
+        let bootnode = Node::new(bootnode_enr.clone(), data_radius);
 
         let connection_direction = ConnectionDirection::Outgoing;
         let status = NodeStatus {
@@ -3423,6 +4355,29 @@ mod tests {
         assert!(query_info.untrusted_enrs.contains(&bootnode_enr));
     }
 
+    #[tokio::test]
+    async fn init_find_content_query_with_recent_peers() {
+        let mut service = task::spawn(build_service());
+
+        // A peer that responded to an earlier query, but is absent from the routing table.
+        let (_, peer_enr) = generate_random_remote_enr();
+        service
+            .content_query_peers
+            .put(peer_enr.node_id(), peer_enr.clone());
+
+        let target_content = NodeId::random();
+        let target_content_key = IdentityContentKey::new(target_content.raw());
+
+        let query_id = service.init_find_content_query(target_content_key, None, false);
+        let query_id = query_id.expect("Query ID for new find content query is `None`");
+
+        let (query_info, _) = service
+            .find_content_query_pool
+            .get_mut(query_id)
+            .expect("Query pool does not contain query");
+        assert!(query_info.untrusted_enrs.contains(&peer_enr));
+    }
+
     #[tokio::test]
     async fn advance_find_content_query_with_enrs() {
         let mut service = task::spawn(build_service());
@@ -3432,10 +4387,7 @@ mod tests {
         let bootnode_key = kbucket::Key::from(bootnode_node_id);
 
         let data_radius = Distance::MAX;
-        let bootnode = Node {
-            enr: bootnode_enr.clone(),
-            data_radius,
-        };
+        let bootnode = Node::new(bootnode_enr.clone(), data_radius);
 
         let connection_direction = ConnectionDirection::Outgoing;
         let status = NodeStatus {
@@ -3497,10 +4449,7 @@ mod tests {
         let bootnode_key = kbucket::Key::from(bootnode_node_id);
 
         let data_radius = Distance::MAX;
-        let bootnode = Node {
-            enr: bootnode_enr.clone(),
-            data_radius,
-        };
+        let bootnode = Node::new(bootnode_enr.clone(), data_radius);
 
         let connection_direction = ConnectionDirection::Outgoing;
         let status = NodeStatus {
@@ -3558,10 +4507,7 @@ mod tests {
         let bootnode_key = kbucket::Key::from(bootnode_node_id);
 
         let data_radius = Distance::MAX;
-        let bootnode = Node {
-            enr: bootnode_enr.clone(),
-            data_radius,
-        };
+        let bootnode = Node::new(bootnode_enr.clone(), data_radius);
 
         let connection_direction = ConnectionDirection::Outgoing;
         let status = NodeStatus {
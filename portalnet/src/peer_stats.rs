@@ -0,0 +1,270 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use discv5::enr::NodeId;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+
+use crate::find::iterators::query::PeerLatencyHint;
+
+/// Maximum number of peers for which we keep latency statistics.
+const PEER_STATS_CAPACITY: usize = 2048;
+
+/// Number of most recent round-trip samples kept for each peer.
+const MAX_RTT_SAMPLES: usize = 64;
+
+/// Minimum number of requests to a peer before its statistics are used to steer queries.
+const MIN_SAMPLES: u64 = 5;
+
+/// A peer whose median round-trip time exceeds this value is considered chronically slow.
+const SLOW_PEER_P50: Duration = Duration::from_secs(2);
+
+/// A peer whose success rate falls below this value is considered chronically unreliable.
+const MIN_SUCCESS_RATE: f64 = 0.5;
+
+/// Summary of the observed responsiveness of a single peer.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PeerLatencySummary {
+    /// Median round-trip time of successful requests, in milliseconds.
+    pub p50_millis: u64,
+    /// 99th percentile round-trip time of successful requests, in milliseconds.
+    pub p99_millis: u64,
+    /// Fraction of requests that received a response.
+    pub success_rate: f64,
+    /// Total number of requests recorded for the peer.
+    pub num_requests: u64,
+}
+
+/// Round-trip time and success statistics for a single peer.
+#[derive(Clone, Debug, Default)]
+pub struct PeerStats {
+    /// The most recent round-trip times of successful requests, oldest first.
+    rtt_samples: VecDeque<Duration>,
+    /// Number of requests that received a response.
+    successes: u64,
+    /// Number of requests that failed or timed out.
+    failures: u64,
+    /// When the peer last sent a request or a response.
+    last_seen: Option<Instant>,
+    /// When the peer last responded to a request.
+    last_response: Option<Instant>,
+}
+
+impl PeerStats {
+    /// Records a request that received a response after `rtt`.
+    pub fn record_success(&mut self, rtt: Duration) {
+        if self.rtt_samples.len() == MAX_RTT_SAMPLES {
+            self.rtt_samples.pop_front();
+        }
+        self.rtt_samples.push_back(rtt);
+        self.successes += 1;
+        let now = Instant::now();
+        self.last_seen = Some(now);
+        self.last_response = Some(now);
+    }
+
+    /// Records a request from the peer.
+    pub fn record_seen(&mut self) {
+        self.last_seen = Some(Instant::now());
+    }
+
+    /// Records a request that failed or timed out.
+    pub fn record_failure(&mut self) {
+        self.failures += 1;
+    }
+
+    /// Returns when the peer last sent a request or a response.
+    pub fn last_seen(&self) -> Option<Instant> {
+        self.last_seen
+    }
+
+    /// Returns when the peer last responded to a request.
+    pub fn last_response(&self) -> Option<Instant> {
+        self.last_response
+    }
+
+    /// Returns the total number of recorded requests.
+    pub fn num_requests(&self) -> u64 {
+        self.successes + self.failures
+    }
+
+    /// Returns the fraction of requests that received a response.
+    pub fn success_rate(&self) -> f64 {
+        match self.num_requests() {
+            0 => 1.0,
+            total => self.successes as f64 / total as f64,
+        }
+    }
+
+    /// Returns the `percentile` (0-100) of the recorded round-trip times, or `None` if no
+    /// successful request was recorded.
+    pub fn percentile(&self, percentile: u8) -> Option<Duration> {
+        if self.rtt_samples.is_empty() {
+            return None;
+        }
+        let mut samples: Vec<Duration> = self.rtt_samples.iter().copied().collect();
+        samples.sort_unstable();
+        let rank = (samples.len() - 1) * usize::from(percentile.min(100)) / 100;
+        Some(samples[rank])
+    }
+
+    /// Returns whether the peer has been consistently slow or unreliable.
+    pub fn is_chronically_slow(&self) -> bool {
+        if self.num_requests() < MIN_SAMPLES {
+            return false;
+        }
+        self.success_rate() < MIN_SUCCESS_RATE
+            || self.percentile(50).map_or(false, |p50| p50 > SLOW_PEER_P50)
+    }
+
+    /// Returns the hint that queries use to order and hedge requests to this peer.
+    pub fn latency_hint(&self) -> PeerLatencyHint {
+        if self.num_requests() < MIN_SAMPLES {
+            return PeerLatencyHint::default();
+        }
+        PeerLatencyHint {
+            expected_latency: self.percentile(99),
+            deprioritized: self.is_chronically_slow(),
+        }
+    }
+
+    /// Returns a summary of the statistics, or `None` if no successful request was recorded.
+    pub fn summary(&self) -> Option<PeerLatencySummary> {
+        let p50 = self.percentile(50)?;
+        let p99 = self.percentile(99)?;
+        Some(PeerLatencySummary {
+            p50_millis: p50.as_millis() as u64,
+            p99_millis: p99.as_millis() as u64,
+            success_rate: self.success_rate(),
+            num_requests: self.num_requests(),
+        })
+    }
+}
+
+/// Tracks round-trip times and success rates of outgoing requests for recently contacted peers.
+#[derive(Debug)]
+pub struct PeerStatsTable {
+    peers: LruCache<NodeId, PeerStats>,
+}
+
+impl PeerStatsTable {
+    pub fn new() -> Self {
+        Self {
+            peers: LruCache::new(PEER_STATS_CAPACITY),
+        }
+    }
+
+    /// Records a request to `node_id` that received a response after `rtt`.
+    pub fn record_success(&mut self, node_id: NodeId, rtt: Duration) {
+        self.entry(node_id).record_success(rtt);
+    }
+
+    /// Records a request to `node_id` that failed or timed out.
+    pub fn record_failure(&mut self, node_id: NodeId) {
+        self.entry(node_id).record_failure();
+    }
+
+    /// Records a request from `node_id`.
+    pub fn record_seen(&mut self, node_id: NodeId) {
+        self.entry(node_id).record_seen();
+    }
+
+    /// Returns the statistics for `node_id`, if any requests to the node were recorded.
+    pub fn get(&self, node_id: &NodeId) -> Option<&PeerStats> {
+        self.peers.peek(node_id)
+    }
+
+    /// Returns the query hint for `node_id`. Unknown peers receive the default hint.
+    pub fn latency_hint(&self, node_id: &NodeId) -> PeerLatencyHint {
+        self.get(node_id)
+            .map(PeerStats::latency_hint)
+            .unwrap_or_default()
+    }
+
+    /// Returns the latency summary for `node_id`, if available.
+    pub fn summary(&self, node_id: &NodeId) -> Option<PeerLatencySummary> {
+        self.get(node_id).and_then(PeerStats::summary)
+    }
+
+    fn entry(&mut self, node_id: NodeId) -> &mut PeerStats {
+        if !self.peers.contains(&node_id) {
+            self.peers.put(node_id, PeerStats::default());
+        }
+        self.peers
+            .get_mut(&node_id)
+            .expect("entry was inserted above if missing")
+    }
+}
+
+impl Default for PeerStatsTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let mut stats = PeerStats::default();
+        assert_eq!(stats.percentile(50), None);
+
+        for millis in 1..=100 {
+            stats.record_success(Duration::from_millis(millis));
+        }
+        // Only the most recent samples are kept.
+        assert_eq!(stats.percentile(0), Some(Duration::from_millis(37)));
+        assert_eq!(stats.percentile(50), Some(Duration::from_millis(68)));
+        assert_eq!(stats.percentile(99), Some(Duration::from_millis(99)));
+        assert_eq!(stats.percentile(100), Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn last_seen_and_last_response() {
+        let mut stats = PeerStats::default();
+        assert_eq!(stats.last_seen(), None);
+
+        stats.record_seen();
+        assert!(stats.last_seen().is_some());
+        assert_eq!(stats.last_response(), None);
+
+        stats.record_success(Duration::from_millis(50));
+        assert!(stats.last_response().is_some());
+        assert_eq!(stats.last_seen(), stats.last_response());
+    }
+
+    #[test]
+    fn new_peer_is_not_deprioritized() {
+        let mut stats = PeerStats::default();
+        stats.record_failure();
+        stats.record_failure();
+        assert!(!stats.is_chronically_slow());
+        assert_eq!(stats.latency_hint(), PeerLatencyHint::default());
+    }
+
+    #[test]
+    fn unreliable_peer_is_deprioritized() {
+        let mut stats = PeerStats::default();
+        stats.record_success(Duration::from_millis(50));
+        for _ in 0..4 {
+            stats.record_failure();
+        }
+        assert_eq!(stats.success_rate(), 0.2);
+        assert!(stats.latency_hint().deprioritized);
+    }
+
+    #[test]
+    fn slow_peer_is_deprioritized() {
+        let mut stats = PeerStats::default();
+        for _ in 0..MIN_SAMPLES {
+            stats.record_success(SLOW_PEER_P50 * 2);
+        }
+        let hint = stats.latency_hint();
+        assert!(hint.deprioritized);
+        assert_eq!(hint.expected_latency, Some(SLOW_PEER_P50 * 2));
+    }
+}
@@ -0,0 +1,341 @@
+use std::time::Instant;
+
+use discv5::enr::NodeId;
+use lru::LruCache;
+use tracing::{debug, warn};
+
+use crate::types::messages::ProtocolId;
+use trin_types::cli::{OverlayConfigArgs, RateLimitArg, ThrottleActionArg};
+
+/// Maximum number of (peer, request kind) token buckets tracked by a single `RateLimiter`.
+const RATE_LIMITER_CAPACITY: usize = 4096;
+
+// SSZ union selectors of the request variants of `Message`.
+const PING_SELECTOR: u8 = 0;
+const FIND_NODES_SELECTOR: u8 = 2;
+const FIND_CONTENT_SELECTOR: u8 = 4;
+const OFFER_SELECTOR: u8 = 6;
+
+/// The kind of an inbound overlay request, as far as rate limiting is concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RequestKind {
+    Ping,
+    FindNodes,
+    FindContent,
+    Offer,
+    /// Any other TALKREQ payload, including malformed and non-request messages.
+    Other,
+}
+
+impl RequestKind {
+    /// Determines the kind of request from the message selector of a TALKREQ payload, without
+    /// decoding the full message.
+    pub fn from_talk_req_body(body: &[u8]) -> Self {
+        match body.first() {
+            Some(&PING_SELECTOR) => Self::Ping,
+            Some(&FIND_NODES_SELECTOR) => Self::FindNodes,
+            Some(&FIND_CONTENT_SELECTOR) => Self::FindContent,
+            Some(&OFFER_SELECTOR) => Self::Offer,
+            _ => Self::Other,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ping => "ping",
+            Self::FindNodes => "find_nodes",
+            Self::FindContent => "find_content",
+            Self::Offer => "offer",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// A token bucket rate limit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    /// Maximum number of requests accepted in a burst.
+    pub burst: u32,
+    /// Number of requests per second accepted on average.
+    pub per_second: f64,
+}
+
+impl RateLimit {
+    pub const fn new(burst: u32, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+}
+
+/// What to do with a request that exceeds its rate limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThrottleAction {
+    /// Drop the request without a response. The requester observes a timeout.
+    Drop,
+    /// Answer the request with an empty TALKRESP.
+    EmptyResponse,
+}
+
+/// Per-peer rate limits for inbound requests of a single overlay network. A limit of `None`
+/// leaves the kind of request unlimited.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimitConfig {
+    pub ping: Option<RateLimit>,
+    pub find_nodes: Option<RateLimit>,
+    pub find_content: Option<RateLimit>,
+    pub offer: Option<RateLimit>,
+    pub other: Option<RateLimit>,
+    pub throttle_action: ThrottleAction,
+}
+
+impl RateLimitConfig {
+    /// Returns a config without any limits.
+    pub fn unlimited() -> Self {
+        Self {
+            ping: None,
+            find_nodes: None,
+            find_content: None,
+            offer: None,
+            other: None,
+            throttle_action: ThrottleAction::Drop,
+        }
+    }
+
+    /// Returns the default config, with the rate limit settings in `args` overridden.
+    pub fn from_args(args: &OverlayConfigArgs) -> Self {
+        let default = Self::default();
+        let limit = |arg: Option<RateLimitArg>, default: Option<RateLimit>| match arg {
+            Some(RateLimitArg::Unlimited) => None,
+            Some(RateLimitArg::Limit { burst, per_second }) => {
+                Some(RateLimit::new(burst, per_second as f64))
+            }
+            None => default,
+        };
+        Self {
+            ping: limit(args.rate_limit_ping, default.ping),
+            find_nodes: limit(args.rate_limit_find_nodes, default.find_nodes),
+            find_content: limit(args.rate_limit_find_content, default.find_content),
+            offer: limit(args.rate_limit_offer, default.offer),
+            other: limit(args.rate_limit_other, default.other),
+            throttle_action: match args.rate_limit_action {
+                Some(ThrottleActionArg::Drop) => ThrottleAction::Drop,
+                Some(ThrottleActionArg::EmptyResponse) => ThrottleAction::EmptyResponse,
+                None => default.throttle_action,
+            },
+        }
+    }
+
+    /// Returns the limit for `kind`.
+    pub fn limit(&self, kind: RequestKind) -> Option<RateLimit> {
+        match kind {
+            RequestKind::Ping => self.ping,
+            RequestKind::FindNodes => self.find_nodes,
+            RequestKind::FindContent => self.find_content,
+            RequestKind::Offer => self.offer,
+            RequestKind::Other => self.other,
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            ping: Some(RateLimit::new(20, 10.0)),
+            find_nodes: Some(RateLimit::new(20, 10.0)),
+            find_content: Some(RateLimit::new(50, 20.0)),
+            offer: Some(RateLimit::new(20, 10.0)),
+            other: Some(RateLimit::new(5, 1.0)),
+            throttle_action: ThrottleAction::Drop,
+        }
+    }
+}
+
+/// A bucket of tokens that refills continuously, up to its burst size.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+    /// Whether the last request was rejected.
+    throttled: bool,
+}
+
+impl TokenBucket {
+    fn new(limit: &RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            last_refill: now,
+            throttled: false,
+        }
+    }
+
+    /// Attempts to take a token from the bucket. Returns whether a token was available.
+    fn try_take(&mut self, limit: &RateLimit, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * limit.per_second).min(limit.burst as f64);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Per-peer, per-request-kind rate limiter for the inbound requests of a single overlay network.
+#[derive(Debug)]
+pub struct RateLimiter {
+    protocol: ProtocolId,
+    config: RateLimitConfig,
+    buckets: LruCache<(NodeId, RequestKind), TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(protocol: ProtocolId, config: RateLimitConfig) -> Self {
+        Self {
+            protocol,
+            config,
+            buckets: LruCache::new(RATE_LIMITER_CAPACITY),
+        }
+    }
+
+    /// Returns the action to take on requests that exceed their limit.
+    pub fn throttle_action(&self) -> ThrottleAction {
+        self.config.throttle_action
+    }
+
+    /// Returns whether a request of `kind` from `node_id` is within its rate limit.
+    pub fn check(&mut self, node_id: NodeId, kind: RequestKind) -> bool {
+        self.check_at(node_id, kind, Instant::now())
+    }
+
+    fn check_at(&mut self, node_id: NodeId, kind: RequestKind, now: Instant) -> bool {
+        let limit = match self.config.limit(kind) {
+            Some(limit) => limit,
+            None => return true,
+        };
+
+        let key = (node_id, kind);
+        if self.buckets.peek(&key).is_none() {
+            self.buckets.put(key, TokenBucket::new(&limit, now));
+        }
+        let bucket = match self.buckets.get_mut(&key) {
+            Some(bucket) => bucket,
+            None => return true,
+        };
+
+        let allowed = bucket.try_take(&limit, now);
+        match (allowed, bucket.throttled) {
+            (false, false) => warn!(
+                protocol = %self.protocol,
+                peer = %node_id,
+                request = kind.as_str(),
+                "Peer exceeded request rate limit, throttling"
+            ),
+            (false, true) => debug!(
+                protocol = %self.protocol,
+                peer = %node_id,
+                request = kind.as_str(),
+                "Throttled request"
+            ),
+            (true, true) => debug!(
+                protocol = %self.protocol,
+                peer = %node_id,
+                request = kind.as_str(),
+                "Peer no longer throttled"
+            ),
+            (true, false) => {}
+        }
+        bucket.throttled = !allowed;
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn request_kind_from_talk_req_body() {
+        assert_eq!(RequestKind::from_talk_req_body(&[0, 1]), RequestKind::Ping);
+        assert_eq!(
+            RequestKind::from_talk_req_body(&[2]),
+            RequestKind::FindNodes
+        );
+        assert_eq!(
+            RequestKind::from_talk_req_body(&[4]),
+            RequestKind::FindContent
+        );
+        assert_eq!(RequestKind::from_talk_req_body(&[6]), RequestKind::Offer);
+        // Pong is not a request.
+        assert_eq!(RequestKind::from_talk_req_body(&[1]), RequestKind::Other);
+        assert_eq!(RequestKind::from_talk_req_body(&[]), RequestKind::Other);
+    }
+
+    #[test]
+    fn throttles_burst_and_refills() {
+        let config = RateLimitConfig {
+            find_content: Some(RateLimit::new(2, 1.0)),
+            ..RateLimitConfig::unlimited()
+        };
+        let mut limiter = RateLimiter::new(ProtocolId::History, config);
+        let peer = NodeId::random();
+        let now = Instant::now();
+
+        assert!(limiter.check_at(peer, RequestKind::FindContent, now));
+        assert!(limiter.check_at(peer, RequestKind::FindContent, now));
+        assert!(!limiter.check_at(peer, RequestKind::FindContent, now));
+
+        // Other kinds of request and other peers are limited separately.
+        assert!(limiter.check_at(peer, RequestKind::Ping, now));
+        assert!(limiter.check_at(NodeId::random(), RequestKind::FindContent, now));
+
+        // A single token is refilled after one second.
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.check_at(peer, RequestKind::FindContent, later));
+        assert!(!limiter.check_at(peer, RequestKind::FindContent, later));
+    }
+
+    #[test]
+    fn config_from_args() {
+        let args = OverlayConfigArgs {
+            rate_limit_find_content: Some(RateLimitArg::Limit {
+                burst: 100,
+                per_second: 40,
+            }),
+            rate_limit_other: Some(RateLimitArg::Unlimited),
+            rate_limit_action: Some(ThrottleActionArg::EmptyResponse),
+            ..Default::default()
+        };
+        let config = RateLimitConfig::from_args(&args);
+        let default = RateLimitConfig::default();
+        assert_eq!(config.find_content, Some(RateLimit::new(100, 40.0)));
+        assert_eq!(config.other, None);
+        assert_eq!(config.throttle_action, ThrottleAction::EmptyResponse);
+        assert_eq!(config.ping, default.ping);
+        assert_eq!(config.offer, default.offer);
+
+        assert_eq!(
+            RateLimitConfig::from_args(&OverlayConfigArgs::default()),
+            default
+        );
+    }
+
+    #[test]
+    fn refill_is_capped_at_burst() {
+        let config = RateLimitConfig {
+            offer: Some(RateLimit::new(1, 10.0)),
+            ..RateLimitConfig::unlimited()
+        };
+        let mut limiter = RateLimiter::new(ProtocolId::State, config);
+        let peer = NodeId::random();
+        let now = Instant::now();
+
+        assert!(limiter.check_at(peer, RequestKind::Offer, now));
+        let later = now + Duration::from_secs(60);
+        assert!(limiter.check_at(peer, RequestKind::Offer, later));
+        assert!(!limiter.check_at(peer, RequestKind::Offer, later));
+    }
+}
@@ -0,0 +1,352 @@
+use std::{marker::PhantomData, sync::Arc, time::Duration};
+
+use parking_lot::RwLock;
+use tokio::{
+    task::JoinHandle,
+    time::{interval, Interval, MissedTickBehavior},
+};
+use tracing::{debug, info, warn};
+
+use crate::storage::{ContentStore, ContentStoreError, SubnetworkStore};
+use trin_types::{cli::OverlayConfigArgs, content_key::OverlayContentKey};
+use trin_utils::bytes::hex_encode;
+use trin_validation::validator::{LookupError, Validator};
+
+/// Delay between two scrubs of the whole store, if no interval is configured.
+pub const DEFAULT_SCRUB_PASS_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Number of content keys read from the store at once.
+const SCRUB_BATCH_SIZE: u64 = 100;
+
+/// Configuration of the integrity scrubber.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScrubConfig {
+    /// Number of content items checked per second.
+    pub rate: u32,
+    /// Delay between the end of a scrub of the whole store and the start of the next one.
+    pub pass_interval: Duration,
+}
+
+impl ScrubConfig {
+    /// Returns the scrubber configuration of `args`, or `None` if the scrubber is disabled.
+    pub fn from_args(args: &OverlayConfigArgs) -> Option<Self> {
+        args.scrub_rate.map(|rate| Self {
+            rate,
+            pass_interval: args
+                .scrub_pass_interval
+                .unwrap_or(DEFAULT_SCRUB_PASS_INTERVAL),
+        })
+    }
+
+    /// Returns the delay between checks of two content items. Rates above one item per
+    /// nanosecond are capped, since a zero period is invalid.
+    pub fn tick_period(&self) -> Duration {
+        (Duration::from_secs(1) / self.rate.max(1)).max(Duration::from_nanos(1))
+    }
+}
+
+/// The outcome of a scrub of the whole store.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScrubSummary {
+    /// Number of content items that were checked.
+    pub num_checked: u64,
+    /// Number of content items that were invalid, and evicted.
+    pub num_evicted: u64,
+    /// Number of content items that could not be validated, because the data needed to validate
+    /// them could not be looked up. They are kept, and checked again in the next scrub.
+    pub num_skipped: u64,
+}
+
+/// Handle to a running scrubber task. The task is aborted when the handle is dropped.
+#[derive(Debug)]
+pub struct ScrubberHandle(JoinHandle<()>);
+
+impl Drop for ScrubberHandle {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Walks the locally stored content in the background, validates each item again, and evicts the
+/// items that fail validation. Items whose validation fails only because the validator could not
+/// look up the data it needs, e.g. when the trusted provider is unavailable, are kept.
+///
+/// The store holds only the content of its own subnetwork, so content keys that do not decode as
+/// `TContentKey` are corrupt, or left over from the shared store of older versions. They can never
+/// be served, so they are evicted like invalid content.
+pub struct Scrubber<TContentKey, TValidator> {
+    store: Arc<RwLock<SubnetworkStore>>,
+    validator: Arc<TValidator>,
+    config: ScrubConfig,
+    phantom_content_key: PhantomData<TContentKey>,
+}
+
+impl<
+        TContentKey: 'static + OverlayContentKey + Send + Sync,
+        TValidator: 'static + Validator<TContentKey> + Send + Sync,
+    > Scrubber<TContentKey, TValidator>
+{
+    pub fn new(
+        store: Arc<RwLock<SubnetworkStore>>,
+        validator: Arc<TValidator>,
+        config: ScrubConfig,
+    ) -> Self {
+        Self {
+            store,
+            validator,
+            config,
+            phantom_content_key: PhantomData,
+        }
+    }
+
+    /// Spawns a task that scrubs the whole store, waits for the pass interval, and starts over,
+    /// until the returned handle is dropped.
+    pub fn spawn(self) -> ScrubberHandle {
+        ScrubberHandle(tokio::spawn(async move {
+            let mut ticker = interval(self.config.tick_period());
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                match self.scrub_pass(&mut ticker).await {
+                    Ok(summary) => info!(
+                        checked = summary.num_checked,
+                        evicted = summary.num_evicted,
+                        skipped = summary.num_skipped,
+                        "Completed integrity scrub of stored content"
+                    ),
+                    Err(err) => warn!(error = %err, "Integrity scrub of stored content aborted"),
+                }
+                tokio::time::sleep(self.config.pass_interval).await;
+            }
+        }))
+    }
+
+    /// Checks all stored content once, one item per tick of `ticker`, and evicts the items that
+    /// are invalid.
+    pub async fn scrub_pass(
+        &self,
+        ticker: &mut Interval,
+    ) -> Result<ScrubSummary, ContentStoreError> {
+        let mut summary = ScrubSummary::default();
+        let mut after = None;
+        loop {
+            let batch = self
+                .store
+                .read()
+                .content_keys_after(after, SCRUB_BATCH_SIZE)?;
+            match batch.last() {
+                Some((last_content_id, _)) => after = Some(*last_content_id),
+                None => break,
+            }
+
+            for (content_id, content_key) in batch {
+                ticker.tick().await;
+                let content_key = match TContentKey::try_from(content_key) {
+                    Ok(content_key) => content_key,
+                    Err(_) => {
+                        debug!(
+                            content.id = hex_encode(content_id),
+                            "Stored content key failed to decode during integrity scrub"
+                        );
+                        self.evict_invalid(content_id, &mut summary);
+                        continue;
+                    }
+                };
+                let content = match self.store.read().get(&content_key) {
+                    Ok(Some(content)) => content,
+                    // The content was evicted since the batch was read.
+                    Ok(None) => continue,
+                    Err(err) => {
+                        warn!(
+                            content.key = %content_key,
+                            error = %err,
+                            "Unable to read stored content during integrity scrub"
+                        );
+                        continue;
+                    }
+                };
+
+                let valid = match self
+                    .validator
+                    .validate_content(&content_key, &content)
+                    .await
+                {
+                    Ok(_) => true,
+                    Err(err) if err.is::<LookupError>() => {
+                        debug!(
+                            content.key = %content_key,
+                            error = %err,
+                            "Unable to validate stored content during integrity scrub, skipping"
+                        );
+                        summary.num_skipped += 1;
+                        continue;
+                    }
+                    Err(err) => {
+                        debug!(
+                            content.key = %content_key,
+                            error = %err,
+                            "Stored content failed validation during integrity scrub"
+                        );
+                        false
+                    }
+                };
+                if valid {
+                    summary.num_checked += 1;
+                    self.store.read().report_scrubbed_content(&content_id, true);
+                } else {
+                    self.evict_invalid(content_id, &mut summary);
+                }
+            }
+        }
+        self.store.read().report_scrub_pass_completed();
+        Ok(summary)
+    }
+
+    /// Evicts the invalid content with id `content_id`, and records it in `summary`.
+    fn evict_invalid(&self, content_id: [u8; 32], summary: &mut ScrubSummary) {
+        match self.store.write().evict(content_id) {
+            Ok(_) => summary.num_evicted += 1,
+            Err(err) => warn!(
+                content.id = hex_encode(content_id),
+                error = %err,
+                "Unable to evict invalid content"
+            ),
+        }
+        summary.num_checked += 1;
+        self.store
+            .read()
+            .report_scrubbed_content(&content_id, false);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::{
+        storage::{PortalStorage, PortalStorageConfig},
+        types::messages::ProtocolId,
+        utils::{
+            db::setup_temp_dir,
+            validator::{TestValidator, INVALID_CONTENT, UNVERIFIABLE_CONTENT},
+        },
+    };
+    use trin_types::content_key::{BlockBodyKey, HistoryContentKey, IdentityContentKey};
+
+    #[test]
+    fn tick_period_is_never_zero() {
+        let config = |rate| ScrubConfig {
+            rate,
+            pass_interval: DEFAULT_SCRUB_PASS_INTERVAL,
+        };
+        assert_eq!(config(0).tick_period(), Duration::from_secs(1));
+        assert_eq!(config(1000).tick_period(), Duration::from_millis(1));
+        assert_eq!(config(u32::MAX).tick_period(), Duration::from_nanos(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    #[serial]
+    async fn scrub_evicts_invalid_content() {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = discv5::enr::NodeId::random();
+        let storage_config =
+            PortalStorageConfig::new(10, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config, ProtocolId::History).unwrap();
+
+        let content_keys: Vec<IdentityContentKey> = (0..250u8)
+            .map(|i| IdentityContentKey::new([i; 32]))
+            .collect();
+        for (i, content_key) in content_keys.iter().enumerate() {
+            let content = if i % 10 == 0 {
+                INVALID_CONTENT.to_vec()
+            } else {
+                vec![0x01]
+            };
+            storage.put(content_key.clone(), content).unwrap();
+        }
+
+        // A content key of another type, which does not decode as `IdentityContentKey`.
+        let undecodable_key = HistoryContentKey::BlockBody(BlockBodyKey {
+            block_hash: [0xaa; 32],
+        });
+        storage.put(undecodable_key.clone(), vec![0x01]).unwrap();
+
+        let store = Arc::new(RwLock::new(SubnetworkStore::Disk(storage)));
+        let config = ScrubConfig {
+            rate: 1000,
+            pass_interval: DEFAULT_SCRUB_PASS_INTERVAL,
+        };
+        let scrubber = Scrubber::new(Arc::clone(&store), Arc::new(TestValidator), config);
+        let mut ticker = interval(Duration::from_millis(1));
+
+        let summary = scrubber.scrub_pass(&mut ticker).await.unwrap();
+        assert_eq!(
+            summary,
+            ScrubSummary {
+                num_checked: 251,
+                num_evicted: 26,
+                num_skipped: 0,
+            }
+        );
+        for (i, content_key) in content_keys.iter().enumerate() {
+            let stored = store.read().get(content_key).unwrap();
+            assert_eq!(stored.is_none(), i % 10 == 0);
+        }
+        assert!(store.read().get(&undecodable_key).unwrap().is_none());
+
+        // A second pass finds nothing left to evict.
+        let summary = scrubber.scrub_pass(&mut ticker).await.unwrap();
+        assert_eq!(summary.num_checked, 225);
+        assert_eq!(summary.num_evicted, 0);
+
+        std::mem::drop(scrubber);
+        std::mem::drop(store);
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    #[serial]
+    async fn scrub_keeps_content_that_cannot_be_validated() {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = discv5::enr::NodeId::random();
+        let storage_config =
+            PortalStorageConfig::new(10, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config, ProtocolId::History).unwrap();
+
+        let unverifiable_key = IdentityContentKey::new([1; 32]);
+        let invalid_key = IdentityContentKey::new([2; 32]);
+        storage
+            .put(unverifiable_key.clone(), UNVERIFIABLE_CONTENT)
+            .unwrap();
+        storage.put(invalid_key.clone(), INVALID_CONTENT).unwrap();
+
+        let store = Arc::new(RwLock::new(SubnetworkStore::Disk(storage)));
+        let config = ScrubConfig {
+            rate: 1000,
+            pass_interval: DEFAULT_SCRUB_PASS_INTERVAL,
+        };
+        let scrubber = Scrubber::new(Arc::clone(&store), Arc::new(TestValidator), config);
+        let mut ticker = interval(Duration::from_millis(1));
+
+        let summary = scrubber.scrub_pass(&mut ticker).await.unwrap();
+        assert_eq!(
+            summary,
+            ScrubSummary {
+                num_checked: 1,
+                num_evicted: 1,
+                num_skipped: 1,
+            }
+        );
+        assert_eq!(
+            store.read().get(&unverifiable_key).unwrap(),
+            Some(UNVERIFIABLE_CONTENT.to_vec())
+        );
+        assert!(store.read().get(&invalid_key).unwrap().is_none());
+
+        std::mem::drop(scrubber);
+        std::mem::drop(store);
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,813 @@
+//! An in-process simulated network for testing overlay networks.
+//!
+//! A `SimNetwork` connects any number of overlay services in a single process. Messages that
+//! would be sent as Discovery v5 TALKREQ messages, including uTP packets, are delivered over
+//! channels instead, with latency, loss and churn controlled by a `SimConfig`. Latency, loss and
+//! churn are drawn from an RNG seeded by the config, and node keys, request IDs and the RNG seeds
+//! of the overlay services are derived from the same seed, so that runs under a paused tokio clock
+//! (`#[tokio::test(start_paused = true)]`) are reproducible.
+
+use std::{
+    collections::HashMap,
+    future, io,
+    net::{Ipv4Addr, SocketAddr},
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use discv5::{
+    enr::{CombinedKey, EnrBuilder, NodeId},
+    kbucket::KBucketsTable,
+    rpc::RequestId,
+    RequestError,
+};
+use futures::channel::oneshot;
+use parking_lot::{Mutex, RwLock};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tokio::sync::{broadcast, mpsc};
+use tracing::warn;
+use utp_rs::{socket::UtpSocket, udp::AsyncUdpSocket};
+
+use crate::{
+    content_cache::ContentCache,
+    discovery::{NodeAddress, ProtocolRequest, Transport, UtpEnr},
+    gossip::GossipTracker,
+    metrics::OverlayMetrics,
+    overlay::OverlayConfig,
+    overlay_service::{
+        propagate_gossip_cross_thread, OverlayCommand, OverlayRequest, OverlayService,
+        RequestDirection,
+    },
+    peer_stats::PeerStatsTable,
+    storage::{DistanceFunction, MemoryContentStore},
+    types::{
+        messages::{Message, ProtocolId, Request},
+        node::Node,
+    },
+};
+use trin_types::{content_key::IdentityContentKey, distance::XorMetric, enr::Enr};
+use trin_validation::validator::MockValidator;
+
+/// The port of every simulated node. Nodes are distinguished by their IP address.
+const SIM_UDP_PORT: u16 = 9000;
+
+/// The configuration of a simulated network.
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    /// The seed of the RNG of the network.
+    pub seed: u64,
+    /// The minimum one-way latency of a message.
+    pub min_latency: Duration,
+    /// The maximum one-way latency of a message.
+    pub max_latency: Duration,
+    /// The probability that a message is lost, between 0 and 1.
+    pub loss_rate: f64,
+    /// The time after which a request without a response fails with a timeout.
+    pub request_timeout: Duration,
+    /// The churn of nodes, if any.
+    pub churn: Option<ChurnConfig>,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            min_latency: Duration::from_millis(10),
+            max_latency: Duration::from_millis(100),
+            loss_rate: 0.0,
+            request_timeout: Duration::from_secs(1),
+            churn: None,
+        }
+    }
+}
+
+/// The churn of a simulated network. Every `interval`, each online node goes offline with
+/// probability `leave_rate`, and each offline node comes back online with probability
+/// `rejoin_rate`.
+#[derive(Clone, Debug)]
+pub struct ChurnConfig {
+    /// The interval between changes to the set of online nodes.
+    pub interval: Duration,
+    /// The probability that an online node goes offline, between 0 and 1.
+    pub leave_rate: f64,
+    /// The probability that an offline node comes back online, between 0 and 1.
+    pub rejoin_rate: f64,
+}
+
+/// A TALKREQ message delivered to a simulated node.
+struct SimTalkRequest {
+    source: NodeId,
+    body: Vec<u8>,
+    responder: oneshot::Sender<Vec<u8>>,
+}
+
+/// The simulated network interface of a node.
+struct SimPeer {
+    enr: Enr,
+    online: bool,
+    talk_reqs: mpsc::UnboundedSender<SimTalkRequest>,
+    utp_packets: mpsc::UnboundedSender<(Vec<u8>, UtpEnr)>,
+}
+
+struct SimNetworkInner {
+    config: SimConfig,
+    rng: Mutex<StdRng>,
+    peers: RwLock<HashMap<NodeId, SimPeer>>,
+    metrics: Arc<OverlayMetrics>,
+}
+
+impl SimNetworkInner {
+    /// Generates a Discovery v5 request ID from the network RNG.
+    fn next_request_id(&self) -> RequestId {
+        let id: u64 = self.rng.lock().gen();
+        RequestId(id.to_be_bytes().to_vec())
+    }
+
+    /// Takes nodes offline and brings them back online according to `churn`.
+    fn churn(&self, churn: &ChurnConfig) {
+        let mut peers = self.peers.write();
+        // Visit the nodes in a fixed order, so that the RNG draws are reproducible.
+        let mut node_ids: Vec<NodeId> = peers.keys().copied().collect();
+        node_ids.sort_by_key(|node_id| node_id.raw());
+        let mut rng = self.rng.lock();
+        for node_id in node_ids {
+            if let Some(peer) = peers.get_mut(&node_id) {
+                let rate = if peer.online {
+                    churn.leave_rate
+                } else {
+                    churn.rejoin_rate
+                };
+                if rng.gen_bool(rate.clamp(0.0, 1.0)) {
+                    peer.online = !peer.online;
+                }
+            }
+        }
+    }
+}
+
+/// An in-process network of simulated nodes.
+#[derive(Clone)]
+pub struct SimNetwork {
+    inner: Arc<SimNetworkInner>,
+}
+
+impl SimNetwork {
+    /// Creates a network without nodes.
+    ///
+    /// If the config has churn, then the churn is driven by a task spawned on the current tokio
+    /// runtime, which ends once the network and its nodes are dropped.
+    pub fn new(config: SimConfig) -> Self {
+        let rng = StdRng::seed_from_u64(config.seed);
+        let churn = config.churn.clone();
+        let network = Self {
+            inner: Arc::new(SimNetworkInner {
+                config,
+                rng: Mutex::new(rng),
+                peers: RwLock::new(HashMap::new()),
+                metrics: Arc::new(OverlayMetrics::new()),
+            }),
+        };
+        if let Some(churn) = churn {
+            spawn_churn(Arc::downgrade(&network.inner), churn);
+        }
+        network
+    }
+
+    /// Returns the number of nodes in the network.
+    pub fn len(&self) -> usize {
+        self.inner.peers.read().len()
+    }
+
+    /// Returns true if the network has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.inner.peers.read().is_empty()
+    }
+
+    /// Sets whether the node with `node_id` is online. Messages to and from offline nodes are
+    /// dropped.
+    pub fn set_online(&self, node_id: &NodeId, online: bool) {
+        if let Some(peer) = self.inner.peers.write().get_mut(node_id) {
+            peer.online = online;
+        }
+    }
+
+    /// Returns true if the node with `node_id` is in the network and online.
+    pub fn is_online(&self, node_id: &NodeId) -> bool {
+        self.inner
+            .peers
+            .read()
+            .get(node_id)
+            .map_or(false, |peer| peer.online)
+    }
+
+    /// Spawns an overlay service for a new node in the network.
+    ///
+    /// The service uses the routing table, query and uTP settings of `config`, an in-memory
+    /// content store and a validator that accepts all content.
+    pub async fn spawn_node(&self, protocol: ProtocolId, config: OverlayConfig) -> SimNode {
+        let enr = self.next_enr();
+        let node_id = enr.node_id();
+        let rng_seed = config
+            .rng_seed
+            .unwrap_or_else(|| self.inner.rng.lock().gen());
+
+        let (talk_req_tx, mut talk_req_rx) = mpsc::unbounded_channel();
+        let (utp_tx, utp_rx) = mpsc::unbounded_channel();
+        self.inner.peers.write().insert(
+            node_id,
+            SimPeer {
+                enr: enr.clone(),
+                online: true,
+                talk_reqs: talk_req_tx,
+                utp_packets: utp_tx,
+            },
+        );
+
+        let transport = Arc::new(SimTransport {
+            local_enr: enr.clone(),
+            network: self.clone(),
+            external_addr_tx: broadcast::channel(1).0,
+        });
+        let utp_socket = Arc::new(UtpSocket::with_socket(SimUdpSocket {
+            local_enr: enr.clone(),
+            network: self.clone(),
+            packets: tokio::sync::Mutex::new(utp_rx),
+        }));
+
+        let store = Arc::new(RwLock::new(MemoryContentStore::new(
+            node_id,
+            DistanceFunction::Xor,
+            &protocol,
+        )));
+        let kbuckets = Arc::new(RwLock::new(KBucketsTable::new(
+            node_id.into(),
+            config.bucket_pending_timeout,
+            config.max_incoming_per_bucket,
+            config.table_filter,
+            config.bucket_filter,
+        )));
+
+        let gossip_tracker = Arc::new(RwLock::new(GossipTracker::new()));
+
+        let command_tx = OverlayService::<
+            IdentityContentKey,
+            XorMetric,
+            MockValidator,
+            MemoryContentStore,
+        >::spawn(
+            transport,
+            Arc::clone(&store),
+            Arc::clone(&kbuckets),
+            config.bootnode_enrs,
+            config.ping_queue_interval,
+            protocol,
+            utp_socket,
+            Arc::clone(&self.inner.metrics),
+            Arc::new(RwLock::new(PeerStatsTable::new())),
+            Arc::new(RwLock::new(ContentCache::new(
+                config.content_cache_capacity_bytes,
+            ))),
+            Arc::clone(&gossip_tracker),
+            config.payload_extension,
+            Arc::new(MockValidator {}),
+            config.query_timeout,
+            config.query_peer_timeout,
+            config.query_parallelism,
+            config.query_num_results,
+            config.findnodes_query_distances_per_peer,
+            config.utp_config,
+            config.liveness,
+            Some(rng_seed),
+        )
+        .await;
+
+        // Pass incoming TALKREQ messages to the overlay service, as the overlay protocol does for
+        // Discovery v5 TALKREQ messages.
+        let request_tx = command_tx.clone();
+        let network = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            while let Some(talk_req) = talk_req_rx.recv().await {
+                let request = match Message::try_from(talk_req.body) {
+                    Ok(message) => match Request::try_from(message) {
+                        Ok(request) => request,
+                        Err(_) => continue,
+                    },
+                    Err(_) => continue,
+                };
+                let id = match network.upgrade() {
+                    Some(network) => network.next_request_id(),
+                    None => break,
+                };
+                let (tx, rx) = oneshot::channel();
+                let direction = RequestDirection::Incoming {
+                    id,
+                    source: talk_req.source,
+                };
+                let overlay_request = OverlayRequest::new(request, direction, Some(tx), None);
+                if request_tx
+                    .send(OverlayCommand::Request(overlay_request))
+                    .is_err()
+                {
+                    break;
+                }
+                tokio::spawn(async move {
+                    // Respond with an empty TALKRESP if there was an error executing the request.
+                    let response = match rx.await {
+                        Ok(Ok(response)) => Message::from(response).into(),
+                        _ => vec![],
+                    };
+                    let _ = talk_req.responder.send(response);
+                });
+            }
+        });
+
+        SimNode {
+            enr,
+            store,
+            kbuckets,
+            gossip_tracker,
+            command_tx,
+        }
+    }
+
+    /// Generates the ENR of the next node, with a key drawn from the network RNG.
+    fn next_enr(&self) -> Enr {
+        let index = self.len() as u32 + 1;
+        let mut rng = self.inner.rng.lock();
+        let key = loop {
+            let mut secret: [u8; 32] = rng.gen();
+            if let Ok(key) = CombinedKey::secp256k1_from_bytes(&mut secret) {
+                break key;
+            }
+        };
+        let [_, a, b, c] = index.to_be_bytes();
+        EnrBuilder::new("v4")
+            .ip4(Ipv4Addr::new(10, a, b, c))
+            .udp4(SIM_UDP_PORT)
+            .build(&key)
+            .expect("a simulated ENR is always valid")
+    }
+
+    /// Returns the one-way latency of a message, or `None` if the message is lost.
+    fn sample_delivery(&self) -> Option<Duration> {
+        let config = &self.inner.config;
+        let mut rng = self.inner.rng.lock();
+        if rng.gen_bool(config.loss_rate.clamp(0.0, 1.0)) {
+            return None;
+        }
+        if config.max_latency <= config.min_latency {
+            return Some(config.min_latency);
+        }
+        Some(rng.gen_range(config.min_latency..=config.max_latency))
+    }
+
+    /// Delivers a TALKREQ message from `source` to `destination` and waits on the response.
+    async fn send_talk_req(
+        &self,
+        source: NodeId,
+        destination: NodeId,
+        body: Vec<u8>,
+    ) -> Result<Vec<u8>, RequestError> {
+        let delivery = async {
+            let request_latency = self.sample_delivery();
+            let response_latency = self.sample_delivery();
+
+            let talk_reqs = match (request_latency, self.deliverable(&source, &destination)) {
+                (Some(latency), Some(peer)) => {
+                    tokio::time::sleep(latency).await;
+                    peer.talk_reqs
+                }
+                // The request is lost.
+                _ => return future::pending().await,
+            };
+            let (responder, response) = oneshot::channel();
+            let _ = talk_reqs.send(SimTalkRequest {
+                source,
+                body,
+                responder,
+            });
+            let response = match response.await {
+                Ok(response) => response,
+                Err(_) => return future::pending().await,
+            };
+
+            match (response_latency, self.deliverable(&destination, &source)) {
+                (Some(latency), Some(_)) => {
+                    tokio::time::sleep(latency).await;
+                    response
+                }
+                // The response is lost.
+                _ => future::pending().await,
+            }
+        };
+
+        tokio::time::timeout(self.inner.config.request_timeout, delivery)
+            .await
+            .map_err(|_| RequestError::Timeout)
+    }
+
+    /// Delivers a uTP packet from `source` to `destination`.
+    fn send_utp_packet(&self, source: UtpEnr, destination: NodeId, packet: Vec<u8>) {
+        let latency = match self.sample_delivery() {
+            Some(latency) => latency,
+            None => return,
+        };
+        let network = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(latency).await;
+            if let Some(peer) = network.deliverable(&source.node_id(), &destination) {
+                let _ = peer.utp_packets.send((packet, source));
+            }
+        });
+    }
+
+    /// Returns the channels of `destination` if a message from `source` can be delivered to it.
+    fn deliverable(&self, source: &NodeId, destination: &NodeId) -> Option<SimPeerChannels> {
+        let peers = self.inner.peers.read();
+        if !peers.get(source).map_or(false, |peer| peer.online) {
+            return None;
+        }
+        peers
+            .get(destination)
+            .filter(|peer| peer.online)
+            .map(|peer| SimPeerChannels {
+                talk_reqs: peer.talk_reqs.clone(),
+                utp_packets: peer.utp_packets.clone(),
+            })
+    }
+}
+
+/// Spawns a task that applies `churn` to the network until the network is dropped.
+fn spawn_churn(network: Weak<SimNetworkInner>, churn: ChurnConfig) {
+    // A zero period would make the interval panic.
+    let period = churn.interval.max(Duration::from_millis(1));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            interval.tick().await;
+            match network.upgrade() {
+                Some(network) => network.churn(&churn),
+                None => return,
+            }
+        }
+    });
+}
+
+struct SimPeerChannels {
+    talk_reqs: mpsc::UnboundedSender<SimTalkRequest>,
+    utp_packets: mpsc::UnboundedSender<(Vec<u8>, UtpEnr)>,
+}
+
+/// A transport that delivers overlay messages over a simulated network.
+pub struct SimTransport {
+    local_enr: Enr,
+    network: SimNetwork,
+    external_addr_tx: broadcast::Sender<SocketAddr>,
+}
+
+#[async_trait]
+impl Transport for SimTransport {
+    fn local_enr(&self) -> Enr {
+        self.local_enr.clone()
+    }
+
+    fn cached_node_addr(&self, node_id: &NodeId) -> Option<NodeAddress> {
+        self.network
+            .inner
+            .peers
+            .read()
+            .get(node_id)
+            .and_then(|peer| {
+                Some(NodeAddress {
+                    enr: peer.enr.clone(),
+                    socket_addr: peer.enr.udp4_socket()?.into(),
+                })
+            })
+    }
+
+    fn subscribe_external_addr_updates(&self) -> broadcast::Receiver<SocketAddr> {
+        // The address of a simulated node never changes.
+        self.external_addr_tx.subscribe()
+    }
+
+    async fn send_talk_req(
+        &self,
+        enr: Enr,
+        protocol: ProtocolId,
+        request: ProtocolRequest,
+    ) -> Result<Vec<u8>, RequestError> {
+        if protocol == ProtocolId::Utp {
+            warn!("uTP packets are sent over the simulated uTP socket");
+            return Err(RequestError::InvalidRemotePacket);
+        }
+        self.network
+            .send_talk_req(self.local_enr.node_id(), enr.node_id(), request)
+            .await
+    }
+}
+
+/// A uTP socket that sends packets over a simulated network.
+struct SimUdpSocket {
+    local_enr: Enr,
+    network: SimNetwork,
+    // `Mutex` for interior mutability. The socket is the only holder of the lock.
+    packets: tokio::sync::Mutex<mpsc::UnboundedReceiver<(Vec<u8>, UtpEnr)>>,
+}
+
+#[async_trait]
+impl AsyncUdpSocket<UtpEnr> for SimUdpSocket {
+    async fn send_to(&self, buf: &[u8], target: &UtpEnr) -> io::Result<usize> {
+        self.network.send_utp_packet(
+            UtpEnr(self.local_enr.clone()),
+            target.node_id(),
+            buf.to_vec(),
+        );
+        Ok(buf.len())
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, UtpEnr)> {
+        let mut packets = self.packets.lock().await;
+        match packets.recv().await {
+            Some((packet, source)) => {
+                let n = std::cmp::min(buf.len(), packet.len());
+                buf[..n].copy_from_slice(&packet[..n]);
+                Ok((n, source))
+            }
+            None => Err(io::Error::from(io::ErrorKind::NotConnected)),
+        }
+    }
+}
+
+/// A node of a simulated network.
+pub struct SimNode {
+    /// The ENR of the node.
+    pub enr: Enr,
+    /// The content store of the node.
+    pub store: Arc<RwLock<MemoryContentStore>>,
+    /// The routing table of the node.
+    pub kbuckets: Arc<RwLock<KBucketsTable<NodeId, Node>>>,
+    gossip_tracker: Arc<RwLock<GossipTracker>>,
+    command_tx: mpsc::UnboundedSender<OverlayCommand<IdentityContentKey>>,
+}
+
+impl SimNode {
+    /// Returns the node ID of the node.
+    pub fn node_id(&self) -> NodeId {
+        self.enr.node_id()
+    }
+
+    /// Returns the node IDs of all nodes in the routing table of the node.
+    pub fn table_entries_id(&self) -> Vec<NodeId> {
+        self.kbuckets
+            .write()
+            .iter()
+            .map(|entry| *entry.node.key.preimage())
+            .collect()
+    }
+
+    /// Performs a FindNodes query for `target` and returns the closest nodes found.
+    pub async fn lookup_node(&self, target: NodeId) -> Vec<Enr> {
+        let (tx, rx) = oneshot::channel();
+        let command = OverlayCommand::FindNodeQuery {
+            target,
+            callback: tx,
+            is_trace: false,
+        };
+        if self.command_tx.send(command).is_err() {
+            return vec![];
+        }
+        rx.await.map(|(enrs, _)| enrs).unwrap_or_default()
+    }
+
+    /// Offers `content` to interested nodes in the routing table of the node. Returns the number
+    /// of nodes that the content was offered to.
+    pub fn propagate_gossip(&self, content: Vec<(IdentityContentKey, Vec<u8>)>) -> usize {
+        propagate_gossip_cross_thread(
+            content,
+            Arc::clone(&self.kbuckets),
+            self.command_tx.clone(),
+            &self.gossip_tracker,
+        )
+    }
+
+    /// Performs a FindContent query for `content_key` and returns the content, if found.
+    pub async fn lookup_content(&self, content_key: IdentityContentKey) -> Option<Vec<u8>> {
+        let (tx, rx) = oneshot::channel();
+        let command = OverlayCommand::FindContentQuery {
+            target: content_key,
+            callback: tx,
+            is_trace: false,
+        };
+        self.command_tx.send(command).ok()?;
+        rx.await.ok().and_then(|(content, _)| content)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    use crate::liveness::LivenessConfig;
+    use crate::storage::ContentStore;
+    use trin_types::content_key::OverlayContentKey;
+    use trin_types::distance::Distance;
+    use trin_types::distance::Metric;
+
+    const NUM_NODES: usize = 256;
+
+    /// The number of nodes that are taken offline in tests with churn.
+    const NUM_OFFLINE_NODES: usize = NUM_NODES / 8;
+
+    async fn spawn_network(config: SimConfig) -> (SimNetwork, Vec<SimNode>) {
+        let network = SimNetwork::new(config);
+        let bootnode = network
+            .spawn_node(ProtocolId::History, OverlayConfig::default())
+            .await;
+        let mut nodes = vec![];
+        for _ in 1..NUM_NODES {
+            let config = OverlayConfig {
+                bootnode_enrs: vec![bootnode.enr.clone()],
+                ..Default::default()
+            };
+            nodes.push(network.spawn_node(ProtocolId::History, config).await);
+        }
+        nodes.insert(0, bootnode);
+        (network, nodes)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn routing_tables_converge() {
+        let (network, nodes) = spawn_network(SimConfig::default()).await;
+        assert_eq!(network.len(), NUM_NODES);
+
+        // Allow for the bootstrap lookups and a few rounds of bucket refreshes.
+        tokio::time::sleep(Duration::from_secs(300)).await;
+
+        // Without loss, a lookup for any node returns that node as the closest node to itself.
+        for target in nodes.iter().skip(2).map(|node| node.node_id()) {
+            let found = nodes[1].lookup_node(target).await;
+            assert_eq!(found.first().map(|enr| enr.node_id()), Some(target));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn content_lookup_with_loss_and_churn() {
+        let config = SimConfig {
+            seed: 7,
+            loss_rate: 0.05,
+            ..Default::default()
+        };
+        let (network, nodes) = spawn_network(config).await;
+        tokio::time::sleep(Duration::from_secs(300)).await;
+
+        for node in nodes.iter().skip(NUM_NODES - NUM_OFFLINE_NODES) {
+            network.set_online(&node.node_id(), false);
+        }
+        let num_online = nodes
+            .iter()
+            .filter(|node| network.is_online(&node.node_id()))
+            .count();
+        assert_eq!(num_online, NUM_NODES - NUM_OFFLINE_NODES);
+
+        // Store the content at the online node closest to the content.
+        let content_key = IdentityContentKey::new([0x42; 32]);
+        let content = vec![0xef; 64];
+        let online_nodes = &nodes[..NUM_NODES - NUM_OFFLINE_NODES];
+        let closest = online_nodes
+            .iter()
+            .min_by_key(|node| {
+                XorMetric::distance(&content_key.content_id(), &node.node_id().raw())
+            })
+            .unwrap();
+        closest
+            .store
+            .write()
+            .put(content_key.clone(), &content)
+            .unwrap();
+
+        let origin = online_nodes
+            .iter()
+            .find(|node| node.node_id() != closest.node_id())
+            .unwrap();
+        let found = origin.lookup_content(content_key).await;
+        assert_eq!(found, Some(content));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gossip_reaches_interested_nodes() {
+        let (_, nodes) = spawn_network(SimConfig::default()).await;
+        tokio::time::sleep(Duration::from_secs(300)).await;
+
+        let content_key = IdentityContentKey::new([0x24; 32]);
+        let content = vec![0xab; 64];
+        nodes[0]
+            .store
+            .write()
+            .put(content_key.clone(), &content)
+            .unwrap();
+        // The routing table of the bootnode holds more than 8 nodes, so the content is offered to
+        // its 4 closest interested nodes and 4 random other interested nodes.
+        assert_eq!(
+            nodes[0].propagate_gossip(vec![(content_key.clone(), content.clone())]),
+            8
+        );
+        tokio::time::sleep(Duration::from_secs(30)).await;
+
+        // Every node has the maximum radius, so nodes that accept the content gossip it further,
+        // each to the closest nodes to the content in their routing table. The content reaches the
+        // 4 closest nodes of the whole network.
+        let mut nodes_by_distance: Vec<&SimNode> = nodes.iter().collect();
+        nodes_by_distance.sort_by_key(|node| {
+            XorMetric::distance(&content_key.content_id(), &node.node_id().raw())
+        });
+        for node in nodes_by_distance.iter().take(4) {
+            assert_eq!(
+                node.store.read().get(&content_key).unwrap(),
+                Some(content.clone())
+            );
+        }
+        for node in nodes.iter() {
+            assert_eq!(node.store.read().radius(), Distance::MAX);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unresponsive_node_is_evicted_after_timeouts() {
+        let network = SimNetwork::new(SimConfig::default());
+        let peer = network
+            .spawn_node(ProtocolId::History, OverlayConfig::default())
+            .await;
+        let config = OverlayConfig {
+            bootnode_enrs: vec![peer.enr.clone()],
+            liveness: LivenessConfig {
+                max_failures: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let node = network.spawn_node(ProtocolId::History, config).await;
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(node.table_entries_id(), vec![peer.node_id()]);
+
+        // The FindNodes request of the lookup to the peer times out once the peer is offline.
+        network.set_online(&peer.node_id(), false);
+        let found = node.lookup_node(NodeId::random()).await;
+        assert!(found.is_empty());
+        assert!(node.table_entries_id().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn churn_is_reproducible() {
+        let config = SimConfig {
+            seed: 11,
+            churn: Some(ChurnConfig {
+                interval: Duration::from_secs(10),
+                leave_rate: 0.2,
+                rejoin_rate: 0.5,
+            }),
+            ..Default::default()
+        };
+
+        // Nodes without bootnodes send no messages, so only the churn draws from the RNG.
+        let mut online_sets = vec![];
+        for _ in 0..2 {
+            let network = SimNetwork::new(config.clone());
+            let mut nodes = vec![];
+            for _ in 0..NUM_NODES {
+                nodes.push(
+                    network
+                        .spawn_node(ProtocolId::History, OverlayConfig::default())
+                        .await,
+                );
+            }
+            tokio::time::sleep(Duration::from_secs(65)).await;
+
+            let online: Vec<bool> = nodes
+                .iter()
+                .map(|node| network.is_online(&node.node_id()))
+                .collect();
+            online_sets.push(online);
+        }
+        assert_eq!(online_sets[0], online_sets[1]);
+
+        // The same seed takes the same nodes offline, in the same order of spawning, and a
+        // different seed takes different nodes offline.
+        let network = SimNetwork::new(SimConfig { seed: 12, ..config });
+        let mut nodes = vec![];
+        for _ in 0..NUM_NODES {
+            nodes.push(
+                network
+                    .spawn_node(ProtocolId::History, OverlayConfig::default())
+                    .await,
+            );
+        }
+        tokio::time::sleep(Duration::from_secs(65)).await;
+        let online: Vec<bool> = nodes
+            .iter()
+            .map(|node| network.is_online(&node.node_id()))
+            .collect();
+        assert_ne!(online, online_sets[0]);
+    }
+}
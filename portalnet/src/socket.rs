@@ -1,14 +1,19 @@
 use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
-// This stun server is part of the testnet infrastructure.
-// If you are unable to connect, please create an issue.
-const STUN_SERVER: &str = "159.223.0.83:3478";
+/// Timeout for a response from a single STUN server.
+const STUN_TIMEOUT: Duration = Duration::from_secs(3);
 
-/// Ping a STUN server on the public network. This does two things:
+/// Ping STUN servers on the public network. This does two things:
 /// - Creates an externally-addressable UDP port, if you are behind a NAT
 /// - Returns the public IP and port that corresponds to your local port
-pub fn stun_for_external(local_socket_addr: &SocketAddr) -> Option<SocketAddr> {
+///
+/// The servers are queried in order, and the first address returned is used.
+pub fn stun_for_external(
+    local_socket_addr: &SocketAddr,
+    stun_servers: &[SocketAddr],
+) -> Option<SocketAddr> {
     let socket = match UdpSocket::bind(local_socket_addr) {
         Ok(val) => val,
         Err(err) => {
@@ -16,22 +21,151 @@ pub fn stun_for_external(local_socket_addr: &SocketAddr) -> Option<SocketAddr> {
             return None;
         }
     };
-    info!("Connecting to STUN server to find public network endpoint");
-    let external_addr = stunclient::StunClient::new(
-        STUN_SERVER
-            .parse()
-            .expect("Parsing static STUN_SERVER to work"),
-    )
-    .query_external_address(&socket);
-
-    match external_addr {
-        Ok(addr) => {
-            debug!(addr = ?addr, "Public address returned from STUN server");
-            Some(addr)
+
+    for stun_server in stun_servers {
+        info!(server = %stun_server, "Connecting to STUN server to find public network endpoint");
+        let mut client = stunclient::StunClient::new(*stun_server);
+        client.set_timeout(STUN_TIMEOUT);
+        match client.query_external_address(&socket) {
+            Ok(addr) => {
+                debug!(addr = ?addr, server = %stun_server, "Public address returned from STUN server");
+                return Some(addr);
+            }
+            Err(err) => {
+                warn!(error = %err, server = %stun_server, "Error setting up STUN traversal");
+            }
         }
-        Err(err) => {
-            warn!(error = %err, "Error setting up STUN traversal");
-            None
+    }
+
+    None
+}
+
+/// UPnP (IGD) port mapping of the discovery port on the local gateway.
+#[cfg(feature = "upnp")]
+pub mod upnp {
+    use std::net::{SocketAddr, SocketAddrV4, UdpSocket};
+    use std::time::Duration;
+
+    use igd::{PortMappingProtocol, SearchOptions};
+    use tracing::{debug, info, warn};
+
+    /// Duration of a port mapping lease. Mappings are renewed at half this interval.
+    pub const LEASE_DURATION: Duration = Duration::from_secs(60 * 60);
+
+    const MAPPING_DESCRIPTION: &str = "trin";
+
+    /// Maps UDP `port` on the gateway to the same port on the local host. Returns the external
+    /// address of the mapping.
+    ///
+    /// This function blocks while the gateway is discovered and the mapping is requested.
+    pub fn map_port(port: u16) -> Option<SocketAddr> {
+        let gateway = match igd::search_gateway(SearchOptions::default()) {
+            Ok(gateway) => gateway,
+            Err(err) => {
+                warn!(error = %err, "Unable to find UPnP gateway");
+                return None;
+            }
+        };
+
+        // Determine the local address that routes to the gateway.
+        let local_ip = match UdpSocket::bind("0.0.0.0:0")
+            .and_then(|socket| socket.connect(gateway.addr).map(|_| socket))
+            .and_then(|socket| socket.local_addr())
+        {
+            Ok(SocketAddr::V4(addr)) => *addr.ip(),
+            Ok(SocketAddr::V6(_)) => return None,
+            Err(err) => {
+                warn!(error = %err, "Unable to determine local address for UPnP mapping");
+                return None;
+            }
+        };
+
+        if let Err(err) = gateway.add_port(
+            PortMappingProtocol::UDP,
+            port,
+            SocketAddrV4::new(local_ip, port),
+            LEASE_DURATION.as_secs() as u32,
+            MAPPING_DESCRIPTION,
+        ) {
+            warn!(error = %err, port, "Unable to add UPnP port mapping");
+            return None;
+        }
+
+        match gateway.get_external_ip() {
+            Ok(ip) => {
+                info!(ip = %ip, port, "Added UPnP port mapping");
+                Some(SocketAddr::new(ip.into(), port))
+            }
+            Err(err) => {
+                debug!(error = %err, "Unable to get external IP from UPnP gateway");
+                None
+            }
         }
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::thread;
+
+    /// STUN magic cookie, from RFC 5389.
+    const MAGIC_COOKIE: u32 = 0x2112_A442;
+
+    /// Spawns a stand-in STUN server that answers a single binding request with the
+    /// XOR-MAPPED-ADDRESS of `mapped_addr`.
+    fn spawn_stun_server(mapped_addr: SocketAddr) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (_, src) = socket.recv_from(&mut buf).unwrap();
+            let ip = match mapped_addr.ip() {
+                IpAddr::V4(ip) => u32::from(ip),
+                IpAddr::V6(_) => panic!("Stand-in STUN server only supports IPv4"),
+            };
+
+            let mut response = vec![];
+            // Binding success response, with a single 12 byte attribute.
+            response.extend_from_slice(&0x0101u16.to_be_bytes());
+            response.extend_from_slice(&12u16.to_be_bytes());
+            response.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+            // Echo the transaction ID.
+            response.extend_from_slice(&buf[8..20]);
+            // XOR-MAPPED-ADDRESS attribute.
+            response.extend_from_slice(&0x0020u16.to_be_bytes());
+            response.extend_from_slice(&8u16.to_be_bytes());
+            response.extend_from_slice(&[0x00, 0x01]);
+            let port = mapped_addr.port() ^ (MAGIC_COOKIE >> 16) as u16;
+            response.extend_from_slice(&port.to_be_bytes());
+            response.extend_from_slice(&(ip ^ MAGIC_COOKIE).to_be_bytes());
+
+            socket.send_to(&response, src).unwrap();
+        });
+        server_addr
+    }
+
+    #[test]
+    fn stun_falls_back_to_next_server() {
+        let mapped_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), 9009);
+
+        // The first server never responds.
+        let unresponsive = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let stun_servers = vec![
+            unresponsive.local_addr().unwrap(),
+            spawn_stun_server(mapped_addr),
+        ];
+
+        let local_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+        let external_addr = stun_for_external(&local_addr, &stun_servers);
+        assert_eq!(external_addr, Some(mapped_addr));
+    }
+
+    #[test]
+    fn stun_without_servers() {
+        let local_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+        assert!(stun_for_external(&local_addr, &[]).is_none());
+    }
+}
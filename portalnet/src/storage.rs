@@ -1,35 +1,73 @@
 use std::{
+    collections::{BTreeMap, BTreeSet},
     convert::TryInto,
-    fs,
+    fmt,
+    ops::Bound,
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
 use anyhow::anyhow;
 use discv5::enr::NodeId;
+use ethereum_types::{H256, U256};
 use ethportal_api::types::portal::PaginateLocalContentInfo;
 use prometheus_exporter::{
     self,
     prometheus::{
         default_registry, opts, register_gauge, register_gauge_with_registry,
-        register_int_gauge_with_registry, Gauge, IntGauge, Registry,
+        register_int_counter_with_registry, register_int_gauge_with_registry, Gauge, IntCounter,
+        IntGauge, Registry,
     },
 };
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rocksdb::{Options, DB};
-use rusqlite::params;
+use rocksdb::{ColumnFamily, Direction, IteratorMode, Options, WriteBatch, WriteOptions, DB};
+use rusqlite::{params, params_from_iter, types::Value, Connection, OptionalExtension};
 use thiserror::Error;
-use tracing::{debug, error, info};
+use tokio::{task::JoinHandle, time::interval};
+use tracing::{debug, error, info, warn};
 
 use crate::types::messages::ProtocolId;
 use trin_types::content_key::{ContentKeyError, HistoryContentKey, OverlayContentKey};
 use trin_types::distance::{Distance, Metric, XorMetric};
+use trin_types::inventory::{
+    LocalContentEntry, LocalContentFilter, LocalContentInventory, LocalContentStats,
+};
 use trin_utils::bytes::{hex_decode, hex_encode, ByteUtilsError};
 
 const BYTES_IN_MB_U64: u64 = 1000 * 1000;
 const BYTES_IN_MB_F64: f64 = 1000.0 * 1000.0;
 
+/// Interval between two samples of the on-disk size of the databases.
+const DISK_USAGE_SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of farthest content items considered for the first eviction band. Storing content
+/// usually evicts one or two items, so the first band is kept small.
+const MIN_EVICTION_BATCH_SIZE: u64 = 16;
+
+/// Maximum number of content items evicted at once when the store is over capacity.
+const EVICTION_BATCH_SIZE: u64 = 1000;
+
+/// Maximum number of content items returned by a single inventory query.
+pub const MAX_INVENTORY_PAGE_SIZE: u64 = 10_000;
+
+/// RocksDB column family that journals the content ids of writes in progress. A write to the
+/// content of a subnetwork spans both RocksDB and SQLite, so an interrupted write is repaired from
+/// the journal when the store is opened again. The journal also marks the subnetworks whose
+/// whole content was reconciled between the two databases.
+const JOURNAL_COLUMN_FAMILY: &str = "journal";
+
+/// Subnetworks whose content is stored in a `PortalStorage`. Each one has its own RocksDB column
+/// family and SQLite metadata table.
+const CONTENT_PROTOCOLS: [ProtocolId; 5] = [
+    ProtocolId::History,
+    ProtocolId::State,
+    ProtocolId::TransactionGossip,
+    ProtocolId::HeaderGossip,
+    ProtocolId::CanonicalIndices,
+];
+
 // TODO: Replace enum with generic type parameter. This will require that we have a way to
 // associate a "find farthest" query with the generic Metric.
 #[derive(Copy, Clone, Debug)]
@@ -75,6 +113,14 @@ pub trait ContentStore {
     /// Looks up a piece of content by `key`.
     fn get<K: OverlayContentKey>(&self, key: &K) -> Result<Option<Vec<u8>>, ContentStoreError>;
 
+    /// Looks up several pieces of content by `keys`. Returns the content of each key, in order.
+    fn get_many<K: OverlayContentKey>(
+        &self,
+        keys: &[K],
+    ) -> Result<Vec<Option<Vec<u8>>>, ContentStoreError> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+
     /// Puts a piece of content into the store.
     fn put<K: OverlayContentKey, V: AsRef<[u8]>>(
         &mut self,
@@ -82,6 +128,18 @@ pub trait ContentStore {
         value: V,
     ) -> Result<(), ContentStoreError>;
 
+    /// Puts several pieces of content into the store, and makes room for all of them at once.
+    /// Returns the outcome of putting each piece of content, in order.
+    fn put_batch<K: OverlayContentKey, V: AsRef<[u8]>>(
+        &mut self,
+        items: Vec<(K, V)>,
+    ) -> Result<Vec<Result<(), ContentStoreError>>, ContentStoreError> {
+        Ok(items
+            .into_iter()
+            .map(|(key, value)| self.put(key, value))
+            .collect())
+    }
+
     /// Returns whether the content denoted by `key` is within the radius of the data store and not
     /// already stored within the data store.
     fn is_key_within_radius_and_unavailable<K: OverlayContentKey>(
@@ -93,53 +151,455 @@ pub trait ContentStore {
     fn radius(&self) -> Distance;
 }
 
-/// An in-memory `ContentStore`.
+/// Configuration of a `MemoryContentStore`.
+#[derive(Clone, Debug)]
+pub struct MemoryContentStoreConfig {
+    pub storage_capacity_mb: u64,
+    pub node_id: NodeId,
+    pub distance_fn: DistanceFunction,
+    /// SSZ union selectors of the content types that are pinned, so that all content of these
+    /// types is stored and never evicted.
+    pub pinned_content_types: Vec<u8>,
+}
+
+impl MemoryContentStoreConfig {
+    pub fn new(storage_capacity_mb: u64, node_id: NodeId) -> Self {
+        Self {
+            storage_capacity_mb,
+            node_id,
+            distance_fn: DistanceFunction::Xor,
+            pinned_content_types: vec![],
+        }
+    }
+}
+
+/// An in-memory `ContentStore`, with the capacity and eviction semantics of `PortalStorage`:
+/// once the content outgrows the capacity, the farthest content is evicted, and the radius
+/// shrinks to the farthest content that is kept.
+#[derive(Debug)]
 pub struct MemoryContentStore {
-    /// The content store.
-    store: std::collections::HashMap<Vec<u8>, Vec<u8>>,
+    /// The stored content, by content id.
+    store: BTreeMap<[u8; 32], MemoryContentEntry>,
+    /// The distance and content id of all stored content that is not pinned, which orders the
+    /// content for eviction.
+    evictable: BTreeSet<(Distance, [u8; 32])>,
+    /// The content keys of all pinned content, by content id, whether it is stored or not.
+    pinned: BTreeMap<[u8; 32], Vec<u8>>,
+    /// SSZ union selectors of the content types that are pinned.
+    pinned_content_types: Vec<u8>,
     /// The `NodeId` of the local node.
     node_id: NodeId,
     /// The distance function used by the store to compute distances.
     distance_fn: DistanceFunction,
     /// The radius of the store.
     radius: Distance,
+    storage_capacity_in_bytes: u64,
+    /// Sum of the sizes of all stored content that is not pinned, in bytes.
+    content_size_total: u64,
+    metrics: StorageMetrics,
+}
+
+/// A content item held by a `MemoryContentStore`.
+#[derive(Debug)]
+struct MemoryContentEntry {
+    content_key: Vec<u8>,
+    value: Vec<u8>,
 }
 
 impl MemoryContentStore {
-    /// Constructs a new `MemoryPortalContentStore`.
-    pub fn new(node_id: NodeId, distance_fn: DistanceFunction) -> Self {
+    /// Constructs a new `MemoryPortalContentStore` for the content of `protocol`, without a
+    /// capacity limit.
+    pub fn new(node_id: NodeId, distance_fn: DistanceFunction, protocol: &ProtocolId) -> Self {
+        let metrics = StorageMetrics::new(protocol);
+        metrics.report_radius(Distance::MAX);
         Self {
-            store: std::collections::HashMap::new(),
+            store: BTreeMap::new(),
+            evictable: BTreeSet::new(),
+            pinned: BTreeMap::new(),
+            pinned_content_types: vec![],
             node_id,
             distance_fn,
             radius: Distance::MAX,
+            storage_capacity_in_bytes: u64::MAX,
+            content_size_total: 0,
+            metrics,
         }
     }
 
+    /// Constructs a new `MemoryContentStore` for the content of `protocol`, with the capacity and
+    /// pinned content types of `config`.
+    pub fn from_config(config: MemoryContentStoreConfig, protocol: &ProtocolId) -> Self {
+        let mut store = Self::new(config.node_id, config.distance_fn, protocol);
+        store.storage_capacity_in_bytes =
+            config.storage_capacity_mb.saturating_mul(BYTES_IN_MB_U64);
+        store
+            .metrics
+            .report_storage_capacity_bytes(store.storage_capacity_in_bytes as f64);
+        store.pinned_content_types = config.pinned_content_types;
+        // Default radius is left in place, unless user selected 0mb capacity
+        if store.storage_capacity_in_bytes == 0 {
+            store.set_radius(Distance::ZERO);
+        }
+        store
+    }
+
     /// Sets the radius of the store to `radius`.
     pub fn set_radius(&mut self, radius: Distance) {
         self.radius = radius;
+        self.metrics.report_radius(radius);
     }
 
     /// Returns the distance to `key` from the local `NodeId` according to the distance function.
     fn distance_to_key<K: OverlayContentKey>(&self, key: &K) -> Distance {
+        self.distance_to_content_id(&key.content_id())
+    }
+
+    /// Returns the distance to `content_id` from the local `NodeId` according to the distance
+    /// function.
+    fn distance_to_content_id(&self, content_id: &[u8; 32]) -> Distance {
         match self.distance_fn {
-            DistanceFunction::Xor => XorMetric::distance(&key.content_id(), &self.node_id.raw()),
+            DistanceFunction::Xor => XorMetric::distance(content_id, &self.node_id.raw()),
         }
     }
 
     /// Returns `true` if the content store contains data for `key`.
     fn contains_key<K: OverlayContentKey>(&self, key: &K) -> bool {
-        let key = key.content_id().to_vec();
-        self.store.contains_key(&key)
+        self.store.contains_key(&key.content_id())
+    }
+
+    /// Stores `value` for `key`, without making room for it if the store is over capacity
+    /// afterwards.
+    fn store_without_pruning<K: OverlayContentKey>(
+        &mut self,
+        key: &K,
+        value: &[u8],
+    ) -> Result<(), ContentStoreError> {
+        let content_id = key.content_id();
+        let distance = self.distance_to_content_id(&content_id);
+        let is_pinned = self.is_pinned(key);
+
+        if distance > self.radius && !is_pinned {
+            debug!("Not storing: {:02X?}", key.clone().into());
+            return Err(ContentStoreError::InsufficientRadius {
+                radius: self.radius,
+                distance,
+            });
+        }
+        if self.store.contains_key(&content_id) {
+            return Ok(());
+        }
+
+        self.store.insert(
+            content_id,
+            MemoryContentEntry {
+                content_key: key.clone().into(),
+                value: value.to_vec(),
+            },
+        );
+        // Pinned content does not count towards the capacity of the store.
+        if !is_pinned {
+            self.evictable.insert((distance, content_id));
+            self.content_size_total += value.len() as u64;
+            self.metrics
+                .report_content_data_storage_bytes(self.content_size_total as f64);
+        }
+        self.metrics.increase_entry_count();
+        Ok(())
+    }
+
+    /// Evicts the farthest content while the store is over capacity, until evicting the next
+    /// item would drop it below capacity, and sets the radius to the farthest content that is
+    /// kept. Does nothing if the store is not over capacity. Returns the number of evicted items.
+    fn prune(&mut self) -> usize {
+        if !self.capacity_reached() {
+            return 0;
+        }
+
+        let mut num_removed_items = 0;
+        while self.capacity_reached() {
+            let (_, farthest) = match self.evictable.iter().next_back() {
+                Some(farthest) => *farthest,
+                None => break,
+            };
+            let size = self.content_size(&farthest);
+            // Removing the item would drop us below capacity. We target slight overfilling.
+            if self.content_size_total.saturating_sub(size) < self.storage_capacity_in_bytes {
+                break;
+            }
+            self.evict_content(&farthest);
+            num_removed_items += 1;
+        }
+
+        // Reset the radius to the farthest content that is left, or to zero if the entire store
+        // has been pruned, eg. user selected 0mb capacity for storage
+        let radius = match self.evictable.iter().next_back() {
+            Some((distance, _)) => *distance,
+            None => Distance::ZERO,
+        };
+        self.set_radius(radius);
+        num_removed_items
+    }
+
+    /// Public method for evicting a certain content id.
+    pub fn evict(&mut self, id: [u8; 32]) -> anyhow::Result<()> {
+        self.evict_content(&id);
+        Ok(())
+    }
+
+    /// Removes the content with id `content_id`. Returns false if it was not stored.
+    fn evict_content(&mut self, content_id: &[u8; 32]) -> bool {
+        let entry = match self.store.remove(content_id) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let distance = self.distance_to_content_id(content_id);
+        if self.evictable.remove(&(distance, *content_id)) {
+            self.content_size_total = self
+                .content_size_total
+                .saturating_sub(entry.value.len() as u64);
+            self.metrics
+                .report_content_data_storage_bytes(self.content_size_total as f64);
+        }
+        self.metrics.decrease_entry_count_by(1);
+        true
+    }
+
+    /// Returns the size of the content with id `content_id`, or zero if it is not stored.
+    fn content_size(&self, content_id: &[u8; 32]) -> u64 {
+        self.store
+            .get(content_id)
+            .map(|entry| entry.value.len() as u64)
+            .unwrap_or_default()
+    }
+
+    fn capacity_reached(&self) -> bool {
+        self.content_size_total > self.storage_capacity_in_bytes
+    }
+
+    /// Pins the content denoted by `key`, so that it is stored even if it falls outside of the
+    /// radius, and never evicted to make room for other content. The content does not need to be
+    /// stored yet. Returns false if the content was already pinned.
+    pub fn pin(&mut self, key: &impl OverlayContentKey) -> Result<bool, ContentStoreError> {
+        let content_id = key.content_id();
+        if self.pinned.contains_key(&content_id) {
+            return Ok(false);
+        }
+        self.pinned.insert(content_id, key.clone().into());
+
+        // Stored content no longer counts towards the capacity of the store.
+        let distance = self.distance_to_content_id(&content_id);
+        if self.evictable.remove(&(distance, content_id)) {
+            let size = self.content_size(&content_id);
+            self.content_size_total = self.content_size_total.saturating_sub(size);
+            self.metrics
+                .report_content_data_storage_bytes(self.content_size_total as f64);
+        }
+        Ok(true)
+    }
+
+    /// Unpins the content denoted by `key`. Stored content counts towards the capacity of the
+    /// store again, and may be evicted right away if the store is over capacity. Returns false if
+    /// the content was not pinned.
+    ///
+    /// Content of a pinned content type stays pinned by its type.
+    pub fn unpin(&mut self, key: &impl OverlayContentKey) -> Result<bool, ContentStoreError> {
+        let content_id = key.content_id();
+        if self.pinned.remove(&content_id).is_none() {
+            return Ok(false);
+        }
+
+        if self.store.contains_key(&content_id) && !self.is_pinned_content_type(key) {
+            let distance = self.distance_to_content_id(&content_id);
+            self.evictable.insert((distance, content_id));
+            self.content_size_total += self.content_size(&content_id);
+            self.metrics
+                .report_content_data_storage_bytes(self.content_size_total as f64);
+            self.prune();
+        }
+        Ok(true)
+    }
+
+    /// Returns the content keys of all content pinned by itself, whether it is stored or not.
+    /// Content that is only pinned by its content type is not listed.
+    pub fn pinned_content_keys(&self) -> Result<Vec<Vec<u8>>, ContentStoreError> {
+        let mut content_keys: Vec<Vec<u8>> = self.pinned.values().cloned().collect();
+        content_keys.sort();
+        Ok(content_keys)
+    }
+
+    /// Returns whether the content denoted by `key` is pinned, either by itself or by its content
+    /// type.
+    fn is_pinned(&self, key: &impl OverlayContentKey) -> bool {
+        self.is_pinned_content_type(key) || self.pinned.contains_key(&key.content_id())
+    }
+
+    /// Returns whether the content type of `key` is pinned.
+    fn is_pinned_content_type(&self, key: &impl OverlayContentKey) -> bool {
+        let content_key: Vec<u8> = key.clone().into();
+        match content_key.first() {
+            Some(content_type) => self.pinned_content_types.contains(content_type),
+            None => false,
+        }
+    }
+
+    /// Returns a paginated list of all stored content keys, in ascending order, according to the
+    /// provided offset and limit.
+    pub fn paginate<K: OverlayContentKey>(
+        &self,
+        offset: &u64,
+        limit: &u64,
+    ) -> Result<PaginateLocalContentInfo<K>, ContentStoreError>
+    where
+        <K as TryFrom<Vec<u8>>>::Error: fmt::Display,
+    {
+        let mut content_keys: Vec<&Vec<u8>> = self
+            .store
+            .values()
+            .map(|entry| &entry.content_key)
+            .collect();
+        content_keys.sort();
+        let content_keys: Result<Vec<K>, ContentStoreError> = content_keys
+            .into_iter()
+            .skip(*offset as usize)
+            .take(*limit as usize)
+            .map(|content_key| decode_content_key(content_key.clone()))
+            .collect();
+        Ok(PaginateLocalContentInfo {
+            content_keys: content_keys?,
+            total_entries: self.store.len() as u64,
+        })
+    }
+
+    /// Returns up to `limit` stored content items that match `filter`, in ascending order of
+    /// content id, starting after the content id `cursor`, or from the lowest content id if
+    /// `cursor` is `None`.
+    pub fn inventory(
+        &self,
+        filter: &LocalContentFilter,
+        cursor: Option<[u8; 32]>,
+        limit: u64,
+    ) -> Result<LocalContentInventory, ContentStoreError> {
+        if limit == 0 {
+            return Err(ContentStoreError::InvalidData {
+                message: "Inventory limit must be positive".to_string(),
+            });
+        }
+        let limit = limit.min(MAX_INVENTORY_PAGE_SIZE) as usize;
+        let matcher = ContentMatcher::new(filter)?;
+        let range = match cursor {
+            Some(cursor) => (Bound::Excluded(cursor), Bound::Unbounded),
+            None => (Bound::Unbounded, Bound::Unbounded),
+        };
+        // Look one item ahead, to tell whether another page follows.
+        let mut entries: Vec<LocalContentEntry> = self
+            .store
+            .range(range)
+            .filter_map(|(content_id, entry)| {
+                let inventory_entry = self.inventory_entry(content_id, entry);
+                if matcher.matches(&entry.content_key, &inventory_entry) {
+                    Some(inventory_entry)
+                } else {
+                    None
+                }
+            })
+            .take(limit + 1)
+            .collect();
+        let next_cursor = if entries.len() > limit {
+            entries.truncate(limit);
+            entries.last().map(|entry| entry.content_id)
+        } else {
+            None
+        };
+        Ok(LocalContentInventory {
+            entries,
+            next_cursor,
+        })
+    }
+
+    /// Returns the number of stored content items that match `filter`, and their total size, for
+    /// each content type.
+    pub fn inventory_stats(
+        &self,
+        filter: &LocalContentFilter,
+    ) -> Result<LocalContentStats, ContentStoreError> {
+        let matcher = ContentMatcher::new(filter)?;
+        let mut stats = LocalContentStats::default();
+        for (content_id, entry) in self.store.iter() {
+            let inventory_entry = self.inventory_entry(content_id, entry);
+            if !matcher.matches(&entry.content_key, &inventory_entry) {
+                continue;
+            }
+            if let Some(content_type) = entry.content_key.first() {
+                stats.add(*content_type, 1, inventory_entry.content_size);
+            }
+        }
+        Ok(stats)
+    }
+
+    fn inventory_entry(
+        &self,
+        content_id: &[u8; 32],
+        entry: &MemoryContentEntry,
+    ) -> LocalContentEntry {
+        LocalContentEntry {
+            content_key: hex_encode(&entry.content_key),
+            content_id: H256::from(content_id),
+            content_size: entry.value.len() as u64,
+            distance: *self.distance_to_content_id(content_id),
+        }
+    }
+
+    /// Returns up to `limit` pairs of content id and content key, in ascending order of content id,
+    /// starting after the content id `after`, or from the lowest content id if `after` is `None`.
+    pub fn content_keys_after(
+        &self,
+        after: Option<[u8; 32]>,
+        limit: u64,
+    ) -> Result<Vec<([u8; 32], Vec<u8>)>, ContentStoreError> {
+        let range = match after {
+            Some(after) => (Bound::Excluded(after), Bound::Unbounded),
+            None => (Bound::Unbounded, Bound::Unbounded),
+        };
+        Ok(self
+            .store
+            .range(range)
+            .take(limit as usize)
+            .map(|(content_id, entry)| (*content_id, entry.content_key.clone()))
+            .collect())
+    }
+
+    /// Returns the content stored under each of `content_ids`, in the same order.
+    pub fn get_by_content_ids(
+        &self,
+        content_ids: &[[u8; 32]],
+    ) -> Result<Vec<Option<Vec<u8>>>, ContentStoreError> {
+        Ok(content_ids
+            .iter()
+            .map(|content_id| self.store.get(content_id).map(|entry| entry.value.clone()))
+            .collect())
+    }
+
+    /// Returns a summary of the radius and the usage of the store, in the format of the summary
+    /// of `PortalStorage`.
+    pub fn get_summary_info(&self) -> String {
+        let radius_percent = radius_ratio(self.radius) * 100.0;
+        format!(
+            "radius={:.*}% content={:.1}/{}mb #={} memory",
+            StorageMetrics::precision_for_percentage(radius_percent),
+            radius_percent,
+            self.content_size_total as f64 / BYTES_IN_MB_F64,
+            self.storage_capacity_in_bytes / BYTES_IN_MB_U64,
+            self.store.len(),
+        )
     }
 }
 
 impl ContentStore for MemoryContentStore {
     fn get<K: OverlayContentKey>(&self, key: &K) -> Result<Option<Vec<u8>>, ContentStoreError> {
-        let key = key.content_id();
-        let val = self.store.get(&key.to_vec()).cloned();
-        Ok(val)
+        Ok(self
+            .store
+            .get(&key.content_id())
+            .map(|entry| entry.value.clone()))
     }
 
     fn put<K: OverlayContentKey, V: AsRef<[u8]>>(
@@ -147,19 +607,29 @@ impl ContentStore for MemoryContentStore {
         key: K,
         value: V,
     ) -> Result<(), ContentStoreError> {
-        let content_id = key.content_id();
-        let value: &[u8] = value.as_ref();
-        self.store.insert(content_id.to_vec(), value.to_vec());
-
+        self.store_without_pruning(&key, value.as_ref())?;
+        self.prune();
         Ok(())
     }
 
+    fn put_batch<K: OverlayContentKey, V: AsRef<[u8]>>(
+        &mut self,
+        items: Vec<(K, V)>,
+    ) -> Result<Vec<Result<(), ContentStoreError>>, ContentStoreError> {
+        let results = items
+            .into_iter()
+            .map(|(key, value)| self.store_without_pruning(&key, value.as_ref()))
+            .collect();
+        self.prune();
+        Ok(results)
+    }
+
     fn is_key_within_radius_and_unavailable<K: OverlayContentKey>(
         &self,
         key: &K,
     ) -> Result<bool, ContentStoreError> {
         let distance = self.distance_to_key(key);
-        if distance > self.radius {
+        if distance > self.radius && !self.is_pinned(key) {
             return Ok(false);
         }
 
@@ -171,7 +641,8 @@ impl ContentStore for MemoryContentStore {
     }
 }
 
-/// Struct for configuring a `PortalStorage` instance.
+/// Struct for configuring a `PortalStorage` instance. The database handles may be shared by the
+/// storage of several subnetworks, which each keep their content in their own namespace.
 #[derive(Clone)]
 pub struct PortalStorageConfig {
     pub storage_capacity_mb: u64,
@@ -180,6 +651,9 @@ pub struct PortalStorageConfig {
     pub distance_fn: DistanceFunction,
     pub db: Arc<rocksdb::DB>,
     pub sql_connection_pool: Pool<SqliteConnectionManager>,
+    /// SSZ union selectors of the content types that are pinned, so that all content of these
+    /// types is stored and never evicted.
+    pub pinned_content_types: Vec<u8>,
 }
 
 impl PortalStorageConfig {
@@ -197,786 +671,2455 @@ impl PortalStorageConfig {
             distance_fn: DistanceFunction::Xor,
             db,
             sql_connection_pool,
+            pinned_content_types: vec![],
         })
     }
 }
 
-/// Struct whose public methods abstract away Kademlia-based store behavior.
-#[derive(Debug)]
-pub struct PortalStorage {
-    node_id: NodeId,
-    node_data_dir: PathBuf,
-    storage_capacity_in_bytes: u64,
-    radius: Distance,
-    db: Arc<rocksdb::DB>,
-    sql_connection_pool: Pool<SqliteConnectionManager>,
-    distance_fn: DistanceFunction,
-    metrics: StorageMetrics,
+/// Configures where the content of a subnetwork is kept: in RocksDB and SQLite in the node data
+/// directory, or in memory for ephemeral nodes.
+#[derive(Clone)]
+pub enum ContentStoreConfig {
+    Disk(PortalStorageConfig),
+    Memory(MemoryContentStoreConfig),
 }
 
-impl ContentStore for PortalStorage {
-    fn get<K: OverlayContentKey>(&self, key: &K) -> Result<Option<Vec<u8>>, ContentStoreError> {
-        let content_id = key.content_id();
-        Ok(self.db.get(content_id)?)
+impl ContentStoreConfig {
+    pub fn storage_capacity_mb(&self) -> u64 {
+        match self {
+            ContentStoreConfig::Disk(config) => config.storage_capacity_mb,
+            ContentStoreConfig::Memory(config) => config.storage_capacity_mb,
+        }
     }
 
-    fn put<K: OverlayContentKey, V: AsRef<[u8]>>(
-        &mut self,
-        key: K,
-        value: V,
-    ) -> Result<(), ContentStoreError> {
-        self.store(&key, &value.as_ref().to_vec())
+    /// Returns the configuration of the store of a single subnetwork, with its own capacity and
+    /// pinned content types.
+    pub fn for_subnetwork(&self, storage_capacity_mb: u64, pinned_content_types: Vec<u8>) -> Self {
+        match self {
+            ContentStoreConfig::Disk(config) => ContentStoreConfig::Disk(PortalStorageConfig {
+                storage_capacity_mb,
+                pinned_content_types,
+                ..config.clone()
+            }),
+            ContentStoreConfig::Memory(config) => {
+                ContentStoreConfig::Memory(MemoryContentStoreConfig {
+                    storage_capacity_mb,
+                    pinned_content_types,
+                    ..config.clone()
+                })
+            }
+        }
     }
+}
 
-    fn is_key_within_radius_and_unavailable<K: OverlayContentKey>(
-        &self,
-        key: &K,
-    ) -> Result<bool, ContentStoreError> {
-        let distance = self.distance_to_key(key);
-        if distance > self.radius {
-            return Ok(false);
-        }
+/// The content store of a subnetwork, as configured by a `ContentStoreConfig`.
+#[derive(Debug)]
+pub enum SubnetworkStore {
+    Disk(PortalStorage),
+    Memory(MemoryContentStore),
+}
 
-        let key = key.content_id();
-        let is_key_available = self.db.get_pinned(key)?.is_some();
-        Ok(!is_key_available)
-    }
+/// Handle to the task that samples the on-disk size of a store. The task is aborted when the
+/// handle is dropped.
+#[derive(Debug)]
+pub struct DiskUsageSampler(JoinHandle<()>);
 
-    fn radius(&self) -> Distance {
-        self.radius
+impl Drop for DiskUsageSampler {
+    fn drop(&mut self) {
+        self.0.abort();
     }
 }
 
-impl PortalStorage {
-    /// Public constructor for building a `PortalStorage` object.
-    /// Checks whether a populated database already exists vs a fresh instance.
+impl SubnetworkStore {
     pub fn new(
-        config: PortalStorageConfig,
+        config: ContentStoreConfig,
         protocol: ProtocolId,
     ) -> Result<Self, ContentStoreError> {
-        // Initialize the instance
-        let mut storage = Self {
-            node_id: config.node_id,
-            node_data_dir: config.node_data_dir,
-            storage_capacity_in_bytes: config.storage_capacity_mb * BYTES_IN_MB_U64,
-            radius: Distance::MAX,
-            db: config.db,
-            sql_connection_pool: config.sql_connection_pool,
-            distance_fn: config.distance_fn,
-            metrics: StorageMetrics::new(&protocol),
-        };
-
-        // Set the metrics to the default radius, to start
-        storage.metrics.report_radius(storage.radius);
-
-        // Check whether we already have data, and use it to set radius
-        match storage.total_entry_count()? {
-            0 => {
-                // Default radius is left in place, unless user selected 0mb capacity
-                if storage.storage_capacity_in_bytes == 0 {
-                    storage.set_radius(Distance::ZERO);
-                }
+        Ok(match config {
+            ContentStoreConfig::Disk(config) => {
+                SubnetworkStore::Disk(PortalStorage::new(config, protocol)?)
             }
-            // Only prunes data when at capacity. (eg. user changed it via mb flag)
-            entry_count => {
-                storage.metrics.report_entry_count(entry_count);
-
-                let _ = storage.prune_db()?;
+            ContentStoreConfig::Memory(config) => {
+                SubnetworkStore::Memory(MemoryContentStore::from_config(config, &protocol))
             }
-        }
-
-        // Report current storage capacity.
-        storage
-            .metrics
-            .report_storage_capacity_bytes(storage.storage_capacity_in_bytes as f64);
-
-        // Report current total storage usage.
-        let total_storage_usage = storage.get_total_storage_usage_in_bytes_on_disk()?;
-        storage
-            .metrics
-            .report_total_storage_usage_bytes(total_storage_usage as f64);
-
-        // Report total storage used by network content.
-        let network_content_storage_usage =
-            storage.get_total_storage_usage_in_bytes_from_network()?;
-        storage
-            .metrics
-            .report_content_data_storage_bytes(network_content_storage_usage as f64);
-
-        Ok(storage)
+        })
     }
 
-    /// Sets the radius of the store to `radius`.
-    pub fn set_radius(&mut self, radius: Distance) {
-        self.radius = radius;
-        self.metrics.report_radius(radius);
+    /// Spawns a task that samples the on-disk size of the databases, if the content is on disk.
+    pub fn spawn_disk_usage_sampler(&self) -> Option<DiskUsageSampler> {
+        match self {
+            SubnetworkStore::Disk(storage) => Some(storage.spawn_disk_usage_sampler()),
+            SubnetworkStore::Memory(_) => None,
+        }
     }
 
-    /// Returns a paginated list of all available content keys from local storage (from any
-    /// subnetwork) according to the provided offset and limit.
-    pub fn paginate(
+    /// Returns a paginated list of all available content keys, according to the provided offset
+    /// and limit, decoded as the content keys of the subnetwork.
+    pub fn paginate<K: OverlayContentKey>(
         &self,
         offset: &u64,
         limit: &u64,
-    ) -> Result<PaginateLocalContentInfo, ContentStoreError> {
-        let conn = self.sql_connection_pool.get()?;
-        let mut query = conn.prepare(PAGINATE_QUERY)?;
+    ) -> Result<PaginateLocalContentInfo<K>, ContentStoreError>
+    where
+        <K as TryFrom<Vec<u8>>>::Error: fmt::Display,
+    {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.paginate(offset, limit),
+            SubnetworkStore::Memory(store) => store.paginate(offset, limit),
+        }
+    }
 
-        let content_keys: Result<Vec<HistoryContentKey>, ContentStoreError> = query
-            .query_map(
-                &[
-                    (":offset", offset.to_string().as_str()),
-                    (":limit", limit.to_string().as_str()),
-                ],
-                |row| {
-                    let row: String = row.get(0)?;
-                    Ok(row)
-                },
-            )?
-            .map(|row| {
-                // value is stored without 0x prefix, so we must add it
-                let bytes: Vec<u8> = hex_decode(&format!("0x{}", row?))
-                    .map_err(ContentStoreError::ByteUtilsError)?;
-                HistoryContentKey::try_from(bytes).map_err(ContentStoreError::ContentKey)
-            })
-            .collect();
-        Ok(PaginateLocalContentInfo {
-            content_keys: content_keys?,
-            total_entries: self.total_entry_count()?,
-        })
+    /// Returns up to `limit` content items that match `filter`, in ascending order of content id,
+    /// starting after the content id `cursor`.
+    pub fn inventory(
+        &self,
+        filter: &LocalContentFilter,
+        cursor: Option<[u8; 32]>,
+        limit: u64,
+    ) -> Result<LocalContentInventory, ContentStoreError> {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.inventory(filter, cursor, limit),
+            SubnetworkStore::Memory(store) => store.inventory(filter, cursor, limit),
+        }
     }
 
-    fn total_entry_count(&self) -> Result<u64, ContentStoreError> {
-        let conn = self.sql_connection_pool.get()?;
-        let mut query = conn.prepare(TOTAL_ENTRY_COUNT_QUERY)?;
-        let result: Result<Vec<EntryCount>, rusqlite::Error> = query
-            .query_map([], |row| Ok(EntryCount(row.get(0)?)))?
-            .collect();
-        match result?.first() {
-            Some(val) => Ok(val.0),
-            None => Err(ContentStoreError::InvalidData {
-                message: "Invalid total entries count returned from sql query.".to_string(),
-            }),
+    /// Returns the number of content items that match `filter`, and their total size, for each
+    /// content type.
+    pub fn inventory_stats(
+        &self,
+        filter: &LocalContentFilter,
+    ) -> Result<LocalContentStats, ContentStoreError> {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.inventory_stats(filter),
+            SubnetworkStore::Memory(store) => store.inventory_stats(filter),
         }
     }
 
-    /// Returns the distance to `key` from the local `NodeId` according to the distance function.
-    fn distance_to_key<K: OverlayContentKey>(&self, key: &K) -> Distance {
-        match self.distance_fn {
-            DistanceFunction::Xor => XorMetric::distance(&key.content_id(), &self.node_id.raw()),
+    /// Pins the content denoted by `key`. Returns false if the content was already pinned.
+    pub fn pin(&mut self, key: &impl OverlayContentKey) -> Result<bool, ContentStoreError> {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.pin(key),
+            SubnetworkStore::Memory(store) => store.pin(key),
         }
     }
 
-    /// Method for storing a given value for a given content-key.
-    fn store(
-        &mut self,
-        key: &impl OverlayContentKey,
-        value: &Vec<u8>,
-    ) -> Result<(), ContentStoreError> {
-        let content_id = key.content_id();
-        let distance_to_content_id = self.distance_to_content_id(&content_id);
-
-        if distance_to_content_id > self.radius {
-            // Return Err if content is outside radius
-            debug!("Not storing: {:02X?}", key.clone().into());
-            return Err(ContentStoreError::InsufficientRadius {
-                radius: self.radius,
-                distance: distance_to_content_id,
-            });
+    /// Unpins the content denoted by `key`. Returns false if the content was not pinned.
+    pub fn unpin(&mut self, key: &impl OverlayContentKey) -> Result<bool, ContentStoreError> {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.unpin(key),
+            SubnetworkStore::Memory(store) => store.unpin(key),
         }
+    }
 
-        // Store the data in radius db
-        self.db_insert(&content_id, value)?;
-        let content_key: Vec<u8> = key.clone().into();
-        // store content key w/o the 0x prefix
-        let content_key = hex_encode(content_key).trim_start_matches("0x").to_string();
-        // Revert rocks db action if there's an error with writing to metadata db
-        if let Err(err) = self.meta_db_insert(&content_id, &content_key, value) {
-            debug!(
-                "Error writing content ID {:?} to meta db. Reverting: {:?}",
-                content_id, err
-            );
-            self.db.delete(content_id)?;
-            return Err(err);
-        } else {
-            self.metrics.increase_entry_count();
+    /// Returns the content keys of all pinned content, whether it is stored or not.
+    pub fn pinned_content_keys(&self) -> Result<Vec<Vec<u8>>, ContentStoreError> {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.pinned_content_keys(),
+            SubnetworkStore::Memory(store) => store.pinned_content_keys(),
         }
-        self.prune_db()?;
-        let total_bytes_on_disk = self.get_total_storage_usage_in_bytes_on_disk()?;
-        self.metrics
-            .report_total_storage_usage_bytes(total_bytes_on_disk as f64);
-
-        Ok(())
     }
 
-    /// Internal method for pruning any data that falls outside of the radius of the store.
-    /// Resets the data radius if it prunes any data. Does nothing if the store is empty.
-    /// Returns the number of items removed during pruning
-    fn prune_db(&mut self) -> Result<usize, ContentStoreError> {
-        let mut farthest_content_id: Option<[u8; 32]> = self.find_farthest_content_id()?;
-        let mut num_removed_items = 0;
-        // Delete furthest data until our data usage is less than capacity.
-        while self.capacity_reached()? {
-            // If the database were empty, then `capacity_reached()` would be false, because the
-            // amount of content (zero) would not be greater than capacity.
-            let id_to_remove =
-                farthest_content_id.expect("Capacity reached, but no farthest id found!");
-            // Test if removing the item would put us under capacity
-            if self.does_eviction_cause_under_capacity(&id_to_remove)? {
-                // If so, we're done pruning
-                debug!(
-                    "Removing item would drop us below capacity. We target slight overfilling. {}",
-                    hex_encode(id_to_remove)
-                );
-                self.set_radius(self.distance_to_content_id(&id_to_remove));
-                break;
-            }
-            debug!(
-                "Capacity reached, deleting farthest: {}",
-                hex_encode(id_to_remove)
-            );
-            if let Err(err) = self.evict(id_to_remove) {
-                debug!("Error writing content ID {id_to_remove:?} to meta db. Reverted: {err:?}",);
-            } else {
-                num_removed_items += 1;
-            }
-            // Calculate new farthest_content_id and reset radius
-            match self.find_farthest_content_id()? {
-                None => {
-                    // We get here if the entire db has been pruned,
-                    // eg. user selected 0mb capacity for storage
-                    self.set_radius(Distance::ZERO);
-                }
-                Some(farthest) => {
-                    debug!("Found new farthest: {}", hex_encode(farthest));
-                    self.set_radius(self.distance_to_content_id(&farthest));
-                    farthest_content_id = Some(farthest);
-                }
-            }
+    /// Evicts the content with id `id`.
+    pub fn evict(&mut self, id: [u8; 32]) -> anyhow::Result<()> {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.evict(id),
+            SubnetworkStore::Memory(store) => store.evict(id),
         }
-        Ok(num_removed_items)
     }
 
-    /// Internal method for testing if an eviction would cause the store to fall under capacity.
-    /// Returns true if the store would fall under capacity, false otherwise.
-    /// Raises an error if there is a problem accessing the database.
-    fn does_eviction_cause_under_capacity(&self, id: &[u8; 32]) -> Result<bool, ContentStoreError> {
-        let total_bytes_on_disk = self.get_total_storage_usage_in_bytes_from_network()?;
-        // Get the size of the content we're about to remove
-        let bytes_to_remove = self.get_content_size(id)?;
-        Ok(total_bytes_on_disk - bytes_to_remove < self.storage_capacity_in_bytes)
+    /// Returns up to `limit` pairs of content id and content key, in ascending order of content id,
+    /// starting after the content id `after`.
+    pub fn content_keys_after(
+        &self,
+        after: Option<[u8; 32]>,
+        limit: u64,
+    ) -> Result<Vec<([u8; 32], Vec<u8>)>, ContentStoreError> {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.content_keys_after(after, limit),
+            SubnetworkStore::Memory(store) => store.content_keys_after(after, limit),
+        }
     }
 
-    /// Internal method for getting the size of a content item in bytes.
-    /// Returns the size of the content item in bytes.
-    /// Raises an error if there is a problem accessing the database.
-    fn get_content_size(&self, id: &[u8; 32]) -> Result<u64, ContentStoreError> {
-        let conn = self.sql_connection_pool.get()?;
-        let mut query = conn.prepare(CONTENT_SIZE_LOOKUP_QUERY)?;
-        let id_vec = id.to_vec();
-        let result = query.query_map([id_vec], |row| {
-            Ok(DataSize {
-                num_bytes: row.get(0)?,
-            })
-        });
-        let byte_size = match result?.next() {
-            Some(data_size) => data_size,
-            None => {
-                // Build error message with hex encoded content id
-                let err = format!("Unable to determine size of item {}", hex_encode(id));
-                return Err(ContentStoreError::Database(err));
+    /// Records that the integrity scrubber checked the content with id `content_id`.
+    pub fn report_scrubbed_content(&self, content_id: &[u8; 32], valid: bool) {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.report_scrubbed_content(content_id, valid),
+            SubnetworkStore::Memory(store) => {
+                store.metrics.report_scrubbed_content(content_id, valid)
             }
-        }?
-        .num_bytes;
-
-        Ok(byte_size as u64)
+        }
     }
 
-    /// Public method for evicting a certain content id. Will revert RocksDB deletion if meta_db
-    /// deletion fails.
-    pub fn evict(&self, id: [u8; 32]) -> anyhow::Result<()> {
-        let deleted_value = self.db.get(id)?;
-        self.db.delete(id)?;
-        // Revert rocksdb action if there's an error with writing to metadata db
-        if let Err(err) = self.meta_db_remove(&id) {
-            if let Some(value) = deleted_value {
-                self.db_insert(&id, &value)?;
-            }
-            return Err(anyhow!("failed deletion {err}"));
+    /// Records that the integrity scrubber checked all stored content.
+    pub fn report_scrub_pass_completed(&self) {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.report_scrub_pass_completed(),
+            SubnetworkStore::Memory(store) => store.metrics.report_scrub_pass_completed(),
         }
-        self.metrics.decrease_entry_count();
-        Ok(())
     }
 
-    /// Public method for looking up a content key by its content id
-    pub fn lookup_content_key(&self, id: [u8; 32]) -> anyhow::Result<Option<Vec<u8>>> {
-        let conn = self.sql_connection_pool.get()?;
-        let mut query = conn.prepare(CONTENT_KEY_LOOKUP_QUERY)?;
-        let id = id.to_vec();
-        let result: Result<Vec<HistoryContentKey>, ContentStoreError> = query
-            .query_map([id], |row| {
-                let row: String = row.get(0)?;
-                Ok(row)
-            })?
-            .map(|row| {
-                // value is stored without 0x prefix, so we must add it
-                let bytes: Vec<u8> = hex_decode(&format!("0x{}", row?))?;
-                HistoryContentKey::try_from(bytes).map_err(ContentStoreError::ContentKey)
-            })
-            .collect();
+    pub fn get_summary_info(&self) -> String {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.get_summary_info(),
+            SubnetworkStore::Memory(store) => store.get_summary_info(),
+        }
+    }
+}
 
-        match result?.first() {
-            Some(val) => Ok(Some(val.into())),
-            None => Ok(None),
+impl ContentStore for SubnetworkStore {
+    fn get<K: OverlayContentKey>(&self, key: &K) -> Result<Option<Vec<u8>>, ContentStoreError> {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.get(key),
+            SubnetworkStore::Memory(store) => store.get(key),
         }
     }
 
-    /// Public method for retrieving the node's current radius.
-    pub fn radius(&self) -> Distance {
-        self.radius
+    fn get_many<K: OverlayContentKey>(
+        &self,
+        keys: &[K],
+    ) -> Result<Vec<Option<Vec<u8>>>, ContentStoreError> {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.get_many(keys),
+            SubnetworkStore::Memory(store) => store.get_many(keys),
+        }
     }
 
-    /// Public method for determining how much actual disk space is being used to store this node's Portal Network data.
-    /// Intended for analysis purposes. PortalStorage's capacity decision-making is not based off of this method.
-    pub fn get_total_storage_usage_in_bytes_on_disk(&self) -> Result<u64, ContentStoreError> {
-        let storage_usage = Self::get_total_size_of_directory_in_bytes(&self.node_data_dir)?;
-        Ok(storage_usage)
+    fn put<K: OverlayContentKey, V: AsRef<[u8]>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<(), ContentStoreError> {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.put(key, value),
+            SubnetworkStore::Memory(store) => store.put(key, value),
+        }
     }
 
-    /// Internal method for inserting data into the db.
-    fn db_insert(&self, content_id: &[u8; 32], value: &Vec<u8>) -> Result<(), ContentStoreError> {
-        self.db.put(content_id, value)?;
-        Ok(())
+    fn put_batch<K: OverlayContentKey, V: AsRef<[u8]>>(
+        &mut self,
+        items: Vec<(K, V)>,
+    ) -> Result<Vec<Result<(), ContentStoreError>>, ContentStoreError> {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.put_batch(items),
+            SubnetworkStore::Memory(store) => store.put_batch(items),
+        }
     }
 
-    /// Internal method for inserting data into the meta db.
-    fn meta_db_insert(
+    fn is_key_within_radius_and_unavailable<K: OverlayContentKey>(
         &self,
-        content_id: &[u8; 32],
-        content_key: &String,
-        value: &Vec<u8>,
-    ) -> Result<(), ContentStoreError> {
-        let content_id_as_u32: u32 = Self::byte_vector_to_u32(content_id.to_vec());
-        let value_size = value.len();
-        if content_key.starts_with("0x") {
-            return Err(ContentStoreError::InvalidData {
-                message: "Content key should not start with 0x".to_string(),
-            });
-        }
-        match self.sql_connection_pool.get()?.execute(
-            INSERT_QUERY,
-            params![
-                content_id.to_vec(),
-                content_id_as_u32,
-                content_key,
-                value_size
-            ],
-        ) {
-            Ok(_) => Ok(()),
-            Err(err) => Err(err.into()),
+        key: &K,
+    ) -> Result<bool, ContentStoreError> {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.is_key_within_radius_and_unavailable(key),
+            SubnetworkStore::Memory(store) => store.is_key_within_radius_and_unavailable(key),
         }
     }
 
-    /// Internal method for removing a given content-id from the meta db.
-    fn meta_db_remove(&self, content_id: &[u8; 32]) -> Result<(), ContentStoreError> {
-        self.sql_connection_pool
-            .get()?
-            .execute(DELETE_QUERY, [content_id.to_vec()])?;
-        Ok(())
+    fn radius(&self) -> Distance {
+        match self {
+            SubnetworkStore::Disk(storage) => storage.radius(),
+            SubnetworkStore::Memory(store) => store.radius(),
+        }
     }
+}
 
-    /// Internal method for determining whether the node is over-capacity.
-    fn capacity_reached(&self) -> Result<bool, ContentStoreError> {
-        let storage_usage = self.get_total_storage_usage_in_bytes_from_network()?;
-        Ok(storage_usage > self.storage_capacity_in_bytes)
+/// Struct whose public methods abstract away Kademlia-based store behavior.
+#[derive(Debug)]
+pub struct PortalStorage {
+    node_id: NodeId,
+    /// The subnetwork whose content is stored.
+    protocol: ProtocolId,
+    storage_capacity_in_bytes: u64,
+    radius: Distance,
+    db: Arc<rocksdb::DB>,
+    /// The RocksDB column family that holds the content of the subnetwork.
+    column_family: &'static str,
+    sql_connection_pool: Pool<SqliteConnectionManager>,
+    /// SQLite statements on the metadata table of the subnetwork.
+    queries: MetadataQueries,
+    /// Sum of the sizes of all stored content that is not pinned, in bytes.
+    content_size_total: u64,
+    /// SSZ union selectors of the content types that are pinned.
+    pinned_content_types: Vec<u8>,
+    distance_fn: DistanceFunction,
+    metrics: StorageMetrics,
+    /// Makes the next write or eviction of content stop between RocksDB and SQLite, as if the
+    /// node crashed.
+    #[cfg(test)]
+    interrupt_next_write: std::sync::atomic::AtomicBool,
+}
+
+impl ContentStore for PortalStorage {
+    fn get<K: OverlayContentKey>(&self, key: &K) -> Result<Option<Vec<u8>>, ContentStoreError> {
+        let content_id = key.content_id();
+        Ok(self.db.get_cf(self.column_family()?, content_id)?)
     }
 
-    /// Internal method for measuring the total amount of requestable data that the node is storing.
-    fn get_total_storage_usage_in_bytes_from_network(&self) -> Result<u64, ContentStoreError> {
-        let conn = self.sql_connection_pool.get()?;
-        let mut query = conn.prepare(TOTAL_DATA_SIZE_QUERY)?;
+    fn get_many<K: OverlayContentKey>(
+        &self,
+        keys: &[K],
+    ) -> Result<Vec<Option<Vec<u8>>>, ContentStoreError> {
+        let content_ids: Vec<[u8; 32]> = keys.iter().map(|key| key.content_id()).collect();
+        self.get_by_content_ids(&content_ids)
+    }
 
-        let result = query.query_map([], |row| {
-            Ok(DataSize {
-                num_bytes: row.get(0)?,
-            })
-        });
+    fn put<K: OverlayContentKey, V: AsRef<[u8]>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<(), ContentStoreError> {
+        self.store(&key, &value.as_ref().to_vec())
+    }
 
-        let sum = match result?.next() {
-            Some(total) => total,
-            None => {
-                let err = "Unable to compute sum over content item sizes".to_string();
-                return Err(ContentStoreError::Database(err));
-            }
-        }?
-        .num_bytes;
+    fn put_batch<K: OverlayContentKey, V: AsRef<[u8]>>(
+        &mut self,
+        items: Vec<(K, V)>,
+    ) -> Result<Vec<Result<(), ContentStoreError>>, ContentStoreError> {
+        let results = self.store_batch(items)?;
+        self.prune_db()?;
+        Ok(results)
+    }
 
-        self.metrics.report_content_data_storage_bytes(sum);
+    fn is_key_within_radius_and_unavailable<K: OverlayContentKey>(
+        &self,
+        key: &K,
+    ) -> Result<bool, ContentStoreError> {
+        let distance = self.distance_to_key(key);
+        if distance > self.radius && !self.is_pinned(key)? {
+            return Ok(false);
+        }
 
-        Ok(sum as u64)
+        let key = key.content_id();
+        let is_key_available = self.db.get_pinned_cf(self.column_family()?, key)?.is_some();
+        Ok(!is_key_available)
     }
 
-    /// Internal method for finding the piece of stored data that has the farthest content id from our
-    /// node id, according to xor distance. Used to determine which data to drop when at a capacity.
-    fn find_farthest_content_id(&self) -> Result<Option<[u8; 32]>, ContentStoreError> {
-        let result = match self.distance_fn {
-            DistanceFunction::Xor => {
-                let node_id_u32 = Self::byte_vector_to_u32(self.node_id.raw().to_vec());
-
-                let conn = self.sql_connection_pool.get()?;
-                let mut query = conn.prepare(XOR_FIND_FARTHEST_QUERY)?;
+    fn radius(&self) -> Distance {
+        self.radius
+    }
+}
 
-                let mut result = query.query_map([node_id_u32], |row| {
-                    Ok(ContentId {
-                        id_long: row.get(0)?,
-                    })
-                })?;
+impl PortalStorage {
+    /// Public constructor for building a `PortalStorage` object.
+    /// Checks whether a populated database already exists vs a fresh instance.
+    pub fn new(
+        config: PortalStorageConfig,
+        protocol: ProtocolId,
+    ) -> Result<Self, ContentStoreError> {
+        let queries = MetadataQueries::new(
+            Self::metadata_table_name(&protocol),
+            &config.pinned_content_types,
+        );
+        Self::setup_metadata_table(&config, &queries)?;
 
-                let result = match result.next() {
-                    Some(row) => row,
-                    None => {
-                        return Ok(None);
-                    }
-                };
-                let result = result?.id_long;
-                let result_vec: [u8; 32] = match result.len() {
-                    // If exact data size, safe to expect conversion.
-                    32 => result.try_into().expect(
-                        "Unexpectedly failed to convert 32 element vec to 32 element array.",
-                    ),
-                    // Received data of size other than 32 bytes.
-                    length => {
-                        let err = format!("content ID of length {} != 32", length);
-                        return Err(ContentStoreError::InvalidData { message: err });
-                    }
-                };
-                result_vec
-            }
+        // Initialize the instance
+        let mut storage = Self {
+            node_id: config.node_id,
+            protocol: protocol.clone(),
+            storage_capacity_in_bytes: config.storage_capacity_mb * BYTES_IN_MB_U64,
+            radius: Distance::MAX,
+            db: config.db,
+            column_family: Self::column_family_name(&protocol),
+            sql_connection_pool: config.sql_connection_pool,
+            queries,
+            content_size_total: 0,
+            pinned_content_types: config.pinned_content_types,
+            distance_fn: config.distance_fn,
+            metrics: StorageMetrics::new(&protocol),
+            #[cfg(test)]
+            interrupt_next_write: Default::default(),
         };
+        storage.recover_interrupted_writes()?;
+        storage.reconcile_databases()?;
+        if protocol == ProtocolId::History {
+            storage.evict_foreign_content()?;
+        }
+        storage.content_size_total = storage.query_total_content_size()?;
 
-        Ok(Some(result))
-    }
+        // Set the metrics to the default radius, to start
+        storage.metrics.report_radius(storage.radius);
 
-    /// Internal method used to measure on-disk storage usage.
-    fn get_total_size_of_directory_in_bytes(
-        path: impl AsRef<Path>,
-    ) -> Result<u64, ContentStoreError> {
-        let metadata = match fs::metadata(&path) {
-            Ok(metadata) => metadata,
-            Err(_) => {
-                return Ok(0);
+        // Check whether we already have data, and use it to set radius
+        match storage.total_entry_count()? {
+            0 => {
+                // Default radius is left in place, unless user selected 0mb capacity
+                if storage.storage_capacity_in_bytes == 0 {
+                    storage.set_radius(Distance::ZERO);
+                }
             }
-        };
-        let mut size = metadata.len();
-
-        if metadata.is_dir() {
-            for entry in fs::read_dir(&path)? {
-                let dir = entry?;
-                let path_string = match dir.path().into_os_string().into_string() {
-                    Ok(path_string) => path_string,
-                    Err(err) => {
-                        let err = format!(
-                            "Unable to convert path {:?} into string {:?}",
-                            path.as_ref(),
-                            err
-                        );
-                        return Err(ContentStoreError::Database(err));
-                    }
-                };
-                size += Self::get_total_size_of_directory_in_bytes(path_string)?;
+            // Only prunes data when at capacity. (eg. user changed it via mb flag)
+            entry_count => {
+                storage.metrics.report_entry_count(entry_count);
+
+                let _ = storage.prune_db()?;
             }
         }
 
-        Ok(size)
-    }
+        // Report current storage capacity.
+        storage
+            .metrics
+            .report_storage_capacity_bytes(storage.storage_capacity_in_bytes as f64);
 
-    /// Method that returns the distance between our node ID and a given content ID.
-    pub fn distance_to_content_id(&self, content_id: &[u8; 32]) -> Distance {
-        match self.distance_fn {
-            DistanceFunction::Xor => XorMetric::distance(content_id, &self.node_id.raw()),
-        }
-    }
+        // Report current total storage usage.
+        let total_storage_usage = storage.get_total_storage_usage_in_bytes_on_disk()?;
+        storage
+            .metrics
+            .report_total_storage_usage_bytes(total_storage_usage as f64);
 
-    /// Converts most significant 4 bytes of a vector to a u32.
-    fn byte_vector_to_u32(vec: Vec<u8>) -> u32 {
-        if vec.len() < 4 {
-            debug!("Error: XOR returned less than 4 bytes.");
-            return 0;
-        }
+        // Report total storage used by network content.
+        let network_content_storage_usage = storage.get_total_storage_usage_in_bytes_from_network();
+        storage
+            .metrics
+            .report_content_data_storage_bytes(network_content_storage_usage as f64);
 
-        let mut array: [u8; 4] = [0, 0, 0, 0];
-        for (index, byte) in vec.iter().take(4).enumerate() {
-            array[index] = *byte;
+        Ok(storage)
+    }
+
+    /// Creates the metadata table of the subnetwork, if it does not exist yet.
+    ///
+    /// Tables created before the distance to the local node was precomputed get the distance
+    /// column added and filled in. The data directory belongs to a single node id, so the
+    /// distance never changes afterwards.
+    fn setup_metadata_table(
+        config: &PortalStorageConfig,
+        queries: &MetadataQueries,
+    ) -> Result<(), ContentStoreError> {
+        let conn = config.sql_connection_pool.get()?;
+        conn.execute(&queries.create_table, params![])?;
+        let has_distance_column: i64 =
+            conn.query_row(&queries.count_distance_column, [], |row| row.get(0))?;
+        if has_distance_column == 0 {
+            info!("Adding precomputed distances to the content metadata table");
+            let node_id_u32 = Self::byte_vector_to_u32(config.node_id.raw().to_vec());
+            conn.execute(&queries.add_distance_column, params![])?;
+            conn.execute(&queries.fill_distance_column, [node_id_u32])?;
         }
+        conn.execute(&queries.create_distance_index, params![])?;
+        conn.execute(&queries.create_pins_table, params![])?;
+        Ok(())
+    }
 
-        u32::from_be_bytes(array)
+    /// Sets the radius of the store to `radius`.
+    pub fn set_radius(&mut self, radius: Distance) {
+        self.radius = radius;
+        self.metrics.report_radius(radius);
     }
 
-    /// Helper function for opening a RocksDB connection for the radius-constrained db.
-    pub fn setup_rocksdb(node_data_dir: &Path) -> Result<rocksdb::DB, ContentStoreError> {
-        let rocksdb_path = node_data_dir.join("rocksdb");
-        info!(path = %rocksdb_path.display(), "Setting up RocksDB");
+    /// Returns a paginated list of all available content keys from the local storage of the
+    /// subnetwork, according to the provided offset and limit.
+    pub fn paginate<K: OverlayContentKey>(
+        &self,
+        offset: &u64,
+        limit: &u64,
+    ) -> Result<PaginateLocalContentInfo<K>, ContentStoreError>
+    where
+        <K as TryFrom<Vec<u8>>>::Error: fmt::Display,
+    {
+        let conn = self.sql_connection_pool.get()?;
+        let mut query = conn.prepare(&self.queries.paginate)?;
 
-        let mut db_opts = Options::default();
-        db_opts.create_if_missing(true);
-        Ok(DB::open(&db_opts, rocksdb_path)?)
+        let content_keys: Result<Vec<K>, ContentStoreError> = query
+            .query_map(
+                &[
+                    (":offset", offset.to_string().as_str()),
+                    (":limit", limit.to_string().as_str()),
+                ],
+                |row| {
+                    let row: String = row.get(0)?;
+                    Ok(row)
+                },
+            )?
+            .map(|row| {
+                // value is stored without 0x prefix, so we must add it
+                let bytes: Vec<u8> = hex_decode(&format!("0x{}", row?))
+                    .map_err(ContentStoreError::ByteUtilsError)?;
+                decode_content_key(bytes)
+            })
+            .collect();
+        Ok(PaginateLocalContentInfo {
+            content_keys: content_keys?,
+            total_entries: self.total_entry_count()?,
+        })
     }
 
-    /// Helper function for opening a RocksDB connection for the trie db.
-    pub fn setup_triedb(node_data_dir: &Path) -> Result<rocksdb::DB, ContentStoreError> {
-        let trie_db_path = node_data_dir.join("triedb");
-        info!(path = %trie_db_path.display(), "Setting up triedb");
+    /// Returns up to `limit` content items of the subnetwork that match `filter`, in ascending
+    /// order of content id, starting after the content id `cursor`, or from the lowest content id
+    /// if `cursor` is `None`.
+    pub fn inventory(
+        &self,
+        filter: &LocalContentFilter,
+        cursor: Option<[u8; 32]>,
+        limit: u64,
+    ) -> Result<LocalContentInventory, ContentStoreError> {
+        if limit == 0 {
+            return Err(ContentStoreError::InvalidData {
+                message: "Inventory limit must be positive".to_string(),
+            });
+        }
+        let limit = limit.min(MAX_INVENTORY_PAGE_SIZE);
+        let distance_range = DistanceRange::new(filter);
+        let mut clause = FilterClause::new(filter)?;
+        if distance_range.is_bounded() {
+            clause.push(
+                "distance_short BETWEEN ? AND ?",
+                [distance_range.min_short(), distance_range.max_short()],
+            );
+        }
 
-        let mut db_opts = Options::default();
-        db_opts.create_if_missing(true);
-        Ok(DB::open(&db_opts, trie_db_path)?)
+        let conn = self.sql_connection_pool.get()?;
+        let mut query = conn.prepare(&format!(
+            "{} WHERE {} AND content_id_long > ? ORDER BY content_id_long LIMIT ?",
+            self.queries.inventory,
+            clause.sql()
+        ))?;
+        let mut entries = vec![];
+        // Every content id compares greater than an empty blob.
+        let mut after = cursor.map(|id| id.to_vec()).unwrap_or_default();
+        let mut exhausted = false;
+        // Distances are only filtered on their most significant bytes in SQL, so content at the
+        // bounds of the distance range may not match, and filling a page may take several queries.
+        while !exhausted && (entries.len() as u64) < limit {
+            let remaining = limit - entries.len() as u64;
+            let mut params = clause.params.clone();
+            params.push(Value::Blob(after.clone()));
+            params.push(Value::Integer(remaining as i64));
+            let rows: Result<Vec<(Vec<u8>, String, i64)>, rusqlite::Error> = query
+                .query_map(params_from_iter(params), |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })?
+                .collect();
+            let rows = rows?;
+            exhausted = (rows.len() as u64) < remaining;
+
+            for (id, content_key, size) in rows {
+                let id: [u8; 32] =
+                    id.try_into()
+                        .map_err(|id: Vec<u8>| ContentStoreError::InvalidData {
+                            message: format!("content ID of length {} != 32", id.len()),
+                        })?;
+                after = id.to_vec();
+                let distance = self.distance_to_content_id(&id);
+                if !distance_range.contains(&distance) {
+                    continue;
+                }
+                entries.push(LocalContentEntry {
+                    // value is stored without 0x prefix, so we must add it
+                    content_key: format!("0x{content_key}"),
+                    content_id: H256::from(id),
+                    content_size: size as u64,
+                    distance: *distance,
+                });
+            }
+        }
+
+        let next_cursor = match exhausted {
+            true => None,
+            false => Some(H256::from_slice(&after)),
+        };
+        Ok(LocalContentInventory {
+            entries,
+            next_cursor,
+        })
     }
 
-    /// Helper function for opening a SQLite connection.
-    pub fn setup_sql(
-        node_data_dir: &Path,
-    ) -> Result<Pool<SqliteConnectionManager>, ContentStoreError> {
-        let sql_path = node_data_dir.join("trin.sqlite");
-        info!(path = %sql_path.display(), "Setting up SqliteDB");
+    /// Returns the number of content items of the subnetwork that match `filter`, and their total
+    /// size, for each content type.
+    pub fn inventory_stats(
+        &self,
+        filter: &LocalContentFilter,
+    ) -> Result<LocalContentStats, ContentStoreError> {
+        let distance_range = DistanceRange::new(filter);
+        let mut clause = FilterClause::new(filter)?;
+        let conn = self.sql_connection_pool.get()?;
+        let mut stats = LocalContentStats::default();
+
+        if distance_range.is_bounded() {
+            // Content at the bounds of the distance range is checked against its full distance,
+            // and the content in between is aggregated in SQL.
+            let mut bounds_clause = clause.clone();
+            bounds_clause.push(
+                "distance_short IN (?, ?)",
+                [distance_range.min_short(), distance_range.max_short()],
+            );
+            let mut query = conn.prepare(&format!(
+                "{} WHERE {}",
+                self.queries.inventory,
+                bounds_clause.sql()
+            ))?;
+            let rows: Result<Vec<(Vec<u8>, String, i64)>, rusqlite::Error> = query
+                .query_map(params_from_iter(bounds_clause.params), |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })?
+                .collect();
+            for (id, content_key, size) in rows? {
+                let id: [u8; 32] =
+                    id.try_into()
+                        .map_err(|id: Vec<u8>| ContentStoreError::InvalidData {
+                            message: format!("content ID of length {} != 32", id.len()),
+                        })?;
+                if distance_range.contains(&self.distance_to_content_id(&id)) {
+                    stats.add(parse_content_type(&content_key)?, 1, size as u64);
+                }
+            }
 
-        let manager = SqliteConnectionManager::file(sql_path);
-        let pool = Pool::new(manager)?;
-        pool.get()?.execute(CREATE_QUERY, params![])?;
-        Ok(pool)
-    }
+            clause.push(
+                "distance_short > ? AND distance_short < ?",
+                [distance_range.min_short(), distance_range.max_short()],
+            );
+        }
 
-    /// Get a summary of the current state of storage
-    pub fn get_summary_info(&self) -> String {
-        self.metrics.get_summary()
+        let mut query = conn.prepare(&format!(
+            "{} WHERE {} GROUP BY 1",
+            self.queries.inventory_stats,
+            clause.sql()
+        ))?;
+        let rows: Result<Vec<(String, i64, f64)>, rusqlite::Error> = query
+            .query_map(params_from_iter(clause.params), |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect();
+        for (content_type, count, bytes) in rows? {
+            stats.add(
+                parse_content_type(&content_type)?,
+                count as u64,
+                bytes as u64,
+            );
+        }
+        Ok(stats)
     }
-}
 
-#[derive(Debug)]
-struct StorageMetrics {
-    content_storage_usage_bytes: Gauge,
-    total_storage_usage_bytes: Gauge,
-    storage_capacity_bytes: Gauge,
-    radius_ratio: Gauge,
-    entry_count: IntGauge,
-}
+    fn total_entry_count(&self) -> Result<u64, ContentStoreError> {
+        let conn = self.sql_connection_pool.get()?;
+        let mut query = conn.prepare(&self.queries.total_entry_count)?;
+        let result: Result<Vec<EntryCount>, rusqlite::Error> = query
+            .query_map([], |row| Ok(EntryCount(row.get(0)?)))?
+            .collect();
+        match result?.first() {
+            Some(val) => Ok(val.0),
+            None => Err(ContentStoreError::InvalidData {
+                message: "Invalid total entries count returned from sql query.".to_string(),
+            }),
+        }
+    }
 
-impl StorageMetrics {
-    pub fn new(protocol: &ProtocolId) -> Self {
-        let content_storage_usage_bytes_options = opts!(
-            format!("trin_content_storage_usage_bytes_{protocol:?}"),
-            "sum of size of individual content stored, in bytes"
-        );
-        // Keep a reference to the registry. Make mutable, because we may have to switch to a new
-        // one if the default registry is already using a metric of the same name (which should
-        // only happen during tests).
-        let mut registry = default_registry();
+    /// Returns the distance to `key` from the local `NodeId` according to the distance function.
+    fn distance_to_key<K: OverlayContentKey>(&self, key: &K) -> Distance {
+        match self.distance_fn {
+            DistanceFunction::Xor => XorMetric::distance(&key.content_id(), &self.node_id.raw()),
+        }
+    }
 
-        // Always create a custom registry, so that we can use it in the error case
-        // It's just an easy way to throw away duplicate metrics.
-        let custom_registry = Registry::default();
+    /// Method for storing a given value for a given content-key.
+    fn store(
+        &mut self,
+        key: &impl OverlayContentKey,
+        value: &Vec<u8>,
+    ) -> Result<(), ContentStoreError> {
+        self.store_without_pruning(key, value)?;
+        self.prune_db()?;
+        Ok(())
+    }
 
-        let content_storage_usage_bytes =
-            match register_gauge!(content_storage_usage_bytes_options.clone()) {
-                Ok(gauge) => gauge,
-                Err(_) => {
-                    error!(
-                        "Failed to register prometheus gauge with default registry, creating new"
-                    );
+    /// Stores a given value for a given content-key, without making room for it if the store is
+    /// over capacity afterwards.
+    fn store_without_pruning(
+        &mut self,
+        key: &impl OverlayContentKey,
+        value: &Vec<u8>,
+    ) -> Result<(), ContentStoreError> {
+        self.store_batch(vec![(key.clone(), value)])?
+            .pop()
+            .unwrap_or(Ok(()))
+    }
+
+    /// Stores the given values for the given content-keys, without making room for them if the
+    /// store is over capacity afterwards. Returns the outcome of storing each piece of content, in
+    /// order.
+    ///
+    /// The values are written to RocksDB in a single write, along with journal entries that are
+    /// only cleared once all metadata is written too, in a single SQLite transaction.
+    fn store_batch<K: OverlayContentKey, V: AsRef<[u8]>>(
+        &mut self,
+        items: Vec<(K, V)>,
+    ) -> Result<Vec<Result<(), ContentStoreError>>, ContentStoreError> {
+        let mut results = Vec::with_capacity(items.len());
+        // The index of the result, content id, content key, size and pin state of every piece of
+        // content within the radius.
+        let mut accepted = vec![];
+        let column_family = self.column_family()?;
+        let journal = self.journal()?;
+        let mut batch = WriteBatch::default();
+        for (key, value) in items {
+            let content_id = key.content_id();
+            let distance_to_content_id = self.distance_to_content_id(&content_id);
+            let is_pinned = self.is_pinned(&key)?;
+
+            if distance_to_content_id > self.radius && !is_pinned {
+                // Return Err if content is outside radius
+                debug!("Not storing: {:02X?}", key.into());
+                results.push(Err(ContentStoreError::InsufficientRadius {
+                    radius: self.radius,
+                    distance: distance_to_content_id,
+                }));
+                continue;
+            }
 
-                    // Assign the new registry to the outer variable, so it can be used by all metrics
-                    registry = &custom_registry;
+            batch.put_cf(journal, self.journal_key(&content_id), []);
+            batch.put_cf(column_family, content_id, value.as_ref());
+            let content_key: Vec<u8> = key.into();
+            // store content key w/o the 0x prefix
+            let content_key = hex_encode(content_key).trim_start_matches("0x").to_string();
+            accepted.push((
+                results.len(),
+                content_id,
+                content_key,
+                value.as_ref().len(),
+                is_pinned,
+            ));
+            results.push(Ok(()));
+        }
+        if accepted.is_empty() {
+            return Ok(results);
+        }
 
-                    // Reattempt to register the gauge with the empty registry
-                    register_gauge_with_registry!(content_storage_usage_bytes_options, registry)
-                        .expect(
-                        "a gauge can always be added to a new custom registry, without conflict",
-                    )
+        // Store the data in radius db, along with journal entries that are only cleared once the
+        // metadata is written too.
+        self.write_synced(batch)?;
+        self.inject_interruption()?;
+
+        let mut inserted = vec![];
+        {
+            let mut conn = self.sql_connection_pool.get()?;
+            let tx = conn.transaction()?;
+            for (index, content_id, content_key, size, is_pinned) in accepted.iter() {
+                match self.meta_db_insert(&tx, content_id, content_key, *size) {
+                    Ok(true) => inserted.push((*size, *is_pinned)),
+                    // The content was already stored.
+                    Ok(false) => {}
+                    Err(err) => results[*index] = Err(err),
                 }
-            };
+            }
+            if let Err(err) = tx.commit() {
+                inserted.clear();
+                let err = err.to_string();
+                for (index, ..) in accepted.iter() {
+                    results[*index] = Err(ContentStoreError::Database(err.clone()));
+                }
+            }
+        }
 
-        let total_storage_usage_bytes = register_gauge_with_registry!(
-            format!("trin_total_storage_usage_bytes_{protocol:?}"),
-            "full on-disk database size, in bytes",
-            registry,
-        )
-        .unwrap();
-        let storage_capacity_bytes = register_gauge_with_registry!(
-            format!("trin_storage_capacity_bytes_{protocol:?}"),
-            "user-defined limit on storage usage, in bytes",
-            registry
-        )
-        .unwrap();
-        let radius_ratio = register_gauge_with_registry!(
-            format!("trin_radius_ratio_{protocol:?}"),
-            "the fraction of the whole data ring covered by the data radius",
-            registry,
-        )
-        .unwrap();
-        let entry_count = register_int_gauge_with_registry!(
-            format!("trin_entry_count_{protocol:?}"),
-            "total number of storage entries",
-            registry,
-        )
-        .unwrap();
+        // Revert rocks db action if there's an error with writing to metadata db
+        let mut batch = WriteBatch::default();
+        for (index, content_id, ..) in accepted.iter() {
+            if let Err(err) = &results[*index] {
+                debug!(
+                    "Error writing content ID {:?} to meta db. Reverting: {:?}",
+                    content_id, err
+                );
+                batch.delete_cf(column_family, content_id);
+            }
+            batch.delete_cf(journal, self.journal_key(content_id));
+        }
+        self.db.write(batch)?;
 
-        Self {
-            content_storage_usage_bytes,
-            total_storage_usage_bytes,
-            storage_capacity_bytes,
-            radius_ratio,
-            entry_count,
+        for (size, is_pinned) in inserted {
+            // Pinned content does not count towards the capacity of the store.
+            if !is_pinned {
+                self.content_size_total += size as u64;
+            }
+            self.metrics.increase_entry_count();
         }
+        self.metrics
+            .report_content_data_storage_bytes(self.content_size_total as f64);
+        Ok(results)
     }
 
-    pub fn report_content_data_storage_bytes(&self, bytes: f64) {
-        self.content_storage_usage_bytes.set(bytes);
-    }
+    /// Internal method for pruning any data that falls outside of the radius of the store.
+    /// Resets the data radius if it prunes any data. Does nothing if the store is empty.
+    /// Returns the number of items removed during pruning
+    ///
+    /// The farthest content is evicted in bands of growing size, up to `EVICTION_BATCH_SIZE`
+    /// items, until evicting the next item would drop us below capacity.
+    fn prune_db(&mut self) -> Result<usize, ContentStoreError> {
+        if !self.capacity_reached() {
+            return Ok(0);
+        }
 
-    pub fn report_total_storage_usage_bytes(&self, bytes: f64) {
-        self.total_storage_usage_bytes.set(bytes);
-    }
+        let mut num_removed_items = 0;
+        let mut batch_size = MIN_EVICTION_BATCH_SIZE;
+        // The farthest content that is kept, which sets the new radius.
+        let mut farthest_kept = None;
+        while farthest_kept.is_none() && self.capacity_reached() {
+            let farthest = self.find_farthest_content(batch_size)?;
+            let is_last_batch = (farthest.len() as u64) < batch_size;
+            let mut usage = self.content_size_total;
+            let mut band = vec![];
+            for (id, size) in farthest {
+                // Test if removing the item would put us under capacity
+                if usage.saturating_sub(size) < self.storage_capacity_in_bytes {
+                    debug!(
+                        "Removing item would drop us below capacity. We target slight overfilling. {}",
+                        hex_encode(id)
+                    );
+                    farthest_kept = Some(id);
+                    break;
+                }
+                usage -= size;
+                band.push(id);
+            }
+            if let Some(last) = band.last() {
+                debug!(
+                    "Capacity reached, deleting {} farthest items, up to: {}",
+                    band.len(),
+                    hex_encode(last)
+                );
+                num_removed_items += self.evict_batch(&band)?;
+            }
+            if is_last_batch {
+                break;
+            }
+            batch_size = (batch_size * 4).min(EVICTION_BATCH_SIZE);
+        }
 
-    pub fn report_storage_capacity_bytes(&self, bytes: f64) {
-        self.storage_capacity_bytes.set(bytes);
+        // Reset the radius to the farthest content that is left.
+        let farthest_kept = match farthest_kept {
+            Some(id) => Some(id),
+            None => self.find_farthest_content_id()?,
+        };
+        match farthest_kept {
+            None => {
+                // We get here if the entire db has been pruned,
+                // eg. user selected 0mb capacity for storage
+                self.set_radius(Distance::ZERO);
+            }
+            Some(farthest) => {
+                debug!("Found new farthest: {}", hex_encode(farthest));
+                self.set_radius(self.distance_to_content_id(&farthest));
+            }
+        }
+        Ok(num_removed_items)
     }
 
-    pub fn report_radius(&self, radius: Distance) {
-        let radius_high_bytes = [
-            radius.byte(31),
-            radius.byte(30),
-            radius.byte(29),
-            radius.byte(28),
-        ];
-        let radius_int = u32::from_be_bytes(radius_high_bytes);
-        let coverage_ratio = radius_int as f64 / u32::MAX as f64;
-        self.radius_ratio.set(coverage_ratio);
+    /// Public method for evicting a certain content id.
+    pub fn evict(&mut self, id: [u8; 32]) -> anyhow::Result<()> {
+        self.evict_batch(&[id])?;
+        Ok(())
     }
 
-    pub fn report_entry_count(&self, count: u64) {
-        let count: i64 = count
-            .try_into()
-            .expect("Number of db entries will be small enough to fit in i64");
-        self.entry_count.set(count);
-    }
+    /// Evicts the content with the given ids, in a single SQLite transaction and a single
+    /// RocksDB write. Returns the number of items that were removed.
+    ///
+    /// The ids are journaled before the metadata is deleted, so values left behind by an
+    /// interrupted eviction are deleted when the store is opened again.
+    fn evict_batch(&mut self, ids: &[[u8; 32]]) -> Result<usize, ContentStoreError> {
+        let journal = self.journal()?;
+        let mut journal_batch = WriteBatch::default();
+        for id in ids {
+            journal_batch.put_cf(journal, self.journal_key(id), []);
+        }
+        self.write_synced(journal_batch)?;
+
+        let mut conn = self.sql_connection_pool.get()?;
+        let tx = conn.transaction()?;
+        let mut num_removed_items = 0;
+        let mut removed_size = 0;
+        {
+            let mut metadata_query = tx.prepare(&self.queries.content_key_and_size_lookup)?;
+            let mut pin_query = tx.prepare(&self.queries.pin_lookup)?;
+            let mut delete_query = tx.prepare(&self.queries.delete)?;
+            for id in ids {
+                let metadata: Option<(String, i64)> = metadata_query
+                    .query_row([id.to_vec()], |row| Ok((row.get(0)?, row.get(1)?)))
+                    .optional()?;
+                let (content_key, size) = metadata.unwrap_or_default();
+                let is_pinned = pin_query.exists([id.to_vec()])?
+                    || self.is_pinned_content_type_hex(&content_key);
+                if delete_query.execute([id.to_vec()])? > 0 {
+                    num_removed_items += 1;
+                    if !is_pinned {
+                        removed_size += size as u64;
+                    }
+                }
+            }
+        }
+        tx.commit()?;
+        self.inject_interruption()?;
+
+        let column_family = self.column_family()?;
+        let mut batch = WriteBatch::default();
+        for id in ids {
+            batch.delete_cf(column_family, id);
+            batch.delete_cf(journal, self.journal_key(id));
+        }
+        self.db.write(batch)?;
+
+        self.content_size_total = self.content_size_total.saturating_sub(removed_size);
+        self.metrics
+            .decrease_entry_count_by(num_removed_items as u64);
+        self.metrics
+            .report_content_data_storage_bytes(self.content_size_total as f64);
+        Ok(num_removed_items)
+    }
+
+    /// Pins the content denoted by `key`, so that it is stored even if it falls outside of the
+    /// radius, and never evicted to make room for other content. The content does not need to be
+    /// stored yet. Returns false if the content was already pinned.
+    pub fn pin(&mut self, key: &impl OverlayContentKey) -> Result<bool, ContentStoreError> {
+        let content_id = key.content_id();
+        let content_key: Vec<u8> = key.clone().into();
+        // store content key w/o the 0x prefix
+        let content_key = hex_encode(content_key).trim_start_matches("0x").to_string();
+        let conn = self.sql_connection_pool.get()?;
+        if conn.execute(
+            &self.queries.insert_pin,
+            params![content_id.to_vec(), content_key],
+        )? == 0
+        {
+            return Ok(false);
+        }
+        // Content of a pinned content type does not count towards the capacity already.
+        if self.is_pinned_content_type(key) {
+            return Ok(true);
+        }
+
+        // Stored content no longer counts towards the capacity of the store.
+        let size: Option<i64> = conn
+            .query_row(
+                &self.queries.content_size_lookup,
+                [content_id.to_vec()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(size) = size {
+            self.content_size_total = self.content_size_total.saturating_sub(size as u64);
+            self.metrics
+                .report_content_data_storage_bytes(self.content_size_total as f64);
+        }
+        Ok(true)
+    }
+
+    /// Unpins the content denoted by `key`. Stored content counts towards the capacity of the
+    /// store again, and may be evicted right away if the store is over capacity. Returns false if
+    /// the content was not pinned.
+    ///
+    /// Content of a pinned content type stays pinned by its type.
+    pub fn unpin(&mut self, key: &impl OverlayContentKey) -> Result<bool, ContentStoreError> {
+        let content_id = key.content_id();
+        let conn = self.sql_connection_pool.get()?;
+        if conn.execute(&self.queries.delete_pin, [content_id.to_vec()])? == 0 {
+            return Ok(false);
+        }
+        if self.is_pinned_content_type(key) {
+            return Ok(true);
+        }
+
+        let size: Option<i64> = conn
+            .query_row(
+                &self.queries.content_size_lookup,
+                [content_id.to_vec()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(size) = size {
+            self.content_size_total += size as u64;
+            self.metrics
+                .report_content_data_storage_bytes(self.content_size_total as f64);
+            self.prune_db()?;
+        }
+        Ok(true)
+    }
+
+    /// Returns the content keys of all content of the subnetwork that is pinned by itself, whether
+    /// it is stored or not. Content that is only pinned by its content type is not listed.
+    pub fn pinned_content_keys(&self) -> Result<Vec<Vec<u8>>, ContentStoreError> {
+        let conn = self.sql_connection_pool.get()?;
+        let mut query = conn.prepare(&self.queries.pinned_content_keys)?;
+        let content_keys: Result<Vec<String>, rusqlite::Error> =
+            query.query_map([], |row| row.get(0))?.collect();
+        content_keys?
+            .into_iter()
+            // value is stored without 0x prefix, so we must add it
+            .map(|content_key| Ok(hex_decode(&format!("0x{content_key}"))?))
+            .collect()
+    }
+
+    /// Returns whether the content denoted by `key` is pinned, either by itself or by its content
+    /// type.
+    fn is_pinned(&self, key: &impl OverlayContentKey) -> Result<bool, ContentStoreError> {
+        if self.is_pinned_content_type(key) {
+            return Ok(true);
+        }
+        let conn = self.sql_connection_pool.get()?;
+        let mut query = conn.prepare(&self.queries.pin_lookup)?;
+        Ok(query.exists([key.content_id().to_vec()])?)
+    }
+
+    /// Returns whether the content type of `key` is pinned.
+    fn is_pinned_content_type(&self, key: &impl OverlayContentKey) -> bool {
+        let content_key: Vec<u8> = key.clone().into();
+        match content_key.first() {
+            Some(content_type) => self.pinned_content_types.contains(content_type),
+            None => false,
+        }
+    }
+
+    /// Returns whether the content type of a content key, stored as hex without the 0x prefix, is
+    /// pinned.
+    fn is_pinned_content_type_hex(&self, content_key: &str) -> bool {
+        content_key
+            .get(..2)
+            .and_then(|content_type| u8::from_str_radix(content_type, 16).ok())
+            .map_or(false, |content_type| {
+                self.pinned_content_types.contains(&content_type)
+            })
+    }
+
+    /// Public method for looking up the raw bytes of a content key by its content id. The bytes
+    /// are not decoded, since the key type depends on the subnetwork.
+    pub fn lookup_content_key(&self, id: [u8; 32]) -> anyhow::Result<Option<Vec<u8>>> {
+        let conn = self.sql_connection_pool.get()?;
+        let mut query = conn.prepare(&self.queries.content_key_lookup)?;
+        let content_key: Option<String> = query
+            .query_row([id.to_vec()], |row| row.get(0))
+            .optional()?;
+        // value is stored without 0x prefix, so we must add it
+        Ok(content_key
+            .map(|content_key| hex_decode(&format!("0x{content_key}")))
+            .transpose()?)
+    }
+
+    /// Returns up to `limit` pairs of content id and content key, in ascending order of content id,
+    /// starting after the content id `after`, or from the lowest content id if `after` is `None`.
+    pub fn content_keys_after(
+        &self,
+        after: Option<[u8; 32]>,
+        limit: u64,
+    ) -> Result<Vec<([u8; 32], Vec<u8>)>, ContentStoreError> {
+        let conn = self.sql_connection_pool.get()?;
+        let mut query = conn.prepare(&self.queries.content_keys_after)?;
+        // Every content id compares greater than an empty blob.
+        let after = after.map(|id| id.to_vec()).unwrap_or_default();
+        let rows: Result<Vec<(Vec<u8>, String)>, rusqlite::Error> = query
+            .query_map(params![after, limit], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect();
+
+        rows?
+            .into_iter()
+            .map(|(id, content_key)| {
+                let id: [u8; 32] =
+                    id.try_into()
+                        .map_err(|id: Vec<u8>| ContentStoreError::InvalidData {
+                            message: format!("content ID of length {} != 32", id.len()),
+                        })?;
+                // value is stored without 0x prefix, so we must add it
+                let content_key = hex_decode(&format!("0x{content_key}"))?;
+                Ok((id, content_key))
+            })
+            .collect()
+    }
+
+    /// Returns the content stored under each of `content_ids`, in the same order.
+    pub fn get_by_content_ids(
+        &self,
+        content_ids: &[[u8; 32]],
+    ) -> Result<Vec<Option<Vec<u8>>>, ContentStoreError> {
+        let column_family = self.column_family()?;
+        self.db
+            .multi_get_cf(content_ids.iter().map(|id| (column_family, id)))
+            .into_iter()
+            .map(|content| Ok(content?))
+            .collect()
+    }
+
+    /// Records that the integrity scrubber checked the content with id `content_id`, and whether
+    /// the content was valid.
+    pub fn report_scrubbed_content(&self, content_id: &[u8; 32], valid: bool) {
+        self.metrics.report_scrubbed_content(content_id, valid);
+    }
+
+    /// Records that the integrity scrubber checked all stored content.
+    pub fn report_scrub_pass_completed(&self) {
+        self.metrics.report_scrub_pass_completed();
+    }
+
+    /// Returns the subnetwork whose content is stored.
+    pub fn protocol(&self) -> &ProtocolId {
+        &self.protocol
+    }
+
+    /// Public method for retrieving the node's current radius.
+    pub fn radius(&self) -> Distance {
+        self.radius
+    }
+
+    /// Public method for determining how much actual disk space is being used to store this node's Portal Network data.
+    /// Intended for analysis purposes. PortalStorage's capacity decision-making is not based off of this method.
+    pub fn get_total_storage_usage_in_bytes_on_disk(&self) -> Result<u64, ContentStoreError> {
+        Self::measure_disk_usage(
+            &self.db,
+            self.column_family,
+            &self.sql_connection_pool,
+            &self.queries.disk_usage,
+        )
+    }
+
+    /// Spawns a task that samples the on-disk size of the databases every
+    /// `DISK_USAGE_SAMPLE_INTERVAL`, and reports it to the metrics. Storing content does not
+    /// measure the size itself, so that the write path never waits on it.
+    ///
+    /// The task keeps the databases open, so it runs until the returned sampler is dropped.
+    pub fn spawn_disk_usage_sampler(&self) -> DiskUsageSampler {
+        let db = Arc::clone(&self.db);
+        let column_family = self.column_family;
+        let sql_connection_pool = self.sql_connection_pool.clone();
+        let disk_usage_query = self.queries.disk_usage.clone();
+        let metrics = self.metrics.clone();
+        DiskUsageSampler(tokio::spawn(async move {
+            let mut ticker = interval(DISK_USAGE_SAMPLE_INTERVAL);
+            loop {
+                ticker.tick().await;
+                match Self::measure_disk_usage(
+                    &db,
+                    column_family,
+                    &sql_connection_pool,
+                    &disk_usage_query,
+                ) {
+                    Ok(bytes) => metrics.report_total_storage_usage_bytes(bytes as f64),
+                    Err(err) => warn!(error = %err, "Unable to measure on-disk storage usage"),
+                }
+            }
+        }))
+    }
+
+    /// Internal method used to measure the on-disk storage usage of the subnetwork: the size of
+    /// the SST files of its RocksDB column family, plus the size of the SQLite pages of its
+    /// metadata and pins tables and their indexes. The databases are shared by all subnetworks,
+    /// so the stores of the node never count the same bytes twice.
+    ///
+    /// Both sizes are kept by the databases themselves, so this does not touch the filesystem.
+    /// Content that RocksDB has not flushed to an SST file yet is not counted, and neither are the
+    /// short-lived entries of the shared write journal.
+    fn measure_disk_usage(
+        db: &DB,
+        column_family: &str,
+        sql_connection_pool: &Pool<SqliteConnectionManager>,
+        disk_usage_query: &str,
+    ) -> Result<u64, ContentStoreError> {
+        let column_family = db.cf_handle(column_family).ok_or_else(|| {
+            ContentStoreError::Database(format!("Missing column family {column_family}"))
+        })?;
+        let size = db
+            .property_int_value_cf(column_family, "rocksdb.total-sst-files-size")?
+            .unwrap_or_default();
+
+        let conn = sql_connection_pool.get()?;
+        let sql_size: f64 = conn.query_row(disk_usage_query, [], |row| row.get(0))?;
+        Ok(size + sql_size as u64)
+    }
+
+    /// Writes `batch` to RocksDB, and waits until it is on disk. Journal entries must be durable
+    /// before the SQLite part of a write starts, or a crash could lose the entry of a write whose
+    /// SQLite part was committed.
+    fn write_synced(&self, batch: WriteBatch) -> Result<(), ContentStoreError> {
+        let mut write_options = WriteOptions::default();
+        write_options.set_sync(true);
+        self.db.write_opt(batch, &write_options)?;
+        Ok(())
+    }
+
+    /// Stops a write between RocksDB and SQLite if the test asked for it.
+    #[cfg(test)]
+    fn inject_interruption(&self) -> Result<(), ContentStoreError> {
+        if self
+            .interrupt_next_write
+            .swap(false, std::sync::atomic::Ordering::Relaxed)
+        {
+            return Err(ContentStoreError::Database(
+                "Write interrupted between RocksDB and SQLite".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(test))]
+    fn inject_interruption(&self) -> Result<(), ContentStoreError> {
+        Ok(())
+    }
+
+    /// Repairs the writes to the content of the subnetwork that were interrupted, eg. by a crash,
+    /// between RocksDB and SQLite. A value without metadata is deleted, and so is metadata
+    /// without a value. Returns the number of repaired content items.
+    fn recover_interrupted_writes(&self) -> Result<usize, ContentStoreError> {
+        let journal = self.journal()?;
+        let prefix = self.journal_prefix();
+        let prefix = prefix.as_slice();
+        let journal_keys: Vec<Box<[u8]>> = self
+            .db
+            .iterator_cf(journal, IteratorMode::From(prefix, Direction::Forward))
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(prefix))
+            .collect();
+        if journal_keys.is_empty() {
+            return Ok(0);
+        }
+
+        let column_family = self.column_family()?;
+        let mut conn = self.sql_connection_pool.get()?;
+        let tx = conn.transaction()?;
+        let mut batch = WriteBatch::default();
+        let mut num_repaired = 0;
+        {
+            let mut size_query = tx.prepare(&self.queries.content_size_lookup)?;
+            let mut delete_query = tx.prepare(&self.queries.delete)?;
+            for journal_key in journal_keys {
+                let content_id = &journal_key[prefix.len()..];
+                let has_metadata = size_query
+                    .query_row([content_id], |row| row.get::<_, i64>(0))
+                    .optional()?
+                    .is_some();
+                let has_value = self.db.get_pinned_cf(column_family, content_id)?.is_some();
+                match (has_metadata, has_value) {
+                    (false, true) => {
+                        batch.delete_cf(column_family, content_id);
+                        num_repaired += 1;
+                    }
+                    (true, false) => {
+                        delete_query.execute([content_id])?;
+                        num_repaired += 1;
+                    }
+                    _ => {}
+                }
+                batch.delete_cf(journal, journal_key);
+            }
+        }
+        tx.commit()?;
+        self.write_synced(batch)?;
+
+        if num_repaired > 0 {
+            warn!(
+                repaired = num_repaired,
+                "Repaired content writes that were interrupted between RocksDB and SQLite"
+            );
+        }
+        Ok(num_repaired)
+    }
+
+    /// Deletes every value of the subnetwork without metadata, and all metadata without a value.
+    /// Writes from before the journal existed were never journaled, so the whole store is
+    /// reconciled once, the first time it is opened with the journal. Returns the number of
+    /// repaired content items.
+    fn reconcile_databases(&self) -> Result<usize, ContentStoreError> {
+        let journal = self.journal()?;
+        let reconciled_key = self.reconciled_key();
+        if self.db.get_pinned_cf(journal, &reconciled_key)?.is_some() {
+            return Ok(0);
+        }
+        info!(protocol = %self.protocol, "Reconciling the content in RocksDB and SQLite");
+
+        let column_family = self.column_family()?;
+        let mut conn = self.sql_connection_pool.get()?;
+        let tx = conn.transaction()?;
+        let mut batch = WriteBatch::default();
+        let mut num_repaired = 0;
+        {
+            let mut size_query = tx.prepare(&self.queries.content_size_lookup)?;
+            for (content_id, _) in self.db.iterator_cf(column_family, IteratorMode::Start) {
+                if !size_query.exists([&*content_id])? {
+                    batch.delete_cf(column_family, content_id);
+                    num_repaired += 1;
+                }
+            }
+
+            let mut ids_query = tx.prepare(&self.queries.all_content_ids)?;
+            let content_ids: Vec<Vec<u8>> = ids_query
+                .query_map([], |row| row.get(0))?
+                .collect::<Result<_, _>>()?;
+            let mut delete_query = tx.prepare(&self.queries.delete)?;
+            for content_id in content_ids {
+                if self.db.get_pinned_cf(column_family, &content_id)?.is_none() {
+                    delete_query.execute([content_id])?;
+                    num_repaired += 1;
+                }
+            }
+        }
+        tx.commit()?;
+        // An interruption before the marker is written makes the next start reconcile again.
+        batch.put_cf(journal, reconciled_key, []);
+        self.write_synced(batch)?;
+
+        if num_repaired > 0 {
+            warn!(
+                repaired = num_repaired,
+                "Repaired content that was inconsistent between RocksDB and SQLite"
+            );
+        }
+        Ok(num_repaired)
+    }
+
+    /// Evicts all content whose key is not a history content key. Before subnetworks had their
+    /// own namespaces, every subnetwork stored its content in the table and column family that
+    /// history still uses, so the content of other subnetworks may be left there. The store is
+    /// only checked the first time it is opened. Returns the number of evicted content items.
+    fn evict_foreign_content(&mut self) -> Result<usize, ContentStoreError> {
+        let journal = self.journal()?;
+        let checked_key = self.foreign_content_checked_key();
+        if self.db.get_pinned_cf(journal, &checked_key)?.is_some() {
+            return Ok(0);
+        }
+
+        let foreign_ids: Vec<[u8; 32]> = {
+            let conn = self.sql_connection_pool.get()?;
+            let mut query = conn.prepare(&self.queries.inventory)?;
+            let rows: Vec<(Vec<u8>, String)> = query
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<_, _>>()?;
+            rows.into_iter()
+                .filter(|(_, content_key)| {
+                    // content key is stored without 0x prefix, so we must add it
+                    hex_decode(&format!("0x{content_key}"))
+                        .ok()
+                        .and_then(|content_key| HistoryContentKey::try_from(content_key).ok())
+                        .is_none()
+                })
+                .filter_map(|(content_id, _)| content_id.try_into().ok())
+                .collect()
+        };
+        let num_evicted = self.evict_batch(&foreign_ids)?;
+
+        let mut batch = WriteBatch::default();
+        batch.put_cf(journal, checked_key, []);
+        self.write_synced(batch)?;
+
+        if num_evicted > 0 {
+            warn!(
+                evicted = num_evicted,
+                "Evicted content of other subnetworks from the history store"
+            );
+        }
+        Ok(num_evicted)
+    }
+
+    /// Returns the handle of the RocksDB column family that journals writes in progress.
+    fn journal(&self) -> Result<&ColumnFamily, ContentStoreError> {
+        self.db.cf_handle(JOURNAL_COLUMN_FAMILY).ok_or_else(|| {
+            ContentStoreError::Database(format!("Missing column family {JOURNAL_COLUMN_FAMILY}"))
+        })
+    }
+
+    /// Returns the prefix of the keys of the journal entries of the subnetwork.
+    fn journal_prefix(&self) -> Vec<u8> {
+        format!("{}/", self.column_family).into_bytes()
+    }
+
+    /// Returns the key of the journal entry that marks the subnetwork as reconciled. It does not
+    /// start with the prefix of the journal entries of writes.
+    fn reconciled_key(&self) -> Vec<u8> {
+        format!("reconciled:{}", self.column_family).into_bytes()
+    }
+
+    /// Returns the key of the journal entry that marks the subnetwork as checked for the content of
+    /// other subnetworks.
+    fn foreign_content_checked_key(&self) -> Vec<u8> {
+        format!("foreign-content-checked:{}", self.column_family).into_bytes()
+    }
+
+    /// Returns the key of the journal entry of a write to `content_id` of the subnetwork.
+    fn journal_key(&self, content_id: &[u8; 32]) -> Vec<u8> {
+        let mut key = self.journal_prefix();
+        key.extend_from_slice(content_id);
+        key
+    }
+
+    /// Returns the handle of the RocksDB column family that holds the content of the subnetwork.
+    fn column_family(&self) -> Result<&ColumnFamily, ContentStoreError> {
+        self.db.cf_handle(self.column_family).ok_or_else(|| {
+            ContentStoreError::Database(format!("Missing column family {}", self.column_family))
+        })
+    }
+
+    /// Returns the name of the RocksDB column family that holds the content of `protocol`.
+    ///
+    /// History content was stored before subnetworks had their own namespaces, so it stays in the
+    /// default column family.
+    pub fn column_family_name(protocol: &ProtocolId) -> &'static str {
+        match protocol {
+            ProtocolId::History => rocksdb::DEFAULT_COLUMN_FAMILY_NAME,
+            ProtocolId::State => "state",
+            ProtocolId::TransactionGossip => "transaction_gossip",
+            ProtocolId::HeaderGossip => "header_gossip",
+            ProtocolId::CanonicalIndices => "canonical_indices",
+            ProtocolId::Utp => "utp",
+        }
+    }
+
+    /// Returns the name of the SQLite table that holds the content metadata of `protocol`.
+    pub fn metadata_table_name(protocol: &ProtocolId) -> &'static str {
+        match protocol {
+            ProtocolId::History => "content_metadata",
+            ProtocolId::State => "content_metadata_state",
+            ProtocolId::TransactionGossip => "content_metadata_transaction_gossip",
+            ProtocolId::HeaderGossip => "content_metadata_header_gossip",
+            ProtocolId::CanonicalIndices => "content_metadata_canonical_indices",
+            ProtocolId::Utp => "content_metadata_utp",
+        }
+    }
+
+    /// Internal method for inserting data into the meta db. Returns false if the content was
+    /// already in the meta db.
+    fn meta_db_insert(
+        &self,
+        conn: &Connection,
+        content_id: &[u8; 32],
+        content_key: &String,
+        value_size: usize,
+    ) -> Result<bool, ContentStoreError> {
+        let content_id_as_u32: u32 = Self::byte_vector_to_u32(content_id.to_vec());
+        let distance_as_u32: u32 =
+            content_id_as_u32 ^ Self::byte_vector_to_u32(self.node_id.raw().to_vec());
+        if content_key.starts_with("0x") {
+            return Err(ContentStoreError::InvalidData {
+                message: "Content key should not start with 0x".to_string(),
+            });
+        }
+        match conn.execute(
+            &self.queries.insert,
+            params![
+                content_id.to_vec(),
+                content_id_as_u32,
+                content_key,
+                value_size,
+                distance_as_u32
+            ],
+        ) {
+            Ok(num_inserted) => Ok(num_inserted > 0),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Internal method for determining whether the node is over-capacity.
+    fn capacity_reached(&self) -> bool {
+        self.content_size_total > self.storage_capacity_in_bytes
+    }
+
+    /// Internal method for measuring the total amount of requestable data that the node is storing.
+    fn get_total_storage_usage_in_bytes_from_network(&self) -> u64 {
+        self.metrics
+            .report_content_data_storage_bytes(self.content_size_total as f64);
+        self.content_size_total
+    }
+
+    /// Internal method for summing the sizes of all content in the meta db. Only used on startup,
+    /// afterwards the sum is kept up to date as content is stored and evicted.
+    fn query_total_content_size(&self) -> Result<u64, ContentStoreError> {
+        let conn = self.sql_connection_pool.get()?;
+        let mut query = conn.prepare(&self.queries.total_data_size)?;
+
+        let result = query.query_map([], |row| {
+            Ok(DataSize {
+                num_bytes: row.get(0)?,
+            })
+        });
+
+        let sum = match result?.next() {
+            Some(total) => total,
+            None => {
+                let err = "Unable to compute sum over content item sizes".to_string();
+                return Err(ContentStoreError::Database(err));
+            }
+        }?
+        .num_bytes;
+
+        Ok(sum as u64)
+    }
+
+    /// Internal method for finding the piece of stored data that has the farthest content id from our
+    /// node id, according to xor distance. Used to determine which data to drop when at a capacity.
+    fn find_farthest_content_id(&self) -> Result<Option<[u8; 32]>, ContentStoreError> {
+        Ok(self
+            .find_farthest_content(1)?
+            .first()
+            .map(|(content_id, _)| *content_id))
+    }
+
+    /// Internal method for finding up to `limit` pieces of stored data that are farthest from our
+    /// node id, farthest first. Returns the content id and size of each piece of data.
+    fn find_farthest_content(&self, limit: u64) -> Result<Vec<([u8; 32], u64)>, ContentStoreError> {
+        match self.distance_fn {
+            DistanceFunction::Xor => {
+                let conn = self.sql_connection_pool.get()?;
+                let mut query = conn.prepare(&self.queries.find_farthest)?;
+                let rows: Result<Vec<(Vec<u8>, i64)>, rusqlite::Error> = query
+                    .query_map([limit], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect();
+
+                rows?
+                    .into_iter()
+                    .map(|(id, size)| {
+                        let id: [u8; 32] = id.try_into().map_err(|id: Vec<u8>| {
+                            ContentStoreError::InvalidData {
+                                message: format!("content ID of length {} != 32", id.len()),
+                            }
+                        })?;
+                        Ok((id, size as u64))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Method that returns the distance between our node ID and a given content ID.
+    pub fn distance_to_content_id(&self, content_id: &[u8; 32]) -> Distance {
+        match self.distance_fn {
+            DistanceFunction::Xor => XorMetric::distance(content_id, &self.node_id.raw()),
+        }
+    }
+
+    /// Converts most significant 4 bytes of a vector to a u32.
+    fn byte_vector_to_u32(vec: Vec<u8>) -> u32 {
+        if vec.len() < 4 {
+            debug!("Error: XOR returned less than 4 bytes.");
+            return 0;
+        }
+
+        let mut array: [u8; 4] = [0, 0, 0, 0];
+        for (index, byte) in vec.iter().take(4).enumerate() {
+            array[index] = *byte;
+        }
+
+        u32::from_be_bytes(array)
+    }
+
+    /// Helper function for opening a RocksDB connection for the radius-constrained db, with a
+    /// column family for each subnetwork, and one for the write journal.
+    pub fn setup_rocksdb(node_data_dir: &Path) -> Result<rocksdb::DB, ContentStoreError> {
+        let rocksdb_path = node_data_dir.join("rocksdb");
+        info!(path = %rocksdb_path.display(), "Setting up RocksDB");
+
+        let mut db_opts = Options::default();
+        db_opts.create_if_missing(true);
+        db_opts.create_missing_column_families(true);
+        Ok(DB::open_cf(
+            &db_opts,
+            rocksdb_path,
+            CONTENT_PROTOCOLS
+                .iter()
+                .map(Self::column_family_name)
+                .chain(std::iter::once(JOURNAL_COLUMN_FAMILY)),
+        )?)
+    }
+
+    /// Helper function for opening a RocksDB connection for the trie db.
+    pub fn setup_triedb(node_data_dir: &Path) -> Result<rocksdb::DB, ContentStoreError> {
+        let trie_db_path = node_data_dir.join("triedb");
+        info!(path = %trie_db_path.display(), "Setting up triedb");
+
+        let mut db_opts = Options::default();
+        db_opts.create_if_missing(true);
+        Ok(DB::open(&db_opts, trie_db_path)?)
+    }
+
+    /// Helper function for opening a SQLite connection. The metadata table of each subnetwork is
+    /// created by the `PortalStorage` of that subnetwork.
+    pub fn setup_sql(
+        node_data_dir: &Path,
+    ) -> Result<Pool<SqliteConnectionManager>, ContentStoreError> {
+        let sql_path = node_data_dir.join("trin.sqlite");
+        info!(path = %sql_path.display(), "Setting up SqliteDB");
+
+        let manager = SqliteConnectionManager::file(sql_path);
+        Ok(Pool::new(manager)?)
+    }
+
+    /// Get a summary of the current state of storage
+    pub fn get_summary_info(&self) -> String {
+        self.metrics.get_summary()
+    }
+}
+
+#[derive(Clone, Debug)]
+struct StorageMetrics {
+    content_storage_usage_bytes: Gauge,
+    total_storage_usage_bytes: Gauge,
+    storage_capacity_bytes: Gauge,
+    radius_ratio: Gauge,
+    entry_count: IntGauge,
+    scrubbed_content_count: IntCounter,
+    scrub_invalid_content_count: IntCounter,
+    scrub_pass_count: IntCounter,
+    scrub_progress_ratio: Gauge,
+}
+
+impl StorageMetrics {
+    pub fn new(protocol: &ProtocolId) -> Self {
+        let content_storage_usage_bytes_options = opts!(
+            format!("trin_content_storage_usage_bytes_{protocol:?}"),
+            "sum of size of individual content stored, in bytes"
+        );
+        // Keep a reference to the registry. Make mutable, because we may have to switch to a new
+        // one if the default registry is already using a metric of the same name (which should
+        // only happen during tests).
+        let mut registry = default_registry();
+
+        // Always create a custom registry, so that we can use it in the error case
+        // It's just an easy way to throw away duplicate metrics.
+        let custom_registry = Registry::default();
+
+        let content_storage_usage_bytes =
+            match register_gauge!(content_storage_usage_bytes_options.clone()) {
+                Ok(gauge) => gauge,
+                Err(_) => {
+                    error!(
+                        "Failed to register prometheus gauge with default registry, creating new"
+                    );
+
+                    // Assign the new registry to the outer variable, so it can be used by all metrics
+                    registry = &custom_registry;
+
+                    // Reattempt to register the gauge with the empty registry
+                    register_gauge_with_registry!(content_storage_usage_bytes_options, registry)
+                        .expect(
+                        "a gauge can always be added to a new custom registry, without conflict",
+                    )
+                }
+            };
+
+        let total_storage_usage_bytes = register_gauge_with_registry!(
+            format!("trin_total_storage_usage_bytes_{protocol:?}"),
+            "on-disk size of the content databases of the subnetwork, in bytes",
+            registry,
+        )
+        .unwrap();
+        let storage_capacity_bytes = register_gauge_with_registry!(
+            format!("trin_storage_capacity_bytes_{protocol:?}"),
+            "user-defined limit on storage usage, in bytes",
+            registry
+        )
+        .unwrap();
+        let radius_ratio = register_gauge_with_registry!(
+            format!("trin_radius_ratio_{protocol:?}"),
+            "the fraction of the whole data ring covered by the data radius",
+            registry,
+        )
+        .unwrap();
+        let entry_count = register_int_gauge_with_registry!(
+            format!("trin_entry_count_{protocol:?}"),
+            "total number of storage entries",
+            registry,
+        )
+        .unwrap();
+        let scrubbed_content_count = register_int_counter_with_registry!(
+            format!("trin_scrubbed_content_total_{protocol:?}"),
+            "count content items checked by the integrity scrubber",
+            registry,
+        )
+        .unwrap();
+        let scrub_invalid_content_count = register_int_counter_with_registry!(
+            format!("trin_scrub_invalid_content_total_{protocol:?}"),
+            "count content items found invalid and evicted by the integrity scrubber",
+            registry,
+        )
+        .unwrap();
+        let scrub_pass_count = register_int_counter_with_registry!(
+            format!("trin_scrub_passes_total_{protocol:?}"),
+            "count completed integrity scrubs of all stored content",
+            registry,
+        )
+        .unwrap();
+        let scrub_progress_ratio = register_gauge_with_registry!(
+            format!("trin_scrub_progress_ratio_{protocol:?}"),
+            "the fraction of the content id space covered by the current integrity scrub",
+            registry,
+        )
+        .unwrap();
+
+        Self {
+            content_storage_usage_bytes,
+            total_storage_usage_bytes,
+            storage_capacity_bytes,
+            radius_ratio,
+            entry_count,
+            scrubbed_content_count,
+            scrub_invalid_content_count,
+            scrub_pass_count,
+            scrub_progress_ratio,
+        }
+    }
+
+    pub fn report_content_data_storage_bytes(&self, bytes: f64) {
+        self.content_storage_usage_bytes.set(bytes);
+    }
+
+    pub fn report_total_storage_usage_bytes(&self, bytes: f64) {
+        self.total_storage_usage_bytes.set(bytes);
+    }
+
+    pub fn report_storage_capacity_bytes(&self, bytes: f64) {
+        self.storage_capacity_bytes.set(bytes);
+    }
+
+    pub fn report_radius(&self, radius: Distance) {
+        self.radius_ratio.set(radius_ratio(radius));
+    }
+
+    pub fn report_entry_count(&self, count: u64) {
+        let count: i64 = count
+            .try_into()
+            .expect("Number of db entries will be small enough to fit in i64");
+        self.entry_count.set(count);
+    }
 
     pub fn increase_entry_count(&self) {
         self.entry_count.inc();
     }
 
-    pub fn decrease_entry_count(&self) {
-        self.entry_count.dec();
+    pub fn decrease_entry_count_by(&self, count: u64) {
+        self.entry_count.sub(count as i64);
+    }
+
+    pub fn report_scrubbed_content(&self, content_id: &[u8; 32], valid: bool) {
+        self.scrubbed_content_count.inc();
+        if !valid {
+            self.scrub_invalid_content_count.inc();
+        }
+        // Content ids are scanned in ascending order, so the high bytes of the last scanned id
+        // tell how far the scrub got.
+        let id_high_bytes = [content_id[0], content_id[1], content_id[2], content_id[3]];
+        let progress_ratio = u32::from_be_bytes(id_high_bytes) as f64 / u32::MAX as f64;
+        self.scrub_progress_ratio.set(progress_ratio);
+    }
+
+    pub fn report_scrub_pass_completed(&self) {
+        self.scrub_pass_count.inc();
+        self.scrub_progress_ratio.set(1.0);
+    }
+
+    pub fn get_summary(&self) -> String {
+        let radius_percent = self.radius_ratio.get() * 100.0;
+        format!(
+            "radius={:.*}% content={:.1}/{}mb #={} disk={:.1}mb",
+            Self::precision_for_percentage(radius_percent),
+            radius_percent,
+            self.content_storage_usage_bytes.get() / BYTES_IN_MB_F64,
+            self.storage_capacity_bytes.get() / BYTES_IN_MB_F64,
+            self.entry_count.get(),
+            self.total_storage_usage_bytes.get() / BYTES_IN_MB_F64,
+        )
+    }
+
+    fn precision_for_percentage(percent: f64) -> usize {
+        match percent {
+            x if x >= 10.0 => 0,
+            x if x >= 1.0 => 1,
+            x if x >= 0.1 => 2,
+            x if x >= 0.01 => 3,
+            _ => 4,
+        }
+    }
+}
+
+/// SQL conditions on the content metadata table that select the content matching a
+/// `LocalContentFilter`, along with the values of their parameters.
+#[derive(Clone, Debug, Default)]
+struct FilterClause {
+    conditions: Vec<String>,
+    params: Vec<Value>,
+}
+
+impl FilterClause {
+    /// Returns the conditions of all of `filter`, except for the distance range, which can only be
+    /// partially checked in SQL.
+    fn new(filter: &LocalContentFilter) -> Result<Self, ContentStoreError> {
+        let mut clause = Self::default();
+        if !filter.content_types.is_empty() {
+            let placeholders = vec!["?"; filter.content_types.len()].join(", ");
+            // content keys are stored as hex w/o the 0x prefix, starting with the content type
+            let content_types: Vec<String> = filter
+                .content_types
+                .iter()
+                .map(|content_type| {
+                    hex_encode([*content_type])
+                        .trim_start_matches("0x")
+                        .to_string()
+                })
+                .collect();
+            clause.push(
+                &format!("substr(content_key, 1, 2) IN ({placeholders})"),
+                content_types,
+            );
+        }
+        if let Some(prefix) = &filter.content_id_prefix {
+            let prefix = parse_content_id_prefix(prefix)?;
+            let mut min_id = prefix.clone();
+            min_id.resize(32, 0);
+            let mut max_id = prefix;
+            max_id.resize(32, 0xff);
+            clause.push("content_id_long BETWEEN ? AND ?", [min_id, max_id]);
+        }
+        if let Some(min_size) = filter.min_size {
+            clause.push(
+                "content_size >= ?",
+                [i64::try_from(min_size).unwrap_or(i64::MAX)],
+            );
+        }
+        if let Some(max_size) = filter.max_size {
+            clause.push(
+                "content_size <= ?",
+                [i64::try_from(max_size).unwrap_or(i64::MAX)],
+            );
+        }
+        Ok(clause)
+    }
+
+    /// Adds `condition`, whose parameters take the values of `params`.
+    fn push<T: Into<Value>>(&mut self, condition: &str, params: impl IntoIterator<Item = T>) {
+        self.conditions.push(condition.to_string());
+        self.params.extend(params.into_iter().map(Into::into));
+    }
+
+    /// Returns the conjunction of the conditions, to be used in a WHERE clause.
+    fn sql(&self) -> String {
+        match self.conditions.is_empty() {
+            true => "1".to_string(),
+            false => self.conditions.join(" AND "),
+        }
+    }
+}
+
+/// Inclusive range of distances from the local node id selected by a `LocalContentFilter`.
+struct DistanceRange {
+    min: U256,
+    max: U256,
+    bounded: bool,
+}
+
+impl DistanceRange {
+    fn new(filter: &LocalContentFilter) -> Self {
+        Self {
+            min: filter.min_distance.unwrap_or_else(U256::zero),
+            max: filter.max_distance.unwrap_or(U256::MAX),
+            bounded: filter.min_distance.is_some() || filter.max_distance.is_some(),
+        }
+    }
+
+    /// Returns whether the filter restricts the distance at all.
+    fn is_bounded(&self) -> bool {
+        self.bounded
+    }
+
+    fn contains(&self, distance: &Distance) -> bool {
+        self.min <= **distance && **distance <= self.max
+    }
+
+    /// Returns the most significant 4 bytes of the minimum distance, as precomputed in the
+    /// `distance_short` column.
+    fn min_short(&self) -> u32 {
+        (self.min >> 224).low_u32()
+    }
+
+    /// Returns the most significant 4 bytes of the maximum distance, as precomputed in the
+    /// `distance_short` column.
+    fn max_short(&self) -> u32 {
+        (self.max >> 224).low_u32()
+    }
+}
+
+/// Checks content held in memory against all of a `LocalContentFilter`.
+struct ContentMatcher {
+    content_types: Vec<u8>,
+    distance_range: DistanceRange,
+    content_id_prefix: Vec<u8>,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl ContentMatcher {
+    fn new(filter: &LocalContentFilter) -> Result<Self, ContentStoreError> {
+        let content_id_prefix = match &filter.content_id_prefix {
+            Some(prefix) => parse_content_id_prefix(prefix)?,
+            None => vec![],
+        };
+        Ok(Self {
+            content_types: filter.content_types.clone(),
+            distance_range: DistanceRange::new(filter),
+            content_id_prefix,
+            min_size: filter.min_size.unwrap_or(0),
+            max_size: filter.max_size.unwrap_or(u64::MAX),
+        })
+    }
+
+    /// Returns whether the content with key `content_key`, described by `entry`, matches.
+    fn matches(&self, content_key: &[u8], entry: &LocalContentEntry) -> bool {
+        let content_type_matches = match content_key.first() {
+            Some(content_type) => {
+                self.content_types.is_empty() || self.content_types.contains(content_type)
+            }
+            None => self.content_types.is_empty(),
+        };
+        content_type_matches
+            && self
+                .distance_range
+                .contains(&Distance::from(entry.distance))
+            && entry
+                .content_id
+                .as_bytes()
+                .starts_with(&self.content_id_prefix)
+            && self.min_size <= entry.content_size
+            && entry.content_size <= self.max_size
+    }
+}
+
+/// Parses the hex-encoded prefix of a content id.
+fn parse_content_id_prefix(prefix: &str) -> Result<Vec<u8>, ContentStoreError> {
+    let prefix = hex_decode(prefix)?;
+    if prefix.len() > 32 {
+        return Err(ContentStoreError::InvalidData {
+            message: format!("content ID prefix of length {} > 32", prefix.len()),
+        });
+    }
+    Ok(prefix)
+}
+
+/// Returns the fraction of the whole data ring covered by `radius`.
+fn radius_ratio(radius: Distance) -> f64 {
+    let radius_high_bytes = [
+        radius.byte(31),
+        radius.byte(30),
+        radius.byte(29),
+        radius.byte(28),
+    ];
+    let radius_int = u32::from_be_bytes(radius_high_bytes);
+    radius_int as f64 / u32::MAX as f64
+}
+
+/// Returns the content type of a content key stored as hex w/o the 0x prefix.
+/// Decodes the content key `bytes` as a content key of type `K`.
+fn decode_content_key<K: OverlayContentKey>(bytes: Vec<u8>) -> Result<K, ContentStoreError>
+where
+    <K as TryFrom<Vec<u8>>>::Error: fmt::Display,
+{
+    K::try_from(bytes).map_err(|err| ContentStoreError::InvalidData {
+        message: format!("Invalid content key in metadata table: {err}"),
+    })
+}
+
+/// Returns the content type of a content key stored as hex w/o the 0x prefix.
+fn parse_content_type(content_key: &str) -> Result<u8, ContentStoreError> {
+    content_key
+        .get(..2)
+        .and_then(|content_type| u8::from_str_radix(content_type, 16).ok())
+        .ok_or_else(|| ContentStoreError::InvalidData {
+            message: format!("Invalid content key in metadata table: {content_key}"),
+        })
+}
+
+// SQLite Statements
+/// SQLite statements on the content metadata table of a single subnetwork.
+#[derive(Debug)]
+struct MetadataQueries {
+    create_table: String,
+    count_distance_column: String,
+    add_distance_column: String,
+    fill_distance_column: String,
+    create_distance_index: String,
+    insert: String,
+    delete: String,
+    find_farthest: String,
+    content_key_lookup: String,
+    total_data_size: String,
+    total_entry_count: String,
+    paginate: String,
+    inventory: String,
+    inventory_stats: String,
+    content_size_lookup: String,
+    content_key_and_size_lookup: String,
+    content_keys_after: String,
+    all_content_ids: String,
+    disk_usage: String,
+    create_pins_table: String,
+    insert_pin: String,
+    delete_pin: String,
+    pin_lookup: String,
+    pinned_content_keys: String,
+}
+
+impl MetadataQueries {
+    fn new(table: &str, pinned_content_types: &[u8]) -> Self {
+        let pins_table = format!("{table}_pins");
+        // Content of the pinned content types is pinned without rows in the pins table. Content
+        // keys are stored as hex w/o the 0x prefix, so their first two characters are the type.
+        let unpinned_filter = if pinned_content_types.is_empty() {
+            format!("content_id_long NOT IN (SELECT content_id_long FROM {pins_table})")
+        } else {
+            let content_types: Vec<String> = pinned_content_types
+                .iter()
+                .map(|content_type| format!("'{content_type:02x}'"))
+                .collect();
+            format!(
+                "content_id_long NOT IN (SELECT content_id_long FROM {pins_table})
+                    AND substr(content_key, 1, 2) NOT IN ({})",
+                content_types.join(", ")
+            )
+        };
+        Self {
+            create_table: format!(
+                "CREATE TABLE IF NOT EXISTS {table} (
+                    content_id_long TEXT PRIMARY KEY,
+                    content_id_short INTEGER NOT NULL,
+                    content_key TEXT NOT NULL,
+                    content_size INTEGER,
+                    distance_short INTEGER
+                )"
+            ),
+            count_distance_column: format!(
+                "SELECT COUNT(*) FROM pragma_table_info('{table}') WHERE name = 'distance_short'"
+            ),
+            add_distance_column: format!("ALTER TABLE {table} ADD COLUMN distance_short INTEGER"),
+            fill_distance_column: format!(
+                "UPDATE {table}
+                    SET distance_short = ((?1 | content_id_short) - (?1 & content_id_short))
+                    WHERE distance_short IS NULL"
+            ),
+            create_distance_index: format!(
+                "CREATE INDEX IF NOT EXISTS {table}_distance_short_idx ON {table}(distance_short)"
+            ),
+            insert: format!(
+                "INSERT OR IGNORE INTO {table}
+                    (content_id_long, content_id_short, content_key, content_size, distance_short)
+                    VALUES (?1, ?2, ?3, ?4, ?5)"
+            ),
+            delete: format!("DELETE FROM {table} WHERE content_id_long = (?1)"),
+            find_farthest: format!(
+                "SELECT content_id_long, content_size FROM {table}
+                    WHERE {unpinned_filter}
+                    ORDER BY distance_short DESC LIMIT (?1)"
+            ),
+            content_key_lookup: format!(
+                "SELECT content_key FROM {table} WHERE content_id_long = (?1)"
+            ),
+            total_data_size: format!(
+                "SELECT TOTAL(content_size) FROM {table} WHERE {unpinned_filter}"
+            ),
+            total_entry_count: format!("SELECT COUNT(content_id_long) FROM {table}"),
+            paginate: format!(
+                "SELECT content_key FROM {table} ORDER BY content_key LIMIT :limit OFFSET :offset"
+            ),
+            inventory: format!("SELECT content_id_long, content_key, content_size FROM {table}"),
+            inventory_stats: format!(
+                "SELECT substr(content_key, 1, 2), COUNT(*), TOTAL(content_size) FROM {table}"
+            ),
+            content_size_lookup: format!(
+                "SELECT content_size FROM {table} WHERE content_id_long = (?1)"
+            ),
+            content_key_and_size_lookup: format!(
+                "SELECT content_key, content_size FROM {table} WHERE content_id_long = (?1)"
+            ),
+            all_content_ids: format!("SELECT content_id_long FROM {table}"),
+            // The dbstat virtual table lists the pages of every table and index of the database.
+            disk_usage: format!(
+                "SELECT TOTAL(pgsize) FROM dbstat WHERE name IN
+                    (SELECT name FROM sqlite_master WHERE tbl_name IN ('{table}', '{pins_table}'))"
+            ),
+            content_keys_after: format!(
+                "SELECT content_id_long, content_key FROM {table}
+                    WHERE content_id_long > (?1)
+                    ORDER BY content_id_long LIMIT (?2)"
+            ),
+            create_pins_table: format!(
+                "CREATE TABLE IF NOT EXISTS {pins_table} (
+                    content_id_long TEXT PRIMARY KEY,
+                    content_key TEXT NOT NULL
+                )"
+            ),
+            insert_pin: format!(
+                "INSERT OR IGNORE INTO {pins_table} (content_id_long, content_key) VALUES (?1, ?2)"
+            ),
+            delete_pin: format!("DELETE FROM {pins_table} WHERE content_id_long = (?1)"),
+            pin_lookup: format!("SELECT 1 FROM {pins_table} WHERE content_id_long = (?1)"),
+            pinned_content_keys: format!(
+                "SELECT content_key FROM {pins_table} ORDER BY content_key"
+            ),
+        }
+    }
+}
+
+// SQLite Result Containers
+struct DataSize {
+    num_bytes: f64,
+}
+
+struct EntryCount(u64);
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+pub mod test {
+
+    use super::*;
+
+    use discv5::enr::{CombinedKey, EnrBuilder};
+    use quickcheck::{quickcheck, QuickCheck, TestResult};
+    use rand::RngCore;
+    use serial_test::serial;
+    use std::sync::atomic::Ordering;
+
+    use crate::utils::db::{configure_node_data_dir, setup_temp_dir};
+    use trin_types::content_key::{BlockHeaderKey, IdentityContentKey};
+
+    const CAPACITY_MB: u64 = 2;
+
+    fn generate_random_content_key() -> IdentityContentKey {
+        let mut key = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        IdentityContentKey::new(key)
+    }
+
+    fn generate_random_history_content_key() -> HistoryContentKey {
+        let mut block_hash = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut block_hash);
+        HistoryContentKey::BlockHeaderWithProof(BlockHeaderKey { block_hash })
+    }
+
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_new() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+
+        let storage_config =
+            PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+
+        // Assert that configs match the storage object's fields
+        assert_eq!(storage.node_id, node_id);
+        assert_eq!(
+            storage.storage_capacity_in_bytes,
+            CAPACITY_MB * BYTES_IN_MB_U64
+        );
+        assert_eq!(storage.radius, Distance::MAX);
+
+        std::mem::drop(storage);
+        temp_dir.close()?;
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_store() {
+        fn test_store_random_bytes() -> TestResult {
+            let temp_dir = setup_temp_dir().unwrap();
+            let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+            let storage_config =
+                PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id)
+                    .unwrap();
+            let mut storage = PortalStorage::new(storage_config, ProtocolId::History).unwrap();
+            let content_key = generate_random_content_key();
+            let mut value = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut value);
+            storage.store(&content_key, &value.to_vec()).unwrap();
+
+            std::mem::drop(storage);
+            temp_dir.close().unwrap();
+
+            TestResult::passed()
+        }
+        QuickCheck::new()
+            .tests(10)
+            .quickcheck(test_store_random_bytes as fn() -> _);
+    }
+
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_get_data() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+        let storage_config =
+            PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        let content_key = generate_random_content_key();
+        let value: Vec<u8> = "OGFWs179fWnqmjvHQFGHszXloc3Wzdb4".into();
+        storage.store(&content_key, &value)?;
+
+        let result = storage.get(&content_key).unwrap().unwrap();
+
+        assert_eq!(result, value);
+
+        std::mem::drop(storage);
+        temp_dir.close()?;
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_put_batch_and_get_many() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+        let storage_config =
+            PortalStorageConfig::new(1, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+
+        // The batch overfills the store, which makes room for it once all content is put.
+        let items: Vec<(IdentityContentKey, Vec<u8>)> = (0..64)
+            .map(|_| (generate_random_content_key(), vec![0xef; 20_000]))
+            .collect();
+        let content_keys: Vec<IdentityContentKey> =
+            items.iter().map(|(key, _)| key.clone()).collect();
+        let results = storage.put_batch(items)?;
+        assert_eq!(results.len(), 64);
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert!(!storage.capacity_reached());
+        assert!(storage.radius < Distance::MAX);
+
+        let content = storage.get_many(&content_keys)?;
+        assert_eq!(content.len(), 64);
+        for (content_key, content) in content_keys.iter().zip(content) {
+            let is_within_radius = storage.distance_to_key(content_key) <= storage.radius;
+            assert_eq!(content.is_some(), is_within_radius);
+        }
+        assert_eq!(
+            storage.total_entry_count()?,
+            storage.get_total_storage_usage_in_bytes_from_network() / 20_000
+        );
+
+        std::mem::drop(storage);
+        temp_dir.close()?;
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_get_total_storage() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+        let storage_config =
+            PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+
+        let content_key = generate_random_content_key();
+        let value: Vec<u8> = "OGFWs179fWnqmjvHQFGHszXloc3Wzdb4".into();
+        storage.store(&content_key, &value)?;
+
+        let bytes = storage.get_total_storage_usage_in_bytes_from_network();
+
+        assert_eq!(32, bytes);
+
+        std::mem::drop(storage);
+        temp_dir.close()?;
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_get_total_storage_on_disk() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+        let storage_config =
+            PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let db = Arc::clone(&storage_config.db);
+        let mut storage = PortalStorage::new(storage_config.clone(), ProtocolId::History)?;
+        let state_storage = PortalStorage::new(storage_config, ProtocolId::State)?;
+
+        // The empty metadata table already takes some pages.
+        let empty_bytes = storage.get_total_storage_usage_in_bytes_on_disk()?;
+        assert!(empty_bytes > 0);
+        let state_empty_bytes = state_storage.get_total_storage_usage_in_bytes_on_disk()?;
+
+        for _ in 0..100 {
+            storage.store(&generate_random_content_key(), &vec![0xef; 10_000])?;
+        }
+        // Content only counts once RocksDB writes it to an SST file.
+        for column_family in [
+            PortalStorage::column_family_name(&ProtocolId::History),
+            PortalStorage::column_family_name(&ProtocolId::State),
+        ] {
+            db.flush_cf(db.cf_handle(column_family).unwrap())?;
+        }
+        let bytes = storage.get_total_storage_usage_in_bytes_on_disk()?;
+        assert!(bytes > 1_000_000);
+
+        // The content of a subnetwork does not count towards the usage of the others.
+        assert_eq!(
+            state_storage.get_total_storage_usage_in_bytes_on_disk()?,
+            state_empty_bytes
+        );
+
+        std::mem::drop(storage);
+        std::mem::drop(state_storage);
+        temp_dir.close()?;
+        Ok(())
     }
 
-    pub fn get_summary(&self) -> String {
-        let radius_percent = self.radius_ratio.get() * 100.0;
-        format!(
-            "radius={:.*}% content={:.1}/{}mb #={} disk={:.1}mb",
-            Self::precision_for_percentage(radius_percent),
-            radius_percent,
-            self.content_storage_usage_bytes.get() / BYTES_IN_MB_F64,
-            self.storage_capacity_bytes.get() / BYTES_IN_MB_F64,
-            self.entry_count.get(),
-            self.total_storage_usage_bytes.get() / BYTES_IN_MB_F64,
-        )
-    }
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_restarting_storage_with_decreased_capacity() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+        let storage_config =
+            PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+
+        for _ in 0..50 {
+            let content_key = generate_random_content_key();
+            let value: Vec<u8> = vec![0; 32000];
+            storage.store(&content_key, &value)?;
+        }
+
+        let bytes = storage.get_total_storage_usage_in_bytes_from_network();
+        assert_eq!(1600000, bytes); // 32kb * 50
+        assert_eq!(storage.radius, Distance::MAX);
+        std::mem::drop(storage);
+
+        // test with 1mb capacity
+        let new_storage_config =
+            PortalStorageConfig::new(1, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let new_storage = PortalStorage::new(new_storage_config, ProtocolId::History)?;
+
+        // test that previously set value has been pruned
+        let bytes = new_storage.get_total_storage_usage_in_bytes_from_network();
+        assert_eq!(1024000, bytes);
+        assert_eq!(32, new_storage.total_entry_count().unwrap());
+        assert_eq!(new_storage.storage_capacity_in_bytes, BYTES_IN_MB_U64);
+        // test that radius has decreased now that we're at capacity
+        assert!(new_storage.radius < Distance::MAX);
+        std::mem::drop(new_storage);
+
+        // test with 0mb capacity
+        let new_storage_config =
+            PortalStorageConfig::new(0, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let new_storage = PortalStorage::new(new_storage_config, ProtocolId::History)?;
+
+        // test that previously set value has been pruned
+        assert_eq!(new_storage.storage_capacity_in_bytes, 0);
+        assert_eq!(new_storage.radius, Distance::ZERO);
+        std::mem::drop(new_storage);
 
-    fn precision_for_percentage(percent: f64) -> usize {
-        match percent {
-            x if x >= 10.0 => 0,
-            x if x >= 1.0 => 1,
-            x if x >= 0.1 => 2,
-            x if x >= 0.01 => 3,
-            _ => 4,
-        }
+        temp_dir.close()?;
+        Ok(())
     }
-}
 
-// SQLite Statements
-const CREATE_QUERY: &str = "CREATE TABLE IF NOT EXISTS content_metadata (
-                                content_id_long TEXT PRIMARY KEY,
-                                content_id_short INTEGER NOT NULL,
-                                content_key TEXT NOT NULL,
-                                content_size INTEGER
-                            );
-                            CREATE INDEX content_size_idx ON content_metadata(content_size);
-                            CREATE INDEX content_id_short_idx ON content_metadata(content_id_short);
-                            CREATE INDEX content_id_long_idx ON content_metadata(content_id_long);";
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_restarting_full_storage_with_same_capacity() -> Result<(), ContentStoreError> {
+        // test a node that gets full and then restarts with the same capacity
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
 
-const INSERT_QUERY: &str =
-    "INSERT OR IGNORE INTO content_metadata (content_id_long, content_id_short, content_key, content_size)
-                            VALUES (?1, ?2, ?3, ?4)";
+        let min_capacity = 1;
+        // Use a tiny storage capacity, to fill up as quickly as possible
+        let storage_config =
+            PortalStorageConfig::new(min_capacity, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config.clone(), ProtocolId::History)?;
 
-const DELETE_QUERY: &str = "DELETE FROM content_metadata
-                            WHERE content_id_long = (?1)";
+        // Fill up the storage.
+        for _ in 0..32 {
+            let content_key = generate_random_content_key();
+            let value: Vec<u8> = vec![0; 32000];
+            storage.store(&content_key, &value)?;
+            // Speed up the test by ending the loop as soon as possible
+            if storage.capacity_reached() {
+                break;
+            }
+        }
+        assert!(storage.capacity_reached());
 
-const XOR_FIND_FARTHEST_QUERY: &str = "SELECT
-                                    content_id_long
-                                    FROM content_metadata
-                                    ORDER BY ((?1 | content_id_short) - (?1 & content_id_short)) DESC";
+        // Save the number of items, to compare with the restarted storage
+        let total_entry_count = storage.total_entry_count().unwrap();
+        // Save the radius, to compare with the restarted storage
+        let radius = storage.radius;
+        assert!(radius < Distance::MAX);
 
-const CONTENT_KEY_LOOKUP_QUERY: &str =
-    "SELECT content_key FROM content_metadata WHERE content_id_long = (?1)";
+        // Restart a filled-up store with the same capacity
+        let new_storage = PortalStorage::new(storage_config, ProtocolId::History)?;
 
-const TOTAL_DATA_SIZE_QUERY: &str = "SELECT TOTAL(content_size) FROM content_metadata";
+        // The restarted store should have the same number of items
+        assert_eq!(total_entry_count, new_storage.total_entry_count().unwrap());
+        // The restarted store should be full
+        assert!(new_storage.capacity_reached());
+        // The restarted store should have the same radius as the original
+        assert_eq!(radius, new_storage.radius);
 
-const TOTAL_ENTRY_COUNT_QUERY: &str = "SELECT COUNT(content_id_long) FROM content_metadata";
+        temp_dir.close()?;
+        Ok(())
+    }
 
-const PAGINATE_QUERY: &str =
-    "SELECT content_key FROM content_metadata ORDER BY content_key LIMIT :limit OFFSET :offset";
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_restarting_storage_with_increased_capacity() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let (node_data_dir, mut private_key) =
+            configure_node_data_dir(temp_dir.path().to_path_buf(), None).unwrap();
+        let private_key = CombinedKey::secp256k1_from_bytes(private_key.0.as_mut_slice()).unwrap();
+        let node_id = EnrBuilder::new("v4").build(&private_key).unwrap().node_id();
+        let storage_config =
+            PortalStorageConfig::new(CAPACITY_MB, node_data_dir.clone(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
 
-const CONTENT_SIZE_LOOKUP_QUERY: &str =
-    "SELECT content_size FROM content_metadata WHERE content_id_long = (?1)";
+        for _ in 0..50 {
+            let content_key = generate_random_content_key();
+            let value: Vec<u8> = vec![0; 32000];
+            storage.store(&content_key, &value)?;
+        }
 
-// SQLite Result Containers
-struct ContentId {
-    id_long: Vec<u8>,
-}
+        let bytes = storage.get_total_storage_usage_in_bytes_from_network();
+        assert_eq!(1600000, bytes); // 32kb * 50
+        assert_eq!(storage.radius, Distance::MAX);
+        // Save the number of items, to compare with the restarted storage
+        let total_entry_count = storage.total_entry_count().unwrap();
+        std::mem::drop(storage);
 
-struct DataSize {
-    num_bytes: f64,
-}
+        // test with increased capacity
+        let new_storage_config =
+            PortalStorageConfig::new(2 * CAPACITY_MB, node_data_dir, node_id).unwrap();
+        let new_storage = PortalStorage::new(new_storage_config, ProtocolId::History)?;
 
-struct EntryCount(u64);
+        // test that previously set value has not been pruned
+        let bytes = new_storage.get_total_storage_usage_in_bytes_from_network();
+        assert_eq!(1600000, bytes);
+        assert_eq!(new_storage.total_entry_count().unwrap(), total_entry_count);
+        assert_eq!(
+            new_storage.storage_capacity_in_bytes,
+            2 * CAPACITY_MB * BYTES_IN_MB_U64
+        );
+        // test that radius is at max
+        assert_eq!(new_storage.radius, Distance::MAX);
+        std::mem::drop(new_storage);
 
-#[cfg(test)]
-#[allow(clippy::unwrap_used)]
-pub mod test {
+        temp_dir.close()?;
+        Ok(())
+    }
 
-    use super::*;
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_new_storage_with_zero_capacity() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+        let storage_config =
+            PortalStorageConfig::new(0, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
 
-    use discv5::enr::{CombinedKey, EnrBuilder};
-    use quickcheck::{quickcheck, QuickCheck, TestResult};
-    use rand::RngCore;
-    use serial_test::serial;
+        let content_key = generate_random_content_key();
+        let value: Vec<u8> = "OGFWs179fWnqmjvHQFGHszXloc3Wzdb4".into();
+        assert!(storage.store(&content_key, &value).is_err());
 
-    use crate::utils::db::{configure_node_data_dir, setup_temp_dir};
-    use trin_types::content_key::IdentityContentKey;
+        let bytes = storage.get_total_storage_usage_in_bytes_from_network();
 
-    const CAPACITY_MB: u64 = 2;
+        assert_eq!(0, bytes);
+        assert_eq!(storage.radius, Distance::ZERO);
 
-    fn generate_random_content_key() -> IdentityContentKey {
-        let mut key = [0u8; 32];
-        rand::thread_rng().fill_bytes(&mut key);
-        IdentityContentKey::new(key)
+        std::mem::drop(storage);
+        temp_dir.close()?;
+        Ok(())
     }
 
     #[test_log::test(tokio::test)]
     #[serial]
-    async fn test_new() -> Result<(), ContentStoreError> {
+    async fn test_find_farthest_empty_db() -> Result<(), ContentStoreError> {
         let temp_dir = setup_temp_dir().unwrap();
         let node_id = get_active_node_id(temp_dir.path().to_path_buf());
-
         let storage_config =
             PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
         let storage = PortalStorage::new(storage_config, ProtocolId::History)?;
 
-        // Assert that configs match the storage object's fields
-        assert_eq!(storage.node_id, node_id);
-        assert_eq!(
-            storage.storage_capacity_in_bytes,
-            CAPACITY_MB * BYTES_IN_MB_U64
-        );
-        assert_eq!(storage.radius, Distance::MAX);
+        let result = storage.find_farthest_content_id()?;
+        assert!(result.is_none());
 
         std::mem::drop(storage);
         temp_dir.close()?;
@@ -985,44 +3128,315 @@ pub mod test {
 
     #[test_log::test(tokio::test)]
     #[serial]
-    async fn test_store() {
-        fn test_store_random_bytes() -> TestResult {
+    async fn test_find_farthest() {
+        fn prop(x: IdentityContentKey, y: IdentityContentKey) -> TestResult {
             let temp_dir = setup_temp_dir().unwrap();
             let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+
+            let val = vec![0x00, 0x01, 0x02, 0x03, 0x04];
             let storage_config =
                 PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id)
                     .unwrap();
             let mut storage = PortalStorage::new(storage_config, ProtocolId::History).unwrap();
-            let content_key = generate_random_content_key();
-            let mut value = [0u8; 32];
-            rand::thread_rng().fill_bytes(&mut value);
-            storage.store(&content_key, &value.to_vec()).unwrap();
+            storage.store(&x, &val).unwrap();
+            storage.store(&y, &val).unwrap();
+
+            let expected_farthest = if storage.distance_to_content_id(&x.content_id())
+                > storage.distance_to_content_id(&y.content_id())
+            {
+                x.content_id()
+            } else {
+                y.content_id()
+            };
+
+            let farthest = storage.find_farthest_content_id();
 
             std::mem::drop(storage);
             temp_dir.close().unwrap();
 
-            TestResult::passed()
+            TestResult::from_bool(farthest.unwrap().unwrap() == expected_farthest)
         }
-        QuickCheck::new()
-            .tests(10)
-            .quickcheck(test_store_random_bytes as fn() -> _);
+
+        quickcheck(prop as fn(IdentityContentKey, IdentityContentKey) -> TestResult);
+    }
+
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_content_keys_after() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+        let storage_config =
+            PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+
+        let mut content_keys: Vec<IdentityContentKey> =
+            (0..5).map(|_| generate_random_content_key()).collect();
+        for content_key in content_keys.iter() {
+            storage.store(content_key, &vec![0xef])?;
+        }
+        content_keys.sort_by_key(|content_key| content_key.content_id());
+
+        // Page through all content, two items at a time.
+        let mut found = vec![];
+        let mut after = None;
+        loop {
+            let page = storage.content_keys_after(after, 2)?;
+            if page.is_empty() {
+                break;
+            }
+            assert!(page.len() <= 2);
+            after = page.last().map(|(id, _)| *id);
+            found.extend(page);
+        }
+
+        let expected: Vec<([u8; 32], Vec<u8>)> = content_keys
+            .into_iter()
+            .map(|content_key| (content_key.content_id(), content_key.into()))
+            .collect();
+        assert_eq!(found, expected);
+
+        std::mem::drop(storage);
+        temp_dir.close()?;
+        Ok(())
+    }
+
+    /// Stores 40 content items of content types 0 to 3 and of sizes 1 to 40 bytes, and returns
+    /// their entries in ascending order of content id.
+    fn store_inventory_content(
+        storage: &mut SubnetworkStore,
+        node_id: NodeId,
+    ) -> Result<Vec<LocalContentEntry>, ContentStoreError> {
+        let mut entries = vec![];
+        for i in 0..40_u8 {
+            let mut key = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            key[0] = i % 4;
+            let content_key = IdentityContentKey::new(key);
+            let size = usize::from(i) + 1;
+            storage.put(content_key, vec![0xef; size])?;
+            entries.push(LocalContentEntry {
+                content_key: hex_encode(key),
+                content_id: H256::from(key),
+                content_size: size as u64,
+                distance: *XorMetric::distance(&key, &node_id.raw()),
+            });
+        }
+        entries.sort_by_key(|entry| entry.content_id);
+        Ok(entries)
+    }
+
+    /// Returns all content matching `filter`, paging through it `limit` items at a time.
+    fn collect_inventory(
+        storage: &SubnetworkStore,
+        filter: &LocalContentFilter,
+        limit: u64,
+    ) -> Result<Vec<LocalContentEntry>, ContentStoreError> {
+        let mut found = vec![];
+        let mut cursor = None;
+        loop {
+            let page = storage.inventory(filter, cursor, limit)?;
+            assert!(page.entries.len() as u64 <= limit);
+            found.extend(page.entries);
+            match page.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor.to_fixed_bytes()),
+                None => return Ok(found),
+            }
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_inventory() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+        let storage_config =
+            PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage =
+            SubnetworkStore::Disk(PortalStorage::new(storage_config, ProtocolId::History)?);
+        check_inventory(&mut storage, node_id)?;
+
+        std::mem::drop(storage);
+        temp_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn memory_store_inventory() -> Result<(), ContentStoreError> {
+        let node_id = NodeId::random();
+        let mut store = SubnetworkStore::Memory(MemoryContentStore::from_config(
+            MemoryContentStoreConfig::new(CAPACITY_MB, node_id),
+            &ProtocolId::History,
+        ));
+        check_inventory(&mut store, node_id)
+    }
+
+    /// Checks that listing and aggregating the content of `storage` with various filters finds
+    /// the expected content, whether the content is on disk or in memory.
+    fn check_inventory(
+        storage: &mut SubnetworkStore,
+        node_id: NodeId,
+    ) -> Result<(), ContentStoreError> {
+        let entries = store_inventory_content(storage, node_id)?;
+
+        let all_content = collect_inventory(storage, &LocalContentFilter::default(), 7)?;
+        assert_eq!(all_content, entries);
+
+        // The median distance is exact, not only on the precomputed most significant bytes.
+        let mut distances: Vec<U256> = entries.iter().map(|entry| entry.distance).collect();
+        distances.sort();
+        let median_distance = distances[distances.len() / 2];
+        let farthest_content_type = entries
+            .iter()
+            .max_by_key(|entry| entry.distance)
+            .unwrap()
+            .content_id
+            .as_bytes()[0];
+        let prefix = entries[0].content_id.as_bytes()[..1].to_vec();
+        let filters = [
+            LocalContentFilter {
+                content_types: vec![1, 3],
+                ..Default::default()
+            },
+            LocalContentFilter {
+                max_distance: Some(median_distance),
+                ..Default::default()
+            },
+            LocalContentFilter {
+                min_distance: Some(median_distance),
+                content_types: vec![farthest_content_type],
+                ..Default::default()
+            },
+            LocalContentFilter {
+                content_id_prefix: Some(hex_encode(prefix.clone())),
+                ..Default::default()
+            },
+            LocalContentFilter {
+                min_size: Some(10),
+                max_size: Some(20),
+                ..Default::default()
+            },
+        ];
+        for filter in filters {
+            let expected: Vec<LocalContentEntry> = entries
+                .iter()
+                .filter(|entry| {
+                    let content_type = entry.content_id.as_bytes()[0];
+                    (filter.content_types.is_empty()
+                        || filter.content_types.contains(&content_type))
+                        && filter
+                            .min_distance
+                            .map_or(true, |min| entry.distance >= min)
+                        && filter
+                            .max_distance
+                            .map_or(true, |max| entry.distance <= max)
+                        && (filter.content_id_prefix.is_none()
+                            || entry.content_id.as_bytes().starts_with(&prefix))
+                        && filter
+                            .min_size
+                            .map_or(true, |min| entry.content_size >= min)
+                        && filter
+                            .max_size
+                            .map_or(true, |max| entry.content_size <= max)
+                })
+                .cloned()
+                .collect();
+            assert!(!expected.is_empty());
+            assert_eq!(collect_inventory(storage, &filter, 3)?, expected);
+
+            let mut expected_stats = LocalContentStats::default();
+            for entry in expected.iter() {
+                expected_stats.add(entry.content_id.as_bytes()[0], 1, entry.content_size);
+            }
+            assert_eq!(storage.inventory_stats(&filter)?, expected_stats);
+        }
+
+        assert!(storage
+            .inventory(&LocalContentFilter::default(), None, 0)
+            .is_err());
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_evicts_farthest_content_in_bands() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+        let storage_config =
+            PortalStorageConfig::new(3, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config.clone(), ProtocolId::History)?;
+
+        let mut content_keys: Vec<IdentityContentKey> =
+            (0..2500).map(|_| generate_random_content_key()).collect();
+        for content_key in content_keys.iter() {
+            storage.store(content_key, &vec![0; 1000])?;
+        }
+        assert_eq!(storage.total_entry_count()?, 2500);
+        std::mem::drop(storage);
+
+        // Restart with less capacity, which evicts several bands.
+        let storage_config = PortalStorageConfig {
+            storage_capacity_mb: 1,
+            ..storage_config
+        };
+        let storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        assert_eq!(storage.total_entry_count()?, 1000);
+        assert_eq!(
+            storage.get_total_storage_usage_in_bytes_from_network(),
+            1_000_000
+        );
+
+        // The closest content is kept, and the radius is the distance to the farthest of it.
+        content_keys.sort_by_key(|content_key| storage.distance_to_key(content_key));
+        let (kept, evicted) = content_keys.split_at(1000);
+        for content_key in kept {
+            assert!(storage.get(content_key)?.is_some());
+        }
+        for content_key in evicted {
+            assert!(storage.get(content_key)?.is_none());
+        }
+        assert_eq!(storage.radius, storage.distance_to_key(&kept[999]));
+
+        std::mem::drop(storage);
+        temp_dir.close()?;
+        Ok(())
     }
 
     #[test_log::test(tokio::test)]
     #[serial]
-    async fn test_get_data() -> Result<(), ContentStoreError> {
+    async fn test_adds_distances_to_existing_metadata_table() -> Result<(), ContentStoreError> {
         let temp_dir = setup_temp_dir().unwrap();
         let node_id = get_active_node_id(temp_dir.path().to_path_buf());
         let storage_config =
             PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
-        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
-        let content_key = generate_random_content_key();
-        let value: Vec<u8> = "OGFWs179fWnqmjvHQFGHszXloc3Wzdb4".into();
-        storage.store(&content_key, &value)?;
 
-        let result = storage.get(&content_key).unwrap().unwrap();
+        // A metadata table that predates precomputed distances.
+        let content_key = generate_random_history_content_key();
+        let content_id = content_key.content_id();
+        let content_key: Vec<u8> = content_key.into();
+        let conn = storage_config.sql_connection_pool.get()?;
+        conn.execute(
+            "CREATE TABLE content_metadata (
+                content_id_long TEXT PRIMARY KEY,
+                content_id_short INTEGER NOT NULL,
+                content_key TEXT NOT NULL,
+                content_size INTEGER
+            )",
+            params![],
+        )?;
+        conn.execute(
+            "INSERT INTO content_metadata VALUES (?1, ?2, ?3, ?4)",
+            params![
+                content_id.to_vec(),
+                PortalStorage::byte_vector_to_u32(content_id.to_vec()),
+                hex_encode(content_key).trim_start_matches("0x"),
+                32
+            ],
+        )?;
+        std::mem::drop(conn);
 
-        assert_eq!(result, value);
+        let storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        assert_eq!(storage.find_farthest_content(1)?, vec![(content_id, 32)]);
+        assert_eq!(storage.get_total_storage_usage_in_bytes_from_network(), 32);
 
         std::mem::drop(storage);
         temp_dir.close()?;
@@ -1031,20 +3445,58 @@ pub mod test {
 
     #[test_log::test(tokio::test)]
     #[serial]
-    async fn test_get_total_storage() -> Result<(), ContentStoreError> {
+    async fn test_evicts_foreign_content_from_legacy_history_store() -> Result<(), ContentStoreError>
+    {
         let temp_dir = setup_temp_dir().unwrap();
         let node_id = get_active_node_id(temp_dir.path().to_path_buf());
         let storage_config =
             PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
-        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
-
-        let content_key = generate_random_content_key();
-        let value: Vec<u8> = "OGFWs179fWnqmjvHQFGHszXloc3Wzdb4".into();
-        storage.store(&content_key, &value)?;
 
-        let bytes = storage.get_total_storage_usage_in_bytes_from_network()?;
+        // The shared table and column family from before subnetworks had their own namespaces.
+        let history_key = generate_random_history_content_key();
+        let foreign_key = generate_random_content_key();
+        let conn = storage_config.sql_connection_pool.get()?;
+        conn.execute(
+            "CREATE TABLE content_metadata (
+                content_id_long TEXT PRIMARY KEY,
+                content_id_short INTEGER NOT NULL,
+                content_key TEXT NOT NULL,
+                content_size INTEGER
+            )",
+            params![],
+        )?;
+        let history_id = history_key.content_id();
+        let foreign_id = foreign_key.content_id();
+        let rows: [([u8; 32], Vec<u8>); 2] = [
+            (history_id, history_key.clone().into()),
+            (foreign_id, foreign_key.clone().into()),
+        ];
+        for (content_id, content_key) in rows {
+            conn.execute(
+                "INSERT INTO content_metadata VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    content_id.to_vec(),
+                    PortalStorage::byte_vector_to_u32(content_id.to_vec()),
+                    hex_encode(content_key).trim_start_matches("0x"),
+                    32
+                ],
+            )?;
+            storage_config.db.put(content_id, vec![0xef; 32])?;
+        }
+        std::mem::drop(conn);
 
-        assert_eq!(32, bytes);
+        let storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        assert_eq!(storage.total_entry_count()?, 1);
+        assert!(storage.get(&history_key)?.is_some());
+        assert!(storage.get(&foreign_key)?.is_none());
+        assert!(storage.lookup_content_key(foreign_id)?.is_none());
+        assert_eq!(
+            storage.lookup_content_key(history_id)?,
+            Some(history_key.clone().into())
+        );
+        let page = storage.paginate::<HistoryContentKey>(&0, &10)?;
+        assert_eq!(page.content_keys, vec![history_key]);
+        assert_eq!(page.total_entries, 1);
 
         std::mem::drop(storage);
         temp_dir.close()?;
@@ -1053,160 +3505,192 @@ pub mod test {
 
     #[test_log::test(tokio::test)]
     #[serial]
-    async fn test_restarting_storage_with_decreased_capacity() -> Result<(), ContentStoreError> {
+    async fn test_subnetworks_are_isolated() -> Result<(), ContentStoreError> {
         let temp_dir = setup_temp_dir().unwrap();
         let node_id = get_active_node_id(temp_dir.path().to_path_buf());
         let storage_config =
             PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
-        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        let mut history_storage = PortalStorage::new(storage_config.clone(), ProtocolId::History)?;
+        let state_config = PortalStorageConfig {
+            storage_capacity_mb: 1,
+            ..storage_config
+        };
+        let mut state_storage = PortalStorage::new(state_config, ProtocolId::State)?;
 
-        for _ in 0..50 {
+        // The same content key may be stored by both networks, with different content.
+        let content_key = generate_random_content_key();
+        history_storage.store(&content_key, &vec![0x01])?;
+        assert_eq!(state_storage.get(&content_key)?, None);
+        state_storage.store(&content_key, &vec![0x02])?;
+        assert_eq!(history_storage.get(&content_key)?, Some(vec![0x01]));
+        assert_eq!(state_storage.get(&content_key)?, Some(vec![0x02]));
+
+        // Filling up the state network does not evict history content.
+        for _ in 0..40 {
             let content_key = generate_random_content_key();
-            let value: Vec<u8> = vec![0; 32000];
-            storage.store(&content_key, &value)?;
+            let _ = state_storage.store(&content_key, &vec![0; 32000]);
         }
+        assert!(state_storage.radius < Distance::MAX);
+        assert_eq!(history_storage.radius, Distance::MAX);
+        assert_eq!(history_storage.total_entry_count()?, 1);
+        assert_eq!(history_storage.get(&content_key)?, Some(vec![0x01]));
 
-        let bytes = storage.get_total_storage_usage_in_bytes_from_network()?;
-        assert_eq!(1600000, bytes); // 32kb * 50
-        assert_eq!(storage.radius, Distance::MAX);
-        std::mem::drop(storage);
-
-        // test with 1mb capacity
-        let new_storage_config =
-            PortalStorageConfig::new(1, temp_dir.path().to_path_buf(), node_id).unwrap();
-        let new_storage = PortalStorage::new(new_storage_config, ProtocolId::History)?;
+        std::mem::drop(history_storage);
+        std::mem::drop(state_storage);
+        temp_dir.close()?;
+        Ok(())
+    }
 
-        // test that previously set value has been pruned
-        let bytes = new_storage.get_total_storage_usage_in_bytes_from_network()?;
-        assert_eq!(1024000, bytes);
-        assert_eq!(32, new_storage.total_entry_count().unwrap());
-        assert_eq!(new_storage.storage_capacity_in_bytes, BYTES_IN_MB_U64);
-        // test that radius has decreased now that we're at capacity
-        assert!(new_storage.radius < Distance::MAX);
-        std::mem::drop(new_storage);
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_recovers_interrupted_store() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+        let storage_config =
+            PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config.clone(), ProtocolId::History)?;
 
-        // test with 0mb capacity
-        let new_storage_config =
-            PortalStorageConfig::new(0, temp_dir.path().to_path_buf(), node_id).unwrap();
-        let new_storage = PortalStorage::new(new_storage_config, ProtocolId::History)?;
+        let stored_key = generate_random_content_key();
+        storage.store(&stored_key, &vec![0x01; 32])?;
+
+        // Crash after writing the values, before writing the metadata: once for new content,
+        // and once for content that was already stored.
+        let interrupted_key = generate_random_content_key();
+        storage.interrupt_next_write.store(true, Ordering::Relaxed);
+        assert!(storage
+            .put_batch(vec![
+                (interrupted_key.clone(), vec![0x02; 32]),
+                (stored_key.clone(), vec![0x01; 32]),
+            ])
+            .is_err());
+        std::mem::drop(storage);
 
-        // test that previously set value has been pruned
-        assert_eq!(new_storage.storage_capacity_in_bytes, 0);
-        assert_eq!(new_storage.radius, Distance::ZERO);
-        std::mem::drop(new_storage);
+        let storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        assert_eq!(storage.get(&interrupted_key)?, None);
+        assert_eq!(storage.get(&stored_key)?, Some(vec![0x01; 32]));
+        assert_eq!(storage.total_entry_count()?, 1);
+        assert_eq!(storage.get_total_storage_usage_in_bytes_from_network(), 32);
+        // The journal is cleared.
+        assert_eq!(storage.recover_interrupted_writes()?, 0);
 
+        std::mem::drop(storage);
         temp_dir.close()?;
         Ok(())
     }
 
     #[test_log::test(tokio::test)]
     #[serial]
-    async fn test_restarting_full_storage_with_same_capacity() -> Result<(), ContentStoreError> {
-        // test a node that gets full and then restarts with the same capacity
+    async fn test_recovers_interrupted_eviction() -> Result<(), ContentStoreError> {
         let temp_dir = setup_temp_dir().unwrap();
         let node_id = get_active_node_id(temp_dir.path().to_path_buf());
-
-        let min_capacity = 1;
-        // Use a tiny storage capacity, to fill up as quickly as possible
         let storage_config =
-            PortalStorageConfig::new(min_capacity, temp_dir.path().to_path_buf(), node_id).unwrap();
+            PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
         let mut storage = PortalStorage::new(storage_config.clone(), ProtocolId::History)?;
 
-        // Fill up the storage.
-        for _ in 0..32 {
-            let content_key = generate_random_content_key();
-            let value: Vec<u8> = vec![0; 32000];
-            storage.store(&content_key, &value)?;
-            // Speed up the test by ending the loop as soon as possible
-            if storage.capacity_reached()? {
-                break;
-            }
+        let content_keys: Vec<IdentityContentKey> =
+            (0..3).map(|_| generate_random_content_key()).collect();
+        for content_key in content_keys.iter() {
+            storage.store(content_key, &vec![0x01; 32])?;
         }
-        assert!(storage.capacity_reached()?);
-
-        // Save the number of items, to compare with the restarted storage
-        let total_entry_count = storage.total_entry_count().unwrap();
-        // Save the radius, to compare with the restarted storage
-        let radius = storage.radius;
-        assert!(radius < Distance::MAX);
 
-        // Restart a filled-up store with the same capacity
-        let new_storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        // Crash after deleting the metadata of the first content, before deleting its value.
+        let evicted_id = content_keys[0].content_id();
+        storage
+            .db
+            .put_cf(storage.journal()?, storage.journal_key(&evicted_id), [])?;
+        storage
+            .sql_connection_pool
+            .get()?
+            .execute(&storage.queries.delete, [evicted_id.to_vec()])?;
+        // Lose the value of the second content, whose metadata is left behind.
+        let lost_id = content_keys[1].content_id();
+        storage
+            .db
+            .put_cf(storage.journal()?, storage.journal_key(&lost_id), [])?;
+        storage.db.delete_cf(storage.column_family()?, lost_id)?;
+        std::mem::drop(storage);
 
-        // The restarted store should have the same number of items
-        assert_eq!(total_entry_count, new_storage.total_entry_count().unwrap());
-        // The restarted store should be full
-        assert!(new_storage.capacity_reached()?);
-        // The restarted store should have the same radius as the original
-        assert_eq!(radius, new_storage.radius);
+        let storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        assert_eq!(storage.get(&content_keys[0])?, None);
+        assert_eq!(storage.get(&content_keys[1])?, None);
+        assert_eq!(storage.get(&content_keys[2])?, Some(vec![0x01; 32]));
+        // Only the metadata of the third content is left.
+        assert_eq!(storage.total_entry_count()?, 1);
+        assert_eq!(storage.get_total_storage_usage_in_bytes_from_network(), 32);
 
+        std::mem::drop(storage);
         temp_dir.close()?;
         Ok(())
     }
 
     #[test_log::test(tokio::test)]
     #[serial]
-    async fn test_restarting_storage_with_increased_capacity() -> Result<(), ContentStoreError> {
+    async fn test_recovers_store_interrupted_between_databases() -> Result<(), ContentStoreError> {
         let temp_dir = setup_temp_dir().unwrap();
-        let (node_data_dir, mut private_key) =
-            configure_node_data_dir(temp_dir.path().to_path_buf(), None).unwrap();
-        let private_key = CombinedKey::secp256k1_from_bytes(private_key.0.as_mut_slice()).unwrap();
-        let node_id = EnrBuilder::new("v4").build(&private_key).unwrap().node_id();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
         let storage_config =
-            PortalStorageConfig::new(CAPACITY_MB, node_data_dir.clone(), node_id).unwrap();
-        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
-
-        for _ in 0..50 {
-            let content_key = generate_random_content_key();
-            let value: Vec<u8> = vec![0; 32000];
-            storage.store(&content_key, &value)?;
-        }
+            PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config.clone(), ProtocolId::History)?;
 
-        let bytes = storage.get_total_storage_usage_in_bytes_from_network()?;
-        assert_eq!(1600000, bytes); // 32kb * 50
-        assert_eq!(storage.radius, Distance::MAX);
-        // Save the number of items, to compare with the restarted storage
-        let total_entry_count = storage.total_entry_count().unwrap();
+        let stored_key = generate_random_content_key();
+        storage.store(&stored_key, &vec![0x01; 32])?;
+
+        // The value is written, but the node stops before writing the metadata.
+        let interrupted_key = generate_random_content_key();
+        storage.interrupt_next_write.store(true, Ordering::Relaxed);
+        assert!(storage.store(&interrupted_key, &vec![0x02; 32]).is_err());
+        let interrupted_id = interrupted_key.content_id();
+        assert!(storage
+            .db
+            .get_pinned_cf(storage.column_family()?, interrupted_id)?
+            .is_some());
         std::mem::drop(storage);
 
-        // test with increased capacity
-        let new_storage_config =
-            PortalStorageConfig::new(2 * CAPACITY_MB, node_data_dir, node_id).unwrap();
-        let new_storage = PortalStorage::new(new_storage_config, ProtocolId::History)?;
-
-        // test that previously set value has not been pruned
-        let bytes = new_storage.get_total_storage_usage_in_bytes_from_network()?;
-        assert_eq!(1600000, bytes);
-        assert_eq!(new_storage.total_entry_count().unwrap(), total_entry_count);
-        assert_eq!(
-            new_storage.storage_capacity_in_bytes,
-            2 * CAPACITY_MB * BYTES_IN_MB_U64
-        );
-        // test that radius is at max
-        assert_eq!(new_storage.radius, Distance::MAX);
-        std::mem::drop(new_storage);
+        let storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        assert!(storage
+            .db
+            .get_pinned_cf(storage.column_family()?, interrupted_id)?
+            .is_none());
+        assert_eq!(storage.get(&stored_key)?, Some(vec![0x01; 32]));
+        assert_eq!(storage.total_entry_count()?, 1);
+        assert_eq!(storage.get_total_storage_usage_in_bytes_from_network(), 32);
+        assert_eq!(storage.recover_interrupted_writes()?, 0);
 
+        std::mem::drop(storage);
         temp_dir.close()?;
         Ok(())
     }
 
     #[test_log::test(tokio::test)]
     #[serial]
-    async fn test_new_storage_with_zero_capacity() -> Result<(), ContentStoreError> {
+    async fn test_recovers_eviction_interrupted_between_databases() -> Result<(), ContentStoreError>
+    {
         let temp_dir = setup_temp_dir().unwrap();
         let node_id = get_active_node_id(temp_dir.path().to_path_buf());
         let storage_config =
-            PortalStorageConfig::new(0, temp_dir.path().to_path_buf(), node_id).unwrap();
-        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+            PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config.clone(), ProtocolId::History)?;
 
-        let content_key = generate_random_content_key();
-        let value: Vec<u8> = "OGFWs179fWnqmjvHQFGHszXloc3Wzdb4".into();
-        assert!(storage.store(&content_key, &value).is_err());
+        let content_keys: Vec<IdentityContentKey> =
+            (0..3).map(|_| generate_random_content_key()).collect();
+        for content_key in content_keys.iter() {
+            storage.store(content_key, &vec![0x01; 32])?;
+        }
 
-        let bytes = storage.get_total_storage_usage_in_bytes_from_network()?;
+        // The metadata is deleted, but the node stops before deleting the values.
+        let evicted_ids = [content_keys[0].content_id(), content_keys[1].content_id()];
+        storage.interrupt_next_write.store(true, Ordering::Relaxed);
+        assert!(storage.evict_batch(&evicted_ids).is_err());
+        assert_eq!(storage.total_entry_count()?, 1);
+        assert!(storage.get(&content_keys[0])?.is_some());
+        std::mem::drop(storage);
 
-        assert_eq!(0, bytes);
-        assert_eq!(storage.radius, Distance::ZERO);
+        let storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        assert_eq!(storage.get(&content_keys[0])?, None);
+        assert_eq!(storage.get(&content_keys[1])?, None);
+        assert_eq!(storage.get(&content_keys[2])?, Some(vec![0x01; 32]));
+        assert_eq!(storage.total_entry_count()?, 1);
+        assert_eq!(storage.get_total_storage_usage_in_bytes_from_network(), 32);
 
         std::mem::drop(storage);
         temp_dir.close()?;
@@ -1215,15 +3699,50 @@ pub mod test {
 
     #[test_log::test(tokio::test)]
     #[serial]
-    async fn test_find_farthest_empty_db() -> Result<(), ContentStoreError> {
+    async fn test_reconciles_databases_once() -> Result<(), ContentStoreError> {
         let temp_dir = setup_temp_dir().unwrap();
         let node_id = get_active_node_id(temp_dir.path().to_path_buf());
         let storage_config =
             PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
-        let storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        let mut storage = PortalStorage::new(storage_config.clone(), ProtocolId::History)?;
+        let stored_key = generate_random_content_key();
+        storage.store(&stored_key, &vec![0x01; 32])?;
 
-        let result = storage.find_farthest_content_id()?;
-        assert!(result.is_none());
+        // Inconsistencies that the journal does not know about, as left by older versions: a
+        // value without metadata, and metadata without a value.
+        let value_only_id = generate_random_content_key().content_id();
+        storage
+            .db
+            .put_cf(storage.column_family()?, value_only_id, [0x02; 32])?;
+        let metadata_only_key = generate_random_content_key();
+        storage.meta_db_insert(
+            &storage.sql_connection_pool.get()?,
+            &metadata_only_key.content_id(),
+            &hex_encode(metadata_only_key.to_bytes())
+                .trim_start_matches("0x")
+                .to_string(),
+            32,
+        )?;
+        std::mem::drop(storage);
+
+        // The store was reconciled when it was first opened, so nothing is repaired.
+        let storage = PortalStorage::new(storage_config.clone(), ProtocolId::History)?;
+        assert_eq!(storage.total_entry_count()?, 2);
+        assert_eq!(storage.reconcile_databases()?, 0);
+        // Until the marker is gone, as on stores opened for the first time with the journal.
+        storage
+            .db
+            .delete_cf(storage.journal()?, storage.reconciled_key())?;
+        std::mem::drop(storage);
+
+        let storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        assert!(storage
+            .db
+            .get_pinned_cf(storage.column_family()?, value_only_id)?
+            .is_none());
+        assert_eq!(storage.total_entry_count()?, 1);
+        assert_eq!(storage.get(&stored_key)?, Some(vec![0x01; 32]));
+        assert_eq!(storage.get_total_storage_usage_in_bytes_from_network(), 32);
 
         std::mem::drop(storage);
         temp_dir.close()?;
@@ -1232,42 +3751,121 @@ pub mod test {
 
     #[test_log::test(tokio::test)]
     #[serial]
-    async fn test_find_farthest() {
-        fn prop(x: IdentityContentKey, y: IdentityContentKey) -> TestResult {
-            let temp_dir = setup_temp_dir().unwrap();
-            let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+    async fn test_pinned_content_is_not_evicted() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+        let storage_config =
+            PortalStorageConfig::new(1, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config, ProtocolId::History)?;
 
-            let val = vec![0x00, 0x01, 0x02, 0x03, 0x04];
-            let storage_config =
-                PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id)
-                    .unwrap();
-            let mut storage = PortalStorage::new(storage_config, ProtocolId::History).unwrap();
-            storage.store(&x, &val).unwrap();
-            storage.store(&y, &val).unwrap();
+        // Pin content before it is stored, and content that is already stored.
+        let pinned_key = generate_random_content_key();
+        assert!(storage.pin(&pinned_key)?);
+        assert!(!storage.pin(&pinned_key)?);
+        storage.store(&pinned_key, &vec![0xef; 100_000])?;
+        // The farthest possible content from the local node.
+        let mut farthest_id = node_id.raw();
+        farthest_id.iter_mut().for_each(|byte| *byte = !*byte);
+        let stored_key = IdentityContentKey::new(farthest_id);
+        storage.store(&stored_key, &vec![0xef; 100_000])?;
+        assert!(storage.pin(&stored_key)?);
+        // Pinned content does not count towards the capacity.
+        assert_eq!(storage.get_total_storage_usage_in_bytes_from_network(), 0);
+
+        // Fill the store, which shrinks the radius.
+        for _ in 0..20 {
+            let _ = storage.store(&generate_random_content_key(), &vec![0xef; 100_000]);
+        }
+        assert!(storage.radius < Distance::MAX);
+        assert!(storage.get(&pinned_key)?.is_some());
+        assert!(storage.get(&stored_key)?.is_some());
+
+        // Pinned content is stored even if it falls outside of the radius.
+        storage.set_radius(Distance::ZERO);
+        let far_key = generate_random_content_key();
+        assert!(!storage.is_key_within_radius_and_unavailable(&far_key)?);
+        storage.pin(&far_key)?;
+        assert!(storage.is_key_within_radius_and_unavailable(&far_key)?);
+        storage.store(&far_key, &vec![0xef; 10])?;
+
+        let mut pinned_keys = vec![pinned_key.clone(), stored_key.clone(), far_key];
+        pinned_keys.sort_by_key(|key| key.to_hex());
+        let listed_keys: Vec<Vec<u8>> = pinned_keys.into_iter().map(|key| key.into()).collect();
+        assert_eq!(storage.pinned_content_keys()?, listed_keys);
+
+        // Unpinned content is evicted again to make room for closer content.
+        assert!(storage.unpin(&stored_key)?);
+        assert!(!storage.unpin(&stored_key)?);
+        assert!(storage.get(&stored_key)?.is_none());
+        assert!(storage.get(&pinned_key)?.is_some());
 
-            let expected_farthest = if storage.distance_to_content_id(&x.content_id())
-                > storage.distance_to_content_id(&y.content_id())
-            {
-                x.content_id()
-            } else {
-                y.content_id()
-            };
+        std::mem::drop(storage);
+        temp_dir.close()?;
+        Ok(())
+    }
 
-            let farthest = storage.find_farthest_content_id();
+    #[test_log::test(tokio::test)]
+    #[serial]
+    async fn test_pinned_content_types() -> Result<(), ContentStoreError> {
+        let temp_dir = setup_temp_dir().unwrap();
+        let node_id = get_active_node_id(temp_dir.path().to_path_buf());
+        let storage_config =
+            PortalStorageConfig::new(CAPACITY_MB, temp_dir.path().to_path_buf(), node_id).unwrap();
+        let mut storage = PortalStorage::new(storage_config.clone(), ProtocolId::History)?;
 
-            std::mem::drop(storage);
-            temp_dir.close().unwrap();
+        // Identity content keys start with their first byte, which acts as the content type.
+        let content_key = |content_type: u8| {
+            let mut key = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            key[0] = content_type;
+            IdentityContentKey::new(key)
+        };
+        let stored_key = content_key(3);
+        storage.store(&stored_key, &vec![0xef; 32])?;
+        storage.store(&content_key(1), &vec![0xef; 32])?;
+        std::mem::drop(storage);
 
-            TestResult::from_bool(farthest.unwrap().unwrap() == expected_farthest)
-        }
+        // Content of a pinned type is pinned whether it was stored before or after startup.
+        let pinned_config = PortalStorageConfig {
+            pinned_content_types: vec![3],
+            ..storage_config.clone()
+        };
+        let mut storage = PortalStorage::new(pinned_config, ProtocolId::History)?;
+        assert_eq!(storage.get_total_storage_usage_in_bytes_from_network(), 32);
+        storage.set_radius(Distance::ZERO);
+        let far_key = content_key(3);
+        storage.store(&far_key, &vec![0xef; 32])?;
+        assert!(storage.store(&content_key(1), &vec![0xef; 32]).is_err());
+        assert!(storage.get(&stored_key)?.is_some());
+        assert!(storage.get(&far_key)?.is_some());
+
+        // Pins of content types are not listed, and unpinning keeps the content pinned by its
+        // type.
+        assert!(storage.pinned_content_keys()?.is_empty());
+        assert!(storage.pin(&stored_key)?);
+        assert_eq!(
+            storage.pinned_content_keys()?,
+            vec![Vec::<u8>::from(stored_key.clone())]
+        );
+        assert!(storage.unpin(&stored_key)?);
+        assert_eq!(storage.get_total_storage_usage_in_bytes_from_network(), 32);
+        std::mem::drop(storage);
 
-        quickcheck(prop as fn(IdentityContentKey, IdentityContentKey) -> TestResult);
+        // Dropping the content type from the config unpins its content again.
+        let storage = PortalStorage::new(storage_config, ProtocolId::History)?;
+        assert!(storage.pinned_content_keys()?.is_empty());
+        assert_eq!(storage.get_total_storage_usage_in_bytes_from_network(), 96);
+
+        std::mem::drop(storage);
+        temp_dir.close()?;
+        Ok(())
     }
 
     #[test]
     fn memory_store_contains_key() {
         let node_id = NodeId::random();
-        let mut store = MemoryContentStore::new(node_id, DistanceFunction::Xor);
+        let mut store =
+            MemoryContentStore::new(node_id, DistanceFunction::Xor, &ProtocolId::History);
 
         let val = vec![0xef];
 
@@ -1283,7 +3881,8 @@ pub mod test {
     #[test]
     fn memory_store_get() {
         let node_id = NodeId::random();
-        let mut store = MemoryContentStore::new(node_id, DistanceFunction::Xor);
+        let mut store =
+            MemoryContentStore::new(node_id, DistanceFunction::Xor, &ProtocolId::History);
 
         let val = vec![0xef];
 
@@ -1299,7 +3898,8 @@ pub mod test {
     #[test]
     fn memory_store_put() {
         let node_id = NodeId::random();
-        let mut store = MemoryContentStore::new(node_id, DistanceFunction::Xor);
+        let mut store =
+            MemoryContentStore::new(node_id, DistanceFunction::Xor, &ProtocolId::History);
 
         let val = vec![0xef];
 
@@ -1312,7 +3912,8 @@ pub mod test {
     #[test]
     fn memory_store_is_within_radius_and_unavailable() {
         let node_id = NodeId::random();
-        let mut store = MemoryContentStore::new(node_id, DistanceFunction::Xor);
+        let mut store =
+            MemoryContentStore::new(node_id, DistanceFunction::Xor, &ProtocolId::History);
 
         let val = vec![0xef];
 
@@ -1329,6 +3930,112 @@ pub mod test {
             .unwrap());
     }
 
+    #[test]
+    fn memory_store_evicts_farthest_content() -> Result<(), ContentStoreError> {
+        let node_id = NodeId::random();
+        let mut store = MemoryContentStore::from_config(
+            MemoryContentStoreConfig::new(1, node_id),
+            &ProtocolId::History,
+        );
+
+        let mut content_keys: Vec<IdentityContentKey> =
+            (0..1500).map(|_| generate_random_content_key()).collect();
+        for content_key in content_keys.iter() {
+            // Content beyond the shrinking radius is rejected.
+            let _ = store.put(content_key.clone(), vec![0; 1000]);
+        }
+        assert_eq!(store.store.len(), 1000);
+        assert_eq!(store.content_size_total, BYTES_IN_MB_U64);
+
+        // The nearest content is kept, and the radius shrinks to the farthest of it.
+        content_keys.sort_by_key(|key| store.distance_to_key(key));
+        for content_key in content_keys[..1000].iter() {
+            assert!(store.contains_key(content_key));
+        }
+        for content_key in content_keys[1000..].iter() {
+            assert!(!store.contains_key(content_key));
+        }
+        assert_eq!(store.radius(), store.distance_to_key(&content_keys[999]));
+
+        // The metrics follow the stored content and the radius.
+        assert_eq!(store.metrics.entry_count.get(), 1000);
+        assert_eq!(
+            store.metrics.content_storage_usage_bytes.get(),
+            BYTES_IN_MB_U64 as f64
+        );
+        assert_eq!(
+            store.metrics.radius_ratio.get(),
+            radius_ratio(store.radius())
+        );
+        assert_eq!(
+            store.metrics.storage_capacity_bytes.get(),
+            BYTES_IN_MB_U64 as f64
+        );
+
+        // Content outside of the radius is rejected.
+        assert!(store
+            .put(content_keys[1000].clone(), vec![0; 1000])
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn memory_store_zero_capacity() {
+        let node_id = NodeId::random();
+        let mut store = MemoryContentStore::from_config(
+            MemoryContentStoreConfig::new(0, node_id),
+            &ProtocolId::History,
+        );
+        assert_eq!(store.radius(), Distance::ZERO);
+
+        let content_key = generate_random_content_key();
+        assert!(!store
+            .is_key_within_radius_and_unavailable(&content_key)
+            .unwrap());
+        assert!(store.put(content_key, vec![0xef]).is_err());
+        assert_eq!(store.store.len(), 0);
+    }
+
+    #[test]
+    fn memory_store_pinned_content() -> Result<(), ContentStoreError> {
+        let node_id = NodeId::random();
+        let config = MemoryContentStoreConfig {
+            pinned_content_types: vec![3],
+            ..MemoryContentStoreConfig::new(0, node_id)
+        };
+        let mut store = MemoryContentStore::from_config(config, &ProtocolId::History);
+
+        // Pinned content is stored outside of the radius, and does not count towards capacity.
+        let mut key = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        key[0] = 1;
+        let pinned_key = IdentityContentKey::new(key);
+        key[0] = 3;
+        let pinned_type_key = IdentityContentKey::new(key);
+        assert!(store.pin(&pinned_key)?);
+        assert!(!store.pin(&pinned_key)?);
+        store.put(pinned_key.clone(), vec![0xef; 32])?;
+        store.put(pinned_type_key.clone(), vec![0xef; 32])?;
+        assert_eq!(store.store.len(), 2);
+        assert_eq!(store.content_size_total, 0);
+
+        // Content pinned by its content type is not listed, and stays pinned when unpinned.
+        assert_eq!(
+            store.pinned_content_keys()?,
+            vec![Vec::<u8>::from(pinned_key.clone())]
+        );
+        assert!(!store.unpin(&pinned_type_key)?);
+        assert!(store.pin(&pinned_type_key)?);
+        assert!(store.unpin(&pinned_type_key)?);
+
+        // Unpinned content counts towards capacity again, and is evicted.
+        assert!(store.unpin(&pinned_key)?);
+        assert!(!store.contains_key(&pinned_key));
+        assert!(store.contains_key(&pinned_type_key));
+        assert_eq!(store.content_size_total, 0);
+        Ok(())
+    }
+
     #[test]
     fn test_precision_for_percentage() {
         fn formatted_percent(ratio: f64) -> String {
@@ -2,7 +2,6 @@ use std::{
     convert::{TryFrom, TryInto},
     fmt,
     net::SocketAddr,
-    ops::Deref,
     str::FromStr,
 };
 
@@ -16,7 +15,10 @@ use ssz_types::{typenum, BitList};
 use thiserror::Error;
 use validator::ValidationError;
 
+use crate::rate_limit::RateLimitConfig;
+
 use trin_types::bytes::ByteList;
+use trin_types::cli::{default_stun_servers, IpMode, OverlayConfigArgs};
 use trin_types::content_key::RawContentKey;
 use trin_types::distance::Distance;
 use trin_types::enr::{Enr, SszEnr};
@@ -64,12 +66,43 @@ pub(crate) const MAX_PORTAL_CONTENT_PAYLOAD_SIZE: usize = MAX_DISCV5_TALK_REQ_PA
     - (ssz::BYTES_PER_UNION_SELECTOR * 2)
     - ssz::BYTES_PER_LENGTH_OFFSET;
 
+/// Length of the SSZ-encoded data radius at the start of a custom payload.
+const CUSTOM_PAYLOAD_RADIUS_LENGTH: usize = 32;
+
 /// Custom payload element of Ping and Pong overlay messages
+///
+/// The payload starts with the SSZ-encoded data radius of the node. A node that supports payload
+/// extensions may append an overlay-specific extension, see `PayloadExtension`.
 #[derive(Debug, PartialEq, Clone)]
 pub struct CustomPayload {
     payload: ByteList,
 }
 
+impl CustomPayload {
+    /// Constructs a payload with `data_radius`, followed by the encoded `extension` if present.
+    pub fn new(data_radius: Distance, extension: Option<Vec<u8>>) -> Self {
+        let mut payload = data_radius.as_ssz_bytes();
+        if let Some(mut extension) = extension {
+            payload.append(&mut extension);
+        }
+        Self::from(payload)
+    }
+
+    /// Returns the data radius advertised in the payload.
+    pub fn data_radius(&self) -> Distance {
+        let len = std::cmp::min(self.payload.len(), CUSTOM_PAYLOAD_RADIUS_LENGTH);
+        U256::from_little_endian(&self.payload[..len]).into()
+    }
+
+    /// Returns the encoded extension that follows the data radius, if present.
+    pub fn extension(&self) -> Option<&[u8]> {
+        match self.payload.get(CUSTOM_PAYLOAD_RADIUS_LENGTH..) {
+            Some(extension) if !extension.is_empty() => Some(extension),
+            _ => None,
+        }
+    }
+}
+
 impl TryFrom<&Value> for CustomPayload {
     type Error = ValidationError;
 
@@ -100,8 +133,7 @@ impl From<Vec<u8>> for CustomPayload {
 
 impl From<CustomPayload> for Distance {
     fn from(val: CustomPayload) -> Self {
-        let bytes = val.payload;
-        U256::from_little_endian(bytes.deref()).into()
+        val.data_radius()
     }
 }
 
@@ -157,16 +189,28 @@ pub enum DiscoveryRequestError {
 /// subnetworks.
 const NODE_ADDR_CACHE_CAPACITY: usize = discv5::kbucket::MAX_NODES_PER_BUCKET * 32;
 
+/// Number of peers that must observe the same external socket address before the local ENR is
+/// updated to that address.
+const ADDRESS_VOTE_THRESHOLD: usize = 10;
+
 #[derive(Clone)]
 pub struct PortalnetConfig {
     pub external_addr: Option<SocketAddr>,
     pub private_key: H256,
     pub listen_port: u16,
+    pub ip_mode: IpMode,
     pub bootnode_enrs: Vec<Enr>,
     pub data_radius: Distance,
     pub internal_ip: bool,
     pub no_stun: bool,
+    pub stun_servers: Vec<SocketAddr>,
+    pub upnp: bool,
+    pub address_vote_threshold: usize,
     pub node_addr_cache_capacity: usize,
+    pub history_rate_limit: RateLimitConfig,
+    pub state_rate_limit: RateLimitConfig,
+    pub history_overlay_config: OverlayConfigArgs,
+    pub state_overlay_config: OverlayConfigArgs,
 }
 
 impl Default for PortalnetConfig {
@@ -175,11 +219,19 @@ impl Default for PortalnetConfig {
             external_addr: None,
             private_key: H256::random(),
             listen_port: 4242,
+            ip_mode: IpMode::default(),
             bootnode_enrs: Vec::<Enr>::new(),
             data_radius: Distance::MAX,
             internal_ip: false,
             no_stun: false,
+            stun_servers: default_stun_servers(),
+            upnp: false,
+            address_vote_threshold: ADDRESS_VOTE_THRESHOLD,
             node_addr_cache_capacity: NODE_ADDR_CACHE_CAPACITY,
+            history_rate_limit: RateLimitConfig::default(),
+            state_rate_limit: RateLimitConfig::default(),
+            history_overlay_config: OverlayConfigArgs::default(),
+            state_overlay_config: OverlayConfigArgs::default(),
         }
     }
 }
@@ -646,6 +698,20 @@ mod test {
         assert_eq!(decoded, pong);
     }
 
+    #[test]
+    fn custom_payload_extension() {
+        let data_radius = Distance::from(U256::MAX / U256::from(2u8));
+        let radius_only = CustomPayload::from(data_radius.as_ssz_bytes());
+        assert_eq!(radius_only, CustomPayload::new(data_radius, None));
+        assert_eq!(radius_only.data_radius(), data_radius);
+        assert!(radius_only.extension().is_none());
+
+        let extended = CustomPayload::new(data_radius, Some(vec![1, 2, 3]));
+        assert_eq!(extended.data_radius(), data_radius);
+        assert_eq!(extended.extension(), Some(&[1u8, 2, 3][..]));
+        assert_eq!(Distance::from(extended), data_radius);
+    }
+
     #[test]
     fn message_encoding_find_nodes() {
         let distances = vec![256, 255];
@@ -1,2 +1,3 @@
 pub mod messages;
 pub mod node;
+pub mod payload_extension;
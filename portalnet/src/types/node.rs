@@ -1,8 +1,16 @@
 use std::fmt;
 
+use discv5::kbucket::Filter;
+
 use trin_types::distance::Distance;
 use trin_types::enr::Enr;
 
+/// Maximum number of nodes in the same /24 subnet in the routing table.
+pub const MAX_NODES_PER_SUBNET_TABLE: usize = 10;
+
+/// Maximum number of nodes in the same /24 subnet in a single routing table bucket.
+pub const MAX_NODES_PER_SUBNET_BUCKET: usize = 2;
+
 /// A node in the overlay network routing table.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Node {
@@ -10,12 +18,18 @@ pub struct Node {
     pub enr: Enr,
     /// The node's data radius.
     pub data_radius: Distance,
+    /// The encoded payload extension advertised by the node in its custom payload, if any.
+    pub payload_extension: Option<Vec<u8>>,
 }
 
 impl Node {
     /// Creates a new node.
     pub fn new(enr: Enr, data_radius: Distance) -> Node {
-        Node { enr, data_radius }
+        Node {
+            enr,
+            data_radius,
+            payload_extension: None,
+        }
     }
 
     /// Returns the ENR of the node.
@@ -28,6 +42,11 @@ impl Node {
         self.data_radius
     }
 
+    /// Returns the encoded payload extension of the node.
+    pub fn payload_extension(&self) -> Option<&[u8]> {
+        self.payload_extension.as_deref()
+    }
+
     /// Sets the ENR of the node.
     pub fn set_enr(&mut self, enr: Enr) {
         self.enr = enr;
@@ -37,6 +56,11 @@ impl Node {
     pub fn set_data_radius(&mut self, radius: Distance) {
         self.data_radius = radius;
     }
+
+    /// Sets the encoded payload extension of the node.
+    pub fn set_payload_extension(&mut self, payload_extension: Option<Vec<u8>>) {
+        self.payload_extension = payload_extension;
+    }
 }
 
 impl fmt::Display for Node {
@@ -49,3 +73,84 @@ impl fmt::Display for Node {
         )
     }
 }
+
+/// Routing table filter that limits the number of nodes in the same /24 subnet in the table.
+#[derive(Clone, Debug)]
+pub struct IpTableFilter;
+
+impl Filter<Node> for IpTableFilter {
+    fn filter(
+        &self,
+        value_to_be_inserted: &Node,
+        other_vals: &mut dyn Iterator<Item = &Node>,
+    ) -> bool {
+        subnet_filter(value_to_be_inserted, other_vals, MAX_NODES_PER_SUBNET_TABLE)
+    }
+}
+
+/// Routing table filter that limits the number of nodes in the same /24 subnet in a bucket.
+#[derive(Clone, Debug)]
+pub struct IpBucketFilter;
+
+impl Filter<Node> for IpBucketFilter {
+    fn filter(
+        &self,
+        value_to_be_inserted: &Node,
+        other_vals: &mut dyn Iterator<Item = &Node>,
+    ) -> bool {
+        subnet_filter(
+            value_to_be_inserted,
+            other_vals,
+            MAX_NODES_PER_SUBNET_BUCKET,
+        )
+    }
+}
+
+/// Returns whether fewer than `limit` nodes in `others` share the /24 subnet of `node`. Nodes
+/// without an IPv4 address are not limited.
+fn subnet_filter(node: &Node, others: &mut dyn Iterator<Item = &Node>, limit: usize) -> bool {
+    let subnet = match node.enr.ip4() {
+        Some(ip) => ip.octets(),
+        None => return true,
+    };
+    let count = others
+        .filter_map(|other| other.enr.ip4())
+        .filter(|ip| ip.octets()[..3] == subnet[..3])
+        .count();
+    count < limit
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    use discv5::enr::{CombinedKey, EnrBuilder};
+
+    fn node_with_ip(ip: Ipv4Addr) -> Node {
+        let key = CombinedKey::generate_secp256k1();
+        let enr = EnrBuilder::new("v4")
+            .ip4(ip)
+            .udp4(9000)
+            .build(&key)
+            .unwrap();
+        Node::new(enr, Distance::MAX)
+    }
+
+    #[test]
+    fn bucket_filter_limits_subnet() {
+        let others = vec![
+            node_with_ip(Ipv4Addr::new(10, 0, 0, 1)),
+            node_with_ip(Ipv4Addr::new(10, 0, 0, 2)),
+            node_with_ip(Ipv4Addr::new(10, 0, 1, 1)),
+        ];
+
+        let same_subnet = node_with_ip(Ipv4Addr::new(10, 0, 0, 3));
+        assert!(!IpBucketFilter.filter(&same_subnet, &mut others.iter()));
+        assert!(IpTableFilter.filter(&same_subnet, &mut others.iter()));
+
+        let other_subnet = node_with_ip(Ipv4Addr::new(10, 0, 1, 2));
+        assert!(IpBucketFilter.filter(&other_subnet, &mut others.iter()));
+    }
+}
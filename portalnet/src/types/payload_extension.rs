@@ -0,0 +1,96 @@
+use serde_json::{json, Value};
+use ssz::{Decode, Encode};
+use ssz_derive::{Decode, Encode};
+
+use trin_types::bytes::ByteList;
+use trin_types::enr::Enr;
+use trin_utils::version::get_trin_version;
+
+/// ENR key that advertises support for payload extensions in Ping and Pong custom payloads. The
+/// value is the supported extension version.
+///
+/// A node only appends an extension to the custom payload of a message to a peer that advertises
+/// support, so that peers that expect a radius-only payload are unaffected.
+pub const ENR_PAYLOAD_EXTENSION_KEY: &str = "px";
+
+/// Version of the payload extension format.
+pub const PAYLOAD_EXTENSION_VERSION: u8 = 1;
+
+/// Returns whether the node with `enr` accepts payload extensions.
+pub fn supports_payload_extension(enr: &Enr) -> bool {
+    enr.get(ENR_PAYLOAD_EXTENSION_KEY).is_some()
+}
+
+/// An overlay-specific extension of the custom payload in Ping and Pong messages, which follows
+/// the data radius.
+pub trait PayloadExtension: Send + Sync {
+    /// Returns the encoded extension advertised by the local node.
+    fn encode_extension(&self) -> Vec<u8>;
+
+    /// Decodes an extension advertised by a remote node into JSON. Returns `None` if the
+    /// extension is invalid.
+    fn extension_to_json(&self, extension: &[u8]) -> Option<Value>;
+}
+
+/// Capabilities of a node, advertised as a payload extension.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub struct Capabilities {
+    /// The version of the overlay protocol implemented by the node.
+    pub protocol_version: u8,
+    /// The name and version of the client.
+    pub client_version: ByteList,
+    /// The storage capacity of the node, in megabytes.
+    pub storage_capacity_mb: u64,
+    /// The SSZ union selectors of the content key types stored by the node.
+    pub content_types: ByteList,
+}
+
+impl Capabilities {
+    /// Constructs the capabilities of the local node.
+    pub fn new(storage_capacity_mb: u64, content_types: Vec<u8>) -> Self {
+        // Use "t" as short-hand for "Trin", as in the ENR.
+        let client_version = format!("t {}", get_trin_version());
+        Self {
+            protocol_version: PAYLOAD_EXTENSION_VERSION,
+            client_version: ByteList::from(client_version.into_bytes()),
+            storage_capacity_mb,
+            content_types: ByteList::from(content_types),
+        }
+    }
+}
+
+impl PayloadExtension for Capabilities {
+    fn encode_extension(&self) -> Vec<u8> {
+        self.as_ssz_bytes()
+    }
+
+    fn extension_to_json(&self, extension: &[u8]) -> Option<Value> {
+        let capabilities = Capabilities::from_ssz_bytes(extension).ok()?;
+        Some(json!({
+            "protocolVersion": capabilities.protocol_version,
+            "clientVersion": String::from_utf8_lossy(&capabilities.client_version),
+            "storageCapacityMb": capabilities.storage_capacity_mb,
+            "contentTypes": capabilities.content_types.to_vec(),
+        }))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn capabilities_json() {
+        let capabilities = Capabilities::new(100, vec![0, 1, 2, 3]);
+        let extension = capabilities.encode_extension();
+
+        let json = capabilities.extension_to_json(&extension).unwrap();
+        assert_eq!(json["protocolVersion"], PAYLOAD_EXTENSION_VERSION);
+        assert_eq!(json["storageCapacityMb"], 100);
+        assert_eq!(json["contentTypes"], json!([0, 1, 2, 3]));
+        assert!(json["clientVersion"].as_str().unwrap().starts_with("t "));
+
+        assert!(capabilities.extension_to_json(&[0xff]).is_none());
+    }
+}
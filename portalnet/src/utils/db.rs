@@ -60,6 +60,17 @@ pub fn configure_node_data_dir(
     Ok((node_data_dir, H256::from_slice(&pk.encode())))
 }
 
+/// Returns the private key of a node that keeps no data on disk. If no private key is provided,
+/// a random one is generated, so the node has a new node id on every run.
+pub fn ephemeral_private_key(private_key: Option<H256>) -> anyhow::Result<H256> {
+    let pk = match private_key {
+        Some(val) => CombinedKey::secp256k1_from_bytes(val.0.clone().as_mut_slice())
+            .map_err(|e| anyhow!("When building server key pair: {e:?}"))?,
+        None => CombinedKey::generate_secp256k1(),
+    };
+    Ok(H256::from_slice(&pk.encode()))
+}
+
 /// Returns the node data directory associated with the provided node id.
 fn get_node_data_dir(trin_data_dir: PathBuf, node_id: NodeId) -> PathBuf {
     // Append first 8 characters of Node ID
@@ -127,6 +138,17 @@ pub mod test {
         assert_eq!(pk, active_pk);
     }
 
+    #[test]
+    fn ephemeral_private_keys() {
+        let pk = CombinedKey::generate_secp256k1();
+        let pk = H256::from_slice(&pk.encode());
+        assert_eq!(ephemeral_private_key(Some(pk)).unwrap(), pk);
+        assert_ne!(
+            ephemeral_private_key(None).unwrap(),
+            ephemeral_private_key(None).unwrap()
+        );
+    }
+
     #[test]
     #[serial]
     fn activated_private_key_persists_over_reconfigurations() {
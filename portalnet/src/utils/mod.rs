@@ -1,2 +1,4 @@
 pub mod db;
 pub mod portal_wire;
+#[cfg(test)]
+pub(crate) mod validator;
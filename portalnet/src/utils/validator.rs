@@ -0,0 +1,36 @@
+//! Validator shared by the tests of the modules that validate stored content.
+
+use async_trait::async_trait;
+
+use trin_types::content_key::IdentityContentKey;
+use trin_validation::validator::{LookupError, Validator};
+
+/// Content that `TestValidator` rejects as invalid.
+pub const INVALID_CONTENT: [u8; 1] = [0xff];
+
+/// Content that `TestValidator` cannot validate, because looking up its validation data fails.
+pub const UNVERIFIABLE_CONTENT: [u8; 1] = [0xfe];
+
+/// Accepts all content except `INVALID_CONTENT`, and fails to look up the data needed to validate
+/// `UNVERIFIABLE_CONTENT`.
+pub struct TestValidator;
+
+#[async_trait]
+impl Validator<IdentityContentKey> for TestValidator {
+    async fn validate_content(
+        &self,
+        _content_key: &IdentityContentKey,
+        content: &[u8],
+    ) -> anyhow::Result<()>
+    where
+        IdentityContentKey: 'async_trait,
+    {
+        if content == INVALID_CONTENT {
+            return Err(anyhow::anyhow!("invalid content"));
+        }
+        if content == UNVERIFIABLE_CONTENT {
+            return Err(LookupError(anyhow::anyhow!("provider unavailable")).into());
+        }
+        Ok(())
+    }
+}
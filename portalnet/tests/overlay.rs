@@ -1,4 +1,4 @@
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::{net::SocketAddr, str::FromStr, sync::Arc};
 
 use portalnet::{
@@ -7,6 +7,7 @@ use portalnet::{
     storage::{ContentStore, DistanceFunction, MemoryContentStore},
     types::messages::{Content, Message, PortalnetConfig, ProtocolId},
 };
+use trin_types::cli::IpMode;
 use trin_types::content_key::IdentityContentKey;
 use trin_types::distance::XorMetric;
 use trin_types::enr::{Enr, SszEnr};
@@ -22,24 +23,28 @@ use utp_rs::socket::UtpSocket;
 
 use trin_utils::bytes::hex_encode_upper;
 
+type TestOverlay =
+    OverlayProtocol<IdentityContentKey, XorMetric, MockValidator, MemoryContentStore>;
+
+/// Returns the overlay, and the sender of the uTP talk requests to its uTP socket.
 async fn init_overlay(
     discovery: Arc<Discovery>,
     protocol: ProtocolId,
-) -> OverlayProtocol<IdentityContentKey, XorMetric, MockValidator, MemoryContentStore> {
+) -> (TestOverlay, mpsc::UnboundedSender<TalkRequest>) {
     let overlay_config = OverlayConfig::default();
 
     let node_id = discovery.local_enr().node_id();
-    let store = MemoryContentStore::new(node_id, DistanceFunction::Xor);
+    let store = MemoryContentStore::new(node_id, DistanceFunction::Xor, &protocol);
     let store = Arc::new(RwLock::new(store));
 
-    let (_utp_talk_req_tx, utp_talk_req_rx) = unbounded_channel();
+    let (utp_talk_req_tx, utp_talk_req_rx) = unbounded_channel();
     let discv5_utp = Discv5UdpSocket::new(Arc::clone(&discovery), utp_talk_req_rx);
     let utp_socket = UtpSocket::with_socket(discv5_utp);
     let utp_socket = Arc::new(utp_socket);
 
     let validator = Arc::new(MockValidator {});
 
-    OverlayProtocol::new(
+    let overlay = OverlayProtocol::new(
         overlay_config,
         discovery,
         utp_socket,
@@ -47,12 +52,14 @@ async fn init_overlay(
         protocol,
         validator,
     )
-    .await
+    .await;
+    (overlay, utp_talk_req_tx)
 }
 
 async fn spawn_overlay(
     mut talk_req_rx: mpsc::Receiver<TalkRequest>,
-    overlay: Arc<OverlayProtocol<IdentityContentKey, XorMetric, MockValidator, MemoryContentStore>>,
+    overlay: Arc<TestOverlay>,
+    utp_talk_req_tx: mpsc::UnboundedSender<TalkRequest>,
 ) {
     let (overlay_tx, mut overlay_rx) = mpsc::unbounded_channel();
 
@@ -65,6 +72,7 @@ async fn spawn_overlay(
                 match (req_protocol, overlay_protocol.clone()) {
                     (ProtocolId::History, ProtocolId::History)
                     | (ProtocolId::State, ProtocolId::State) => overlay_tx.send(talk_req).unwrap(),
+                    (ProtocolId::Utp, _) => utp_talk_req_tx.send(talk_req).unwrap(),
                     _ => panic!("Unexpected protocol"),
                 }
             } else {
@@ -105,8 +113,10 @@ async fn overlay() {
     let mut discovery_one = Discovery::new(portal_config_one).unwrap();
     let talk_req_rx_one = discovery_one.start().await.unwrap();
     let discovery_one = Arc::new(discovery_one);
-    let overlay_one = Arc::new(init_overlay(Arc::clone(&discovery_one), protocol.clone()).await);
-    spawn_overlay(talk_req_rx_one, Arc::clone(&overlay_one)).await;
+    let (overlay_one, utp_talk_req_tx) =
+        init_overlay(Arc::clone(&discovery_one), protocol.clone()).await;
+    let overlay_one = Arc::new(overlay_one);
+    spawn_overlay(talk_req_rx_one, Arc::clone(&overlay_one), utp_talk_req_tx).await;
     time::sleep(sleep_duration).await;
 
     // Node two.
@@ -118,8 +128,10 @@ async fn overlay() {
     let mut discovery_two = Discovery::new(portal_config_two).unwrap();
     let talk_req_rx_two = discovery_two.start().await.unwrap();
     let discovery_two = Arc::new(discovery_two);
-    let overlay_two = Arc::new(init_overlay(Arc::clone(&discovery_two), protocol.clone()).await);
-    spawn_overlay(talk_req_rx_two, Arc::clone(&overlay_two)).await;
+    let (overlay_two, utp_talk_req_tx) =
+        init_overlay(Arc::clone(&discovery_two), protocol.clone()).await;
+    let overlay_two = Arc::new(overlay_two);
+    spawn_overlay(talk_req_rx_two, Arc::clone(&overlay_two), utp_talk_req_tx).await;
     time::sleep(sleep_duration).await;
 
     // Node three.
@@ -131,9 +143,15 @@ async fn overlay() {
     let mut discovery_three = Discovery::new(portal_config_three).unwrap();
     let talk_req_rx_three = discovery_three.start().await.unwrap();
     let discovery_three = Arc::new(discovery_three);
-    let overlay_three =
-        Arc::new(init_overlay(Arc::clone(&discovery_three), protocol.clone()).await);
-    spawn_overlay(talk_req_rx_three, Arc::clone(&overlay_three)).await;
+    let (overlay_three, utp_talk_req_tx) =
+        init_overlay(Arc::clone(&discovery_three), protocol.clone()).await;
+    let overlay_three = Arc::new(overlay_three);
+    spawn_overlay(
+        talk_req_rx_three,
+        Arc::clone(&overlay_three),
+        utp_talk_req_tx,
+    )
+    .await;
     time::sleep(sleep_duration).await;
 
     // All routing tables are empty.
@@ -237,3 +255,232 @@ async fn overlay() {
         }
     }
 }
+
+// Content too large to fit in a Content response is transferred over uTP, and the requester
+// keeps it like content received inline.
+#[test_log::test(tokio::test)]
+async fn find_content_over_utp() {
+    let protocol = ProtocolId::History;
+    let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+    let mut overlays = vec![];
+    for _ in 0..2 {
+        let port = unused_udp_port(ip_addr);
+        let portal_config = PortalnetConfig {
+            listen_port: port,
+            external_addr: Some(SocketAddr::new(ip_addr, port)),
+            ..PortalnetConfig::default()
+        };
+        let mut discovery = Discovery::new(portal_config).unwrap();
+        let talk_req_rx = discovery.start().await.unwrap();
+        let (overlay, utp_talk_req_tx) = init_overlay(Arc::new(discovery), protocol.clone()).await;
+        let overlay = Arc::new(overlay);
+        spawn_overlay(talk_req_rx, Arc::clone(&overlay), utp_talk_req_tx).await;
+        overlays.push(overlay);
+    }
+    let (overlay_one, overlay_two) = (&overlays[0], &overlays[1]);
+
+    let content_key = IdentityContentKey::new([0xef; 32]);
+    let content = vec![0xef; 10_000];
+    overlay_one
+        .store
+        .write()
+        .put(content_key.clone(), &content)
+        .expect("Unable to store content");
+
+    match overlay_two
+        .send_find_content(overlay_one.local_enr(), content_key.clone().into())
+        .await
+    {
+        Ok(Content::Content(found_content)) => assert_eq!(found_content, content),
+        Ok(other) => panic!("Unexpected response to find content: {other:?}"),
+        Err(err) => panic!("Unable to find content over uTP: {err}"),
+    }
+
+    // The content falls within the radius of node two, so it is stored in the background.
+    time::timeout(Duration::from_secs(5), async {
+        while overlay_two
+            .store
+            .read()
+            .get(&content_key)
+            .unwrap()
+            .is_none()
+        {
+            time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("Content received over uTP to be stored");
+    assert_eq!(
+        overlay_two.store.read().get(&content_key).unwrap(),
+        Some(content)
+    );
+}
+
+/// Returns a UDP port on `ip_addr` that is not in use, as assigned by the OS.
+fn unused_udp_port(ip_addr: IpAddr) -> u16 {
+    std::net::UdpSocket::bind(SocketAddr::new(ip_addr, 0))
+        .and_then(|socket| socket.local_addr())
+        .expect("Binding to an ephemeral port to work")
+        .port()
+}
+
+// Tests that nodes communicate over IPv6 loopback, and that a dual-stack node communicates with
+// both IPv6 and IPv4 nodes.
+#[test_log::test(tokio::test)]
+async fn overlay_ip6() {
+    let protocol = ProtocolId::History;
+    let sleep_duration = Duration::from_millis(5);
+    let ip4_addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let ip6_addr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+
+    // IPv6-only node.
+    let portal_config_one = PortalnetConfig {
+        listen_port: 8011,
+        ip_mode: IpMode::Ip6,
+        external_addr: Some(SocketAddr::new(ip6_addr, 8011)),
+        ..PortalnetConfig::default()
+    };
+    let mut discovery_one = Discovery::new(portal_config_one).unwrap();
+    let talk_req_rx_one = discovery_one.start().await.unwrap();
+    let discovery_one = Arc::new(discovery_one);
+    let (overlay_one, utp_talk_req_tx) =
+        init_overlay(Arc::clone(&discovery_one), protocol.clone()).await;
+    let overlay_one = Arc::new(overlay_one);
+    spawn_overlay(talk_req_rx_one, Arc::clone(&overlay_one), utp_talk_req_tx).await;
+
+    let enr_one = overlay_one.local_enr();
+    assert_eq!(enr_one.ip6(), Some(Ipv6Addr::LOCALHOST));
+    assert_eq!(enr_one.udp6(), Some(8011));
+    assert!(enr_one.ip4().is_none());
+    assert!(enr_one.udp4().is_none());
+
+    // Dual-stack node.
+    let portal_config_two = PortalnetConfig {
+        listen_port: 8012,
+        ip_mode: IpMode::DualStack,
+        external_addr: Some(SocketAddr::new(ip6_addr, 8012)),
+        no_stun: true,
+        ..PortalnetConfig::default()
+    };
+    let mut discovery_two = Discovery::new(portal_config_two).unwrap();
+    let talk_req_rx_two = discovery_two.start().await.unwrap();
+    let discovery_two = Arc::new(discovery_two);
+    let (overlay_two, utp_talk_req_tx) =
+        init_overlay(Arc::clone(&discovery_two), protocol.clone()).await;
+    let overlay_two = Arc::new(overlay_two);
+    spawn_overlay(talk_req_rx_two, Arc::clone(&overlay_two), utp_talk_req_tx).await;
+
+    // IPv4-only node.
+    let portal_config_three = PortalnetConfig {
+        listen_port: 8013,
+        external_addr: Some(SocketAddr::new(ip4_addr, 8013)),
+        ..PortalnetConfig::default()
+    };
+    let mut discovery_three = Discovery::new(portal_config_three).unwrap();
+    let talk_req_rx_three = discovery_three.start().await.unwrap();
+    let discovery_three = Arc::new(discovery_three);
+    let (overlay_three, utp_talk_req_tx) =
+        init_overlay(Arc::clone(&discovery_three), protocol.clone()).await;
+    let overlay_three = Arc::new(overlay_three);
+    spawn_overlay(
+        talk_req_rx_three,
+        Arc::clone(&overlay_three),
+        utp_talk_req_tx,
+    )
+    .await;
+    time::sleep(sleep_duration).await;
+
+    // Ping the dual-stack node from the IPv6-only node.
+    if let Err(err) = overlay_one.send_ping(overlay_two.local_enr()).await {
+        panic!("Unable to respond to ping over IPv6: {err}");
+    }
+    time::sleep(sleep_duration).await;
+    assert!(overlay_one
+        .table_entries_enr()
+        .contains(&overlay_two.local_enr()));
+
+    // Ping the IPv4-only node from the dual-stack node.
+    if let Err(err) = overlay_two.send_ping(overlay_three.local_enr()).await {
+        panic!("Unable to respond to ping over IPv4: {err}");
+    }
+    time::sleep(sleep_duration).await;
+    assert!(overlay_two
+        .table_entries_enr()
+        .contains(&overlay_three.local_enr()));
+
+    // The IPv4 address of the IPv4-only node is cached without the IPv4-mapped IPv6 form.
+    let node_addr = discovery_two
+        .cached_node_addr(&overlay_three.local_enr().node_id())
+        .expect("node address to be cached");
+    assert_eq!(node_addr.socket_addr, SocketAddr::new(ip4_addr, 8013));
+
+    // An IPv4 external address is rejected in IPv6-only mode.
+    let invalid_config = PortalnetConfig {
+        listen_port: 8014,
+        ip_mode: IpMode::Ip6,
+        external_addr: Some(SocketAddr::new(ip4_addr, 8014)),
+        ..PortalnetConfig::default()
+    };
+    assert!(Discovery::new(invalid_config).is_err());
+}
+
+// Tests that the local ENR advertises the addresses of each IP version in use, independently of
+// which external addresses are known.
+#[test_log::test(tokio::test)]
+async fn enr_ip_fields() {
+    let ip4_addr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1));
+    let ip6_addr = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+
+    // IPv6-only node without a known external address.
+    let config = PortalnetConfig {
+        listen_port: 8021,
+        ip_mode: IpMode::Ip6,
+        ..PortalnetConfig::default()
+    };
+    let enr = Discovery::new(config).unwrap().local_enr();
+    assert!(enr.ip6().is_none());
+    assert_eq!(enr.udp6(), Some(8021));
+    assert!(enr.ip4().is_none());
+    assert!(enr.udp4().is_none());
+
+    // Dual-stack node without a known external address.
+    let config = PortalnetConfig {
+        listen_port: 8022,
+        ip_mode: IpMode::DualStack,
+        no_stun: true,
+        ..PortalnetConfig::default()
+    };
+    let enr = Discovery::new(config).unwrap().local_enr();
+    assert!(enr.ip4().is_none());
+    assert_eq!(enr.udp4(), Some(8022));
+    assert!(enr.ip6().is_none());
+    assert_eq!(enr.udp6(), Some(8022));
+
+    // Dual-stack node with a known external IPv6 address keeps its IPv4 port.
+    let config = PortalnetConfig {
+        listen_port: 8023,
+        ip_mode: IpMode::DualStack,
+        external_addr: Some(SocketAddr::new(ip6_addr, 9023)),
+        no_stun: true,
+        ..PortalnetConfig::default()
+    };
+    let enr = Discovery::new(config).unwrap().local_enr();
+    assert!(enr.ip4().is_none());
+    assert_eq!(enr.udp4(), Some(8023));
+    assert_eq!(enr.ip6().map(IpAddr::from), Some(ip6_addr));
+    assert_eq!(enr.udp6(), Some(9023));
+
+    // Dual-stack node with a known external IPv4 address keeps its IPv6 port.
+    let config = PortalnetConfig {
+        listen_port: 8024,
+        ip_mode: IpMode::DualStack,
+        external_addr: Some(SocketAddr::new(ip4_addr, 9024)),
+        ..PortalnetConfig::default()
+    };
+    let enr = Discovery::new(config).unwrap().local_enr();
+    assert_eq!(enr.ip4().map(IpAddr::from), Some(ip4_addr));
+    assert_eq!(enr.udp4(), Some(9024));
+    assert!(enr.ip6().is_none());
+    assert_eq!(enr.udp6(), Some(8024));
+}
@@ -1,8 +1,13 @@
 use crate::jsonrpsee::core::{async_trait, Error, RpcResult};
 use anyhow::anyhow;
+use ethereum_types::H256;
+use ethportal_api::types::gossip_status::GossipStatus;
+use ethportal_api::types::inventory::{
+    LocalContentFilter, LocalContentInventory, LocalContentStats,
+};
 use ethportal_api::types::portal::{
-    AcceptInfo, ContentInfo, DataRadius, FindNodesInfo, PaginateLocalContentInfo, PongInfo,
-    TraceContentInfo,
+    AcceptInfo, BatchContentInfo, ContentInfo, DataRadius, FindNodesInfo, PaginateLocalContentInfo,
+    PongInfo, RoutingTableHealth, TraceContentInfo, TraceNodesInfo,
 };
 use ethportal_api::HistoryContentKey;
 use ethportal_api::HistoryContentValue;
@@ -58,6 +63,14 @@ impl HistoryNetworkApiServer for HistoryNetworkApi {
         Ok(result)
     }
 
+    /// Returns diagnostics of the health of the overlay routing table.
+    async fn routing_table_health(&self) -> RpcResult<RoutingTableHealth> {
+        let endpoint = HistoryEndpoint::RoutingTableHealth;
+        let result = self.proxy_query_to_history_subnet(endpoint).await?;
+        let result: RoutingTableHealth = from_value(result)?;
+        Ok(result)
+    }
+
     /// Write an Ethereum Node Record to the overlay routing table.
     async fn add_enr(&self, _enr: Enr) -> RpcResult<bool> {
         Err(Error::MethodNotFound("add_enr".to_owned()))
@@ -103,6 +116,14 @@ impl HistoryNetworkApiServer for HistoryNetworkApi {
         Ok(result)
     }
 
+    /// Lookup a target node within in the network. Return tracing info.
+    async fn trace_recursive_find_nodes(&self, node_id: NodeId) -> RpcResult<TraceNodesInfo> {
+        let endpoint = HistoryEndpoint::TraceRecursiveFindNodes(node_id);
+        let result = self.proxy_query_to_history_subnet(endpoint).await?;
+        let info: TraceNodesInfo = from_value(result)?;
+        Ok(info)
+    }
+
     /// Lookup a target node within in the network
     async fn radius(&self) -> RpcResult<DataRadius> {
         let endpoint = HistoryEndpoint::DataRadius;
@@ -148,6 +169,18 @@ impl HistoryNetworkApiServer for HistoryNetworkApi {
         Ok(info)
     }
 
+    /// Lookup a batch of target content keys in the network. Return the result of each lookup.
+    async fn recursive_find_content_batch(
+        &self,
+        content_keys: Vec<HistoryContentKey>,
+        max_concurrency: Option<usize>,
+    ) -> RpcResult<Vec<BatchContentInfo>> {
+        let endpoint = HistoryEndpoint::RecursiveFindContentBatch(content_keys, max_concurrency);
+        let result = self.proxy_query_to_history_subnet(endpoint).await?;
+        let result: Vec<BatchContentInfo> = from_value(result)?;
+        Ok(result)
+    }
+
     /// Pagination of local content keys
     async fn paginate_local_content_keys(
         &self,
@@ -160,6 +193,31 @@ impl HistoryNetworkApiServer for HistoryNetworkApi {
         Ok(result)
     }
 
+    /// List local content items that match the filter, starting after the content id `cursor`.
+    async fn local_content_inventory(
+        &self,
+        filter: LocalContentFilter,
+        cursor: Option<H256>,
+        limit: u64,
+    ) -> RpcResult<LocalContentInventory> {
+        let endpoint = HistoryEndpoint::LocalContentInventory(filter, cursor, limit);
+        let result = self.proxy_query_to_history_subnet(endpoint).await?;
+        let result: LocalContentInventory = from_value(result)?;
+        Ok(result)
+    }
+
+    /// Return the number of local content items that match the filter, and their total size, for
+    /// each content type.
+    async fn local_content_stats(
+        &self,
+        filter: LocalContentFilter,
+    ) -> RpcResult<LocalContentStats> {
+        let endpoint = HistoryEndpoint::LocalContentStats(filter);
+        let result = self.proxy_query_to_history_subnet(endpoint).await?;
+        let result: LocalContentStats = from_value(result)?;
+        Ok(result)
+    }
+
     /// Send the provided content to interested peers. Clients may choose to send to some or all peers.
     /// Return the number of peers that the content was gossiped to.
     async fn gossip(
@@ -173,6 +231,14 @@ impl HistoryNetworkApiServer for HistoryNetworkApi {
         Ok(result)
     }
 
+    /// Return the delivery status of the most recent gossip of the content key.
+    async fn gossip_status(&self, content_key: HistoryContentKey) -> RpcResult<GossipStatus> {
+        let endpoint = HistoryEndpoint::GossipStatus(content_key);
+        let result = self.proxy_query_to_history_subnet(endpoint).await?;
+        let result: GossipStatus = from_value(result)?;
+        Ok(result)
+    }
+
     /// Send an OFFER request with given ContentKey, to the designated peer and wait for a response.
     /// Returns the content keys bitlist upon successful content transmission or empty bitlist receive.
     async fn offer(
@@ -212,6 +278,30 @@ impl HistoryNetworkApiServer for HistoryNetworkApi {
         let content: HistoryContentValue = from_value(result)?;
         Ok(PossibleHistoryContentValue::ContentPresent(content))
     }
+
+    /// Pin content in the local database.
+    async fn pin_content(&self, content_key: HistoryContentKey) -> RpcResult<bool> {
+        let endpoint = HistoryEndpoint::PinContent(content_key);
+        let result = self.proxy_query_to_history_subnet(endpoint).await?;
+        let result: bool = from_value(result)?;
+        Ok(result)
+    }
+
+    /// Unpin content in the local database.
+    async fn unpin_content(&self, content_key: HistoryContentKey) -> RpcResult<bool> {
+        let endpoint = HistoryEndpoint::UnpinContent(content_key);
+        let result = self.proxy_query_to_history_subnet(endpoint).await?;
+        let result: bool = from_value(result)?;
+        Ok(result)
+    }
+
+    /// List the content keys of all pinned content.
+    async fn pinned_content(&self) -> RpcResult<Vec<HistoryContentKey>> {
+        let endpoint = HistoryEndpoint::PinnedContent;
+        let result = self.proxy_query_to_history_subnet(endpoint).await?;
+        let result: Vec<HistoryContentKey> = from_value(result)?;
+        Ok(result)
+    }
 }
 
 impl std::fmt::Debug for HistoryNetworkApi {
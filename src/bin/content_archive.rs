@@ -0,0 +1,299 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use discv5::enr::{CombinedKey, EnrBuilder, NodeId};
+use ethereum_types::H256;
+use tokio::sync::RwLock;
+use tracing::info;
+
+use ethportal_api::{HistoryContentKey, StateContentKey};
+use portalnet::archive::{export_content, import_content, ExportFilter};
+use portalnet::storage::{PortalStorage, PortalStorageConfig};
+use portalnet::types::messages::ProtocolId;
+use portalnet::utils::db::{configure_node_data_dir, configure_trin_data_dir};
+use trin_history::validation::ChainHistoryValidator;
+use trin_state::validation::StateValidator;
+use trin_types::cli::{
+    PinPattern, TrinConfig, DEFAULT_MASTER_ACC_PATH, HISTORY_NETWORK, STATE_NETWORK,
+};
+use trin_types::provider::TrustedProvider;
+use trin_utils::log::init_tracing_logger;
+use trin_validation::{accumulator::MasterAccumulator, oracle::HeaderOracle};
+
+/// Storage capacity used unless the user passes one, large enough that opening the store never
+/// evicts anything.
+const NON_PRUNING_CAPACITY_MB: u64 = u64::MAX / 1_000_000;
+
+///
+/// This script exports the locally stored content of a subnetwork to an archive file, or imports
+/// an archive file into local storage, validating every entry. Archives let new nodes be seeded
+/// without re-gossiping their content, and capture snapshots of content for tests.
+///
+/// Validating history block bodies and receipts looks up their headers with the trusted provider,
+/// so imports need the same provider environment variables as trin itself.
+///
+#[tokio::main]
+pub async fn main() -> Result<()> {
+    init_tracing_logger();
+    let archive_config = ArchiveConfig::parse();
+
+    match archive_config.command {
+        ArchiveCommand::Export(args) => export(args),
+        ArchiveCommand::Import(args) => import(args).await,
+    }
+}
+
+fn export(args: ExportArgs) -> Result<()> {
+    let storage = open_storage(&args.storage, NON_PRUNING_CAPACITY_MB)?;
+    let network = args.storage.network.name();
+    let filter = ExportFilter {
+        content_types: args
+            .content_type
+            .iter()
+            .flat_map(|pattern| pattern.content_types(network))
+            .collect(),
+        min_content_id: args.min_content_id.map(|id| id.0),
+        max_content_id: args.max_content_id.map(|id| id.0),
+    };
+    if !args.content_type.is_empty() && filter.content_types.is_empty() {
+        anyhow::bail!("No content type of the {network} network matches --content-type");
+    }
+
+    let writer = BufWriter::new(File::create(&args.storage.path)?);
+    let trailer = export_content(&storage, writer, &filter)?;
+    info!(
+        "Exported {} items of {network} content to {:?}",
+        trailer.num_entries, args.storage.path
+    );
+    Ok(())
+}
+
+async fn import(args: ImportArgs) -> Result<()> {
+    // Opening the store with less than its current usage evicts content, so a capacity is only
+    // applied when the user asks for one.
+    let capacity_mb = args.storage_capacity_mb.unwrap_or(NON_PRUNING_CAPACITY_MB);
+    let mut storage = open_storage(&args.storage, capacity_mb)?;
+    let reader = BufReader::new(File::open(&args.storage.path)?);
+
+    let trin_config = TrinConfig {
+        master_acc_path: args.master_acc_path,
+        ..Default::default()
+    };
+    let master_acc = MasterAccumulator::try_from_file(trin_config.master_acc_path.clone())?;
+    let header_oracle =
+        HeaderOracle::new(TrustedProvider::from_trin_config(&trin_config), master_acc);
+    let header_oracle = Arc::new(RwLock::new(header_oracle));
+
+    let summary = match args.storage.network {
+        Network::History => {
+            let validator = ChainHistoryValidator { header_oracle };
+            import_content::<HistoryContentKey, _, _>(&mut storage, reader, &validator).await?
+        }
+        Network::State => {
+            let validator = StateValidator { header_oracle };
+            import_content::<StateContentKey, _, _>(&mut storage, reader, &validator).await?
+        }
+    };
+    info!(
+        "Imported {} items from {:?}, skipped {} invalid items and {} items declined by the store",
+        summary.num_imported, args.storage.path, summary.num_invalid, summary.num_rejected
+    );
+    Ok(())
+}
+
+/// Opens the local storage of the subnetwork selected by `args`.
+fn open_storage(args: &StorageArgs, capacity_mb: u64) -> Result<PortalStorage> {
+    let enr_key = CombinedKey::secp256k1_from_bytes(args.private_key.0.clone().as_mut_slice())
+        .expect("Failed to create ENR key");
+    let enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+    let node_id: NodeId = enr.node_id();
+    let trin_data_dir = configure_trin_data_dir(false)?;
+    let (node_data_dir, _) = configure_node_data_dir(trin_data_dir, Some(args.private_key))?;
+    info!(
+        "Opening {} content of NodeID: {node_id}",
+        args.network.name()
+    );
+    info!("DB Path: {node_data_dir:?}");
+
+    let config = PortalStorageConfig::new(capacity_mb, node_data_dir, node_id)?;
+    Ok(PortalStorage::new(config, args.network.protocol())?)
+}
+
+// CLI Parameter Handling
+#[derive(Parser, Debug, PartialEq)]
+#[command(
+    name = "Trin Content Archive Util",
+    about = "Export local content to an archive file, or import an archive file into local storage"
+)]
+pub struct ArchiveConfig {
+    #[command(subcommand)]
+    pub command: ArchiveCommand,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum ArchiveCommand {
+    /// Export the locally stored content of a subnetwork to an archive file
+    Export(ExportArgs),
+    /// Validate the content of an archive file and import it into local storage
+    Import(ImportArgs),
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct StorageArgs {
+    #[arg(
+        long,
+        help = "(unsafe) Hex private key to generate node id for database namespace (with 0x prefix)"
+    )]
+    pub private_key: H256,
+
+    #[arg(default_value = "history", long, help = "Subnetwork of the content")]
+    pub network: Network,
+
+    #[arg(
+        long,
+        help = "Path of the archive file. Archives are read and written in a single pass, so it may also be a pipe"
+    )]
+    pub path: PathBuf,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub storage: StorageArgs,
+
+    #[arg(
+        long,
+        use_value_delimiter = true,
+        help = "Only export content of these types, as <network>:<content type> (eg. history:block-header)"
+    )]
+    pub content_type: Vec<PinPattern>,
+
+    #[arg(long, help = "Only export content with at least this content id")]
+    pub min_content_id: Option<H256>,
+
+    #[arg(long, help = "Only export content with at most this content id")]
+    pub max_content_id: Option<H256>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct ImportArgs {
+    #[command(flatten)]
+    pub storage: StorageArgs,
+
+    #[arg(
+        long,
+        help = "Maximum number of megabytes of content stored by the node in the subnetwork. Stored content beyond it is evicted before importing. If unset, nothing is evicted, and the node applies its own capacity when it next starts"
+    )]
+    pub storage_capacity_mb: Option<u64>,
+
+    #[arg(
+        default_value = DEFAULT_MASTER_ACC_PATH,
+        long,
+        help = "Path to the master accumulator, used to validate history content"
+    )]
+    pub master_acc_path: PathBuf,
+}
+
+#[derive(ValueEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Network {
+    History,
+    State,
+}
+
+impl Network {
+    fn name(&self) -> &'static str {
+        match self {
+            Network::History => HISTORY_NETWORK,
+            Network::State => STATE_NETWORK,
+        }
+    }
+
+    fn protocol(&self) -> ProtocolId {
+        match self {
+            Network::History => ProtocolId::History,
+            Network::State => ProtocolId::State,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    const PRIVATE_KEY: &str = "0x9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn test_export_config() {
+        let archive_config = ArchiveConfig::parse_from([
+            "test",
+            "export",
+            "--private-key",
+            PRIVATE_KEY,
+            "--path",
+            "history.archive",
+            "--content-type",
+            "history:block-header,history:receipts",
+        ]);
+        let args = match archive_config.command {
+            ArchiveCommand::Export(args) => args,
+            command => panic!("Expected export command, got {command:?}"),
+        };
+        assert_eq!(
+            args.storage.private_key,
+            H256::from_str(PRIVATE_KEY).unwrap()
+        );
+        assert_eq!(args.storage.network, Network::History);
+        assert_eq!(args.storage.path, PathBuf::from("history.archive"));
+        let content_types: Vec<u8> = args
+            .content_type
+            .iter()
+            .flat_map(|pattern| pattern.content_types(HISTORY_NETWORK))
+            .collect();
+        assert_eq!(content_types, vec![0, 2]);
+        assert_eq!(args.min_content_id, None);
+    }
+
+    #[test]
+    fn test_import_config() {
+        let archive_config = ArchiveConfig::parse_from([
+            "test",
+            "import",
+            "--private-key",
+            PRIVATE_KEY,
+            "--network",
+            "state",
+            "--path",
+            "state.archive",
+        ]);
+        let args = match archive_config.command {
+            ArchiveCommand::Import(args) => args,
+            command => panic!("Expected import command, got {command:?}"),
+        };
+        assert_eq!(args.storage.network, Network::State);
+        assert_eq!(args.storage_capacity_mb, None);
+        assert_eq!(args.master_acc_path, PathBuf::from(DEFAULT_MASTER_ACC_PATH));
+
+        let archive_config = ArchiveConfig::parse_from([
+            "test",
+            "import",
+            "--private-key",
+            PRIVATE_KEY,
+            "--path",
+            "history.archive",
+            "--storage-capacity-mb",
+            "500",
+        ]);
+        let args = match archive_config.command {
+            ArchiveCommand::Import(args) => args,
+            command => panic!("Expected import command, got {command:?}"),
+        };
+        assert_eq!(args.storage_capacity_mb, Some(500));
+    }
+}
@@ -18,11 +18,8 @@ use trin_utils::bytes::hex_encode;
 use trin_utils::log::init_tracing_logger;
 
 ///
-/// This script will iterate through all content id / key pairs in rocksd & meta db.
-/// However, if it is run in "invalid-only" mode, it will error if it encounters any
-/// non-history network content. Since we only support history network content, this
-/// shouldn't be a problem, but as we add support for more sub-networks this script will
-/// need to be updated to avoid panicking.
+/// This script will iterate through all history network content id / key pairs in rocksdb &
+/// meta db. Content of other sub-networks is stored in separate namespaces, and left untouched.
 ///
 pub fn main() -> Result<()> {
     init_tracing_logger();
@@ -43,9 +40,13 @@ pub fn main() -> Result<()> {
     let capacity = 0;
     let protocol = ProtocolId::History;
     let config = PortalStorageConfig::new(capacity, node_data_dir, node_id)?;
-    let storage =
+    let mut storage =
         PortalStorage::new(config.clone(), protocol).expect("Failed to create portal storage");
-    let iter = config.db.iterator(IteratorMode::Start);
+    let column_family = config
+        .db
+        .cf_handle(PortalStorage::column_family_name(&ProtocolId::History))
+        .expect("Failed to find history column family");
+    let iter = config.db.iterator_cf(column_family, IteratorMode::Start);
     let mut item_count = 0;
     let mut remove_count = 0;
     for (id, value) in iter {
@@ -12,9 +12,10 @@ use utp_rs::socket::UtpSocket;
 use portalnet::{
     discovery::{Discovery, Discv5UdpSocket},
     events::PortalnetEvents,
-    storage::PortalStorageConfig,
+    rate_limit::RateLimitConfig,
+    storage::{ContentStoreConfig, MemoryContentStoreConfig, PortalStorageConfig},
     types::messages::PortalnetConfig,
-    utils::db::{configure_node_data_dir, configure_trin_data_dir},
+    utils::db::{configure_node_data_dir, configure_trin_data_dir, ephemeral_private_key},
 };
 use trin_history::initialize_history_network;
 use trin_state::initialize_state_network;
@@ -32,19 +33,30 @@ pub async fn run_trin(
     info!("Launching Trin: v{trin_version}");
     info!(config = %trin_config, "With:");
 
-    // Setup temp trin data directory if we're in ephemeral mode
-    let trin_data_dir = configure_trin_data_dir(trin_config.ephemeral)?;
-
-    // Configure node data dir based on the provided private key
-    let (node_data_dir, private_key) =
-        configure_node_data_dir(trin_data_dir, trin_config.private_key)?;
+    // Ephemeral nodes keep all their data in memory, so they have no data directory
+    let (node_data_dir, private_key) = if trin_config.ephemeral {
+        (None, ephemeral_private_key(trin_config.private_key)?)
+    } else {
+        let trin_data_dir = configure_trin_data_dir(false)?;
+        // Configure node data dir based on the provided private key
+        let (node_data_dir, private_key) =
+            configure_node_data_dir(trin_data_dir, trin_config.private_key)?;
+        (Some(node_data_dir), private_key)
+    };
 
     let portalnet_config = PortalnetConfig {
         external_addr: trin_config.external_addr,
         private_key,
         listen_port: trin_config.discovery_port,
+        ip_mode: trin_config.ip_mode,
         no_stun: trin_config.no_stun,
+        stun_servers: trin_config.stun_servers.clone(),
+        upnp: trin_config.upnp,
         bootnode_enrs: trin_config.bootnodes.clone().into(),
+        history_rate_limit: RateLimitConfig::from_args(&trin_config.history_overlay_config),
+        state_rate_limit: RateLimitConfig::from_args(&trin_config.state_overlay_config),
+        history_overlay_config: trin_config.history_overlay_config.clone(),
+        state_overlay_config: trin_config.state_overlay_config.clone(),
         ..Default::default()
     };
 
@@ -64,11 +76,18 @@ pub async fn run_trin(
     let utp_socket = UtpSocket::with_socket(discv5_utp_socket);
     let utp_socket = Arc::new(utp_socket);
 
-    let storage_config = PortalStorageConfig::new(
-        trin_config.mb.into(),
-        node_data_dir,
-        discovery.local_enr().node_id(),
-    )?;
+    let node_id = discovery.local_enr().node_id();
+    let storage_config = match node_data_dir {
+        Some(node_data_dir) => ContentStoreConfig::Disk(PortalStorageConfig::new(
+            trin_config.mb.into(),
+            node_data_dir,
+            node_id,
+        )?),
+        None => ContentStoreConfig::Memory(MemoryContentStoreConfig::new(
+            trin_config.mb.into(),
+            node_id,
+        )),
+    };
 
     // Initialize validation oracle
     let master_accumulator = MasterAccumulator::try_from_file(trin_config.master_acc_path.clone())?;
@@ -86,7 +105,10 @@ pub async fn run_trin(
                 &discovery,
                 Arc::clone(&utp_socket),
                 portalnet_config.clone(),
-                storage_config.clone(),
+                storage_config.for_subnetwork(
+                    trin_config.storage_capacity_mb(STATE_NETWORK).into(),
+                    trin_config.pinned_content_types(STATE_NETWORK),
+                ),
                 header_oracle.clone(),
             )
             .await?
@@ -105,7 +127,10 @@ pub async fn run_trin(
                 &discovery,
                 utp_socket,
                 portalnet_config.clone(),
-                storage_config.clone(),
+                storage_config.for_subnetwork(
+                    trin_config.storage_capacity_mb(HISTORY_NETWORK).into(),
+                    trin_config.pinned_content_types(HISTORY_NETWORK),
+                ),
                 header_oracle.clone(),
             )
             .await?
@@ -133,6 +158,8 @@ pub async fn run_trin(
             history_event_tx,
             state_event_tx,
             utp_talk_reqs_tx,
+            portalnet_config.history_rate_limit,
+            portalnet_config.state_rate_limit,
         )
         .await;
         events.start().await;
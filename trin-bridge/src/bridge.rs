@@ -1,7 +1,7 @@
 use crate::cli::BridgeMode;
 use crate::constants::PANDAOPS_URL;
-use crate::utils::get_ranges;
-use anyhow::{anyhow, bail};
+use crate::utils::{get_era1_files, get_ranges};
+use anyhow::{anyhow, bail, ensure};
 use ethereum_types::H256;
 use ethportal_api::jsonrpsee::http_client::HttpClient;
 use ethportal_api::HistoryNetworkApiClient;
@@ -10,7 +10,7 @@ use ssz::Decode;
 use std::env;
 use std::fs;
 use std::ops::Range;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time;
 use tokio::task::JoinHandle;
 use tokio::time::{sleep, Duration};
@@ -19,6 +19,7 @@ use trin_types::content_key::{
     BlockBodyKey, BlockHeaderKey, BlockReceiptsKey, EpochAccumulatorKey, HistoryContentKey,
 };
 use trin_types::content_value::HistoryContentValue;
+use trin_types::era1::{BlockTuple, Era1};
 use trin_types::execution::accumulator::EpochAccumulator;
 use trin_types::execution::block_body::{BlockBody, EncodableHeaderList};
 use trin_types::execution::header::{
@@ -31,13 +32,14 @@ use trin_types::jsonrpc::request::JsonRequest;
 use trin_utils::bytes::hex_encode;
 use trin_validation::accumulator::MasterAccumulator;
 use trin_validation::constants::{EPOCH_SIZE as EPOCH_SIZE_USIZE, MERGE_BLOCK_NUMBER};
-use trin_validation::oracle::HeaderOracle;
 
 pub struct Bridge {
     pub mode: BridgeMode,
     pub portal_clients: Vec<HttpClient>,
-    pub header_oracle: HeaderOracle,
+    pub master_acc: MasterAccumulator,
     pub epoch_acc_path: PathBuf,
+    /// Directory of era1 files to backfill from, instead of the provider.
+    pub era1_path: Option<PathBuf>,
 }
 
 // todo: calculate / test optimal saturation delay
@@ -99,6 +101,9 @@ impl Bridge {
     }
 
     pub async fn launch_backfill(&self, starting_epoch: Option<u64>) {
+        if let Some(era1_path) = &self.era1_path {
+            return self.launch_era1_backfill(era1_path, starting_epoch).await;
+        }
         let latest_block = get_latest_block_number().await.expect(
             "Error launching bridge in backfill mode. Unable to get latest block from provider.",
         );
@@ -154,6 +159,132 @@ impl Bridge {
         }
     }
 
+    /// Backfills pre-merge history from the era1 files in `era1_path`, one epoch per file. Unlike
+    /// the provider backfill, no requests are made other than gossip.
+    async fn launch_era1_backfill(&self, era1_path: &Path, starting_epoch: Option<u64>) {
+        let era1_files = get_era1_files(era1_path).expect(
+            "Error launching bridge in backfill mode. Unable to list era1 files in era1 path.",
+        );
+        let starting_epoch = starting_epoch.unwrap_or(0);
+        for (epoch_index, path) in era1_files {
+            if epoch_index < starting_epoch {
+                continue;
+            }
+            if let Err(msg) = self.gossip_era1(epoch_index, &path).await {
+                warn!("Error gossiping era1 file: {path:?} - {msg:?}. Skipping iteration.");
+            }
+        }
+    }
+
+    /// Gossips the epoch accumulator, headers with proofs, bodies and receipts of an era1 file.
+    async fn gossip_era1(&self, epoch_index: u64, path: &Path) -> anyhow::Result<()> {
+        info!("Reading era1 file: {path:?}");
+        let era1 = Era1::read_file(path)?;
+        ensure!(
+            era1.starting_number == epoch_index * EPOCH_SIZE,
+            "Era1 file starts at block {}, expected the start of epoch {epoch_index}",
+            era1.starting_number
+        );
+        let epoch_hash = self
+            .master_acc
+            .historical_epochs
+            .get(epoch_index as usize)
+            .ok_or_else(|| anyhow!("Epoch {epoch_index} is not in the master accumulator"))?;
+        // The era1 reader checked the accumulator root against the headers of the file.
+        ensure!(
+            era1.accumulator_root == *epoch_hash,
+            "Era1 accumulator root doesn't match the master accumulator: {:?} - {epoch_hash:?}",
+            era1.accumulator_root
+        );
+        let epoch_acc = era1.epoch_accumulator()?;
+        let range = Range {
+            start: era1.starting_number,
+            end: era1.starting_number + era1.blocks.len() as u64,
+        };
+        let mut gossip_batch = GossipBatch::new(range, Some(epoch_index), vec![]);
+
+        let content_key = HistoryContentKey::EpochAccumulator(EpochAccumulatorKey {
+            epoch_hash: *epoch_hash,
+        });
+        let content_value = HistoryContentValue::EpochAccumulator(epoch_acc.clone());
+        let _ =
+            Bridge::gossip_content(self.portal_clients.clone(), content_key, content_value).await;
+
+        info!("Gossiping headers in range: {:?}", gossip_batch.range);
+        for block in era1.blocks.iter() {
+            let (content_key, hwp) = Bridge::construct_proof(&block.header, &epoch_acc).await?;
+            let content_value = HistoryContentValue::BlockHeaderWithProof(hwp);
+            debug!("Gossip: Block #{:?} HeaderWithProof", block.header.number);
+            if Bridge::gossip_content(self.portal_clients.clone(), content_key, content_value)
+                .await
+                .is_ok()
+            {
+                gossip_batch.hwp_count += 1;
+            }
+        }
+        // Sleep for 5 seconds to allow headers to saturate network,
+        // since they must be available for body / receipt validation
+        sleep(Duration::from_secs(HEADER_SATURATION_DELAY)).await;
+
+        info!(
+            "Serving bodies and receipts in range: {:?}",
+            gossip_batch.range
+        );
+        for block in era1.blocks {
+            let (body_gossiped, receipts_gossiped) =
+                Bridge::gossip_era1_block(self.portal_clients.clone(), block).await;
+            if body_gossiped {
+                gossip_batch.bodies_count += 1;
+            }
+            if receipts_gossiped {
+                gossip_batch.receipts_count += 1;
+            }
+        }
+        gossip_batch.display_stats();
+        Ok(())
+    }
+
+    /// Validates the body and receipts of an era1 block against its header & gossips them.
+    /// Returns whether the body and the receipts were gossiped.
+    async fn gossip_era1_block(portal_clients: Vec<HttpClient>, block: BlockTuple) -> (bool, bool) {
+        let block_hash = block.header.hash().to_fixed_bytes();
+        let body_gossiped = match validate_era1_body(&block) {
+            Ok(_) => {
+                let content_key = HistoryContentKey::BlockBody(BlockBodyKey { block_hash });
+                let content_value = HistoryContentValue::BlockBody(block.body);
+                debug!("Gossip: Block #{:?} BlockBody", block.header.number);
+                Bridge::gossip_content(portal_clients.clone(), content_key, content_value)
+                    .await
+                    .is_ok()
+            }
+            Err(err) => {
+                warn!(
+                    "Invalid era1 body of block #{:?}: {err:?}",
+                    block.header.number
+                );
+                false
+            }
+        };
+        let receipts_gossiped = match block.receipts.root() {
+            Ok(receipts_root) if receipts_root == block.header.receipts_root => {
+                let content_key = HistoryContentKey::BlockReceipts(BlockReceiptsKey { block_hash });
+                let content_value = HistoryContentValue::Receipts(block.receipts);
+                debug!("Gossip: Block #{:?} Receipts", block.header.number);
+                Bridge::gossip_content(portal_clients, content_key, content_value)
+                    .await
+                    .is_ok()
+            }
+            _ => {
+                warn!(
+                    "Era1 receipts of block #{:?} don't match header receipts root",
+                    block.header.number
+                );
+                false
+            }
+        };
+        (body_gossiped, receipts_gossiped)
+    }
+
     async fn gossip_headers(&self, gossip_batch: &mut GossipBatch) -> anyhow::Result<()> {
         info!("Gossiping headers in range: {:?}", gossip_batch.range);
         if gossip_batch.range.end <= MERGE_BLOCK_NUMBER {
@@ -230,7 +361,7 @@ impl Bridge {
     /// Attempt to lookup an epoch accumulator from local portal-accumulators path provided via cli
     /// arg. Fallback to retrieving epoch acc from network if unable to find epoch acc locally.
     async fn get_epoch_acc(&self, epoch_index: u64) -> anyhow::Result<EpochAccumulator> {
-        let epoch_hash = self.header_oracle.master_acc.historical_epochs[epoch_index as usize];
+        let epoch_hash = self.master_acc.historical_epochs[epoch_index as usize];
         let epoch_hash_pretty = hex_encode(epoch_hash);
         let epoch_hash_pretty = epoch_hash_pretty.trim_start_matches("0x");
         let epoch_acc_path = format!(
@@ -538,6 +669,25 @@ impl GossipBatch {
     }
 }
 
+/// Checks the uncles and transactions of an era1 block against its header.
+fn validate_era1_body(block: &BlockTuple) -> anyhow::Result<()> {
+    let uncles_root = block.body.uncles_root()?;
+    if uncles_root != block.header.uncles_hash {
+        bail!(
+            "Block body uncles root doesn't match header uncles root: {uncles_root:?} - {:?}",
+            block.header.uncles_hash
+        );
+    }
+    let txs_root = block.body.transactions_root()?;
+    if txs_root != block.header.transactions_root {
+        bail!(
+            "Block body txs root doesn't match header txs root: {txs_root:?} - {:?}",
+            block.header.transactions_root
+        );
+    }
+    Ok(())
+}
+
 async fn get_latest_block_number() -> anyhow::Result<u64> {
     let params = Params::Array(vec![json!("latest"), json!(false)]);
     let method = "eth_getBlockByNumber".to_string();
@@ -34,6 +34,12 @@ pub struct BridgeConfig {
         help = "Path to epoch accumulator repo for bridge mode"
     )]
     pub epoch_acc_path: PathBuf,
+
+    #[arg(
+        long = "era1-path",
+        help = "Directory of era1 files to backfill pre-merge history from, instead of a provider"
+    )]
+    pub era1_path: Option<PathBuf>,
 }
 
 fn check_node_count(val: &str) -> Result<u8, String> {
@@ -97,6 +103,7 @@ mod test {
         );
         assert_eq!(bridge_config.mode, BridgeMode::Latest);
         assert_eq!(bridge_config.epoch_acc_path, PathBuf::from(EPOCH_ACC_PATH));
+        assert_eq!(bridge_config.era1_path, None);
     }
 
     #[test]
@@ -125,6 +132,29 @@ mod test {
         assert_eq!(bridge_config.epoch_acc_path, PathBuf::from(EPOCH_ACC_PATH));
     }
 
+    #[test]
+    fn test_bridge_config_with_era1_path() {
+        const NODE_COUNT: &str = "1";
+        const EXECUTABLE_PATH: &str = "path/to/executable";
+        const EPOCH_ACC_PATH: &str = "path/to/epoch/accumulator";
+        const ERA1_PATH: &str = "path/to/era1";
+        let bridge_config = BridgeConfig::parse_from([
+            "test",
+            "--node-count",
+            NODE_COUNT,
+            "--executable-path",
+            EXECUTABLE_PATH,
+            "--epoch-accumulator-path",
+            EPOCH_ACC_PATH,
+            "--mode",
+            "backfill",
+            "--era1-path",
+            ERA1_PATH,
+        ]);
+        assert_eq!(bridge_config.mode, BridgeMode::Backfill);
+        assert_eq!(bridge_config.era1_path, Some(PathBuf::from(ERA1_PATH)));
+    }
+
     #[test]
     fn test_bridge_config_with_max_node_count() {
         let node_count_string = MAX_NODE_COUNT.to_string();
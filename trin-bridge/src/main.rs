@@ -5,18 +5,20 @@ use tokio::time::{sleep, Duration};
 use tracing::info;
 use trin_bridge::bridge::Bridge;
 use trin_bridge::cli::{BridgeConfig, BridgeMode};
-use trin_bridge::constants::PANDAOPS_URL;
 use trin_bridge::utils::generate_spaced_private_keys;
-use trin_types::provider::{build_pandaops_http_client_from_env, TrustedProvider};
 use trin_utils::log::init_tracing_logger;
 use trin_validation::accumulator::MasterAccumulator;
-use trin_validation::oracle::HeaderOracle;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     init_tracing_logger();
 
     let bridge_config = BridgeConfig::parse();
+    if bridge_config.era1_path.is_some() && bridge_config.mode == BridgeMode::Latest {
+        return Err(
+            "Era1 files only hold pre-merge history, use a backfill mode with --era1-path".into(),
+        );
+    }
     let private_keys = generate_spaced_private_keys(bridge_config.node_count);
     let mut handles = vec![];
     let mut http_addresses = vec![];
@@ -38,11 +40,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     sleep(Duration::from_secs(5)).await;
 
-    let trusted_provider = TrustedProvider {
-        http: build_pandaops_http_client_from_env(PANDAOPS_URL.to_string()),
-    };
     let master_acc = MasterAccumulator::try_from_file("validation_assets/merge_macc.bin".into())?;
-    let header_oracle = HeaderOracle::new(trusted_provider, master_acc);
 
     let portal_clients: Result<Vec<HttpClient>, String> = http_addresses
         .iter()
@@ -55,8 +53,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let bridge = Bridge {
         mode: bridge_config.mode,
         portal_clients: portal_clients?,
-        header_oracle,
+        master_acc,
         epoch_acc_path: bridge_config.epoch_acc_path,
+        era1_path: bridge_config.era1_path,
     };
 
     info!("Launching bridge mode: {:?}", bridge.mode);
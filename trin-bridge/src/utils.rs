@@ -1,6 +1,8 @@
 use crate::constants::BATCH_SIZE;
 use discv5::enr::{CombinedKey, EnrBuilder, NodeId};
+use std::fs;
 use std::ops::Range;
+use std::path::{Path, PathBuf};
 use trin_utils::bytes::hex_encode;
 
 /// Splits a range into chunks of (at most) BATCH_SIZE,
@@ -17,6 +19,34 @@ pub fn get_ranges(range: &Range<u64>) -> Vec<Range<u64>> {
     ranges
 }
 
+/// Returns the era1 files in `dir` with their epoch index, in ascending order of epoch index.
+pub fn get_era1_files(dir: &Path) -> anyhow::Result<Vec<(u64, PathBuf)>> {
+    let mut era1_files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let epoch_index = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .and_then(era1_epoch_index);
+        if let Some(epoch_index) = epoch_index {
+            era1_files.push((epoch_index, path));
+        }
+    }
+    era1_files.sort();
+    Ok(era1_files)
+}
+
+/// Parses the epoch index from the name of an era1 file, which is
+/// `<network>-<epoch index>-<short accumulator root>.era1`.
+fn era1_epoch_index(file_name: &str) -> Option<u64> {
+    let name = file_name.strip_suffix(".era1")?;
+    let mut parts = name.split('-');
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(_network), Some(epoch_index), Some(_root), None) => epoch_index.parse().ok(),
+        _ => None,
+    }
+}
+
 /// Generates a set of N private keys, with node ids that are equally spaced
 /// around the 256-bit keys space.
 // count as u8 is used as a safety precaution against foot-gunning, since it could
@@ -64,6 +94,16 @@ mod tests {
     use trin_types::distance::{Metric, XorMetric};
     use trin_utils::bytes::hex_decode;
 
+    #[rstest]
+    #[case("mainnet-00000-5ec1ffb8.era1", Some(0))]
+    #[case("mainnet-01895-3f81607c.era1", Some(1895))]
+    #[case("mainnet-00001-a5364e9a.era", None)]
+    #[case("mainnet-00001.era1", None)]
+    #[case("mainnet-latest-a5364e9a.era1", None)]
+    fn test_era1_epoch_index(#[case] file_name: &str, #[case] expected: Option<u64>) {
+        assert_eq!(era1_epoch_index(file_name), expected);
+    }
+
     #[rstest]
     #[case(2)]
     #[case(4)]
@@ -1,20 +1,23 @@
 pub mod dashboard;
-use clap::{Args, Parser, Subcommand};
+pub mod trace;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[cfg(unix)]
 use std::os::unix::net::UnixStream;
 #[cfg(windows)]
 use uds_windows::UnixStream;
 
+use std::fs;
 use std::path::{Path, PathBuf};
 
 use ethereum_types::H256;
-use serde_json::value::RawValue;
+use serde_json::{value::RawValue, Value};
 use thiserror::Error;
 
 use dashboard::grafana::{GrafanaAPI, DASHBOARD_TEMPLATES};
 use ethportal_api::{BlockBodyKey, BlockHeaderKey, BlockReceiptsKey, HistoryContentKey};
 use trin_types::cli::DEFAULT_WEB3_IPC_PATH;
+use trin_types::query_trace::QueryTrace;
 use trin_utils::bytes::hex_encode;
 
 #[derive(Parser, Debug, PartialEq)]
@@ -29,6 +32,7 @@ enum Trin {
     #[command(subcommand)]
     EncodeKey(EncodeKey),
     CreateDashboard(DashboardConfig),
+    RenderTrace(RenderTrace),
 }
 
 #[derive(Args, Debug, PartialEq)]
@@ -89,11 +93,36 @@ struct DashboardConfig {
     prometheus_address: String,
 }
 
+#[derive(Args, Debug, PartialEq)]
+#[command(
+    name = "render-trace",
+    about = "Render a saved query trace as a Graphviz DOT graph or a text waterfall timeline"
+)]
+struct RenderTrace {
+    /// Path to a JSON file with a query trace, or with the response of a trace JSON-RPC method
+    /// (e.g. portal_historyTraceRecursiveFindNodes).
+    #[arg(required = true)]
+    path: PathBuf,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = TraceFormat::Waterfall)]
+    format: TraceFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum TraceFormat {
+    /// Graphviz DOT graph of the nodes each node responded with.
+    Dot,
+    /// Text timeline of the requests sent during the query.
+    Waterfall,
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     match Trin::parse() {
         Trin::JsonRpc(rpc) => json_rpc(rpc),
         Trin::EncodeKey(content_key) => encode_content_key(content_key),
         Trin::CreateDashboard(dashboard_config) => create_dashboard(dashboard_config),
+        Trin::RenderTrace(render) => render_trace(render),
     }
 }
 
@@ -161,6 +190,31 @@ fn create_dashboard(dashboard_config: DashboardConfig) -> Result<(), Box<dyn std
     Ok(())
 }
 
+fn render_trace(render: RenderTrace) -> Result<(), Box<dyn std::error::Error>> {
+    let json: Value = serde_json::from_str(&fs::read_to_string(&render.path)?)?;
+    let trace = parse_trace(json)?;
+
+    let output = match render.format {
+        TraceFormat::Dot => trace::render_dot(&trace),
+        TraceFormat::Waterfall => trace::render_waterfall(&trace),
+    };
+    print!("{output}");
+
+    Ok(())
+}
+
+/// Parses a query trace, which may be wrapped in a JSON-RPC response and in the result of a
+/// trace method.
+fn parse_trace(mut json: Value) -> Result<QueryTrace, serde_json::Error> {
+    if let Some(result) = json.get_mut("result") {
+        json = result.take();
+    }
+    if let Some(trace) = json.get_mut("trace") {
+        json = trace.take();
+    }
+    serde_json::from_value(json)
+}
+
 fn build_request<'a>(
     method: &'a str,
     raw_params: &'a Option<Vec<Box<RawValue>>>,
@@ -359,4 +413,39 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_trin_with_render_trace() {
+        let trin = Trin::parse_from(["test", "render-trace", "--format", "dot", "trace.json"]);
+        assert_eq!(
+            trin,
+            Trin::RenderTrace(RenderTrace {
+                path: PathBuf::from("trace.json"),
+                format: TraceFormat::Dot,
+            })
+        );
+
+        let trin = Trin::parse_from(["test", "render-trace", "trace.json"]);
+        assert_eq!(
+            trin,
+            Trin::RenderTrace(RenderTrace {
+                path: PathBuf::from("trace.json"),
+                format: TraceFormat::Waterfall,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_trace() {
+        let (_, local_enr) = trin_types::enr::generate_random_remote_enr();
+        let trace = QueryTrace::new(&local_enr, local_enr.node_id().into());
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": { "nodes": [], "trace": trace },
+        });
+
+        assert_eq!(parse_trace(response).unwrap(), trace);
+        assert_eq!(parse_trace(serde_json::json!(trace)).unwrap(), trace);
+    }
 }
@@ -0,0 +1,259 @@
+use std::fmt::Write;
+
+use trin_types::distance::{Metric, XorMetric};
+use trin_types::node_id::NodeId;
+use trin_types::query_trace::{QueryFailureKind, QueryTrace};
+use trin_utils::bytes::{hex_encode, hex_encode_compact};
+
+/// Width, in characters, of the timeline in a waterfall.
+const WATERFALL_WIDTH: u64 = 50;
+
+/// Renders `trace` as a Graphviz DOT graph, with an edge from each node to every node it
+/// responded with.
+pub fn render_dot(trace: &QueryTrace) -> String {
+    let mut dot = String::new();
+    let _ = writeln!(dot, "digraph query_trace {{");
+    let _ = writeln!(dot, "    rankdir=LR;");
+    let _ = writeln!(dot, "    node [shape=box, fontname=\"monospace\"];");
+
+    for node_id in sorted_node_ids(trace) {
+        let mut label = hex_encode_compact(node_id.raw());
+        if let Some(info) = trace.node_metadata.get(&node_id) {
+            let _ = write!(label, "\\nlog2 distance: {}", info.distance_log2);
+        }
+        let (style, color) = if node_id == trace.origin {
+            label.push_str("\\norigin");
+            ("filled", "lightblue")
+        } else if trace.received_content_from_node == Some(node_id) {
+            if let Some(response) = trace.responses.get(&node_id) {
+                let _ = write!(label, "\\ncontent at {} ms", response.timestamp_millis);
+            }
+            ("filled", "palegreen")
+        } else if let Some(failure) = trace.failures.get(&node_id) {
+            let _ = write!(
+                label,
+                "\\n{} at {} ms",
+                failure_str(failure.kind),
+                failure.timestamp_millis
+            );
+            ("filled", "salmon")
+        } else if let Some(response) = trace.responses.get(&node_id) {
+            let _ = write!(label, "\\nresponded at {} ms", response.timestamp_millis);
+            ("filled", "white")
+        } else {
+            ("filled,dashed", "lightgrey")
+        };
+        if trace.contacted_for_radius.contains(&node_id) {
+            label.push_str("\\ncontacted for radius");
+        }
+        let _ = writeln!(
+            dot,
+            "    \"{}\" [label=\"{label}\", style=\"{style}\", fillcolor=\"{color}\"];",
+            hex_encode(node_id.raw())
+        );
+    }
+
+    for node_id in sorted_node_ids(trace) {
+        if let Some(response) = trace.responses.get(&node_id) {
+            for responded_with in response.responded_with.iter() {
+                let _ = writeln!(
+                    dot,
+                    "    \"{}\" -> \"{}\";",
+                    hex_encode(node_id.raw()),
+                    hex_encode(responded_with.raw())
+                );
+            }
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+/// Renders `trace` as a text waterfall timeline, with one row for each node that was contacted
+/// during the query, in the order the requests were sent.
+pub fn render_waterfall(trace: &QueryTrace) -> String {
+    let mut rows: Vec<WaterfallRow> = sorted_node_ids(trace)
+        .into_iter()
+        .filter(|node_id| *node_id != trace.origin)
+        .filter_map(|node_id| WaterfallRow::new(trace, node_id))
+        .collect();
+    rows.sort_by_key(|row| (row.start(), row.end));
+
+    let duration = rows
+        .iter()
+        .map(|row| row.end.unwrap_or_else(|| row.start()))
+        .max()
+        .unwrap_or_default()
+        .max(1);
+
+    let mut waterfall = String::new();
+    let _ = writeln!(
+        waterfall,
+        "{:<14} {:>5} {:>9} {:>9}  {:<12} timeline (0-{duration} ms)",
+        "node", "log2", "sent ms", "done ms", "result"
+    );
+    for row in rows {
+        let log2_distance = trace
+            .node_metadata
+            .get(&row.node_id)
+            .map(|info| info.distance_log2.to_string())
+            .unwrap_or_default();
+        let sent = row.sent.map(|t| t.to_string()).unwrap_or_default();
+        let end = row.end.map(|t| t.to_string()).unwrap_or_default();
+        let _ = writeln!(
+            waterfall,
+            "{:<14} {:>5} {:>9} {:>9}  {:<12} {}",
+            hex_encode_compact(row.node_id.raw()),
+            log2_distance,
+            sent,
+            end,
+            row.result,
+            row.bar(duration)
+        );
+    }
+    waterfall
+}
+
+/// A single row of a waterfall timeline.
+struct WaterfallRow {
+    node_id: NodeId,
+    /// Milliseconds since the query started at which the request was sent.
+    sent: Option<u64>,
+    /// Milliseconds since the query started at which the request completed.
+    end: Option<u64>,
+    result: String,
+    marker: char,
+}
+
+impl WaterfallRow {
+    /// Returns the row for `node_id`, or `None` if the node was never contacted.
+    fn new(trace: &QueryTrace, node_id: NodeId) -> Option<Self> {
+        let sent = trace.requests_sent.get(&node_id).copied();
+        let (end, result, marker) = if trace.received_content_from_node == Some(node_id) {
+            let end = trace
+                .responses
+                .get(&node_id)
+                .map(|response| response.timestamp_millis);
+            (end, "content".to_owned(), '*')
+        } else if let Some(response) = trace.responses.get(&node_id) {
+            let result = format!("nodes ({})", response.responded_with.len());
+            (Some(response.timestamp_millis), result, '|')
+        } else if let Some(failure) = trace.failures.get(&node_id) {
+            let result = failure_str(failure.kind).to_owned();
+            (Some(failure.timestamp_millis), result, 'x')
+        } else if sent.is_some() {
+            (None, "pending".to_owned(), '>')
+        } else {
+            return None;
+        };
+
+        Some(Self {
+            node_id,
+            sent,
+            end,
+            result,
+            marker,
+        })
+    }
+
+    fn start(&self) -> u64 {
+        self.sent.or(self.end).unwrap_or_default()
+    }
+
+    /// Returns the bar of the row, on a timeline that spans `duration` milliseconds.
+    fn bar(&self, duration: u64) -> String {
+        let column = |t: u64| (t.min(duration) * WATERFALL_WIDTH / duration) as usize;
+        let start = column(self.start());
+        let end = self.end.map(column).unwrap_or(WATERFALL_WIDTH as usize);
+
+        let mut bar = " ".repeat(start);
+        bar.push_str(&"=".repeat(end.saturating_sub(start)));
+        bar.push(self.marker);
+        bar
+    }
+}
+
+/// Returns the IDs of all nodes in `trace`, sorted by distance to the target.
+fn sorted_node_ids(trace: &QueryTrace) -> Vec<NodeId> {
+    let mut node_ids: Vec<NodeId> = trace.node_metadata.keys().copied().collect();
+    if !node_ids.contains(&trace.origin) {
+        node_ids.push(trace.origin);
+    }
+    let target = trace.target_id();
+    node_ids.sort_by_key(|node_id| XorMetric::distance(&node_id.raw(), &target.raw()));
+    node_ids
+}
+
+fn failure_str(kind: QueryFailureKind) -> &'static str {
+    match kind {
+        QueryFailureKind::Timeout => "timeout",
+        QueryFailureKind::Error => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use trin_types::enr::generate_random_remote_enr;
+
+    fn build_trace() -> (QueryTrace, NodeId, NodeId, NodeId) {
+        let (_, local_enr) = generate_random_remote_enr();
+        let (_, enr_a) = generate_random_remote_enr();
+        let (_, enr_b) = generate_random_remote_enr();
+        let (_, enr_c) = generate_random_remote_enr();
+
+        let mut trace = QueryTrace::new(&local_enr, NodeId::random());
+        trace.node_responded_with(&local_enr, vec![&enr_a, &enr_b]);
+        trace.node_request_sent(&enr_a);
+        trace.node_request_sent(&enr_b);
+        trace.node_responded_with(&enr_a, vec![&enr_c]);
+        trace.node_failed(&enr_b, QueryFailureKind::Timeout);
+        trace.node_request_sent(&enr_c);
+
+        (trace, enr_a.into(), enr_b.into(), enr_c.into())
+    }
+
+    #[test]
+    fn dot_contains_nodes_and_edges() {
+        let (trace, node_a, node_b, node_c) = build_trace();
+        let dot = render_dot(&trace);
+
+        assert!(dot.starts_with("digraph query_trace {"));
+        assert!(dot.trim_end().ends_with('}'));
+        let edge = |from: NodeId, to: NodeId| {
+            format!(
+                "\"{}\" -> \"{}\";",
+                hex_encode(from.raw()),
+                hex_encode(to.raw())
+            )
+        };
+        assert!(dot.contains(&edge(trace.origin, node_a)));
+        assert!(dot.contains(&edge(trace.origin, node_b)));
+        assert!(dot.contains(&edge(node_a, node_c)));
+        assert!(dot.contains("\\ntimeout at "));
+        // The origin, a, b and c.
+        assert_eq!(dot.matches("[label=").count(), 4);
+    }
+
+    #[test]
+    fn waterfall_has_row_per_contacted_node() {
+        let (trace, node_a, node_b, node_c) = build_trace();
+        let waterfall = render_waterfall(&trace);
+        let lines: Vec<&str> = waterfall.lines().collect();
+
+        // A header and a row for each of a, b and c.
+        assert_eq!(lines.len(), 4);
+        let row = |node_id: NodeId| {
+            let id = hex_encode_compact(node_id.raw());
+            *lines.iter().find(|line| line.starts_with(&id)).unwrap()
+        };
+        assert!(row(node_a).contains("nodes (1)"));
+        assert!(row(node_a).ends_with('|'));
+        assert!(row(node_b).contains("timeout"));
+        assert!(row(node_b).ends_with('x'));
+        assert!(row(node_c).contains("pending"));
+        assert!(row(node_c).ends_with('>'));
+    }
+}
@@ -1,11 +1,19 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use discv5::enr::NodeId;
+use ethereum_types::H256;
 use ethportal_api::{
-    types::portal::{AcceptInfo, FindNodesInfo, PongInfo, TraceContentInfo},
+    types::portal::{AcceptInfo, FindNodesInfo, PongInfo, TraceContentInfo, TraceNodesInfo},
     ContentValue, {HistoryContentKey, OverlayContentKey},
 };
-use portalnet::storage::ContentStore;
+use futures::StreamExt;
+use portalnet::{
+    overlay::{DEFAULT_BATCH_LOOKUP_CONCURRENCY, MAX_BATCH_LOOKUP_SIZE},
+    storage::ContentStore,
+};
 use serde_json::{json, Value};
 use ssz::Encode;
 use tokio::sync::{mpsc, Mutex, RwLock};
@@ -15,6 +23,7 @@ use trin_types::{
     content_key::RawContentKey,
     distance::{Metric, XorMetric},
     enr::Enr,
+    inventory::LocalContentFilter,
     jsonrpc::endpoints::HistoryEndpoint,
     jsonrpc::request::HistoryJsonRpcRequest,
     query_trace::QueryTrace,
@@ -48,15 +57,25 @@ async fn complete_request(network: Arc<RwLock<HistoryNetwork>>, request: History
         HistoryEndpoint::PaginateLocalContentKeys(offset, limit) => {
             paginate_local_content_keys(network, offset, limit).await
         }
+        HistoryEndpoint::LocalContentInventory(filter, cursor, limit) => {
+            local_content_inventory(network, filter, cursor, limit).await
+        }
+        HistoryEndpoint::LocalContentStats(filter) => local_content_stats(network, filter).await,
         HistoryEndpoint::Store(content_key, content_value) => {
             store(network, content_key, content_value).await
         }
+        HistoryEndpoint::PinContent(content_key) => pin_content(network, content_key).await,
+        HistoryEndpoint::UnpinContent(content_key) => unpin_content(network, content_key).await,
+        HistoryEndpoint::PinnedContent => pinned_content(network).await,
         HistoryEndpoint::RecursiveFindContent(content_key) => {
             recursive_find_content(network, content_key, false).await
         }
         HistoryEndpoint::TraceRecursiveFindContent(content_key) => {
             recursive_find_content(network, content_key, true).await
         }
+        HistoryEndpoint::RecursiveFindContentBatch(content_keys, max_concurrency) => {
+            recursive_find_content_batch(network, content_keys, max_concurrency).await
+        }
         HistoryEndpoint::DataRadius => {
             let radius = network.read().await.overlay.data_radius();
             Ok(json!(*radius))
@@ -68,6 +87,7 @@ async fn complete_request(network: Arc<RwLock<HistoryNetwork>>, request: History
         HistoryEndpoint::Gossip(content_key, content_value) => {
             gossip(network, content_key, content_value).await
         }
+        HistoryEndpoint::GossipStatus(content_key) => gossip_status(network, content_key).await,
         HistoryEndpoint::Offer(enr, content_key, content_value) => {
             offer(network, enr, content_key, content_value).await
         }
@@ -75,8 +95,15 @@ async fn complete_request(network: Arc<RwLock<HistoryNetwork>>, request: History
         HistoryEndpoint::RoutingTableInfo => Ok(bucket_entries_to_json(
             network.read().await.overlay.bucket_entries(),
         )),
+        HistoryEndpoint::RoutingTableHealth => {
+            let health = network.read().await.overlay.routing_table_health();
+            serde_json::to_value(health).map_err(|err| err.to_string())
+        }
         HistoryEndpoint::RecursiveFindNodes(node_id) => {
-            recursive_find_nodes(network, node_id).await
+            recursive_find_nodes(network, node_id, false).await
+        }
+        HistoryEndpoint::TraceRecursiveFindNodes(node_id) => {
+            recursive_find_nodes(network, node_id, true).await
         }
     };
     let _ = request.resp.send(response);
@@ -135,6 +162,92 @@ async fn recursive_find_content(
     }
 }
 
+/// Constructs a JSON call for the RecursiveFindContentBatch method.
+///
+/// Duplicate keys are looked up once, and the results are returned in the order of the first
+/// occurrence of each key.
+async fn recursive_find_content_batch(
+    network: Arc<RwLock<HistoryNetwork>>,
+    content_keys: Vec<HistoryContentKey>,
+    max_concurrency: Option<usize>,
+) -> Result<Value, String> {
+    if content_keys.len() > MAX_BATCH_LOOKUP_SIZE {
+        return Err(format!(
+            "Batch of {} content keys exceeds the maximum of {MAX_BATCH_LOOKUP_SIZE}",
+            content_keys.len()
+        ));
+    }
+    let content_keys = dedup_content_keys(content_keys);
+    let overlay = network.read().await.overlay.clone();
+
+    // Serve the content that is available locally, and look up the rest in the network.
+    let mut results = HashMap::new();
+    let mut lookup_keys = vec![];
+    for content_key in &content_keys {
+        match overlay.store.read().get(content_key) {
+            Ok(Some(data)) => {
+                results.insert(
+                    content_key.content_id(),
+                    batch_result(content_key, hex_encode(data), Value::Null),
+                );
+            }
+            Ok(None) => lookup_keys.push(content_key.clone()),
+            Err(err) => {
+                error!(
+                    error = %err,
+                    content.key = %content_key,
+                    "Error checking data store for content",
+                );
+                lookup_keys.push(content_key.clone());
+            }
+        }
+    }
+
+    let max_concurrency = max_concurrency.unwrap_or(DEFAULT_BATCH_LOOKUP_CONCURRENCY);
+    let mut lookups = overlay.lookup_content_batch(lookup_keys, max_concurrency);
+    while let Some((content_key, result)) = lookups.next().await {
+        let (content, error) = match result {
+            Ok(data) => (hex_encode(data), Value::Null),
+            Err(err) => (CONTENT_ABSENT.to_string(), Value::String(err.to_string())),
+        };
+        results.insert(
+            content_key.content_id(),
+            batch_result(&content_key, content, error),
+        );
+    }
+
+    Ok(Value::Array(order_batch_results(&content_keys, results)))
+}
+
+/// Removes duplicate content keys, keeping the first occurrence of each key.
+fn dedup_content_keys(content_keys: Vec<HistoryContentKey>) -> Vec<HistoryContentKey> {
+    let mut seen = HashSet::new();
+    content_keys
+        .into_iter()
+        .filter(|content_key| seen.insert(content_key.content_id()))
+        .collect()
+}
+
+/// Orders the results of a batch lookup, keyed by content ID, as their content keys.
+fn order_batch_results(
+    content_keys: &[HistoryContentKey],
+    mut results: HashMap<[u8; 32], Value>,
+) -> Vec<Value> {
+    content_keys
+        .iter()
+        .filter_map(|content_key| results.remove(&content_key.content_id()))
+        .collect()
+}
+
+/// Constructs the JSON result of a single lookup in a batch.
+fn batch_result(content_key: &HistoryContentKey, content: String, error: Value) -> Value {
+    json!({
+        "contentKey": content_key,
+        "content": content,
+        "error": error,
+    })
+}
+
 /// Constructs a JSON call for the LocalContent method.
 async fn local_content(
     network: Arc<RwLock<HistoryNetwork>>,
@@ -165,7 +278,7 @@ async fn paginate_local_content_keys(
     limit: u64,
 ) -> Result<Value, String> {
     let store = network.read().await.overlay.store.clone();
-    let response = match store.read().paginate(&offset, &limit)
+    let response = match store.read().paginate::<HistoryContentKey>(&offset, &limit)
         {
             Ok(val) => Ok(json!(val)),
             Err(err) => Err(format!(
@@ -175,6 +288,39 @@ async fn paginate_local_content_keys(
     response
 }
 
+/// Constructs a JSON call for the LocalContentInventory method.
+async fn local_content_inventory(
+    network: Arc<RwLock<HistoryNetwork>>,
+    filter: LocalContentFilter,
+    cursor: Option<H256>,
+    limit: u64,
+) -> Result<Value, String> {
+    let store = network.read().await.overlay.store.clone();
+    let cursor = cursor.map(|cursor| cursor.to_fixed_bytes());
+    let result = store.read().inventory(&filter, cursor, limit);
+    match result {
+        Ok(inventory) => Ok(json!(inventory)),
+        Err(err) => Err(format!(
+            "Database error while listing local content with filter: {filter:?}. Error message: {err}"
+        )),
+    }
+}
+
+/// Constructs a JSON call for the LocalContentStats method.
+async fn local_content_stats(
+    network: Arc<RwLock<HistoryNetwork>>,
+    filter: LocalContentFilter,
+) -> Result<Value, String> {
+    let store = network.read().await.overlay.store.clone();
+    let result = store.read().inventory_stats(&filter);
+    match result {
+        Ok(stats) => Ok(json!(stats)),
+        Err(err) => Err(format!(
+            "Database error while aggregating local content with filter: {filter:?}. Error message: {err}"
+        )),
+    }
+}
+
 /// Constructs a JSON call for the Store method.
 async fn store(
     network: Arc<RwLock<HistoryNetwork>>,
@@ -193,6 +339,53 @@ async fn store(
     response
 }
 
+/// Constructs a JSON call for the PinContent method.
+async fn pin_content(
+    network: Arc<RwLock<HistoryNetwork>>,
+    content_key: HistoryContentKey,
+) -> Result<Value, String> {
+    let store = network.read().await.overlay.store.clone();
+    let result = store.write().pin(&content_key);
+    match result {
+        Ok(pinned) => Ok(Value::Bool(pinned)),
+        Err(err) => Err(format!(
+            "Database error while pinning content key: {content_key:?}, with error: {err}"
+        )),
+    }
+}
+
+/// Constructs a JSON call for the UnpinContent method.
+async fn unpin_content(
+    network: Arc<RwLock<HistoryNetwork>>,
+    content_key: HistoryContentKey,
+) -> Result<Value, String> {
+    let store = network.read().await.overlay.store.clone();
+    let result = store.write().unpin(&content_key);
+    match result {
+        Ok(unpinned) => Ok(Value::Bool(unpinned)),
+        Err(err) => Err(format!(
+            "Database error while unpinning content key: {content_key:?}, with error: {err}"
+        )),
+    }
+}
+
+/// Constructs a JSON call for the PinnedContent method.
+async fn pinned_content(network: Arc<RwLock<HistoryNetwork>>) -> Result<Value, String> {
+    let store = network.read().await.overlay.store.clone();
+    let content_keys = store
+        .read()
+        .pinned_content_keys()
+        .map_err(|err| format!("Database error while listing pinned content: {err}"))?;
+    let content_keys: Result<Vec<HistoryContentKey>, _> = content_keys
+        .into_iter()
+        .map(HistoryContentKey::try_from)
+        .collect();
+    match content_keys {
+        Ok(content_keys) => Ok(json!(content_keys)),
+        Err(err) => Err(format!("Unable to decode pinned content key: {err:?}")),
+    }
+}
+
 /// Constructs a JSON call for the FindContent method.
 async fn find_content(
     network: Arc<RwLock<HistoryNetwork>>,
@@ -242,6 +435,18 @@ async fn gossip(
     Ok(num_peers.into())
 }
 
+/// Constructs a JSON call for the GossipStatus method.
+async fn gossip_status(
+    network: Arc<RwLock<HistoryNetwork>>,
+    content_key: HistoryContentKey,
+) -> Result<Value, String> {
+    let overlay = network.read().await.overlay.clone();
+    match overlay.gossip_status(&content_key) {
+        Some(status) => Ok(json!(status)),
+        None => Err(format!("No gossip found for content key: {content_key}")),
+    }
+}
+
 /// Constructs a JSON call for the Offer method.
 async fn offer(
     network: Arc<RwLock<HistoryNetwork>>,
@@ -291,14 +496,70 @@ async fn ping(
 async fn recursive_find_nodes(
     network: Arc<RwLock<HistoryNetwork>>,
     node_id: ethportal_api::NodeId,
+    is_trace: bool,
 ) -> Result<Value, String> {
     let node_id = discv5::enr::NodeId::from(node_id.0);
     let overlay = network.read().await.overlay.clone();
-    let mut nodes = overlay.lookup_node(node_id).await;
+    let (mut nodes, trace) = overlay.lookup_node(node_id, is_trace).await;
     nodes.sort_by(|a, b| {
         XorMetric::distance(&node_id.raw(), &a.node_id().raw())
             .cmp(&XorMetric::distance(&node_id.raw(), &b.node_id().raw()))
     });
     let nodes: Vec<Enr> = nodes.into_iter().take(16).collect();
-    Ok(json!(nodes))
+    if !is_trace {
+        return Ok(json!(nodes));
+    }
+    match trace {
+        Some(trace) => Ok(json!(TraceNodesInfo { nodes, trace })),
+        None => Err("Node query trace requested but none provided.".to_owned()),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    use ethportal_api::BlockHeaderKey;
+
+    fn header_key(byte: u8) -> HistoryContentKey {
+        HistoryContentKey::BlockHeaderWithProof(BlockHeaderKey {
+            block_hash: [byte; 32],
+        })
+    }
+
+    #[test]
+    fn dedup_content_keys_keeps_first_occurrences() {
+        let content_keys = vec![
+            header_key(3),
+            header_key(1),
+            header_key(3),
+            header_key(2),
+            header_key(1),
+        ];
+        let deduped = dedup_content_keys(content_keys);
+        assert_eq!(deduped, vec![header_key(3), header_key(1), header_key(2)]);
+    }
+
+    #[test]
+    fn batch_results_follow_order_of_content_keys() {
+        let content_keys = vec![header_key(3), header_key(1), header_key(2)];
+
+        // Results arrive in a different order than the content keys.
+        let mut results = HashMap::new();
+        for byte in [2, 3, 1] {
+            let content_key = header_key(byte);
+            results.insert(
+                content_key.content_id(),
+                batch_result(&content_key, hex_encode([byte]), Value::Null),
+            );
+        }
+
+        let ordered = order_batch_results(&content_keys, results);
+        let ordered_keys: Vec<HistoryContentKey> = ordered
+            .into_iter()
+            .map(|result| serde_json::from_value(result["contentKey"].clone()).unwrap())
+            .collect();
+        assert_eq!(ordered_keys, content_keys);
+    }
 }
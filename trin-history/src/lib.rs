@@ -21,7 +21,7 @@ use utp_rs::socket::UtpSocket;
 use crate::{events::HistoryEvents, jsonrpc::HistoryRequestHandler};
 use portalnet::{
     discovery::{Discovery, UtpEnr},
-    storage::PortalStorageConfig,
+    storage::ContentStoreConfig,
     types::messages::PortalnetConfig,
 };
 use trin_types::jsonrpc::request::HistoryJsonRpcRequest;
@@ -37,7 +37,7 @@ pub async fn initialize_history_network(
     utp_socket: Arc<UtpSocket<UtpEnr>>,
 
     portalnet_config: PortalnetConfig,
-    storage_config: PortalStorageConfig,
+    storage_config: ContentStoreConfig,
     header_oracle: Arc<RwLock<HeaderOracle>>,
 ) -> anyhow::Result<(
     HistoryHandler,
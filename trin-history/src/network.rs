@@ -8,38 +8,61 @@ use ethportal_api::HistoryContentKey;
 use portalnet::{
     discovery::{Discovery, UtpEnr},
     overlay::{OverlayConfig, OverlayProtocol},
-    storage::{PortalStorage, PortalStorageConfig},
-    types::messages::{PortalnetConfig, ProtocolId},
+    scrub::{ScrubConfig, Scrubber, ScrubberHandle},
+    storage::{ContentStoreConfig, DiskUsageSampler, SubnetworkStore},
+    types::{
+        messages::{PortalnetConfig, ProtocolId},
+        payload_extension::Capabilities,
+    },
 };
 use trin_types::distance::XorMetric;
 use trin_validation::oracle::HeaderOracle;
 
 use crate::validation::ChainHistoryValidator;
 
+/// SSZ union selectors of the history network content key types.
+const HISTORY_CONTENT_TYPES: [u8; 4] = [0, 1, 2, 3];
+
 /// History network layer on top of the overlay protocol. Encapsulates history network specific data and logic.
 #[derive(Clone)]
 pub struct HistoryNetwork {
     pub overlay:
-        Arc<OverlayProtocol<HistoryContentKey, XorMetric, ChainHistoryValidator, PortalStorage>>,
+        Arc<OverlayProtocol<HistoryContentKey, XorMetric, ChainHistoryValidator, SubnetworkStore>>,
+    /// Samples the on-disk size of the store until the last clone of the network is dropped.
+    _disk_usage_sampler: Option<Arc<DiskUsageSampler>>,
+    /// Scrubs the stored content until the last clone of the network is dropped.
+    _scrubber: Option<Arc<ScrubberHandle>>,
 }
 
 impl HistoryNetwork {
     pub async fn new(
         discovery: Arc<Discovery>,
         utp_socket: Arc<UtpSocket<UtpEnr>>,
-        storage_config: PortalStorageConfig,
+        storage_config: ContentStoreConfig,
         portal_config: PortalnetConfig,
         header_oracle: Arc<RwLock<HeaderOracle>>,
     ) -> anyhow::Result<Self> {
         let config = OverlayConfig {
             bootnode_enrs: portal_config.bootnode_enrs.clone(),
-            ..Default::default()
+            payload_extension: Some(Arc::new(Capabilities::new(
+                storage_config.storage_capacity_mb(),
+                HISTORY_CONTENT_TYPES.to_vec(),
+            ))),
+            ..OverlayConfig::from_args(&portal_config.history_overlay_config)
         };
-        let storage = Arc::new(PLRwLock::new(PortalStorage::new(
+        let storage = Arc::new(PLRwLock::new(SubnetworkStore::new(
             storage_config,
             ProtocolId::History,
         )?));
+        let disk_usage_sampler = storage.read().spawn_disk_usage_sampler().map(Arc::new);
         let validator = Arc::new(ChainHistoryValidator { header_oracle });
+        let scrubber =
+            ScrubConfig::from_args(&portal_config.history_overlay_config).map(|scrub_config| {
+                Arc::new(
+                    Scrubber::new(Arc::clone(&storage), Arc::clone(&validator), scrub_config)
+                        .spawn(),
+                )
+            });
         let overlay = OverlayProtocol::new(
             config,
             discovery,
@@ -52,6 +75,8 @@ impl HistoryNetwork {
 
         Ok(Self {
             overlay: Arc::new(overlay),
+            _disk_usage_sampler: disk_usage_sampler,
+            _scrubber: scrubber,
         })
     }
 }
@@ -3,14 +3,23 @@ use discv5::{
     kbucket::{ConnectionState, NodeStatus},
 };
 use serde_json::{json, Value};
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, net::SocketAddr};
 
+use portalnet::peer_stats::PeerLatencySummary;
 use trin_types::distance::Distance;
 use trin_types::enr::Enr;
 use trin_utils::bytes::hex_encode;
 
 type NodeMap = BTreeMap<String, String>;
-type NodeTuple = (NodeId, Enr, NodeStatus, Distance, Option<String>);
+type NodeTuple = (
+    NodeId,
+    Enr,
+    NodeStatus,
+    Distance,
+    Option<String>,
+    Option<PeerLatencySummary>,
+    Option<Value>,
+);
 
 /// Converts the output of the Overlay's bucket_entries method to a JSON Value
 pub fn bucket_entries_to_json(bucket_entries: BTreeMap<usize, Vec<NodeTuple>>) -> Value {
@@ -23,38 +32,69 @@ pub fn bucket_entries_to_json(bucket_entries: BTreeMap<usize, Vec<NodeTuple>>) -
                 bucket_index,
                 bucket
                     .iter()
-                    .map(|(node_id, enr, node_status, data_radius, client_info)| {
-                        node_count += 1;
-                        if node_status.state == ConnectionState::Connected {
-                            connected_count += 1
-                        }
-                        let mut map = BTreeMap::new();
-                        map.insert("node_id".to_owned(), hex_encode(node_id.raw()));
-                        map.insert("enr".to_owned(), enr.to_base64());
-                        map.insert("status".to_owned(), format!("{:?}", node_status.state));
-                        map.insert("radius".to_owned(), format!("{data_radius}"));
-                        if let Some(client_info) = client_info {
-                            // Expand client name if possible, otherwise leave as-is.
-                            match expand_client_name(client_info) {
-                                Some(expanded_name) => {
-                                    map.insert("client".to_owned(), expanded_name);
-                                }
-                                None => {
-                                    map.insert("client".to_owned(), client_info.to_string());
-                                }
-                            };
-                        } else {
-                            // Include address (IP:port) for convenience.
-                            // TODO: Can be removed once a portal dashboard does UI-side ENR decoding.
-                            let port = match enr.udp4_socket() {
-                                Some(port) => format!("{port}"),
-                                None => "None".to_string(),
-                            };
-                            map.insert("address".to_owned(), port);
-                        }
+                    .map(
+                        |(
+                            node_id,
+                            enr,
+                            node_status,
+                            data_radius,
+                            client_info,
+                            latency,
+                            capabilities,
+                        )| {
+                            node_count += 1;
+                            if node_status.state == ConnectionState::Connected {
+                                connected_count += 1
+                            }
+                            let mut map = BTreeMap::new();
+                            map.insert("node_id".to_owned(), hex_encode(node_id.raw()));
+                            map.insert("enr".to_owned(), enr.to_base64());
+                            map.insert("status".to_owned(), format!("{:?}", node_status.state));
+                            map.insert("radius".to_owned(), format!("{data_radius}"));
+                            if let Some(client_info) = client_info {
+                                // Expand client name if possible, otherwise leave as-is.
+                                match expand_client_name(client_info) {
+                                    Some(expanded_name) => {
+                                        map.insert("client".to_owned(), expanded_name);
+                                    }
+                                    None => {
+                                        map.insert("client".to_owned(), client_info.to_string());
+                                    }
+                                };
+                            } else {
+                                // Include address (IP:port) for convenience.
+                                // TODO: Can be removed once a portal dashboard does UI-side ENR decoding.
+                                let port = match enr
+                                    .udp4_socket()
+                                    .map(SocketAddr::from)
+                                    .or_else(|| enr.udp6_socket().map(SocketAddr::from))
+                                {
+                                    Some(port) => format!("{port}"),
+                                    None => "None".to_string(),
+                                };
+                                map.insert("address".to_owned(), port);
+                            }
+                            if let Some(latency) = latency {
+                                map.insert(
+                                    "latency_p50_ms".to_owned(),
+                                    latency.p50_millis.to_string(),
+                                );
+                                map.insert(
+                                    "latency_p99_ms".to_owned(),
+                                    latency.p99_millis.to_string(),
+                                );
+                                map.insert(
+                                    "success_rate".to_owned(),
+                                    format!("{:.2}", latency.success_rate),
+                                );
+                            }
+                            if let Some(capabilities) = capabilities {
+                                map.insert("capabilities".to_owned(), capabilities.to_string());
+                            }
 
-                        map
-                    })
+                            map
+                        },
+                    )
                     .collect(),
             )
         })
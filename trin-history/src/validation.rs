@@ -14,7 +14,10 @@ use trin_types::execution::{
     header::{Header, HeaderWithProof},
     receipts::Receipts,
 };
-use trin_validation::{oracle::HeaderOracle, validator::Validator};
+use trin_validation::{
+    oracle::HeaderOracle,
+    validator::{LookupError, Validator},
+};
 
 pub struct ChainHistoryValidator {
     pub header_oracle: Arc<RwLock<HeaderOracle>>,
@@ -49,7 +52,8 @@ impl Validator<HistoryContentKey> for ChainHistoryValidator {
                     .write()
                     .await
                     .get_header_by_hash(H256::from(key.block_hash))
-                    .await?;
+                    .await
+                    .map_err(LookupError)?;
                 let actual_uncles_root = block_body.uncles_root()?;
                 if actual_uncles_root != trusted_header.uncles_hash {
                     return Err(anyhow!(
@@ -77,7 +81,8 @@ impl Validator<HistoryContentKey> for ChainHistoryValidator {
                     .write()
                     .await
                     .get_header_by_hash(H256::from(key.block_hash))
-                    .await?;
+                    .await
+                    .map_err(LookupError)?;
                 let actual_receipts_root = receipts.root()?;
                 if actual_receipts_root != trusted_header.receipts_root {
                     return Err(anyhow!(
@@ -14,7 +14,7 @@ use utp_rs::socket::UtpSocket;
 use crate::{events::StateEvents, jsonrpc::StateRequestHandler};
 use portalnet::{
     discovery::{Discovery, UtpEnr},
-    storage::PortalStorageConfig,
+    storage::ContentStoreConfig,
     types::messages::PortalnetConfig,
 };
 use trin_types::jsonrpc::request::StateJsonRpcRequest;
@@ -36,7 +36,7 @@ pub async fn initialize_state_network(
     discovery: &Arc<Discovery>,
     utp_socket: Arc<UtpSocket<UtpEnr>>,
     portalnet_config: PortalnetConfig,
-    storage_config: PortalStorageConfig,
+    storage_config: ContentStoreConfig,
     header_oracle: Arc<RwLock<HeaderOracle>>,
 ) -> anyhow::Result<(StateHandler, StateNetworkTask, StateEventTx, StateJsonRpcTx)> {
     let (state_jsonrpc_tx, state_jsonrpc_rx) = mpsc::unbounded_channel::<StateJsonRpcRequest>();
@@ -9,41 +9,69 @@ use ethportal_api::StateContentKey;
 use portalnet::{
     discovery::{Discovery, UtpEnr},
     overlay::{OverlayConfig, OverlayProtocol},
-    storage::{PortalStorage, PortalStorageConfig},
-    types::messages::{PortalnetConfig, ProtocolId},
+    scrub::{ScrubConfig, Scrubber, ScrubberHandle},
+    storage::{ContentStoreConfig, DiskUsageSampler, PortalStorage, SubnetworkStore},
+    types::{
+        messages::{PortalnetConfig, ProtocolId},
+        payload_extension::Capabilities,
+    },
 };
 use trin_types::distance::XorMetric;
 use trin_validation::oracle::HeaderOracle;
 
 use crate::{trie::TrieDB, validation::StateValidator};
 
+/// SSZ union selectors of the state network content key types.
+const STATE_CONTENT_TYPES: [u8; 5] = [0, 1, 2, 3, 4];
+
 /// State network layer on top of the overlay protocol. Encapsulates state network specific data and logic.
 #[derive(Clone)]
 pub struct StateNetwork {
-    pub overlay: Arc<OverlayProtocol<StateContentKey, XorMetric, StateValidator, PortalStorage>>,
+    pub overlay: Arc<OverlayProtocol<StateContentKey, XorMetric, StateValidator, SubnetworkStore>>,
     pub trie: Arc<EthTrie<TrieDB>>,
+    /// Samples the on-disk size of the store until the last clone of the network is dropped.
+    _disk_usage_sampler: Option<Arc<DiskUsageSampler>>,
+    /// Scrubs the stored content until the last clone of the network is dropped.
+    _scrubber: Option<Arc<ScrubberHandle>>,
 }
 
 impl StateNetwork {
     pub async fn new(
         discovery: Arc<Discovery>,
         utp_socket: Arc<UtpSocket<UtpEnr>>,
-        storage_config: PortalStorageConfig,
+        storage_config: ContentStoreConfig,
         portal_config: PortalnetConfig,
         header_oracle: Arc<RwLock<HeaderOracle>>,
     ) -> anyhow::Result<Self> {
-        let db = PortalStorage::setup_triedb(&storage_config.node_data_dir)?;
-        let triedb = TrieDB::new(Arc::new(db));
+        let capabilities = Capabilities::new(
+            storage_config.storage_capacity_mb(),
+            STATE_CONTENT_TYPES.to_vec(),
+        );
+        let triedb = match &storage_config {
+            ContentStoreConfig::Disk(config) => TrieDB::new(Arc::new(PortalStorage::setup_triedb(
+                &config.node_data_dir,
+            )?)),
+            ContentStoreConfig::Memory(_) => TrieDB::new_in_memory(),
+        };
         let trie = EthTrie::new(Arc::new(triedb));
 
-        let storage = Arc::new(PLRwLock::new(PortalStorage::new(
+        let storage = Arc::new(PLRwLock::new(SubnetworkStore::new(
             storage_config,
             ProtocolId::State,
         )?));
+        let disk_usage_sampler = storage.read().spawn_disk_usage_sampler().map(Arc::new);
         let validator = Arc::new(StateValidator { header_oracle });
+        let scrubber =
+            ScrubConfig::from_args(&portal_config.state_overlay_config).map(|scrub_config| {
+                Arc::new(
+                    Scrubber::new(Arc::clone(&storage), Arc::clone(&validator), scrub_config)
+                        .spawn(),
+                )
+            });
         let config = OverlayConfig {
             bootnode_enrs: portal_config.bootnode_enrs.clone(),
-            ..Default::default()
+            payload_extension: Some(Arc::new(capabilities)),
+            ..OverlayConfig::from_args(&portal_config.state_overlay_config)
         };
         let overlay = OverlayProtocol::new(
             config,
@@ -58,6 +86,8 @@ impl StateNetwork {
         Ok(Self {
             overlay: Arc::new(overlay),
             trie: Arc::new(trie),
+            _disk_usage_sampler: disk_usage_sampler,
+            _scrubber: scrubber,
         })
     }
 }
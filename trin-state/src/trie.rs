@@ -1,12 +1,20 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-pub struct TrieDB {
-    db: Arc<rocksdb::DB>,
+use parking_lot::RwLock;
+
+pub enum TrieDB {
+    RocksDB(Arc<rocksdb::DB>),
+    /// Keeps the trie nodes in memory, for nodes that do not persist their data.
+    Memory(RwLock<HashMap<Vec<u8>, Vec<u8>>>),
 }
 
 impl TrieDB {
     pub fn new(db: Arc<rocksdb::DB>) -> TrieDB {
-        TrieDB { db }
+        TrieDB::RocksDB(db)
+    }
+
+    pub fn new_in_memory() -> TrieDB {
+        TrieDB::Memory(RwLock::new(HashMap::new()))
     }
 }
 
@@ -14,15 +22,30 @@ impl eth_trie::DB for TrieDB {
     type Error = rocksdb::Error;
 
     fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
-        self.db.get(key)
+        match self {
+            TrieDB::RocksDB(db) => db.get(key),
+            TrieDB::Memory(db) => Ok(db.read().get(key).cloned()),
+        }
     }
 
     fn insert(&self, key: &[u8], value: Vec<u8>) -> Result<(), Self::Error> {
-        self.db.put(key, value)
+        match self {
+            TrieDB::RocksDB(db) => db.put(key, value),
+            TrieDB::Memory(db) => {
+                db.write().insert(key.to_vec(), value);
+                Ok(())
+            }
+        }
     }
 
     fn remove(&self, key: &[u8]) -> Result<(), Self::Error> {
-        self.db.delete(key)
+        match self {
+            TrieDB::RocksDB(db) => db.delete(key),
+            TrieDB::Memory(db) => {
+                db.write().remove(key);
+                Ok(())
+            }
+        }
     }
 
     fn flush(&self) -> Result<(), Self::Error> {
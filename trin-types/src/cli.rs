@@ -1,5 +1,6 @@
-use std::{env, ffi::OsString, fmt, net::SocketAddr, path::PathBuf, str::FromStr};
+use std::{env, ffi::OsString, fmt, net::SocketAddr, path::PathBuf, str::FromStr, time::Duration};
 
+use discv5::kbucket::MAX_NODES_PER_BUCKET;
 use ethereum_types::H256;
 use structopt::StructOpt;
 use url::Url;
@@ -17,6 +18,21 @@ const DEFAULT_SUBNETWORKS: &str = "history";
 pub const DEFAULT_STORAGE_CAPACITY_MB: &str = "100";
 pub const DEFAULT_TRUSTED_PROVIDER: &str = "infura";
 pub const DEFAULT_WEB3_TRANSPORT: &str = "ipc";
+const DEFAULT_IP_MODE: &str = "ip4";
+// This stun server is part of the testnet infrastructure.
+// If you are unable to connect, please create an issue.
+pub const DEFAULT_STUN_SERVERS: &str = "159.223.0.83:3478";
+
+/// Returns the STUN servers of `DEFAULT_STUN_SERVERS`, parsed like the `--stun-servers` flag.
+pub fn default_stun_servers() -> Vec<SocketAddr> {
+    DEFAULT_STUN_SERVERS
+        .split(',')
+        .map(|addr| {
+            addr.parse()
+                .expect("Parsing static DEFAULT_STUN_SERVERS to work")
+        })
+        .collect()
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Web3TransportType {
@@ -57,6 +73,433 @@ impl FromStr for Web3TransportType {
     }
 }
 
+/// The IP protocol version(s) used by the discovery layer.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum IpMode {
+    /// Listen and advertise on IPv4 only.
+    #[default]
+    Ip4,
+    /// Listen and advertise on IPv6 only.
+    Ip6,
+    /// Listen on a single IPv6 socket that also accepts IPv4 traffic (through IPv4-mapped
+    /// addresses), and advertise on both.
+    DualStack,
+}
+
+impl IpMode {
+    /// Returns whether IPv4 traffic is supported in this mode.
+    pub fn supports_ip4(&self) -> bool {
+        matches!(self, Self::Ip4 | Self::DualStack)
+    }
+
+    /// Returns whether IPv6 traffic is supported in this mode.
+    pub fn supports_ip6(&self) -> bool {
+        matches!(self, Self::Ip6 | Self::DualStack)
+    }
+}
+
+impl fmt::Display for IpMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Ip4 => write!(f, "ip4"),
+            Self::Ip6 => write!(f, "ip6"),
+            Self::DualStack => write!(f, "dual"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseIpModeError;
+
+impl fmt::Display for ParseIpModeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid ip-mode arg. Expected either 'ip4', 'ip6' or 'dual'"
+        )
+    }
+}
+
+impl FromStr for IpMode {
+    type Err = ParseIpModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ip4" => Ok(IpMode::Ip4),
+            "ip6" => Ok(IpMode::Ip6),
+            "dual" => Ok(IpMode::DualStack),
+            _ => Err(ParseIpModeError),
+        }
+    }
+}
+
+/// Largest uTP packet size. uTP packets are carried in discv5 TALKREQ messages, which are limited to
+/// 1280 bytes including the discv5 overhead.
+const MAX_UTP_PACKET_SIZE: u16 = 1024;
+
+/// Default timeout of a whole lookup.
+pub const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Default timeout after which a peer in an ongoing lookup is considered unresponsive.
+pub const DEFAULT_QUERY_PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default interval at which peers in the farthest bucket are pinged.
+pub const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default minimum interval at which peers in closer buckets are pinged.
+pub const DEFAULT_PING_MIN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default initial uTP retransmission timeout.
+pub const DEFAULT_UTP_INITIAL_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Default minimum uTP retransmission timeout.
+pub const DEFAULT_UTP_MIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Overrides of the overlay network configuration of a single subnetwork. Settings that are `None`
+/// keep their default value.
+///
+/// Parsed from a comma-separated list of `key=value` pairs, e.g.
+/// `query-parallelism=5,query-timeout-ms=30000`. An empty string overrides nothing.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct OverlayConfigArgs {
+    /// Number of peers queried concurrently during a lookup.
+    pub query_parallelism: Option<usize>,
+    /// Timeout of a whole lookup.
+    pub query_timeout: Option<Duration>,
+    /// Timeout after which a peer in an ongoing lookup is considered unresponsive.
+    pub query_peer_timeout: Option<Duration>,
+    /// Number of closest nodes returned by a lookup.
+    pub query_num_results: Option<usize>,
+    /// Number of distances requested from each peer in a FindNodes lookup.
+    pub findnodes_query_distances_per_peer: Option<usize>,
+    /// Interval at which peers in the farthest bucket are pinged.
+    pub ping_queue_interval: Option<Duration>,
+    /// Minimum interval at which peers in closer buckets are pinged.
+    pub ping_min_interval: Option<Duration>,
+    /// Maximum interval at which peers that failed to respond are pinged.
+    pub ping_max_backoff: Option<Duration>,
+    /// Number of consecutive failed requests after which a peer is evicted from the routing table.
+    pub max_failures: Option<u32>,
+    /// Timeout after which a pending routing table entry replaces an unresponsive node.
+    pub bucket_pending_timeout: Option<Duration>,
+    /// Maximum number of incoming connections per routing table bucket.
+    pub max_incoming_per_bucket: Option<usize>,
+    /// Whether to limit the number of routing table nodes in the same /24 subnet.
+    pub ip_limit: bool,
+    /// Maximum size of a uTP packet, in bytes.
+    pub utp_max_packet_size: Option<u16>,
+    /// Maximum number of attempts to establish a uTP connection.
+    pub utp_max_conn_attempts: Option<usize>,
+    /// Timeout after which an idle uTP connection is closed.
+    pub utp_max_idle_timeout: Option<Duration>,
+    /// Initial uTP retransmission timeout.
+    pub utp_initial_timeout: Option<Duration>,
+    /// Minimum uTP retransmission timeout.
+    pub utp_min_timeout: Option<Duration>,
+    /// uTP congestion control target delay.
+    pub utp_target_delay: Option<Duration>,
+    /// Number of stored content items re-validated per second by the integrity scrubber. The
+    /// scrubber is disabled if unset.
+    pub scrub_rate: Option<u32>,
+    /// Delay between the end of a scrub of the whole store and the start of the next one.
+    pub scrub_pass_interval: Option<Duration>,
+    /// Per-peer rate limit of inbound Ping requests.
+    pub rate_limit_ping: Option<RateLimitArg>,
+    /// Per-peer rate limit of inbound FindNodes requests.
+    pub rate_limit_find_nodes: Option<RateLimitArg>,
+    /// Per-peer rate limit of inbound FindContent requests.
+    pub rate_limit_find_content: Option<RateLimitArg>,
+    /// Per-peer rate limit of inbound Offer requests.
+    pub rate_limit_offer: Option<RateLimitArg>,
+    /// Per-peer rate limit of other inbound messages.
+    pub rate_limit_other: Option<RateLimitArg>,
+    /// What to do with an inbound request that exceeds its rate limit.
+    pub rate_limit_action: Option<ThrottleActionArg>,
+}
+
+/// A per-peer rate limit of a kind of inbound request.
+///
+/// Parsed from `<burst>/<per-second>`, the number of requests accepted in a burst and on average
+/// per second, or from `none` to leave the kind of request unlimited.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RateLimitArg {
+    Unlimited,
+    Limit { burst: u32, per_second: u32 },
+}
+
+/// What to do with an inbound request that exceeds its rate limit.
+///
+/// Parsed from `drop` or `empty-response`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ThrottleActionArg {
+    /// Drop the request without a response.
+    Drop,
+    /// Answer the request with an empty TALKRESP.
+    EmptyResponse,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseOverlayConfigError(String);
+
+impl fmt::Display for ParseOverlayConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid overlay config arg: {}", self.0)
+    }
+}
+
+impl FromStr for OverlayConfigArgs {
+    type Err = ParseOverlayConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut args = OverlayConfigArgs::default();
+        for pair in s.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').ok_or_else(|| {
+                ParseOverlayConfigError(format!("expected 'key=value', found '{pair}'"))
+            })?;
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "query-parallelism" => args.query_parallelism = Some(parse_nonzero(key, value)?),
+                "query-timeout-ms" => args.query_timeout = Some(parse_millis(key, value)?),
+                "query-peer-timeout-ms" => {
+                    args.query_peer_timeout = Some(parse_millis(key, value)?)
+                }
+                "query-num-results" => args.query_num_results = Some(parse_nonzero(key, value)?),
+                "findnodes-distances-per-peer" => {
+                    args.findnodes_query_distances_per_peer = Some(parse_nonzero(key, value)?)
+                }
+                "ping-interval-ms" => args.ping_queue_interval = Some(parse_millis(key, value)?),
+                "ping-min-interval-ms" => args.ping_min_interval = Some(parse_millis(key, value)?),
+                "ping-max-backoff-ms" => args.ping_max_backoff = Some(parse_millis(key, value)?),
+                "max-failures" => args.max_failures = Some(parse_nonzero(key, value)?),
+                "bucket-pending-timeout-ms" => {
+                    args.bucket_pending_timeout = Some(parse_millis(key, value)?)
+                }
+                "max-incoming-per-bucket" => {
+                    args.max_incoming_per_bucket = Some(parse_nonzero(key, value)?)
+                }
+                "ip-limit" => {
+                    args.ip_limit = value.parse().map_err(|_| {
+                        ParseOverlayConfigError(format!("{key} must be 'true' or 'false'"))
+                    })?
+                }
+                "utp-max-packet-size" => {
+                    args.utp_max_packet_size = Some(parse_nonzero(key, value)?)
+                }
+                "utp-max-conn-attempts" => {
+                    args.utp_max_conn_attempts = Some(parse_nonzero(key, value)?)
+                }
+                "utp-max-idle-timeout-ms" => {
+                    args.utp_max_idle_timeout = Some(parse_millis(key, value)?)
+                }
+                "utp-initial-timeout-ms" => {
+                    args.utp_initial_timeout = Some(parse_millis(key, value)?)
+                }
+                "utp-min-timeout-ms" => args.utp_min_timeout = Some(parse_millis(key, value)?),
+                "utp-target-delay-ms" => args.utp_target_delay = Some(parse_millis(key, value)?),
+                "scrub-rate" => args.scrub_rate = Some(parse_nonzero(key, value)?),
+                "scrub-pass-interval-ms" => {
+                    args.scrub_pass_interval = Some(parse_millis(key, value)?)
+                }
+                "rate-limit-ping" => args.rate_limit_ping = Some(parse_rate_limit(key, value)?),
+                "rate-limit-find-nodes" => {
+                    args.rate_limit_find_nodes = Some(parse_rate_limit(key, value)?)
+                }
+                "rate-limit-find-content" => {
+                    args.rate_limit_find_content = Some(parse_rate_limit(key, value)?)
+                }
+                "rate-limit-offer" => args.rate_limit_offer = Some(parse_rate_limit(key, value)?),
+                "rate-limit-other" => args.rate_limit_other = Some(parse_rate_limit(key, value)?),
+                "rate-limit-action" => {
+                    args.rate_limit_action = Some(match value {
+                        "drop" => ThrottleActionArg::Drop,
+                        "empty-response" => ThrottleActionArg::EmptyResponse,
+                        _ => {
+                            return Err(ParseOverlayConfigError(format!(
+                                "{key} must be 'drop' or 'empty-response'"
+                            )))
+                        }
+                    })
+                }
+                _ => return Err(ParseOverlayConfigError(format!("unknown key '{key}'"))),
+            }
+        }
+        args.validate()?;
+        Ok(args)
+    }
+}
+
+impl OverlayConfigArgs {
+    /// Checks the consistency of settings that depend on each other. A setting that is not
+    /// overridden is checked with its default value.
+    fn validate(&self) -> Result<(), ParseOverlayConfigError> {
+        if let Some(max_incoming_per_bucket) = self.max_incoming_per_bucket {
+            if max_incoming_per_bucket > MAX_NODES_PER_BUCKET {
+                return Err(ParseOverlayConfigError(format!(
+                    "max-incoming-per-bucket must be at most {MAX_NODES_PER_BUCKET}"
+                )));
+            }
+        }
+        if let Some(utp_max_packet_size) = self.utp_max_packet_size {
+            if utp_max_packet_size > MAX_UTP_PACKET_SIZE {
+                return Err(ParseOverlayConfigError(format!(
+                    "utp-max-packet-size must be at most {MAX_UTP_PACKET_SIZE}"
+                )));
+            }
+        }
+        let peer_timeout = self
+            .query_peer_timeout
+            .unwrap_or(DEFAULT_QUERY_PEER_TIMEOUT);
+        if peer_timeout > self.query_timeout.unwrap_or(DEFAULT_QUERY_TIMEOUT) {
+            return Err(ParseOverlayConfigError(
+                "query-peer-timeout-ms must not exceed query-timeout-ms".to_owned(),
+            ));
+        }
+        let min_interval = self.ping_min_interval.unwrap_or(DEFAULT_PING_MIN_INTERVAL);
+        if min_interval > self.ping_queue_interval.unwrap_or(DEFAULT_PING_INTERVAL) {
+            return Err(ParseOverlayConfigError(
+                "ping-min-interval-ms must not exceed ping-interval-ms".to_owned(),
+            ));
+        }
+        let min_timeout = self.utp_min_timeout.unwrap_or(DEFAULT_UTP_MIN_TIMEOUT);
+        if min_timeout
+            > self
+                .utp_initial_timeout
+                .unwrap_or(DEFAULT_UTP_INITIAL_TIMEOUT)
+        {
+            return Err(ParseOverlayConfigError(
+                "utp-min-timeout-ms must not exceed utp-initial-timeout-ms".to_owned(),
+            ));
+        }
+        if self.scrub_pass_interval.is_some() && self.scrub_rate.is_none() {
+            return Err(ParseOverlayConfigError(
+                "scrub-pass-interval-ms requires scrub-rate".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Parses a non-zero integer value of `key`.
+fn parse_nonzero<T: FromStr + Default + PartialEq>(
+    key: &str,
+    value: &str,
+) -> Result<T, ParseOverlayConfigError> {
+    match value.parse::<T>() {
+        Ok(value) if value != T::default() => Ok(value),
+        _ => Err(ParseOverlayConfigError(format!(
+            "{key} must be a positive integer"
+        ))),
+    }
+}
+
+/// Parses a rate limit value of `key`, either `<burst>/<per-second>` or `none`.
+fn parse_rate_limit(key: &str, value: &str) -> Result<RateLimitArg, ParseOverlayConfigError> {
+    if value == "none" {
+        return Ok(RateLimitArg::Unlimited);
+    }
+    let (burst, per_second) = value.split_once('/').ok_or_else(|| {
+        ParseOverlayConfigError(format!("{key} must be '<burst>/<per-second>' or 'none'"))
+    })?;
+    Ok(RateLimitArg::Limit {
+        burst: parse_nonzero(key, burst.trim())?,
+        per_second: parse_nonzero(key, per_second.trim())?,
+    })
+}
+
+/// Parses a non-zero duration value of `key`, in milliseconds.
+fn parse_millis(key: &str, value: &str) -> Result<Duration, ParseOverlayConfigError> {
+    parse_nonzero::<u64>(key, value).map(Duration::from_millis)
+}
+
+/// Names of the history network content types, indexed by their SSZ union selector.
+const HISTORY_CONTENT_TYPES: [&str; 4] = [
+    "block-header",
+    "block-body",
+    "receipts",
+    "epoch-accumulator",
+];
+
+/// Names of the state network content types, indexed by their SSZ union selector.
+const STATE_CONTENT_TYPES: [&str; 5] = [
+    "account-trie-node",
+    "contract-storage-trie-node",
+    "account-trie-proof",
+    "contract-storage-trie-proof",
+    "contract-bytecode",
+];
+
+/// Returns the names of the content types of `network`, indexed by their SSZ union selector.
+fn content_type_names(network: &str) -> &'static [&'static str] {
+    match network {
+        HISTORY_NETWORK => &HISTORY_CONTENT_TYPES,
+        STATE_NETWORK => &STATE_CONTENT_TYPES,
+        _ => &[],
+    }
+}
+
+/// A pattern of content types to pin in local storage, as `<network>:<content type>`, e.g.
+/// `history:epoch-accumulator`. Either part may be `*`, to match every network or every content
+/// type.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PinPattern {
+    network: String,
+    content_type: String,
+}
+
+impl PinPattern {
+    /// Returns the SSZ union selectors of the content types of `network` that match the pattern.
+    pub fn content_types(&self, network: &str) -> Vec<u8> {
+        if self.network != "*" && self.network != network {
+            return vec![];
+        }
+        content_type_names(network)
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| self.content_type == "*" || self.content_type == **name)
+            .map(|(selector, _)| selector as u8)
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParsePinPatternError(String);
+
+impl fmt::Display for ParsePinPatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid pin-content arg: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParsePinPatternError {}
+
+impl FromStr for PinPattern {
+    type Err = ParsePinPatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (network, content_type) = s.trim().split_once(':').ok_or_else(|| {
+            ParsePinPatternError(format!("expected <network>:<content type>, got '{s}'"))
+        })?;
+        let networks = match network {
+            "*" => vec![HISTORY_NETWORK, STATE_NETWORK],
+            HISTORY_NETWORK | STATE_NETWORK => vec![network],
+            _ => return Err(ParsePinPatternError(format!("unknown network '{network}'"))),
+        };
+        let is_known_content_type = content_type == "*"
+            || networks
+                .iter()
+                .any(|network| content_type_names(network).contains(&content_type));
+        if !is_known_content_type {
+            return Err(ParsePinPatternError(format!(
+                "unknown content type '{content_type}' of network '{network}'"
+            )));
+        }
+        Ok(Self {
+            network: network.to_owned(),
+            content_type: content_type.to_owned(),
+        })
+    }
+}
+
 #[derive(StructOpt, Debug, PartialEq, Clone)]
 #[structopt(
     name = "trin",
@@ -93,6 +536,13 @@ pub struct TrinConfig {
     )]
     pub discovery_port: u16,
 
+    #[structopt(
+        default_value(DEFAULT_IP_MODE),
+        long = "ip-mode",
+        help = "The IP version(s) to use for discovery: 'ip4', 'ip6' or 'dual' (IPv4 and IPv6)"
+    )]
+    pub ip_mode: IpMode,
+
     #[structopt(
         default_value("default"),
         long = "bootnodes",
@@ -114,6 +564,20 @@ pub struct TrinConfig {
     )]
     pub no_stun: bool,
 
+    #[structopt(
+        default_value(DEFAULT_STUN_SERVERS),
+        long = "stun-servers",
+        help = "Comma-separated list of STUN servers (IP:port) used to determine an external IP, tried in order",
+        use_delimiter = true
+    )]
+    pub stun_servers: Vec<SocketAddr>,
+
+    #[structopt(
+        long = "upnp",
+        help = "Request a UPnP port mapping for the discovery port from the local gateway (requires the 'upnp' build feature)"
+    )]
+    pub upnp: bool,
+
     #[structopt(
         validator(check_private_key_length),
         long = "unsafe-private-key",
@@ -137,6 +601,25 @@ pub struct TrinConfig {
     )]
     pub mb: u32,
 
+    #[structopt(
+        long = "history-mb",
+        help = "Megabytes of the --mb budget reserved for history network content. By default, the budget is split evenly between the enabled networks"
+    )]
+    pub history_mb: Option<u32>,
+
+    #[structopt(
+        long = "state-mb",
+        help = "Megabytes of the --mb budget reserved for state network content. By default, the budget is split evenly between the enabled networks"
+    )]
+    pub state_mb: Option<u32>,
+
+    #[structopt(
+        long = "pin-content",
+        help = "Comma-separated list of content types to pin in local storage, so that they are always stored and never evicted, as <network>:<content type> (e.g. history:epoch-accumulator). Either part may be '*'. History content types: block-header, block-body, receipts, epoch-accumulator. State content types: account-trie-node, contract-storage-trie-node, account-trie-proof, contract-storage-trie-proof, contract-bytecode",
+        use_delimiter = true
+    )]
+    pub pin_content: Vec<PinPattern>,
+
     #[structopt(
         long = "enable-metrics-with-url",
         help = "Enable prometheus metrics reporting (provide local IP/Port from which your Prometheus server is configured to fetch metrics)"
@@ -146,7 +629,7 @@ pub struct TrinConfig {
     #[structopt(
         short = "e",
         long = "ephemeral",
-        help = "Use temporary data storage that is deleted on exit."
+        help = "Keep all content in memory instead of on disk, so nothing persists after exit."
     )]
     pub ephemeral: bool,
 
@@ -171,6 +654,20 @@ pub struct TrinConfig {
         parse(from_os_str)
     )]
     pub master_acc_path: PathBuf,
+
+    #[structopt(
+        default_value = "",
+        long = "history-overlay-config",
+        help = "Comma-separated key=value overrides of the history network overlay config. Keys: query-parallelism, query-timeout-ms, query-peer-timeout-ms, query-num-results, findnodes-distances-per-peer, ping-interval-ms, ping-min-interval-ms, ping-max-backoff-ms, max-failures, bucket-pending-timeout-ms, max-incoming-per-bucket, ip-limit, utp-max-packet-size, utp-max-conn-attempts, utp-max-idle-timeout-ms, utp-initial-timeout-ms, utp-min-timeout-ms, utp-target-delay-ms, scrub-rate, scrub-pass-interval-ms, rate-limit-ping, rate-limit-find-nodes, rate-limit-find-content, rate-limit-offer, rate-limit-other (each '<burst>/<per-second>' or 'none'), rate-limit-action ('drop' or 'empty-response')"
+    )]
+    pub history_overlay_config: OverlayConfigArgs,
+
+    #[structopt(
+        default_value = "",
+        long = "state-overlay-config",
+        help = "Comma-separated key=value overrides of the state network overlay config. Accepts the same keys as --history-overlay-config"
+    )]
+    pub state_overlay_config: OverlayConfigArgs,
 }
 
 impl Default for TrinConfig {
@@ -184,9 +681,13 @@ impl Default for TrinConfig {
             discovery_port: DEFAULT_DISCOVERY_PORT
                 .parse()
                 .expect("Parsing static DEFAULT_DISCOVERY_PORT to work"),
+            ip_mode: IpMode::from_str(DEFAULT_IP_MODE)
+                .expect("Parsing static DEFAULT_IP_MODE to work"),
             bootnodes: Bootnodes::Default,
             external_addr: None,
             no_stun: false,
+            stun_servers: default_stun_servers(),
+            upnp: false,
             private_key: None,
             networks: DEFAULT_SUBNETWORKS
                 .split(',')
@@ -195,11 +696,16 @@ impl Default for TrinConfig {
             mb: DEFAULT_STORAGE_CAPACITY_MB
                 .parse()
                 .expect("Parsing static DEFAULT_STORAGE_CAPACITY_MB to work"),
+            history_mb: None,
+            state_mb: None,
+            pin_content: vec![],
             enable_metrics_with_url: None,
             ephemeral: false,
             trusted_provider: TrustedProviderType::Infura,
             trusted_provider_url: None,
             master_acc_path: PathBuf::from(DEFAULT_MASTER_ACC_PATH.to_string()),
+            history_overlay_config: OverlayConfigArgs::default(),
+            state_overlay_config: OverlayConfigArgs::default(),
         }
     }
 }
@@ -242,6 +748,19 @@ impl TrinConfig {
                 ),
             },
         }
+        let reserved_mb: u64 = config
+            .networks
+            .iter()
+            .filter_map(|network| config.reserved_storage_capacity_mb(network))
+            .map(u64::from)
+            .sum();
+        if reserved_mb > config.mb.into() {
+            panic!(
+                "--history-mb and --state-mb must not exceed --mb in total (found {reserved_mb} > {}).",
+                config.mb
+            )
+        }
+
         // Should not serve http over same port as localhost provider.
         if config.web3_transport == Web3TransportType::HTTP
             && config.trusted_provider == TrustedProviderType::Custom
@@ -256,6 +775,44 @@ impl TrinConfig {
         }
         Ok(config)
     }
+
+    /// Returns the storage capacity of `network`, in megabytes. Networks without a reserved
+    /// capacity split what remains of the total capacity evenly.
+    pub fn storage_capacity_mb(&self, network: &str) -> u32 {
+        if let Some(reserved_mb) = self.reserved_storage_capacity_mb(network) {
+            return reserved_mb;
+        }
+        let mut reserved_mb = 0u32;
+        let mut num_unreserved = 0u32;
+        for network in self.networks.iter() {
+            match self.reserved_storage_capacity_mb(network) {
+                Some(mb) => reserved_mb = reserved_mb.saturating_add(mb),
+                None => num_unreserved += 1,
+            }
+        }
+        self.mb.saturating_sub(reserved_mb) / num_unreserved.max(1)
+    }
+
+    /// Returns the SSZ union selectors of the content types of `network` that are pinned.
+    pub fn pinned_content_types(&self, network: &str) -> Vec<u8> {
+        let mut content_types: Vec<u8> = self
+            .pin_content
+            .iter()
+            .flat_map(|pattern| pattern.content_types(network))
+            .collect();
+        content_types.sort_unstable();
+        content_types.dedup();
+        content_types
+    }
+
+    /// Returns the storage capacity reserved for `network` on the command line, if any.
+    fn reserved_storage_capacity_mb(&self, network: &str) -> Option<u32> {
+        match network {
+            HISTORY_NETWORK => self.history_mb,
+            STATE_NETWORK => self.state_mb,
+            _ => None,
+        }
+    }
 }
 
 /// A validator function for CLI URL arguments.
@@ -323,8 +880,15 @@ mod test {
             expected_config.web3_http_address
         );
         assert_eq!(actual_config.external_addr, expected_config.external_addr);
+        assert_eq!(actual_config.ip_mode, expected_config.ip_mode);
         assert_eq!(actual_config.no_stun, expected_config.no_stun);
+        assert_eq!(actual_config.stun_servers, expected_config.stun_servers);
+        assert_eq!(actual_config.upnp, expected_config.upnp);
         assert_eq!(actual_config.ephemeral, expected_config.ephemeral);
+        assert_eq!(
+            actual_config.history_overlay_config,
+            expected_config.history_overlay_config
+        );
     }
 
     #[test]
@@ -450,6 +1014,173 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_custom_stun_servers() {
+        let default_config = TrinConfig::new_from(["trin"].iter()).unwrap();
+        assert_eq!(default_config.stun_servers, default_stun_servers());
+
+        let actual_config =
+            TrinConfig::new_from(["trin", "--stun-servers", "127.0.0.1:3478,[::1]:3479"].iter())
+                .unwrap();
+        assert_eq!(
+            actual_config.stun_servers,
+            vec![
+                SocketAddr::from(([127, 0, 0, 1], 3478)),
+                SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], 3479)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ip_mode() {
+        let actual_config = TrinConfig::new_from(["trin", "--ip-mode", "ip6"].iter()).unwrap();
+        assert_eq!(actual_config.ip_mode, IpMode::Ip6);
+        let actual_config = TrinConfig::new_from(["trin", "--ip-mode", "dual"].iter()).unwrap();
+        assert_eq!(actual_config.ip_mode, IpMode::DualStack);
+        TrinConfig::new_from(["trin", "--ip-mode", "ip5"].iter())
+            .expect_err("Should be an error to pass an unknown ip mode");
+    }
+
+    #[test]
+    fn test_overlay_config() {
+        let actual_config = TrinConfig::new_from(
+            [
+                "trin",
+                "--history-overlay-config",
+                "query-parallelism=8,query-timeout-ms=30000,ip-limit=true,utp-max-packet-size=512,\
+                 max-failures=5,scrub-rate=10,rate-limit-find-content=100/40,rate-limit-other=none,\
+                 rate-limit-action=empty-response",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert_eq!(
+            actual_config.history_overlay_config,
+            OverlayConfigArgs {
+                query_parallelism: Some(8),
+                query_timeout: Some(Duration::from_secs(30)),
+                ip_limit: true,
+                utp_max_packet_size: Some(512),
+                max_failures: Some(5),
+                scrub_rate: Some(10),
+                rate_limit_find_content: Some(RateLimitArg::Limit {
+                    burst: 100,
+                    per_second: 40
+                }),
+                rate_limit_other: Some(RateLimitArg::Unlimited),
+                rate_limit_action: Some(ThrottleActionArg::EmptyResponse),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            actual_config.state_overlay_config,
+            OverlayConfigArgs::default()
+        );
+
+        for invalid in [
+            "query-parallelism=0",
+            "query-parallelism",
+            "unknown-key=1",
+            "query-timeout-ms=1000,query-peer-timeout-ms=2000",
+            "query-peer-timeout-ms=120000",
+            "query-timeout-ms=5000",
+            "utp-max-packet-size=2048",
+            "max-incoming-per-bucket=17",
+            "ip-limit=yes",
+            "max-failures=0",
+            "ping-interval-ms=10000,ping-min-interval-ms=20000",
+            "ping-interval-ms=10000",
+            "utp-min-timeout-ms=2000",
+            "scrub-rate=0",
+            "scrub-pass-interval-ms=60000",
+            "rate-limit-ping=10",
+            "rate-limit-ping=0/10",
+            "rate-limit-offer=10/x",
+            "rate-limit-action=reject",
+        ] {
+            TrinConfig::new_from(["trin", "--state-overlay-config", invalid].iter())
+                .expect_err("Should be an error to pass an invalid overlay config");
+        }
+    }
+
+    #[test]
+    fn test_storage_capacity_split() {
+        let config = TrinConfig::new_from(["trin", "--mb", "90"].iter()).unwrap();
+        assert_eq!(config.storage_capacity_mb(HISTORY_NETWORK), 90);
+
+        let config =
+            TrinConfig::new_from(["trin", "--networks", "history,state", "--mb", "90"].iter())
+                .unwrap();
+        assert_eq!(config.storage_capacity_mb(HISTORY_NETWORK), 45);
+        assert_eq!(config.storage_capacity_mb(STATE_NETWORK), 45);
+
+        let config = TrinConfig::new_from(
+            [
+                "trin",
+                "--networks",
+                "history,state",
+                "--mb",
+                "90",
+                "--state-mb",
+                "10",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert_eq!(config.storage_capacity_mb(HISTORY_NETWORK), 80);
+        assert_eq!(config.storage_capacity_mb(STATE_NETWORK), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "--history-mb and --state-mb must not exceed --mb in total")]
+    fn test_reserved_storage_capacity_must_not_exceed_total() {
+        TrinConfig::new_from(["trin", "--mb", "10", "--history-mb", "20"].iter()).unwrap();
+    }
+
+    #[test]
+    fn test_pinned_content_types() {
+        let config = TrinConfig::new_from(["trin"].iter()).unwrap();
+        assert!(config.pinned_content_types(HISTORY_NETWORK).is_empty());
+
+        let config = TrinConfig::new_from(
+            [
+                "trin",
+                "--pin-content",
+                "history:epoch-accumulator,history:block-header,*:contract-bytecode",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert_eq!(config.pinned_content_types(HISTORY_NETWORK), vec![0, 3]);
+        assert_eq!(config.pinned_content_types(STATE_NETWORK), vec![4]);
+
+        let config =
+            TrinConfig::new_from(["trin", "--pin-content", "state:*,history:receipts"].iter())
+                .unwrap();
+        assert_eq!(config.pinned_content_types(HISTORY_NETWORK), vec![2]);
+        assert_eq!(
+            config.pinned_content_types(STATE_NETWORK),
+            vec![0, 1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_invalid_pin_patterns() {
+        for pattern in [
+            "history",
+            "history:contract-bytecode",
+            "beacon:*",
+            "history:",
+        ] {
+            assert!(
+                PinPattern::from_str(pattern).is_err(),
+                "pattern {pattern} should be invalid"
+            );
+        }
+        TrinConfig::new_from(["trin", "--pin-content", "history:headers"].iter())
+            .expect_err("Unknown content types should be rejected");
+    }
+
     #[test]
     fn test_custom_private_key() {
         let expected_config = TrinConfig {
@@ -0,0 +1,380 @@
+//! Reader of era1 files, which hold the pre-merge history of one epoch.
+//!
+//! An era1 file is a sequence of e2store entries:
+//! `Version | (CompressedHeader | CompressedBody | CompressedReceipts | TotalDifficulty)* |
+//! Accumulator | BlockIndex`. Headers, bodies and receipts are RLP encoded, then compressed with
+//! the snappy framing format. Each e2store entry starts with a 2 byte type, a 4 byte little endian
+//! length and 2 reserved zero bytes.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, ensure};
+use ethereum_types::{H256, U256};
+use rlp::Decodable;
+use ssz_types::VariableList;
+use tree_hash::TreeHash;
+
+use crate::execution::{
+    accumulator::{EpochAccumulator, HeaderRecord},
+    block_body::BlockBody,
+    header::Header,
+    receipts::Receipts,
+};
+
+/// Entry types of the e2store entries in an era1 file.
+const VERSION: [u8; 2] = [0x65, 0x32];
+const COMPRESSED_HEADER: [u8; 2] = [0x03, 0x00];
+const COMPRESSED_BODY: [u8; 2] = [0x04, 0x00];
+const COMPRESSED_RECEIPTS: [u8; 2] = [0x05, 0x00];
+const TOTAL_DIFFICULTY: [u8; 2] = [0x06, 0x00];
+const ACCUMULATOR: [u8; 2] = [0x07, 0x00];
+const BLOCK_INDEX: [u8; 2] = [0x66, 0x32];
+
+/// Size of the header of an e2store entry: type, length and reserved bytes.
+const E2STORE_HEADER_SIZE: usize = 8;
+
+/// A single entry of an e2store file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct E2StoreEntry {
+    pub entry_type: [u8; 2],
+    pub value: Vec<u8>,
+}
+
+impl E2StoreEntry {
+    /// Reads the next entry from `reader`, or returns `None` at the end of the file.
+    pub fn read(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut header = [0; E2STORE_HEADER_SIZE];
+        match reader.read_exact(&mut header) {
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let length = u32::from_le_bytes([header[2], header[3], header[4], header[5]]) as u64;
+        if header[6..] != [0, 0] {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "reserved bytes of e2store entry are not zero",
+            ));
+        }
+        // Read rather than allocate the announced length, which may be corrupted.
+        let mut value = vec![];
+        reader.take(length).read_to_end(&mut value)?;
+        if value.len() as u64 != length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(Some(Self {
+            entry_type: [header[0], header[1]],
+            value,
+        }))
+    }
+
+    /// Returns the entry encoded as in an e2store file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(E2STORE_HEADER_SIZE + self.value.len());
+        bytes.extend_from_slice(&self.entry_type);
+        bytes.extend_from_slice(&(self.value.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&self.value);
+        bytes
+    }
+}
+
+/// A block of an era1 file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockTuple {
+    pub header: Header,
+    pub body: BlockBody,
+    pub receipts: Receipts,
+    pub total_difficulty: U256,
+}
+
+/// Reads the blocks of an era1 file in order.
+///
+/// The accumulator root and block index at the end of the file are available once the last block
+/// was read, and the reader checks that the accumulator root matches the blocks it yielded.
+pub struct Era1Reader<R: Read> {
+    reader: R,
+    header_records: Vec<HeaderRecord>,
+    accumulator_root: Option<H256>,
+    starting_number: Option<u64>,
+}
+
+impl<R: Read> Era1Reader<R> {
+    pub fn new(mut reader: R) -> anyhow::Result<Self> {
+        let version =
+            E2StoreEntry::read(&mut reader)?.ok_or_else(|| anyhow!("era1 file is empty"))?;
+        ensure!(
+            version.entry_type == VERSION && version.value.is_empty(),
+            "era1 file does not start with a version entry"
+        );
+        Ok(Self {
+            reader,
+            header_records: vec![],
+            accumulator_root: None,
+            starting_number: None,
+        })
+    }
+
+    /// Reads the next block, or returns `None` after the last block.
+    pub fn next_block(&mut self) -> anyhow::Result<Option<BlockTuple>> {
+        if self.accumulator_root.is_some() {
+            return Ok(None);
+        }
+        let entry = self.read_entry()?;
+        match entry.entry_type {
+            COMPRESSED_HEADER => {}
+            ACCUMULATOR => {
+                self.read_accumulator(entry)?;
+                return Ok(None);
+            }
+            entry_type => bail!("unexpected era1 entry type {entry_type:02x?}"),
+        }
+        let header: Header = decode_compressed(&entry.value)?;
+        let body: BlockBody = decode_compressed(&self.expect_entry(COMPRESSED_BODY)?)?;
+        let receipts: Receipts = decode_compressed(&self.expect_entry(COMPRESSED_RECEIPTS)?)?;
+        let total_difficulty = self.expect_entry(TOTAL_DIFFICULTY)?;
+        ensure!(
+            total_difficulty.len() == 32,
+            "total difficulty of {} bytes != 32",
+            total_difficulty.len()
+        );
+        let total_difficulty = U256::from_little_endian(&total_difficulty);
+
+        self.header_records.push(HeaderRecord {
+            block_hash: header.hash(),
+            total_difficulty,
+        });
+        Ok(Some(BlockTuple {
+            header,
+            body,
+            receipts,
+            total_difficulty,
+        }))
+    }
+
+    /// Returns the root of the epoch accumulator, once all blocks were read.
+    pub fn accumulator_root(&self) -> Option<H256> {
+        self.accumulator_root
+    }
+
+    /// Returns the number of the first block, once all blocks were read.
+    pub fn starting_number(&self) -> Option<u64> {
+        self.starting_number
+    }
+
+    /// Checks the accumulator root against the blocks read so far, then reads the block index.
+    fn read_accumulator(&mut self, entry: E2StoreEntry) -> anyhow::Result<()> {
+        ensure!(
+            entry.value.len() == 32,
+            "accumulator root of {} bytes != 32",
+            entry.value.len()
+        );
+        let accumulator_root = H256::from_slice(&entry.value);
+        let epoch_acc = epoch_accumulator(self.header_records.clone())?;
+        ensure!(
+            epoch_acc.tree_hash_root() == accumulator_root,
+            "accumulator root of era1 file does not match its blocks"
+        );
+
+        let index = self.expect_entry(BLOCK_INDEX)?;
+        ensure!(index.len() >= 16, "block index of era1 file is too short");
+        let mut starting_number = [0; 8];
+        starting_number.copy_from_slice(&index[..8]);
+        let mut count = [0; 8];
+        count.copy_from_slice(&index[index.len() - 8..]);
+        ensure!(
+            u64::from_le_bytes(count) == self.header_records.len() as u64,
+            "block index of era1 file does not match its blocks"
+        );
+        self.starting_number = Some(u64::from_le_bytes(starting_number));
+        self.accumulator_root = Some(accumulator_root);
+        Ok(())
+    }
+
+    fn read_entry(&mut self) -> anyhow::Result<E2StoreEntry> {
+        E2StoreEntry::read(&mut self.reader)?.ok_or_else(|| anyhow!("era1 file ends early"))
+    }
+
+    /// Reads the next entry, and returns its value if it is of `entry_type`.
+    fn expect_entry(&mut self, entry_type: [u8; 2]) -> anyhow::Result<Vec<u8>> {
+        let entry = self.read_entry()?;
+        ensure!(
+            entry.entry_type == entry_type,
+            "expected era1 entry type {entry_type:02x?}, found {:02x?}",
+            entry.entry_type
+        );
+        Ok(entry.value)
+    }
+}
+
+impl<R: Read> Iterator for Era1Reader<R> {
+    type Item = anyhow::Result<BlockTuple>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_block().transpose()
+    }
+}
+
+/// The blocks of an era1 file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Era1 {
+    pub blocks: Vec<BlockTuple>,
+    pub starting_number: u64,
+    pub accumulator_root: H256,
+}
+
+impl Era1 {
+    /// Reads and checks the era1 file at `path`.
+    pub fn read_file(path: &Path) -> anyhow::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads and checks an era1 file from `reader`.
+    pub fn read(reader: impl Read) -> anyhow::Result<Self> {
+        let mut reader = Era1Reader::new(reader)?;
+        let blocks = reader
+            .by_ref()
+            .collect::<anyhow::Result<Vec<BlockTuple>>>()?;
+        match (reader.starting_number(), reader.accumulator_root()) {
+            (Some(starting_number), Some(accumulator_root)) => Ok(Self {
+                blocks,
+                starting_number,
+                accumulator_root,
+            }),
+            _ => bail!("era1 file has no accumulator"),
+        }
+    }
+
+    /// Returns the epoch accumulator of the blocks, whose root is `accumulator_root`.
+    pub fn epoch_accumulator(&self) -> anyhow::Result<EpochAccumulator> {
+        epoch_accumulator(
+            self.blocks
+                .iter()
+                .map(|block| HeaderRecord {
+                    block_hash: block.header.hash(),
+                    total_difficulty: block.total_difficulty,
+                })
+                .collect(),
+        )
+    }
+}
+
+fn epoch_accumulator(header_records: Vec<HeaderRecord>) -> anyhow::Result<EpochAccumulator> {
+    VariableList::new(header_records)
+        .map_err(|err| anyhow!("too many blocks for an epoch accumulator: {err:?}"))
+}
+
+/// Decompresses a snappy framed value, then decodes it from RLP.
+fn decode_compressed<T: Decodable>(value: &[u8]) -> anyhow::Result<T> {
+    let mut decompressed = vec![];
+    snap::read::FrameDecoder::new(value).read_to_end(&mut decompressed)?;
+    rlp::decode(&decompressed).map_err(|err| anyhow!("invalid RLP in era1 entry: {err:?}"))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use rlp::RlpStream;
+
+    use crate::execution::block_body::EncodableHeaderList;
+    use trin_utils::bytes::hex_decode;
+
+    // Mainnet block #1 rlp encoded header
+    const HEADER_1: &str = "0xf90211a0d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d493479405a56e2d52c817161883f50c441c3228cfe54d9fa0d67e4d450343046425ae4271474353857ab860dbc0a1dde64b41b5cd3a532bf3a056e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421a056e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421b90100000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000008503ff80000001821388808455ba422499476574682f76312e302e302f6c696e75782f676f312e342e32a0969b900de27b6ac6a67742365dd65f55a0526c41fd18e1b16f1a1215c2e66f5988539bd4979fef1ec4";
+    const TOTAL_DIFFICULTY_1: u64 = 34_351_349_760;
+
+    fn compressed(value: &[u8]) -> Vec<u8> {
+        let mut encoder = snap::write::FrameEncoder::new(vec![]);
+        encoder.write_all(value).unwrap();
+        encoder.into_inner().unwrap()
+    }
+
+    fn entry(entry_type: [u8; 2], value: Vec<u8>) -> Vec<u8> {
+        E2StoreEntry { entry_type, value }.to_bytes()
+    }
+
+    /// Returns an era1 file holding block #1 only, and its accumulator root.
+    fn era1_file() -> (Vec<u8>, H256) {
+        let header: Header = rlp::decode(&hex_decode(HEADER_1).unwrap()).unwrap();
+        let mut body = RlpStream::new_list(2);
+        body.begin_list(0);
+        body.append(&EncodableHeaderList { list: vec![] });
+        let receipts = RlpStream::new_list(0);
+        let mut total_difficulty = [0; 32];
+        U256::from(TOTAL_DIFFICULTY_1).to_little_endian(&mut total_difficulty);
+        let accumulator_root = epoch_accumulator(vec![HeaderRecord {
+            block_hash: header.hash(),
+            total_difficulty: U256::from(TOTAL_DIFFICULTY_1),
+        }])
+        .unwrap()
+        .tree_hash_root();
+        let mut index = 1u64.to_le_bytes().to_vec();
+        index.extend_from_slice(&16i64.to_le_bytes());
+        index.extend_from_slice(&1u64.to_le_bytes());
+
+        let file = [
+            entry(VERSION, vec![]),
+            entry(COMPRESSED_HEADER, compressed(&rlp::encode(&header))),
+            entry(COMPRESSED_BODY, compressed(&body.out())),
+            entry(COMPRESSED_RECEIPTS, compressed(&receipts.out())),
+            entry(TOTAL_DIFFICULTY, total_difficulty.to_vec()),
+            entry(ACCUMULATOR, accumulator_root.as_bytes().to_vec()),
+            entry(BLOCK_INDEX, index),
+        ]
+        .concat();
+        (file, accumulator_root)
+    }
+
+    #[test]
+    fn read_era1_file() {
+        let (file, accumulator_root) = era1_file();
+        let era1 = Era1::read(file.as_slice()).unwrap();
+        assert_eq!(era1.starting_number, 1);
+        assert_eq!(era1.accumulator_root, accumulator_root);
+        assert_eq!(era1.blocks.len(), 1);
+
+        let block = &era1.blocks[0];
+        assert_eq!(block.header.number, 1);
+        assert_eq!(
+            block.header.hash(),
+            H256::from_slice(
+                &hex_decode("0x88e96d4537bea4d9c05d12549907b32561d3bf31f45aae734cdc119f13406cb6")
+                    .unwrap()
+            )
+        );
+        assert!(block.body.txs.is_empty());
+        assert_eq!(block.body.uncles_root().unwrap(), block.header.uncles_hash);
+        assert_eq!(block.receipts.root().unwrap(), block.header.receipts_root);
+        assert_eq!(block.total_difficulty, U256::from(TOTAL_DIFFICULTY_1));
+        assert_eq!(
+            era1.epoch_accumulator().unwrap().tree_hash_root(),
+            accumulator_root
+        );
+    }
+
+    #[test]
+    fn era1_file_with_wrong_accumulator_root_is_rejected() {
+        let (mut file, accumulator_root) = era1_file();
+        let root_offset = file
+            .windows(32)
+            .position(|window| window == accumulator_root.as_bytes())
+            .unwrap();
+        file[root_offset] ^= 0xff;
+        assert!(Era1::read(file.as_slice()).is_err());
+    }
+
+    #[test]
+    fn truncated_era1_file_is_rejected() {
+        let (file, _) = era1_file();
+        assert!(Era1::read(&file[..file.len() - 1]).is_err());
+        assert!(Era1::read(&file[..E2STORE_HEADER_SIZE]).is_err());
+    }
+}
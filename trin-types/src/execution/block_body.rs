@@ -50,6 +50,29 @@ impl BlockBody {
     }
 }
 
+impl Decodable for BlockBody {
+    /// Decodes a block body from its RLP network encoding, a list of the transactions and a list
+    /// of the uncles, where typed transactions are encoded as byte strings.
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let txs = rlp
+            .at(0)?
+            .iter()
+            .map(|tx| {
+                if tx.is_list() {
+                    Transaction::decode(tx.as_raw())
+                } else {
+                    Transaction::decode(tx.data()?)
+                }
+            })
+            .collect::<Result<Vec<Transaction>, _>>()?;
+        let uncles = rlp.val_at(1)?;
+        Ok(Self { txs, uncles })
+    }
+}
+
 impl TryFrom<EncodedBlockBodyParts> for BlockBody {
     type Error = DecoderError;
 
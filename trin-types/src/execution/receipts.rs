@@ -40,6 +40,24 @@ impl Receipts {
     }
 }
 
+impl Decodable for Receipts {
+    /// Decodes receipts from their RLP network encoding, a list of the receipts where typed
+    /// receipts are encoded as byte strings.
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        let receipt_list = rlp
+            .iter()
+            .map(|receipt| {
+                if receipt.is_list() {
+                    Receipt::decode(receipt.as_raw())
+                } else {
+                    Receipt::decode(receipt.data()?)
+                }
+            })
+            .collect::<Result<Vec<Receipt>, _>>()?;
+        Ok(Self { receipt_list })
+    }
+}
+
 impl ssz::Encode for Receipts {
     // note: MAX_LENGTH attributes (defined in portal history spec) are not currently enforced
     fn is_ssz_fixed_len() -> bool {
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// Identifies a gossip job, i.e. a single call to propagate a set of content to the network.
+pub type GossipJobId = u64;
+
+/// Delivery status of a single content key within a gossip job.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GossipStatus {
+    /// The gossip job that offered the content.
+    pub job_id: GossipJobId,
+    /// The hex-encoded content key.
+    pub content_key: String,
+    /// Number of peers that accepted the content and received it over uTP.
+    pub num_stored: u32,
+    /// Number of peers that declined the offer, e.g. because the content is outside their radius
+    /// or already stored.
+    pub num_declined: u32,
+    /// Number of peers whose offer or uTP transfer failed.
+    pub num_failed: u32,
+    /// Number of peers with an offer or transfer in flight.
+    pub num_pending: u32,
+}
+
+impl GossipStatus {
+    /// Returns whether the delivery of the content has finished, successfully or not.
+    pub fn is_complete(&self) -> bool {
+        self.num_pending == 0
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+
+use ethereum_types::{H256, U256};
+use serde::{Deserialize, Serialize};
+
+/// Filter on the content in local storage. Every condition that is set must hold for content to
+/// match, and the default filter matches all content.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LocalContentFilter {
+    /// Content types to match, i.e. the first byte of the content key. Empty matches all types.
+    pub content_types: Vec<u8>,
+    /// Minimum distance of the content id from the local node id, inclusive.
+    pub min_distance: Option<U256>,
+    /// Maximum distance of the content id from the local node id, inclusive.
+    pub max_distance: Option<U256>,
+    /// Hex-encoded prefix of the content id, of at most 32 bytes.
+    pub content_id_prefix: Option<String>,
+    /// Minimum size of the content in bytes, inclusive.
+    pub min_size: Option<u64>,
+    /// Maximum size of the content in bytes, inclusive.
+    pub max_size: Option<u64>,
+}
+
+/// A single content item in local storage.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalContentEntry {
+    /// The hex-encoded content key.
+    pub content_key: String,
+    pub content_id: H256,
+    pub content_size: u64,
+    /// Distance of the content id from the local node id.
+    pub distance: U256,
+}
+
+/// A page of the content in local storage that matches a filter, in ascending order of content
+/// id.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalContentInventory {
+    pub entries: Vec<LocalContentEntry>,
+    /// Cursor to pass to the next query to continue after this page, or `None` if there is no
+    /// more matching content.
+    pub next_cursor: Option<H256>,
+}
+
+/// Aggregate statistics of the content in local storage that matches a filter.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalContentStats {
+    pub total_entries: u64,
+    pub total_bytes: u64,
+    /// Statistics of each content type with matching content, by content type.
+    pub content_types: BTreeMap<u8, ContentTypeStats>,
+}
+
+/// Aggregate statistics of the matching content of a single content type.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentTypeStats {
+    pub entries: u64,
+    pub bytes: u64,
+}
+
+impl LocalContentStats {
+    /// Adds `count` content items of `content_type`, totalling `bytes` bytes.
+    pub fn add(&mut self, content_type: u8, count: u64, bytes: u64) {
+        self.total_entries += count;
+        self.total_bytes += bytes;
+        let stats = self.content_types.entry(content_type).or_default();
+        stats.entries += count;
+        stats.bytes += bytes;
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn deserialize_partial_filter() {
+        let filter: LocalContentFilter = serde_json::from_value(json!({
+            "contentTypes": [2],
+            "maxDistance": "0x100",
+        }))
+        .unwrap();
+        assert_eq!(
+            filter,
+            LocalContentFilter {
+                content_types: vec![2],
+                max_distance: Some(U256::from(256)),
+                ..Default::default()
+            }
+        );
+    }
+}
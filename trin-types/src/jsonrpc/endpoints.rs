@@ -1,7 +1,9 @@
 use crate::content_key::HistoryContentKey;
 use crate::content_value::HistoryContentValue;
 use crate::enr::Enr;
+use crate::inventory::LocalContentFilter;
 use crate::node_id::NodeId;
+use ethereum_types::H256;
 
 /// Discv5 JSON-RPC endpoints. Start with "discv5_" prefix
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -37,6 +39,8 @@ pub enum HistoryEndpoint {
     LocalContent(HistoryContentKey),
     /// params: [content_key, content_value]
     Gossip(HistoryContentKey, HistoryContentValue),
+    /// params: content_key
+    GossipStatus(HistoryContentKey),
     /// params: [enr, content_key]
     Offer(Enr, HistoryContentKey, Option<HistoryContentValue>),
     /// params: [enr]
@@ -45,15 +49,31 @@ pub enum HistoryEndpoint {
     RecursiveFindContent(HistoryContentKey),
     /// params: content_key
     TraceRecursiveFindContent(HistoryContentKey),
+    /// params: [content_keys, max_concurrency]
+    RecursiveFindContentBatch(Vec<HistoryContentKey>, Option<usize>),
     /// params: [content_key, content_value]
     Store(HistoryContentKey, HistoryContentValue),
+    /// params: content_key
+    PinContent(HistoryContentKey),
+    /// params: content_key
+    UnpinContent(HistoryContentKey),
+    /// params: None
+    PinnedContent,
     /// params: None
     RoutingTableInfo,
+    /// params: None
+    RoutingTableHealth,
     // This endpoint is not History network specific
     /// params: [offset, limit]
     PaginateLocalContentKeys(u64, u64),
+    /// params: [filter, cursor, limit]
+    LocalContentInventory(LocalContentFilter, Option<H256>, u64),
+    /// params: filter
+    LocalContentStats(LocalContentFilter),
     /// params: [node_id]
     RecursiveFindNodes(NodeId),
+    /// params: [node_id]
+    TraceRecursiveFindNodes(NodeId),
 }
 
 /// Ethereum JSON-RPC endpoints not currently supported by portal network requests, proxied to
@@ -11,7 +11,10 @@ pub mod content_value;
 pub mod discv5;
 pub mod distance;
 pub mod enr;
+pub mod era1;
 pub mod execution;
+pub mod gossip_status;
+pub mod inventory;
 pub mod jsonrpc;
 pub mod node_id;
 pub mod provider;
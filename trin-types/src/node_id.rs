@@ -1,4 +1,5 @@
 use crate::enr::Enr;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::ops::Deref;
 use stremio_serde_hex::{SerHex, StrictPfx};
@@ -28,8 +29,22 @@ impl NodeId {
     /// Then we XOR the result distance with the local NodeId to get the random target NodeId
     // TODO: We should be able to make this generic over a `Metric`.
     pub fn generate_random_node_id(target_bucket_idx: u8, local_node_id: NodeId) -> NodeId {
+        Self::generate_random_node_id_with_rng(
+            target_bucket_idx,
+            local_node_id,
+            &mut rand::thread_rng(),
+        )
+    }
+
+    /// Generates a random node ID in the target bucket, drawing the random bits from `rng`.
+    pub fn generate_random_node_id_with_rng<R: Rng + ?Sized>(
+        target_bucket_idx: u8,
+        local_node_id: NodeId,
+        rng: &mut R,
+    ) -> NodeId {
         let distance_leading_zeroes = 255 - target_bucket_idx;
-        let random_distance = trin_utils::bytes::random_32byte_array(distance_leading_zeroes);
+        let random_distance =
+            trin_utils::bytes::random_32byte_array_with_rng(distance_leading_zeroes, rng);
 
         let raw_node_id = XorMetric::distance(&local_node_id.raw(), &random_distance);
 
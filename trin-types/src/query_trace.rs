@@ -19,6 +19,17 @@ pub struct QueryTrace {
     /// Map of a node's ID to its corresponding `QueryResponse`
     pub responses: HashMap<NodeId, QueryResponse>,
     pub node_metadata: HashMap<NodeId, NodeInfo>,
+    /// Nodes that were contacted ahead of closer nodes, because their advertised radius covers
+    /// the target.
+    #[serde(default)]
+    pub contacted_for_radius: Vec<NodeId>,
+    /// Map of a node's ID to the milliseconds since the query started at which a request was
+    /// sent to the node.
+    #[serde(default)]
+    pub requests_sent: HashMap<NodeId, u64>,
+    /// Map of a node's ID to the failure of the request sent to the node.
+    #[serde(default)]
+    pub failures: HashMap<NodeId, QueryFailure>,
     started_at: SystemTime,
     target_id: ContentId,
 }
@@ -30,6 +41,9 @@ impl QueryTrace {
             origin: local_enr.into(),
             responses: HashMap::new(),
             node_metadata: HashMap::new(),
+            contacted_for_radius: vec![],
+            requests_sent: HashMap::new(),
+            failures: HashMap::new(),
             started_at: SystemTime::now(),
             target_id,
         }
@@ -77,6 +91,43 @@ impl QueryTrace {
         self.add_node_metadata(enr, true);
     }
 
+    /// Records that `enr` was contacted because its advertised radius covers the target.
+    pub fn node_contacted_for_radius(&mut self, enr: &Enr) {
+        let node_id = enr.into();
+        if !self.contacted_for_radius.contains(&node_id) {
+            self.contacted_for_radius.push(node_id);
+        }
+        self.add_node_metadata(enr, false);
+    }
+
+    /// Records that a request was sent to `enr`.
+    /// Timestamp is only set on the first call for a given `enr`.
+    pub fn node_request_sent(&mut self, enr: &Enr) {
+        let timestamp_u64 = QueryTrace::timestamp_millis_u64(self.started_at);
+        self.requests_sent
+            .entry(enr.into())
+            .or_insert(timestamp_u64);
+        self.add_node_metadata(enr, false);
+    }
+
+    /// Records that the request sent to `enr` failed.
+    pub fn node_failed(&mut self, enr: &Enr, kind: QueryFailureKind) {
+        let timestamp_u64 = QueryTrace::timestamp_millis_u64(self.started_at);
+        self.failures.insert(
+            enr.into(),
+            QueryFailure {
+                timestamp_millis: timestamp_u64,
+                kind,
+            },
+        );
+        self.add_node_metadata(enr, false);
+    }
+
+    /// Returns the target of the query.
+    pub fn target_id(&self) -> ContentId {
+        self.target_id
+    }
+
     /// Returns milliseconds since the time provided.
     fn timestamp_millis_u64(since: SystemTime) -> u64 {
         let timestamp_millis_u128 = SystemTime::now()
@@ -110,11 +161,13 @@ impl QueryTrace {
     fn get_node_metadata(enr: &Enr, target: &ContentId) -> NodeInfo {
         let node_id = enr.node_id();
         let node_id_raw = node_id.raw();
-        let ip = match enr.ip4() {
-            Some(ip) => ip.to_string(),
-            None => "".to_owned(),
+        // Prefer the IPv4 address, and fall back to the IPv6 address for IPv6-only nodes.
+        let (ip, port) = match (enr.ip4(), enr.ip6()) {
+            (Some(ip), _) => (ip.to_string(), enr.udp4()),
+            (None, Some(ip)) => (ip.to_string(), enr.udp6()),
+            (None, None) => ("".to_owned(), enr.udp4().or_else(|| enr.udp6())),
         };
-        let port = enr.udp4().unwrap_or(0).to_string();
+        let port = port.unwrap_or(0).to_string();
         let distance = XorMetric::distance(&node_id_raw, &target.raw());
         let distance_log2 = distance.log2().unwrap_or(0);
         let distance: u64 = distance.0[3];
@@ -139,6 +192,24 @@ pub struct QueryResponse {
     pub responded_with: Vec<NodeId>,
 }
 
+/// Represents a failed request to a single node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryFailure {
+    /// Milliseconds since query started.
+    pub timestamp_millis: u64,
+    pub kind: QueryFailureKind,
+}
+
+/// The reason a request to a node failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryFailureKind {
+    /// The node did not respond in time.
+    Timeout,
+    /// The request failed for any other reason.
+    Error,
+}
+
 /// Represents additional info for a given node.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct NodeInfo {
@@ -209,4 +280,65 @@ mod tests {
         let local_data = tracer.node_metadata.get(local_node_id).unwrap();
         assert_eq!(local_data.enr, local_enr);
     }
+
+    #[test]
+    fn test_query_trace_contacted_for_radius() {
+        let (_, local_enr) = generate_random_remote_enr();
+        let mut tracer = QueryTrace::new(&local_enr, local_enr.node_id().into());
+        let (_, enr_a) = generate_random_remote_enr();
+        let node_id_a: NodeId = enr_a.clone().into();
+
+        tracer.node_contacted_for_radius(&enr_a);
+        tracer.node_contacted_for_radius(&enr_a);
+
+        assert_eq!(tracer.contacted_for_radius, vec![node_id_a]);
+        assert_eq!(tracer.node_metadata.get(&node_id_a).unwrap().enr, enr_a);
+        assert!(tracer.responses.get(&node_id_a).is_none());
+    }
+
+    #[test]
+    fn test_query_trace_requests_and_failures() {
+        let (_, local_enr) = generate_random_remote_enr();
+        let mut tracer = QueryTrace::new(&local_enr, local_enr.node_id().into());
+        let (_, enr_a) = generate_random_remote_enr();
+        let node_id_a: NodeId = enr_a.clone().into();
+        let (_, enr_b) = generate_random_remote_enr();
+        let node_id_b: NodeId = enr_b.clone().into();
+
+        tracer.node_request_sent(&enr_a);
+        tracer.node_request_sent(&enr_b);
+        tracer.node_responded_with(&enr_a, vec![]);
+        tracer.node_failed(&enr_b, QueryFailureKind::Timeout);
+
+        assert!(tracer.requests_sent.contains_key(&node_id_a));
+        assert!(tracer.requests_sent.contains_key(&node_id_b));
+        assert!(tracer.failures.get(&node_id_a).is_none());
+        assert_eq!(
+            tracer.failures.get(&node_id_b).unwrap().kind,
+            QueryFailureKind::Timeout
+        );
+        assert_eq!(tracer.node_metadata.get(&node_id_b).unwrap().enr, enr_b);
+
+        // Traces serialized without requests and failures remain readable.
+        let mut json = serde_json::to_value(&tracer).unwrap();
+        json.as_object_mut().unwrap().remove("requests_sent");
+        json.as_object_mut().unwrap().remove("failures");
+        let decoded: QueryTrace = serde_json::from_value(json).unwrap();
+        assert!(decoded.requests_sent.is_empty());
+        assert!(decoded.failures.is_empty());
+    }
+
+    #[test]
+    fn test_node_metadata_ip6() {
+        let key = discv5::enr::CombinedKey::generate_secp256k1();
+        let enr = discv5::enr::EnrBuilder::new("v4")
+            .ip6(std::net::Ipv6Addr::LOCALHOST)
+            .udp6(9001)
+            .build(&key)
+            .unwrap();
+
+        let metadata = QueryTrace::get_node_metadata(&enr, &enr.node_id().into());
+        assert_eq!(metadata.ip, "::1");
+        assert_eq!(metadata.port, "9001");
+    }
 }
@@ -1,5 +1,5 @@
 use hex::FromHexError;
-use rand::{Rng, RngCore};
+use rand::Rng;
 use thiserror::Error;
 
 /// An error from a byte utils operation.
@@ -57,11 +57,19 @@ pub fn hex_encode_upper<T: AsRef<[u8]>>(data: T) -> String {
 
 /// Generate 32 byte array with N leading bit zeros
 pub fn random_32byte_array(leading_bit_zeros: u8) -> [u8; 32] {
+    random_32byte_array_with_rng(leading_bit_zeros, &mut rand::thread_rng())
+}
+
+/// Generate 32 byte array with N leading bit zeros, drawing the random bits from `rng`
+pub fn random_32byte_array_with_rng<R: Rng + ?Sized>(
+    leading_bit_zeros: u8,
+    rng: &mut R,
+) -> [u8; 32] {
     let first_zero_bytes: usize = leading_bit_zeros as usize / 8;
     let first_nonzero_byte_leading_zeros = leading_bit_zeros % 8u8;
 
     let mut bytes = [0; 32];
-    rand::thread_rng().fill_bytes(&mut bytes[first_zero_bytes..]);
+    rng.fill_bytes(&mut bytes[first_zero_bytes..]);
 
     if first_zero_bytes == 32 {
         return bytes;
@@ -69,14 +77,13 @@ pub fn random_32byte_array(leading_bit_zeros: u8) -> [u8; 32] {
 
     bytes[first_zero_bytes] = if first_nonzero_byte_leading_zeros == 0 {
         // We want the byte after first zero bytes to start with 1 bit, i.e value > 128
-        rand::thread_rng().gen_range(128..=255)
+        rng.gen_range(128..=255)
     } else {
         // Based on the leading zeroes in this byte, we want to generate a random value within
         // min and max u8 range
         let min_nonzero_byte_value =
             (128_f32 * 0.5_f32.powi(first_nonzero_byte_leading_zeros as i32)) as u8;
-        rand::thread_rng()
-            .gen_range(min_nonzero_byte_value..min_nonzero_byte_value.saturating_mul(2))
+        rng.gen_range(min_nonzero_byte_value..min_nonzero_byte_value.saturating_mul(2))
     };
 
     bytes
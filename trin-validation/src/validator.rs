@@ -1,3 +1,5 @@
+use std::fmt;
+
 use async_trait::async_trait;
 
 use trin_types::content_key::IdentityContentKey;
@@ -14,6 +16,19 @@ pub trait Validator<TContentKey> {
         TContentKey: 'async_trait;
 }
 
+/// Error of a `Validator` that could not fetch the data it needs to validate content, for example a
+/// trusted header. Unlike other validation errors, it does not mean that the content is invalid.
+#[derive(Debug)]
+pub struct LookupError(pub anyhow::Error);
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unable to look up validation data: {}", self.0)
+    }
+}
+
+impl std::error::Error for LookupError {}
+
 /// For use in tests where no validation needs to be performed.
 pub struct MockValidator {}
 